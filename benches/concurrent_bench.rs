@@ -30,6 +30,7 @@ fn concurrent_benchmark(c: &mut Criterion) {
         create_dir: true,
         encryption_enabled: false,
         master_key: None,
+        ..Default::default()
     };
     let storage = Storage::open(config).unwrap();
 
@@ -32,6 +32,7 @@ fn payload_size_benchmark(c: &mut Criterion) {
                 create_dir: true,
                 encryption_enabled: false,
                 master_key: None,
+                ..Default::default()
             };
             let storage = Storage::open(config).unwrap();
             let mut writer = Writer::<PayloadEvent>::new(storage.clone());
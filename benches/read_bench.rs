@@ -20,6 +20,13 @@ pub struct BenchEvent {
 }
 
 fn read_benchmark(c: &mut Criterion) {
+    run_read_benchmark(c, "read_event_sequential", false);
+
+    // Tail latency with the map locked into RAM. Requires a sufficient RLIMIT_MEMLOCK.
+    run_read_benchmark(c, "read_event_sequential_locked", true);
+}
+
+fn run_read_benchmark(c: &mut Criterion, name: &str, lock_memory: bool) {
     let dir = tempdir().unwrap();
     let config = StorageConfig {
         path: dir.path().join("bench_read.mdb"),
@@ -28,6 +35,7 @@ fn read_benchmark(c: &mut Criterion) {
         create_dir: true,
         encryption_enabled: false,
         master_key: None,
+        ..Default::default()
     };
     let storage = Storage::open(config).unwrap();
     let mut writer = Writer::<BenchEvent>::new(storage.clone());
@@ -43,12 +51,19 @@ fn read_benchmark(c: &mut Criterion) {
         writer.append(1, i as u32, event).unwrap();
     }
 
+    if lock_memory {
+        if let Err(e) = storage.lock_memory() {
+            eprintln!("Skipping {}: {}", name, e);
+            return;
+        }
+    }
+
     let txn = storage.env.read_txn().unwrap();
     let mut group = c.benchmark_group("read_throughput");
     group.throughput(Throughput::Elements(1));
 
     let mut i = 1;
-    group.bench_function("read_event_sequential", |b| {
+    group.bench_function(name, |b| {
         b.iter(|| {
             criterion::black_box(reader.get(&txn, i).unwrap());
             i = (i % count) + 1;
@@ -57,5 +72,167 @@ fn read_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, read_benchmark);
+/// Closes the environment on drop, so the next open maps the file afresh and pays the
+/// page faults a newly started process would.
+struct Closing(Option<Storage>);
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        if let Some(storage) = self.0.take() {
+            let closing = storage.env.clone().prepare_for_closing();
+            drop(storage);
+            closing.wait();
+        }
+    }
+}
+
+fn first_read_benchmark(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().join("bench_first_read.mdb"),
+        map_size: 1024 * 1024 * 1024,
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone()).unwrap();
+    let mut writer = Writer::<BenchEvent>::new(storage.clone());
+    let count = 2_000;
+    for i in 0..count {
+        let event = BenchEvent {
+            id: i,
+            payload: [0u8; 256],
+        };
+        writer.append(1, i as u32 + 1, event).unwrap();
+    }
+    drop(writer);
+    drop(Closing(Some(storage)));
+
+    let mut group = c.benchmark_group("first_read_latency");
+    group.sample_size(10);
+    group.warm_up_time(std::time::Duration::from_secs(1));
+    group.measurement_time(std::time::Duration::from_secs(2));
+
+    // Reads spread over the log right after opening, which fault pages in unless warmed.
+    // The open itself, and so the warming, is not measured.
+    for warm_on_open in [false, true] {
+        let config = StorageConfig {
+            warm_on_open,
+            ..config.clone()
+        };
+        group.bench_function(format!("first_reads_warm_{}", warm_on_open), |b| {
+            b.iter_batched(
+                || Storage::open(config.clone()).unwrap(),
+                |storage| {
+                    let reader = Reader::<BenchEvent>::new(storage.clone());
+                    let txn = storage.env.read_txn().unwrap();
+                    for seq in (1..=count).step_by(20) {
+                        criterion::black_box(reader.get(&txn, seq).unwrap());
+                    }
+                    drop(txn);
+                    drop(reader);
+                    Closing(Some(storage))
+                },
+                criterion::BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn scan_benchmark(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().join("bench_scan.mdb"),
+        map_size: 10 * 1024 * 1024 * 1024,
+        ..Default::default()
+    };
+    let storage = Storage::open(config).unwrap();
+    let mut writer = Writer::<BenchEvent>::new(storage.clone());
+    let reader = Reader::<BenchEvent>::new(storage.clone());
+
+    let count = 100_000;
+    for i in 0..count {
+        let event = BenchEvent {
+            id: i,
+            payload: [0u8; 256],
+        };
+        writer.append(1, i as u32 + 1, event).unwrap();
+    }
+
+    let mut group = c.benchmark_group("scan_throughput");
+    group.throughput(Throughput::Elements(count));
+    group.sample_size(20);
+
+    // Full-log scans, as done by projection rebuilds. The difference only shows when the
+    // log is not already in the page cache.
+    for window in [0, 256, 4096] {
+        group.bench_function(format!("scan_full_log_prefetch_{}", window), |b| {
+            b.iter(|| {
+                let snapshot = reader.snapshot().unwrap();
+                for event in snapshot.iter().with_prefetch(window) {
+                    criterion::black_box(event.unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct NestedEvent {
+    pub id: u64,
+    pub tags: Vec<String>,
+    pub values: Vec<u64>,
+}
+
+/// Cost of `bytecheck` validation on a 10k-event scan, against `iter_range_unchecked`.
+fn validation_benchmark(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().join("bench_validation.mdb"),
+        map_size: 1024 * 1024 * 1024,
+        ..Default::default()
+    };
+    let storage = Storage::open(config).unwrap();
+    let mut writer = Writer::<NestedEvent>::new(storage.clone());
+    let reader = Reader::<NestedEvent>::new(storage.clone());
+
+    let count = 10_000;
+    for i in 0..count {
+        let event = NestedEvent {
+            id: i,
+            tags: (0..64).map(|t| format!("tag-{}", t)).collect(),
+            values: (0..32).collect(),
+        };
+        writer.append(1, i as u32 + 1, event).unwrap();
+    }
+
+    let txn = storage.env.read_txn().unwrap();
+    let mut group = c.benchmark_group("scan_validation");
+    group.throughput(Throughput::Elements(count));
+
+    group.bench_function("scan_checked", |b| {
+        b.iter(|| {
+            for seq in 1..=count {
+                criterion::black_box(reader.get(&txn, seq).unwrap());
+            }
+        })
+    });
+    group.bench_function("scan_unchecked", |b| {
+        b.iter(|| {
+            // Safety: every event was just written by a `Writer<NestedEvent>`.
+            for event in unsafe { reader.iter_range_unchecked(&txn, 1, count + 1) }.unwrap() {
+                criterion::black_box(event.unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    read_benchmark,
+    first_read_benchmark,
+    scan_benchmark,
+    validation_benchmark
+);
 criterion_main!(benches);
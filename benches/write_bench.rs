@@ -11,6 +11,7 @@ use rkyv::{Archive, Deserialize, Serialize};
 use tempfile::tempdir;
 use varvedb::engine::Writer;
 use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
 
 #[derive(Archive, Serialize, Deserialize, Debug)]
 #[repr(C)]
@@ -45,5 +46,38 @@ fn write_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, write_benchmark);
+fn bulk_write_benchmark(c: &mut Criterion) {
+    const BATCH: u64 = 1000;
+
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().join("bench.mdb"),
+        ..Default::default()
+    };
+    let storage = Storage::open(config).unwrap();
+    let mut writer = Writer::<BenchEvent>::new(storage.clone());
+
+    let mut group = c.benchmark_group("bulk_write_throughput");
+    group.throughput(Throughput::Elements(BATCH));
+
+    let mut stream_id = 0u128;
+    group.bench_function("append_multi_1000", |b| {
+        b.iter(|| {
+            stream_id += 1;
+            let items = (0..BATCH)
+                .map(|id| {
+                    let event = BenchEvent {
+                        id,
+                        payload: [0u8; 256],
+                    };
+                    (stream_id, ExpectedVersion::exact(id as u32 + 1), event)
+                })
+                .collect();
+            writer.append_multi(items).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, write_benchmark, bulk_write_benchmark);
 criterion_main!(benches);
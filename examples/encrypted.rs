@@ -53,6 +53,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: true, // Enable encryption
         master_key: Some(zeroize::Zeroizing::new(master_key)), // Provide the master key
+        ..Default::default()
     };
 
     // Verify authorized access in a scope
@@ -94,6 +95,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: true,
         master_key: Some(zeroize::Zeroizing::new(wrong_key)),
+        ..Default::default()
     };
 
     // Try to open with wrong key
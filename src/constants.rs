@@ -24,13 +24,50 @@ pub const KEY_SIZE: usize = 32;
 /// The size of the nonce in bytes (AES-GCM).
 pub const NONCE_SIZE: usize = 12;
 
+/// The size of a stream key wrapped under a master key (Nonce + Key + Tag).
+/// Nonce (12) + Key (32) + Tag (16) = 60 bytes. Keys wrapped under a master key version
+/// other than 0 are stored with one more, leading byte holding the version.
+pub const WRAPPED_KEY_SIZE: usize = 60;
+
+/// How many unwrapped stream keys a `KeyManager` keeps in memory.
+pub const KEY_CACHE_CAPACITY: usize = 1024;
+
 /// The minimum size of an encrypted event (StreamID + Nonce + Tag).
 /// StreamID (16) + Nonce (12) + Tag (16) = 44 bytes.
 pub const ENCRYPTED_EVENT_MIN_SIZE: usize = 44;
 
+/// Trailing byte of a plaintext record when `StorageConfig::encrypted_streams` is set.
+pub const RECORD_PLAINTEXT: u8 = 0;
+
+/// Trailing byte of an encrypted record when `StorageConfig::encrypted_streams` is set.
+pub const RECORD_ENCRYPTED: u8 = 1;
+
 /// The capacity of the AAD buffer (StreamID + Seq).
 /// StreamID (16) + Seq (8) = 24 bytes.
 pub const AAD_CAPACITY: usize = 24;
 
 /// The maximum size of a payload to be stored inline (2KB).
 pub const MAX_INLINE_SIZE: usize = 2048;
+
+/// Size of the header at the start of every LMDB page.
+pub const LMDB_PAGE_HEADER_SIZE: usize = 16;
+
+/// Size of the header of every LMDB leaf node (one key/value pair).
+pub const LMDB_NODE_HEADER_SIZE: usize = 8;
+
+/// Upper bound on what a stored record adds around the event bytes: the rkyv payload
+/// envelope plus, with encryption, the stream ID, nonce and authentication tag.
+pub const INLINE_RECORD_OVERHEAD: usize = 64;
+
+/// The on-disk format version written to the `meta` database.
+///
+/// Version 2 counts the records referring to each blob, so trimming can delete blobs.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The stream whose encryption key protects raw events (see `Writer::append_raw`).
+///
+/// Reserved: appending events to it fails, so its key only ever protects raw events.
+pub const RAW_LOG_STREAM_ID: u128 = u128::MAX - 1;
+
+/// The fraction of the memory map that must remain free for `Varve::health_check` to pass.
+pub const HEALTH_MIN_FREE_MAP_FRACTION: f64 = 0.1;
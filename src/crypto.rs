@@ -8,13 +8,17 @@
 
 use crate::storage::Storage;
 use aes_gcm::{
-    aead::{Aead, KeyInit, Payload},
+    aead::{Aead, AeadInPlace, KeyInit, Payload},
     Aes256Gcm, Key, Nonce,
 };
 use rand::rngs::OsRng;
 use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use zeroize::Zeroizing;
 
+type StreamKey = Zeroizing<[u8; crate::constants::KEY_SIZE]>;
+
 /// Manages the lifecycle of encryption keys.
 ///
 /// The `KeyManager` is responsible for generating, retrieving, and securely storing per-stream encryption keys.
@@ -23,9 +27,19 @@ use zeroize::Zeroizing;
 ///
 /// # Key Hierarchy
 ///
-/// 1.  **Master Key**: Provided in `StorageConfig`. Used to encrypt Stream Keys.
+/// 1.  **Master Key**: Provided in `StorageConfig`. Used to encrypt Stream Keys. Several
+///     versions can be configured at once to rotate it; see `StorageConfig::master_keys`.
 /// 2.  **Stream Key**: Generated randomly (32 bytes) for each stream. Used to encrypt Event Data.
 ///
+/// # Key Cache
+///
+/// Unwrapped stream keys are cached in memory, shared by all clones of a `KeyManager`, so
+/// each key is decrypted under the master key once rather than on every read. A cached key
+/// is only used while the keystore still holds the exact wrapped key it came from, so deleted
+/// or replaced keys are never served from the cache. At most
+/// [`KEY_CACHE_CAPACITY`](crate::constants::KEY_CACHE_CAPACITY) keys are kept, evicting the
+/// least recently used; evicted keys are zeroized.
+///
 /// # Examples
 ///
 /// ```rust
@@ -53,20 +67,72 @@ use zeroize::Zeroizing;
 #[derive(Debug, Clone)]
 pub struct KeyManager {
     storage: Storage,
+    cache: Arc<Mutex<KeyCache>>,
 }
 
 impl KeyManager {
     /// Creates a new `KeyManager` instance.
     pub fn new(storage: Storage) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            cache: Arc::new(Mutex::new(KeyCache::new(
+                crate::constants::KEY_CACHE_CAPACITY,
+            ))),
+        }
+    }
+
+    /// Returns master key `version`; version 0 is `StorageConfig::master_key`.
+    fn get_master_key(
+        &self,
+        version: u8,
+    ) -> crate::error::Result<&[u8; crate::constants::KEY_SIZE]> {
+        let config = &self.storage.config;
+        if version == 0 {
+            return config
+                .master_key
+                .as_deref()
+                .ok_or_else(|| crate::error::Error::KeyNotFound(0)); // 0 for master key
+        }
+        config
+            .master_keys
+            .get(&version)
+            .map(|key| &**key)
+            .ok_or_else(|| {
+                crate::error::Error::InvalidConfig(format!(
+                    "master key version {} is not configured",
+                    version
+                ))
+            })
     }
 
-    fn get_master_key(&self) -> crate::error::Result<&[u8; crate::constants::KEY_SIZE]> {
+    /// The master key version new stream keys are wrapped with.
+    fn latest_master_version(&self) -> u8 {
         self.storage
             .config
-            .master_key
-            .as_deref()
-            .ok_or_else(|| crate::error::Error::KeyNotFound(0)) // 0 for master key
+            .master_keys
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Wraps a stream key under the latest master key, as stored in the keystore.
+    ///
+    /// Version 0 wraps are `[Nonce][Ciphertext]`, as before versioning; later versions
+    /// are prefixed with the version byte.
+    fn wrap_key(&self, stream_id: u128, key: &StreamKey) -> crate::error::Result<Vec<u8>> {
+        let version = self.latest_master_version();
+        let master_key = self.get_master_key(version)?;
+        let aad = stream_id.to_be_bytes(); // Bind key to StreamID
+        let encrypted_key = encrypt(master_key, &**key, &aad)?;
+        if version == 0 {
+            return Ok(encrypted_key);
+        }
+
+        let mut wrapped = Vec::with_capacity(1 + encrypted_key.len());
+        wrapped.push(version);
+        wrapped.extend_from_slice(&encrypted_key);
+        Ok(wrapped)
     }
 
     pub fn get_or_create_key(
@@ -84,34 +150,21 @@ impl KeyManager {
         txn: &mut heed::RwTxn,
         stream_id: u128,
     ) -> crate::error::Result<Zeroizing<[u8; crate::constants::KEY_SIZE]>> {
+        // Keys are stored (and bound) under the on-disk stream ID.
+        let stream_id = self.storage.stored_stream_id(stream_id);
         match self.storage.keystore.get(txn, &stream_id)? {
-            Some(encrypted_key_bytes) => {
-                // Decrypt existing key
-                let master_key = self.get_master_key()?;
-                let aad = stream_id.to_be_bytes(); // Bind key to StreamID
-                let plaintext_key_vec = decrypt(master_key, encrypted_key_bytes, &aad)?;
-
-                let mut key = Zeroizing::new([0u8; crate::constants::KEY_SIZE]);
-                if plaintext_key_vec.len() != crate::constants::KEY_SIZE {
-                    return Err(crate::error::Error::InvalidKeyLength {
-                        actual: plaintext_key_vec.len(),
-                        expected: crate::constants::KEY_SIZE,
-                    });
-                }
-                key.copy_from_slice(&plaintext_key_vec);
-                Ok(key)
-            }
+            Some(encrypted_key_bytes) => self.unwrap_key(stream_id, encrypted_key_bytes),
             None => {
                 // Generate new key
                 let mut key = Zeroizing::new([0u8; crate::constants::KEY_SIZE]);
                 OsRng.fill_bytes(&mut *key);
 
                 // Encrypt with Master Key
-                let master_key = self.get_master_key()?;
-                let aad = stream_id.to_be_bytes();
-                let encrypted_key = encrypt(master_key, &*key, &aad)?;
+                let encrypted_key = self.wrap_key(stream_id, &key)?;
 
                 self.storage.keystore.put(txn, &stream_id, &encrypted_key)?;
+                self.lock_cache()
+                    .insert(stream_id, &encrypted_key, key.clone());
                 Ok(key)
             }
         }
@@ -129,35 +182,290 @@ impl KeyManager {
         &self,
         txn: &heed::RoTxn,
         stream_id: u128,
+    ) -> crate::error::Result<Option<Zeroizing<[u8; crate::constants::KEY_SIZE]>>> {
+        self.get_stored_key_with_txn(txn, self.storage.stored_stream_id(stream_id))
+    }
+
+    /// Like [`get_key_with_txn`](Self::get_key_with_txn), but takes the on-disk stream ID
+    /// (see [`Storage::stored_stream_id`]), as found in encrypted record headers.
+    pub(crate) fn get_stored_key_with_txn(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
     ) -> crate::error::Result<Option<Zeroizing<[u8; crate::constants::KEY_SIZE]>>> {
         match self.storage.keystore.get(txn, &stream_id)? {
-            Some(encrypted_key_bytes) => {
-                let master_key = self.get_master_key()?;
-                let aad = stream_id.to_be_bytes();
-                let plaintext_key_vec = decrypt(master_key, encrypted_key_bytes, &aad)?;
+            Some(encrypted_key_bytes) => self.unwrap_key(stream_id, encrypted_key_bytes).map(Some),
+            None => Ok(None),
+        }
+    }
 
-                let mut key = Zeroizing::new([0u8; crate::constants::KEY_SIZE]);
-                if plaintext_key_vec.len() != crate::constants::KEY_SIZE {
-                    return Err(crate::error::Error::InvalidKeyLength {
-                        actual: plaintext_key_vec.len(),
-                        expected: crate::constants::KEY_SIZE,
-                    });
+    /// Decrypts a wrapped stream key under the master key, or returns it from the cache.
+    fn unwrap_key(&self, stream_id: u128, wrapped: &[u8]) -> crate::error::Result<StreamKey> {
+        if let Some(key) = self.lock_cache().get(stream_id, wrapped) {
+            return Ok(key);
+        }
+
+        let (version, encrypted_key) = split_wrapped(wrapped);
+        let master_key = self.get_master_key(version)?;
+        let aad = stream_id.to_be_bytes(); // Bind key to StreamID
+        let plaintext_key_vec = Zeroizing::new(decrypt(master_key, encrypted_key, &aad)?);
+
+        let mut key = Zeroizing::new([0u8; crate::constants::KEY_SIZE]);
+        if plaintext_key_vec.len() != crate::constants::KEY_SIZE {
+            return Err(crate::error::Error::InvalidKeyLength {
+                actual: plaintext_key_vec.len(),
+                expected: crate::constants::KEY_SIZE,
+            });
+        }
+        key.copy_from_slice(&plaintext_key_vec);
+        self.lock_cache().insert(stream_id, wrapped, key.clone());
+        Ok(key)
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, KeyCache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the master key version the key of `stream_id` is wrapped with, or `None` if
+    /// the stream has no key.
+    pub fn key_version(&self, stream_id: u128) -> crate::error::Result<Option<u8>> {
+        let stream_id = self.storage.stored_stream_id(stream_id);
+        let txn = self.storage.env.read_txn()?;
+        Ok(self
+            .storage
+            .keystore
+            .get(&txn, &stream_id)?
+            .map(|wrapped| split_wrapped(wrapped).0))
+    }
+
+    /// Re-wraps every stream key not yet wrapped with the latest master key.
+    ///
+    /// Completes a master key rotation (see
+    /// [`StorageConfig::master_keys`](crate::storage::StorageConfig::master_keys)): once
+    /// this returns, no stream key depends on an older master key. Only the keystore is
+    /// rewritten, in one transaction; events stay encrypted under their stream keys.
+    /// Returns how many keys were re-wrapped.
+    ///
+    /// # Errors
+    ///
+    /// Fails, without changing anything, if any key cannot be unwrapped, e.g. because its
+    /// master key version is no longer configured, or with
+    /// [`LeaseLost`](crate::error::Error::LeaseLost) if this process lost its writer lease.
+    pub fn rewrap_all_keys(&self) -> crate::error::Result<usize> {
+        let latest = self.latest_master_version();
+        let mut txn = self.storage.env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let mut stale = Vec::new();
+        for result in self.storage.keystore.iter(&txn)? {
+            let (stream_id, wrapped) = result?;
+            if split_wrapped(wrapped).0 != latest {
+                stale.push((stream_id, wrapped.to_vec()));
+            }
+        }
+
+        for (stream_id, wrapped) in &stale {
+            let key = self.unwrap_key(*stream_id, wrapped)?;
+            let rewrapped = self.wrap_key(*stream_id, &key)?;
+            self.storage.keystore.put(&mut txn, stream_id, &rewrapped)?;
+        }
+        txn.commit()?;
+        Ok(stale.len())
+    }
+
+    /// Like [`rewrap_all_keys`](Self::rewrap_all_keys), but commits every `batch_size`
+    /// re-wrapped keys and stops between batches once `cancel` is cancelled.
+    ///
+    /// For keystores large enough that one transaction would hold the write lock for too
+    /// long. Every batch commits on its own, and a key is either wrapped with its old or
+    /// its new master key, so stopping early leaves the keystore consistent; calling this
+    /// again resumes, since keys already on the latest version are skipped. Returns how many
+    /// keys were re-wrapped by this call.
+    ///
+    /// ```rust
+    /// # use varvedb::crypto::KeyManager;
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::processor::CancellationToken;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use tempfile::tempdir;
+    /// # use zeroize::Zeroizing;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// let mut config = StorageConfig {
+    ///     path: dir.path().to_path_buf(),
+    ///     encryption_enabled: true,
+    ///     master_key: Some(Zeroizing::new([1u8; 32])),
+    ///     ..Default::default()
+    /// };
+    /// # {
+    /// # let mut writer = Writer::<u64>::new(Storage::open(config.clone())?);
+    /// # writer.append(1, 1, 10)?;
+    /// # writer.append(2, 1, 20)?;
+    /// # }
+    /// // Rotate: add a newer master key, then re-wrap the stream keys with it.
+    /// config.master_keys.insert(1, Zeroizing::new([2u8; 32]));
+    /// let keys = KeyManager::new(Storage::open(config)?);
+    ///
+    /// let cancel = CancellationToken::new();
+    /// // e.g. cancelled from a signal handler
+    /// let rewrapped = keys.rewrap_keys_in_batches(1000, &cancel)?;
+    /// assert_eq!(rewrapped, 2);
+    /// assert_eq!(keys.key_version(1)?, Some(1));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `batch_size` is 0.
+    /// Fails like `rewrap_all_keys` if a key cannot be unwrapped or the writer lease is lost;
+    /// batches committed before the failure stay committed.
+    pub fn rewrap_keys_in_batches(
+        &self,
+        batch_size: usize,
+        cancel: &crate::processor::CancellationToken,
+    ) -> crate::error::Result<usize> {
+        if batch_size == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+
+        let latest = self.latest_master_version();
+        let mut rewrapped = 0;
+        let mut from = std::ops::Bound::Unbounded;
+        while !cancel.is_cancelled() {
+            let mut txn = self.storage.env.write_txn()?;
+            self.storage.check_writer_lease(&txn)?;
+            let mut stale = Vec::with_capacity(batch_size);
+            let mut last = None;
+            for result in self
+                .storage
+                .keystore
+                .range(&txn, &(from, std::ops::Bound::Unbounded))?
+            {
+                let (stream_id, wrapped) = result?;
+                last = Some(stream_id);
+                if split_wrapped(wrapped).0 != latest {
+                    stale.push((stream_id, wrapped.to_vec()));
+                    if stale.len() == batch_size {
+                        break;
+                    }
                 }
-                key.copy_from_slice(&plaintext_key_vec);
-                Ok(Some(key))
             }
-            None => Ok(None),
+
+            for (stream_id, wrapped) in &stale {
+                let key = self.unwrap_key(*stream_id, wrapped)?;
+                let rewrapped = self.wrap_key(*stream_id, &key)?;
+                self.storage.keystore.put(&mut txn, stream_id, &rewrapped)?;
+            }
+            txn.commit()?;
+            rewrapped += stale.len();
+
+            match last {
+                Some(stream_id) if stale.len() == batch_size => {
+                    from = std::ops::Bound::Excluded(stream_id);
+                }
+                _ => break,
+            }
         }
+        Ok(rewrapped)
     }
 
     pub fn delete_key(&self, stream_id: u128) -> crate::error::Result<()> {
+        let stream_id = self.storage.stored_stream_id(stream_id);
         let mut txn = self.storage.env.write_txn()?;
         self.storage.keystore.delete(&mut txn, &stream_id)?;
         txn.commit()?;
+        self.lock_cache().remove(stream_id);
         Ok(())
     }
 }
 
+/// Splits a keystore value into its master key version and the encrypted key.
+pub(crate) fn split_wrapped(wrapped: &[u8]) -> (u8, &[u8]) {
+    if wrapped.len() == crate::constants::WRAPPED_KEY_SIZE + 1 {
+        (wrapped[0], &wrapped[1..])
+    } else {
+        (0, wrapped)
+    }
+}
+
+/// A bounded LRU map from on-disk stream ID to the unwrapped key and the wrapped bytes it
+/// was decrypted from.
+struct KeyCache {
+    capacity: usize,
+    /// Incremented on every access; entries remember the tick of their last use.
+    tick: u64,
+    entries: HashMap<u128, CachedKey>,
+}
+
+struct CachedKey {
+    wrapped: Vec<u8>,
+    key: StreamKey,
+    last_used: u64,
+}
+
+// Keeps key material out of `KeyManager`'s `Debug` output.
+impl std::fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+impl KeyCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached key for `stream_id` if it was unwrapped from `wrapped`.
+    fn get(&mut self, stream_id: u128, wrapped: &[u8]) -> Option<StreamKey> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&stream_id)?;
+        if entry.wrapped != wrapped {
+            return None;
+        }
+        entry.last_used = self.tick;
+        Some(entry.key.clone())
+    }
+
+    fn insert(&mut self, stream_id: u128, wrapped: &[u8], key: StreamKey) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&stream_id) {
+            // Eviction scans the map, but only happens once the cache is full.
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| *id);
+            if let Some(id) = lru {
+                self.entries.remove(&id);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(
+            stream_id,
+            CachedKey {
+                wrapped: wrapped.to_vec(),
+                key,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, stream_id: u128) {
+        self.entries.remove(&stream_id);
+    }
+}
+
 /// Encrypts data using AES-256-GCM.
 ///
 /// This function performs authenticated encryption with associated data (AEAD).
@@ -244,3 +552,61 @@ pub fn decrypt(
         .decrypt(nonce, payload)
         .map_err(|e| crate::error::Error::DecryptionError(format!("Decryption failed: {}", e)))
 }
+
+/// Like [`decrypt`], but decrypts into `out`, replacing its contents, instead of allocating.
+///
+/// `out` only grows when the plaintext is larger than its capacity, so reusing one buffer
+/// across calls avoids a per-call allocation.
+///
+/// # Errors
+///
+/// Same as [`decrypt`]. `out` holds unspecified bytes after an error.
+pub fn decrypt_into(
+    key: &[u8; crate::constants::KEY_SIZE],
+    ciphertext_with_nonce: &[u8],
+    aad: &[u8],
+    out: &mut rkyv::util::AlignedVec,
+) -> crate::error::Result<()> {
+    if ciphertext_with_nonce.len() < crate::constants::NONCE_SIZE {
+        return Err(crate::error::Error::InvalidCiphertextLength {
+            actual: ciphertext_with_nonce.len(),
+            minimum: crate::constants::NONCE_SIZE,
+        });
+    }
+
+    let (nonce_bytes, ciphertext) = ciphertext_with_nonce.split_at(crate::constants::NONCE_SIZE);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    out.clear();
+    out.extend_from_slice(ciphertext);
+    cipher
+        .decrypt_in_place(Nonce::from_slice(nonce_bytes), aad, &mut AlignedBuffer(out))
+        .map_err(|e| crate::error::Error::DecryptionError(format!("Decryption failed: {}", e)))
+}
+
+/// Lets AES-GCM decrypt in place into an `AlignedVec`.
+struct AlignedBuffer<'a>(&'a mut rkyv::util::AlignedVec);
+
+impl AsRef<[u8]> for AlignedBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl AsMut<[u8]> for AlignedBuffer<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+impl aes_gcm::aead::Buffer for AlignedBuffer<'_> {
+    fn extend_from_slice(&mut self, other: &[u8]) -> aes_gcm::aead::Result<()> {
+        self.0.extend_from_slice(other);
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len < self.0.len() {
+            self.0.resize(len, 0);
+        }
+    }
+}
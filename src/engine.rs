@@ -6,27 +6,132 @@
 // v. 2.0. If a copy of the MPL was not distributed with this file, You can
 // obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::model::StoragePayload;
-use crate::storage::Storage;
+use crate::model::{Payload, StoragePayload};
+use crate::storage::{MissingBlob, SecondaryIndexDb, Storage};
+use crate::traits::{IndexKey, MetadataExt, StreamState};
+use crate::varve::ExpectedVersion;
 use rkyv::bytecheck::CheckBytes;
 use sha2::{Digest, Sha256};
 
 use crate::crypto::{self, KeyManager};
 use crate::metrics::VarveMetrics;
-use rkyv::api::high::{HighSerializer, HighValidator};
+use rkyv::api::high::{HighDeserializer, HighSerializer, HighValidator};
 use rkyv::rancor::Error as RancorError;
-use rkyv::ser::allocator::ArenaHandle;
+use rkyv::ser::allocator::{Arena, ArenaHandle};
 use rkyv::util::AlignedVec;
 use rkyv::Portable;
 
+use std::ops::Bound;
 use std::sync::Arc;
 
+/// Serializes `value` into the fixed-size `buf` without allocating the output, and returns
+/// the archived bytes.
+///
+/// This is a standalone helper for callers that manage their own buffers; [`Writer`] keeps
+/// serializing into its reusable arena and does not go through it. The bytes are only as
+/// aligned as `buf`; use an aligned buffer to access them in place.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`](crate::error::Error::BufferTooSmall) if the archive does
+/// not fit in `buf`, and [`Error::EventSerialization`](crate::error::Error::EventSerialization)
+/// for other serialization failures. An archive that does not fit is still serialized to the
+/// end, only counting the bytes past `buf`, so `needed` is its exact size.
+pub fn serialize_into<'b, T>(value: &T, buf: &'b mut [u8]) -> crate::error::Result<&'b [u8]>
+where
+    T: for<'a, 'c> rkyv::Serialize<
+        HighSerializer<fixed_buffer::FixedBuffer<'c>, ArenaHandle<'a>, RancorError>,
+    >,
+{
+    let capacity = buf.len();
+    let writer =
+        rkyv::api::high::to_bytes_in::<_, RancorError>(value, fixed_buffer::FixedBuffer::new(buf))?;
+    let (buf, len) = writer.into_parts();
+    if len > capacity {
+        return Err(crate::error::Error::BufferTooSmall {
+            needed: Some(len),
+            capacity,
+        });
+    }
+    Ok(&buf[..len])
+}
+
+mod fixed_buffer {
+    use rkyv::ser::{Positional, Writer};
+
+    /// Writes into a fixed-size slice, and keeps counting once it is full instead of failing,
+    /// so an archive that overflows still reports its size.
+    pub struct FixedBuffer<'b> {
+        buf: &'b mut [u8],
+        pos: usize,
+    }
+
+    impl<'b> FixedBuffer<'b> {
+        pub(super) fn new(buf: &'b mut [u8]) -> Self {
+            Self { buf, pos: 0 }
+        }
+
+        /// Returns the slice and the number of bytes the archive needs, which may exceed it.
+        pub(super) fn into_parts(self) -> (&'b mut [u8], usize) {
+            (self.buf, self.pos)
+        }
+    }
+
+    impl Positional for FixedBuffer<'_> {
+        fn pos(&self) -> usize {
+            self.pos
+        }
+    }
+
+    impl<E> Writer<E> for FixedBuffer<'_> {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), E> {
+            let end = self.pos + bytes.len();
+            if let Some(dest) = self.buf.get_mut(self.pos..end) {
+                dest.copy_from_slice(bytes);
+            }
+            self.pos = end;
+            Ok(())
+        }
+    }
+}
+
 /// Appends events to the store with optimistic concurrency control.
 ///
 /// The `Writer` ensures that events are appended in a strictly ordered sequence. It enforces
 /// optimistic locking by requiring the expected `version` for each stream, preventing
 /// concurrent modifications from overwriting data.
 ///
+/// # Ordering
+///
+/// Every append takes the next global sequence number inside the LMDB write transaction, and
+/// LMDB admits one write transaction at a time, so sequence numbers follow commit order no
+/// matter how appends to different streams interleave. An append must name a version above
+/// the stream's current head, and fails with
+/// [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict) otherwise, so **within a
+/// stream, a higher version always has a higher global sequence number**. Replaying the
+/// global log therefore replays every stream in version order. [`Writer::append_at`] with
+/// gaps allowed places records at caller-chosen sequence numbers, but rejects any that would
+/// land out of order with the stream's other versions. The one exception is
+/// [`Writer::append_reserved`]: reserved versions may be filled after later versions were
+/// appended, and then sit behind them in the global log.
+/// [`Reader::verify_stream_monotonic`] checks the invariant.
+///
+/// # Thread Safety
+///
+/// `Writer` is `Send` and `Sync`, but appending takes `&mut self`, so one writer is used by
+/// one thread at a time. Clones are independent writers over the same storage: they can
+/// append concurrently from different threads or tasks, and LMDB serializes their write
+/// transactions, so every append still sees the latest log head and takes the next sequence
+/// number. Two clones appending to the same stream race on versions and one of them gets a
+/// [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict). To share a single
+/// writer (and its indexes and settings) between tasks instead, wrap it in a [`SyncWriter`].
+///
+/// # Shared Pointers
+///
+/// Events are serialized with rkyv's high-level serializer, which tracks shared pointers.
+/// An `Rc` or `Arc` referenced several times within one event is stored once, every archived
+/// reference points at that copy, and deserializing restores a single shared allocation.
+///
 /// # Examples
 ///
 /// ```rust
@@ -54,9 +159,77 @@ pub struct Writer<E> {
     storage: Storage,
     metrics: Option<Arc<VarveMetrics>>,
     key_manager: Option<KeyManager>,
+    indexes: Vec<SecondaryIndex<E>>,
+    stream_type: String,
+    allow_gaps: bool,
+    schema_version: SchemaVersion,
+    reservation_ttl: std::time::Duration,
+    /// Event ID for the next record, set by `append_with_id`.
+    next_id: Option<u128>,
+    arena: ReusableArena,
+    /// Reads the stream for `append_if`, sharing this writer's key cache and schema version.
+    reader: Reader<E>,
     _marker: std::marker::PhantomData<E>,
 }
 
+/// The default time versions reserved with [`Writer::reserve_versions`] stay reserved.
+pub const DEFAULT_RESERVATION_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Scratch space for the serializer, reused across appends instead of being allocated per call.
+///
+/// Only ever accessed through `&mut self` (via `Mutex::get_mut`), so it never locks; the
+/// mutex only keeps `Writer` `Sync`, since `Arena` itself is not.
+struct ReusableArena(std::sync::Mutex<Arena>);
+
+impl ReusableArena {
+    fn new() -> Self {
+        Self(std::sync::Mutex::new(Arena::new()))
+    }
+
+    fn get_mut(&mut self) -> &mut Arena {
+        self.0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for ReusableArena {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReusableArena").finish_non_exhaustive()
+    }
+}
+
+/// Extracts the encoded secondary index key from an event, if any.
+type IndexKeyFn<E> = Arc<dyn Fn(&E) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Tells snapshot events apart, see [`Writer::trim_stream_keeping_snapshot`].
+type IsSnapshotFn<'f, E> = &'f dyn Fn(&<E as rkyv::Archive>::Archived) -> bool;
+
+/// A secondary index maintained by a [`Writer`] at append time.
+struct SecondaryIndex<E> {
+    name: String,
+    db: SecondaryIndexDb,
+    key_fn: IndexKeyFn<E>,
+}
+
+impl<E> Clone for SecondaryIndex<E> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            db: self.db,
+            key_fn: self.key_fn.clone(),
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for SecondaryIndex<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecondaryIndex")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<E> Writer<E>
 where
     E: rkyv::Archive
@@ -71,9 +244,17 @@ where
         };
 
         Self {
+            reader: Reader::with_key_manager(storage.clone(), key_manager.clone()),
             storage,
             metrics: None,
             key_manager,
+            indexes: Vec::new(),
+            stream_type: std::any::type_name::<E>().to_string(),
+            allow_gaps: false,
+            schema_version: 0,
+            reservation_ttl: DEFAULT_RESERVATION_TTL,
+            next_id: None,
+            arena: ReusableArena::new(),
             _marker: std::marker::PhantomData,
         }
     }
@@ -84,6 +265,112 @@ where
         self
     }
 
+    /// Sets the type tag recorded for streams written by this writer.
+    ///
+    /// Only used when [`StorageConfig::enforce_stream_types`](crate::storage::StorageConfig::enforce_stream_types)
+    /// is enabled. The default tag is `std::any::type_name::<E>()`, which is not guaranteed to
+    /// be stable across compiler versions or module moves; set an explicit tag for databases
+    /// that outlive a single build.
+    pub fn with_stream_type(mut self, tag: impl Into<String>) -> Self {
+        self.stream_type = tag.into();
+        self
+    }
+
+    /// Allows [`append_at`](Self::append_at) to leave holes in the global sequence.
+    ///
+    /// Off by default. Gaps stall sequential consumers such as `Processor`, which stop at
+    /// the first missing sequence number until it is filled. Sequences deleted by
+    /// [`trim_stream`](Self::trim_stream) are not gaps; those are skipped.
+    pub fn with_allow_gaps(mut self, allow_gaps: bool) -> Self {
+        self.allow_gaps = allow_gaps;
+        self
+    }
+
+    /// Sets how long versions reserved with [`reserve_versions`](Self::reserve_versions) stay
+    /// reserved. Defaults to [`DEFAULT_RESERVATION_TTL`].
+    pub fn with_reservation_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.reservation_ttl = ttl;
+        self
+    }
+
+    /// Tags every event written by this writer with schema version `version`.
+    ///
+    /// Bump it whenever the layout of `E` changes incompatibly, so that readers of the new
+    /// type can upcast older events with [`Reader::with_upcaster`]. Version 0, the default,
+    /// writes untagged records, which readers treat as version 0.
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = version;
+        self.reader.schema_version = version;
+        self
+    }
+
+    /// Upcasts older events read by [`append_if`](Self::append_if), as
+    /// [`Reader::with_upcaster`] does.
+    ///
+    /// Only needed when the stream may hold events written with an older schema version than
+    /// [`with_schema_version`](Self::with_schema_version).
+    pub fn with_upcaster<F>(mut self, upcaster: F) -> Self
+    where
+        F: Fn(SchemaVersion, &[u8]) -> crate::error::Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.reader.upcaster = Some(Arc::new(upcaster));
+        self
+    }
+
+    /// Registers a secondary index maintained on every append.
+    ///
+    /// At append time, `key_fn` is called with the event. If it returns `Some(key)`, the
+    /// event's global sequence number is recorded under `key` in the `index_name` index,
+    /// within the same transaction as the event itself. Events appended before the index was
+    /// registered are not indexed retroactively.
+    ///
+    /// Query the index with [`Reader::find_by`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Writer, Reader};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize, Debug)]
+    /// #[rkyv(derive(Debug))]
+    /// struct Payment { pub customer_id: u64, pub amount: u64 }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::<Payment>::new(storage.clone());
+    /// writer.with_index("customer", |p: &Payment| Some(p.customer_id))?;
+    /// writer.append(1, 1, Payment { customer_id: 7, amount: 100 })?;
+    ///
+    /// let reader = Reader::<Payment>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let payments = reader.find_by(&txn, "customer", &7u64)?;
+    /// assert_eq!(payments.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index database cannot be created (e.g. `max_dbs` is exhausted).
+    pub fn with_index<K, F>(&mut self, index_name: &str, key_fn: F) -> crate::error::Result<()>
+    where
+        K: IndexKey,
+        F: Fn(&E) -> Option<K> + Send + Sync + 'static,
+    {
+        let db = self.storage.create_secondary_index(index_name)?;
+        self.indexes.push(SecondaryIndex {
+            name: index_name.to_string(),
+            db,
+            key_fn: Arc::new(move |event| key_fn(event).map(|k| k.to_index_bytes())),
+        });
+        Ok(())
+    }
+
     /// Returns a receiver for real-time event notifications.
     pub fn subscribe(&self) -> tokio::sync::watch::Receiver<u64> {
         self.storage.notifier.subscribe()
@@ -96,6 +383,14 @@ impl<E> Clone for Writer<E> {
             storage: self.storage.clone(),
             metrics: self.metrics.clone(),
             key_manager: self.key_manager.clone(),
+            indexes: self.indexes.clone(),
+            stream_type: self.stream_type.clone(),
+            allow_gaps: self.allow_gaps,
+            schema_version: self.schema_version,
+            reservation_ttl: self.reservation_ttl,
+            next_id: None,
+            arena: ReusableArena::new(),
+            reader: self.reader.clone(),
             _marker: std::marker::PhantomData,
         }
     }
@@ -113,458 +408,4831 @@ where
     /// # Errors
     ///
     /// Returns an error if:
-    /// *   The `stream_id` and `version` pair already exists (Concurrency Conflict).
+    /// *   The `stream_id` and `version` pair already exists, or `version` is not above the
+    ///     stream's head (Concurrency Conflict).
+    /// *   `version` exceeds `StorageConfig::max_stream_versions` (Stream Full).
+    /// *   `stream_id` is [`RAW_LOG_STREAM_ID`](crate::constants::RAW_LOG_STREAM_ID), which
+    ///     is reserved for [`append_raw`](Self::append_raw) (Invalid Config).
     /// *   Serialization of the event fails.
     /// *   Encryption fails (if enabled).
     /// *   The underlying storage encounters an I/O error.
     pub fn append(&mut self, stream_id: u128, version: u32, event: E) -> crate::error::Result<u64> {
+        self.append_event(stream_id, version, &event)
+            .map(|(seq, _)| seq)
+    }
+
+    /// Like [`append`](Self::append), but stores the event under event ID `id` instead of
+    /// a writer-assigned one.
+    ///
+    /// Requires [`LogKey::U128`](crate::storage::LogKey::U128). IDs must increase with the
+    /// log, so `id` has to be greater than every ID stored so far; time-sortable IDs such as
+    /// ULIDs from a single generator satisfy this.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if the storage was not
+    /// created with `LogKey::U128` or `id` is not greater than the last ID, and otherwise
+    /// the same errors as [`append`](Self::append).
+    pub fn append_with_id(
+        &mut self,
+        stream_id: u128,
+        version: u32,
+        id: u128,
+        event: E,
+    ) -> crate::error::Result<u64> {
+        if self.storage.events_by_id.is_none() {
+            return Err(crate::error::Error::InvalidConfig(
+                "append_with_id requires LogKey::U128".to_string(),
+            ));
+        }
+
+        self.next_id = Some(id);
+        let result = self.append_event(stream_id, version, &event);
+        self.next_id = None;
+        result.map(|(seq, _)| seq)
+    }
+
+    /// Like [`append`](Self::append), but also returns the archived event bytes it stored.
+    ///
+    /// These are the same bytes [`EventView::to_bytes`] returns when reading the event back
+    /// (before encryption and blob offloading), so they can be handed straight to a replica's
+    /// [`append_at`](Self::append_at) or put in a write-through cache without reading the
+    /// event again.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`append`](Self::append).
+    pub fn append_returning_bytes(
+        &mut self,
+        stream_id: u128,
+        version: u32,
+        event: E,
+    ) -> crate::error::Result<(u64, Vec<u8>)> {
+        self.append_event(stream_id, version, &event)
+            .map(|(seq, bytes)| (seq, bytes.to_vec()))
+    }
+
+    /// Starts a new stream with `event` as version 1, under a stream ID chosen by the writer.
+    ///
+    /// The ID is drawn at random and checked against the stream index, and any live version
+    /// reservations, inside the same write transaction that writes the event, so it never
+    /// collides with an existing stream, whether that was created here or by the caller.
+    /// Returns the new stream ID and the sequence number of the event.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize, Debug)]
+    /// # struct CartOpened { pub customer: u64 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::new(storage.clone());
+    /// let (cart_id, _seq) = writer.create_stream(CartOpened { customer: 7 })?;
+    ///
+    /// // Later events go to the assigned stream as usual.
+    /// writer.append(cart_id, 2, CartOpened { customer: 7 })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`append`](Self::append).
+    pub fn create_stream(&mut self, event: E) -> crate::error::Result<(u128, u64)> {
         let _timer = self
             .metrics
             .as_ref()
             .map(|m| m.append_latency.start_timer());
 
-        let mut txn = self.storage.env.write_txn()?;
-
-        // Concurrency Check
-        let key = crate::storage::StreamKey::new(stream_id, version);
-        let key_bytes = key.to_be_bytes();
-
-        if self
-            .storage
-            .stream_index
-            .get(&txn, key_bytes.as_slice())?
-            .is_some()
-        {
-            // We don't know the expected version here, but we know the current version exists.
-            // Actually, the error I defined `VersionMismatch` expects `expected` and `actual`.
-            // But here we just know that `version` already exists.
-            // Maybe I should add `StreamVersionExists` error?
-            // Or just use `VersionMismatch` with some assumption?
-            // The original code was: "Concurrency conflict: Stream {} version {} already exists"
-
-            // Let's check what I defined in error.rs:
-            // VersionMismatch { stream_id, expected, actual }
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let stream_id = loop {
+            let candidate = rand::random::<u128>();
+            if candidate != crate::constants::RAW_LOG_STREAM_ID
+                && self.next_version(&txn, candidate)? == 1
+            {
+                break candidate;
+            }
+        };
+        let (new_seq, bytes_len, _) = self.write_event(&mut txn, stream_id, 1, &event)?;
+        self.commit(txn)?;
 
-            // If I am trying to write version V, and it exists, it means actual is >= V.
-            // But I don't know the head version without querying it.
+        self.storage.publish_head(new_seq);
 
-            // Let's add `ConcurrencyConflict` error to `error.rs` instead of reusing `VersionMismatch` incorrectly here.
-            return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
         }
 
-        // Get next Global Sequence
-        let last_seq = self
-            .storage
-            .events_log
-            .last(&txn)?
-            .map(|(k, _)| k)
-            .unwrap_or(0);
-        let new_seq = last_seq + 1;
-
-        // Serialize Event
-        let event_bytes = rkyv::api::high::to_bytes::<rkyv::rancor::Error>(&event)?;
-
-        // Check size and determine Payload
-        let payload = if event_bytes.len() > crate::constants::MAX_INLINE_SIZE {
-            // Large Payload: Store in Blobs DB
-            let mut hasher = Sha256::new();
-            hasher.update(&event_bytes);
-            let hash = hasher.finalize();
-            let hash_array: [u8; 32] = hash.into();
+        Ok((stream_id, new_seq))
+    }
 
-            self.storage
-                .blobs
-                .put(&mut txn, hash_array.as_slice(), event_bytes.as_slice())?;
-            StoragePayload::BlobRef(hash_array)
-        } else {
-            // Small Payload: Inline
-            StoragePayload::Inline(event_bytes.into_vec())
+    /// Like [`append`](Self::append), but backs off when the map is nearly full.
+    ///
+    /// Returns `Ok(None)` without writing if the free map space is below
+    /// [`StorageConfig::append_reserve_bytes`](crate::storage::StorageConfig::append_reserve_bytes),
+    /// so an ingest pipeline can slow down, or grow the map, instead of failing mid-batch
+    /// with `MDB_MAP_FULL`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`append`](Self::append).
+    pub fn try_append(
+        &mut self,
+        stream_id: u128,
+        version: u32,
+        event: E,
+    ) -> crate::error::Result<Option<u64>> {
+        let free = {
+            let txn = self.storage.env.read_txn()?;
+            self.storage.free_space_bytes(&txn)?
         };
+        if free < self.storage.config.append_reserve_bytes {
+            tracing::debug!(
+                "Backing off append: {} bytes free, reserve is {}",
+                free,
+                self.storage.config.append_reserve_bytes
+            );
+            return Ok(None);
+        }
+        self.append(stream_id, version, event).map(Some)
+    }
 
-        // Serialize Payload
-        let bytes = rkyv::api::high::to_bytes::<rkyv::rancor::Error>(&payload)?;
-
-        // Encrypt if enabled
-        let final_bytes = if let Some(km) = &self.key_manager {
-            let key = km.get_or_create_key_with_txn(&mut txn, stream_id)?;
-
-            // Construct AAD: StreamID (16 bytes) + GlobalSeq (8 bytes)
-            let mut aad = [0u8; crate::constants::AAD_CAPACITY];
-            aad[..crate::constants::STREAM_ID_SIZE].copy_from_slice(&stream_id.to_be_bytes());
-            aad[crate::constants::STREAM_ID_SIZE..].copy_from_slice(&new_seq.to_be_bytes());
-
-            let mut encrypted = crypto::encrypt(&key, &bytes, &aad)?;
+    /// Appends `payload.event` and stores `payload.metadata` alongside it.
+    ///
+    /// The stream ID is taken from the metadata, and the version is resolved from `expected`
+    /// exactly like [`Varve::append`](crate::Varve::append) does, so callers no longer pass
+    /// the stream ID twice. The metadata is archived with rkyv and written in the same
+    /// transaction as the event, encrypted with the stream's key if encryption is enabled;
+    /// read it back with [`Reader::get_metadata`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`append`](Self::append), plus serialization errors for the metadata.
+    pub fn append_payload<M>(
+        &mut self,
+        payload: Payload<E, M>,
+        expected: ExpectedVersion,
+    ) -> crate::error::Result<u64>
+    where
+        M: MetadataExt
+            + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, RancorError>>,
+    {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
 
-            // Prepend StreamID (16 bytes) to allow Reader to find the key
-            let mut final_vec =
-                Vec::with_capacity(crate::constants::STREAM_ID_SIZE + encrypted.len());
-            final_vec.extend_from_slice(&stream_id.to_be_bytes());
-            final_vec.append(&mut encrypted);
-            final_vec
-        } else {
-            bytes.to_vec()
+        let stream_id = payload.metadata.stream_id();
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let version = match expected {
+            ExpectedVersion::Exact(v) => v.get(),
+            ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
         };
+        let (new_seq, bytes_len, _) =
+            self.write_event(&mut txn, stream_id, version, &payload.event)?;
 
-        let bytes_len = final_bytes.len() as u64;
-
-        // Write to Log and Index
-        self.storage
-            .events_log
-            .put(&mut txn, &new_seq, &final_bytes)?;
+        let metadata = rkyv::api::high::to_bytes_with_alloc::<_, RancorError>(
+            &payload.metadata,
+            self.arena.get_mut().acquire(),
+        )?;
+        let record = self.encode_metadata(&mut txn, new_seq, stream_id, &metadata)?;
         self.storage
-            .stream_index
-            .put(&mut txn, key_bytes.as_slice(), &new_seq)?;
-
-        txn.commit()?;
+            .event_metadata
+            .create(&mut txn)?
+            .put(&mut txn, &new_seq, &record)?;
+        self.commit(txn)?;
 
-        // Notify Subscribers
-        let _ = self.storage.notifier.send(new_seq);
-
-        // Metrics
+        self.storage.publish_head(new_seq);
         if let Some(metrics) = &self.metrics {
             metrics.events_appended.inc();
-            metrics.bytes_written.inc_by(bytes_len);
+            metrics
+                .bytes_written
+                .inc_by(bytes_len + record.len() as u64);
         }
 
         Ok(new_seq)
     }
-}
-
-pub enum EventData<'a> {
-    Borrowed(&'a [u8]),
-    Owned(Vec<u8>),
-}
-
-pub struct EventView<'a, E>
-where
-    E: rkyv::Archive,
-{
-    data: EventData<'a>,
-    _marker: std::marker::PhantomData<E>,
-}
-
-impl<'a, E> std::ops::Deref for EventView<'a, E>
-where
-    E: rkyv::Archive,
-    E::Archived: Portable,
-{
-    type Target = E::Archived;
 
-    fn deref(&self) -> &Self::Target {
-        let bytes = match &self.data {
-            EventData::Borrowed(b) => *b,
-            EventData::Owned(b) => b.as_slice(),
-        };
-        // Safety: We verify the bytes in Reader::get using rkyv::check_archived_root
-        // unsafe { rkyv::archived_root::<E>(bytes) }
-        // Safety: We verify the bytes in Reader::get using rkyv::access
+    /// Appends `event` only if `predicate` accepts the current state of the stream.
+    ///
+    /// The stream's events are folded, in version order, into `S::default()` with
+    /// [`StreamState::apply`], and `predicate` is called with the result. Both happen inside
+    /// the write transaction that appends the event, so no other append can change the
+    /// stream between the check and the write. This is how to enforce invariants such as
+    /// "refund an order at most once" without a separate read.
+    ///
+    /// The version is resolved from `expected` as in [`append_payload`](Self::append_payload).
+    /// Returns `Ok(None)`, without writing anything, if `predicate` returns `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use varvedb::{ExpectedVersion, StreamState};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize, Debug)]
+    /// # enum OrderEvent { Placed, Refunded }
+    /// #
+    /// #[derive(Default)]
+    /// struct Order {
+    ///     refunded: bool,
+    /// }
+    ///
+    /// impl StreamState<OrderEvent> for Order {
+    ///     fn apply(&mut self, event: &ArchivedOrderEvent) {
+    ///         if matches!(event, ArchivedOrderEvent::Refunded) {
+    ///             self.refunded = true;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::new(storage.clone());
+    /// writer.append(1, 1, OrderEvent::Placed)?;
+    ///
+    /// let not_refunded = |order: &Order| !order.refunded;
+    /// let refund = writer.append_if(1, ExpectedVersion::Auto, OrderEvent::Refunded, not_refunded)?;
+    /// assert_eq!(refund, Some(2));
+    /// let again = writer.append_if(1, ExpectedVersion::Auto, OrderEvent::Refunded, not_refunded)?;
+    /// assert_eq!(again, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`append`](Self::append), plus any error reading the stream's events.
+    pub fn append_if<S, P>(
+        &mut self,
+        stream_id: u128,
+        expected: ExpectedVersion,
+        event: E,
+        predicate: P,
+    ) -> crate::error::Result<Option<u64>>
+    where
+        E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+        S: StreamState<E>,
+        P: Fn(&S) -> bool,
+    {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let head = self.stream_head(&txn, stream_id)?;
+        let version = match expected {
+            ExpectedVersion::Exact(v) => v.get(),
+            ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
+        };
+
+        let mut state = S::default();
+        for (_, view) in
+            self.reader
+                .get_by_stream_range(&txn, stream_id, 1, head.saturating_add(1))?
+        {
+            state.apply(&view);
+        }
+        if !predicate(&state) {
+            // Dropping the transaction aborts it.
+            return Ok(None);
+        }
+
+        let (new_seq, bytes_len, _) = self.write_event(&mut txn, stream_id, version, &event)?;
+        self.commit(txn)?;
+
+        self.storage.publish_head(new_seq);
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok(Some(new_seq))
+    }
+
+    /// Appends one event in its own transaction, returning its sequence and archived bytes.
+    fn append_event(
+        &mut self,
+        stream_id: u128,
+        version: u32,
+        event: &E,
+    ) -> crate::error::Result<(u64, AlignedVec)> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        // `write_event` needs `&mut self` for the arena, so the txn borrows a handle clone.
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let (new_seq, bytes_len, event_bytes) =
+            self.write_event(&mut txn, stream_id, version, event)?;
+        self.commit(txn)?;
+
+        // Notify Subscribers
+        self.storage.publish_head(new_seq);
+
+        // Metrics
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok((new_seq, event_bytes))
+    }
+
+    /// Appends events to several streams in a single atomic transaction.
+    ///
+    /// Each item is `(stream_id, expected_version, event)`. Items are applied in order, so
+    /// several items may target the same stream; [`ExpectedVersion::Auto`] resolves against
+    /// the stream head including earlier items of the same call.
+    ///
+    /// Either every event is committed or none is: if any version check, serialization or
+    /// write fails, the whole transaction is rolled back and the error is returned.
+    ///
+    /// Returns the global sequence numbers assigned to the events, in item order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use varvedb::ExpectedVersion;
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize, Debug)]
+    /// # #[rkyv(derive(Debug))]
+    /// # struct Transfer { pub amount: i64 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::new(storage.clone());
+    ///
+    /// // Debit one account and credit another, atomically.
+    /// let seqs = writer.append_multi(vec![
+    ///     (1, ExpectedVersion::exact(1), Transfer { amount: -50 }),
+    ///     (2, ExpectedVersion::Auto, Transfer { amount: 50 }),
+    /// ])?;
+    /// assert_eq!(seqs, vec![1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict) if any
+    /// `Exact` version already exists, plus any error [`append`](Self::append) can return.
+    pub fn append_multi(
+        &mut self,
+        items: Vec<(u128, ExpectedVersion, E)>,
+    ) -> crate::error::Result<Vec<u64>> {
+        self.append_iter(items)
+    }
+
+    /// Like [`append_multi`](Self::append_multi), but drains `items` inside the write
+    /// transaction instead of taking them collected.
+    pub(crate) fn append_iter(
+        &mut self,
+        items: impl IntoIterator<Item = (u128, ExpectedVersion, E)>,
+    ) -> crate::error::Result<Vec<u64>> {
+        let mut items = items.into_iter().peekable();
+        if items.peek().is_none() {
+            return Ok(Vec::new());
+        }
+
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let mut seqs = Vec::with_capacity(items.size_hint().0);
+        let mut total_bytes = 0;
+
+        for (stream_id, expected, event) in items {
+            let version = match expected {
+                ExpectedVersion::Exact(v) => v.get(),
+                ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
+            };
+            // An early return drops `txn`, aborting everything written so far.
+            let (seq, bytes_len, _) = self.write_event(&mut txn, stream_id, version, &event)?;
+            seqs.push(seq);
+            total_bytes += bytes_len;
+        }
+
+        self.commit(txn)?;
+
+        if let Some(&last_seq) = seqs.last() {
+            self.storage.publish_head(last_seq);
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc_by(seqs.len() as u64);
+            metrics.bytes_written.inc_by(total_bytes);
+        }
+
+        Ok(seqs)
+    }
+
+    /// Reserves the next `count` versions of a stream for [`append_reserved`](Self::append_reserved).
+    ///
+    /// A command handler that emits several events can take its versions up front, e.g. to
+    /// hand out IDs derived from them, before computing the events. The reservation is
+    /// recorded in the `stream_reservations` table, so it holds across writers and
+    /// processes: until it expires, plain appends to a reserved version fail with
+    /// [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict), and
+    /// [`ExpectedVersion::Auto`] resolves past it.
+    ///
+    /// Reservations expire after the writer's
+    /// [`reservation_ttl`](Self::with_reservation_ttl). Versions of an expired reservation
+    /// that were never filled become free again: those above the stream's head can be
+    /// appended normally and are handed out by later reservations.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct LineAdded { line: u32 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::new(storage);
+    /// let versions = writer.reserve_versions(7, 3)?;
+    /// assert_eq!(versions, 1..4);
+    /// for version in versions {
+    ///     writer.append_reserved(7, version, LineAdded { line: version })?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `count` is 0 or the
+    /// range would pass `u32::MAX`, or an error if the underlying storage fails.
+    pub fn reserve_versions(
+        &mut self,
+        stream_id: u128,
+        count: u32,
+    ) -> crate::error::Result<std::ops::Range<u32>> {
+        if count == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "reservation count must be greater than 0".to_string(),
+            ));
+        }
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+
+        // Expired reservations are dropped, so their unfilled versions can be handed out again.
+        let now = now_millis();
+        let reservations = self.storage.stream_reservations.create(&mut txn)?;
+        for (range, expires_at) in self.reservations(&txn, stream_id)? {
+            if expires_at <= now {
+                let key = self.storage.stream_key(stream_id, range.start);
+                reservations.delete(&mut txn, key.as_slice())?;
+            }
+        }
+
+        let start = self.next_version(&txn, stream_id)?;
+        let end = start.checked_add(count).ok_or_else(|| {
+            crate::error::Error::InvalidConfig("reserved versions would overflow".to_string())
+        })?;
+        self.check_not_raw(stream_id)?;
+        self.check_stream_cap(stream_id, end - 1)?;
+        let mut value = [0u8; 12];
+        value[..4].copy_from_slice(&end.to_be_bytes());
+        value[4..].copy_from_slice(
+            &now.saturating_add(millis(self.reservation_ttl))
+                .to_be_bytes(),
+        );
+        let key = self.storage.stream_key(stream_id, start);
+        reservations.put(&mut txn, key.as_slice(), &value)?;
+        txn.commit()?;
+
+        Ok(start..end)
+    }
+
+    /// Appends an event at a version reserved with [`reserve_versions`](Self::reserve_versions).
+    ///
+    /// Reserved versions can be filled in any order, by any writer. Once every version of a
+    /// reservation is filled, the reservation is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict) if `version`
+    /// is not in an unexpired reservation (it may have been taken by another append since)
+    /// or is already filled, plus any error [`append`](Self::append) can return.
+    pub fn append_reserved(
+        &mut self,
+        stream_id: u128,
+        version: u32,
+        event: E,
+    ) -> crate::error::Result<u64> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+
+        let now = now_millis();
+        let Some((range, _)) = self
+            .reservations(&txn, stream_id)?
+            .into_iter()
+            .find(|(range, expires_at)| *expires_at > now && range.contains(&version))
+        else {
+            return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+        };
+        let (new_seq, bytes_len, _) = self.write_slot(&mut txn, stream_id, version, &event)?;
+
+        let start = self.storage.stream_key(stream_id, range.start);
+        let end = self.storage.stream_key(stream_id, range.end);
+        let bounds = (
+            Bound::Included(start.as_slice()),
+            Bound::Excluded(end.as_slice()),
+        );
+        let filled = self.storage.stream_index.range(&txn, &bounds)?.count();
+        if filled == range.len() {
+            self.storage
+                .stream_reservations
+                .create(&mut txn)?
+                .delete(&mut txn, start.as_slice())?;
+        }
+        self.commit(txn)?;
+
+        self.storage.publish_head(new_seq);
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok(new_seq)
+    }
+
+    /// Appends an event and moves a consumer cursor in the same transaction.
+    ///
+    /// Sets `consumer_cursors[consumer_id]` to `cursor_seq` (the last input sequence the
+    /// consumer has processed, as a [`Processor`](crate::processor::Processor) stores it) and
+    /// appends `event` atomically: after a crash either both are visible or neither is. A
+    /// projection that writes derived events this way never emits an output twice or skips
+    /// an input, which separate `append` and cursor commits cannot guarantee.
+    ///
+    /// `expected` is resolved like in [`append_multi`](Self::append_multi). Returns the
+    /// global sequence number of the appended event.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error [`append`](Self::append) can return; the cursor is left unchanged
+    /// when the append fails.
+    pub fn append_and_commit_cursor(
+        &mut self,
+        stream_id: u128,
+        expected: ExpectedVersion,
+        event: E,
+        consumer_id: u64,
+        cursor_seq: u64,
+    ) -> crate::error::Result<u64> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let version = match expected {
+            ExpectedVersion::Exact(v) => v.get(),
+            ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
+        };
+        let (new_seq, bytes_len, _) = self.write_event(&mut txn, stream_id, version, &event)?;
+        self.storage
+            .consumer_cursors
+            .put(&mut txn, &consumer_id, &cursor_seq)?;
+        self.commit(txn)?;
+
+        self.storage.publish_head(new_seq);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok(new_seq)
+    }
+
+    /// Appends `event` and stores `entry` as a pending outbox message, atomically.
+    ///
+    /// The event and the message commit in the same transaction, so a message exists if
+    /// and only if its event does; an [`OutboxRelay`](crate::outbox::OutboxRelay) delivers it
+    /// afterwards. `expected` is resolved like in [`append_multi`](Self::append_multi).
+    /// With encryption enabled, the message is encrypted with the key of `stream_id`, like the
+    /// event. Returns the global sequence number of the appended event.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error [`append`](Self::append) can return; no message is stored when the
+    /// append fails.
+    pub fn append_with_outbox(
+        &mut self,
+        stream_id: u128,
+        expected: ExpectedVersion,
+        event: E,
+        entry: crate::outbox::OutboxEntry,
+    ) -> crate::error::Result<u64> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let version = match expected {
+            ExpectedVersion::Exact(v) => v.get(),
+            ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
+        };
+        let (new_seq, bytes_len, _) = self.write_event(&mut txn, stream_id, version, &event)?;
+        crate::outbox::put_pending(
+            &self.storage,
+            &mut txn,
+            self.encrypting_key_manager(stream_id),
+            stream_id,
+            new_seq,
+            &entry,
+        )?;
+        self.commit(txn)?;
+
+        self.storage.publish_head(new_seq);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok(new_seq)
+    }
+
+    /// Checks whether appending `event` to `stream_id` would succeed, without writing it.
+    ///
+    /// Runs the checks an append with the same `expected` would run, against a read
+    /// transaction: the writer lease, reservations, the optimistic concurrency check, stream
+    /// type enforcement, [`max_stream_versions`](crate::storage::StorageConfig::max_stream_versions),
+    /// [`max_serialize_bytes`](crate::storage::StorageConfig::max_serialize_bytes) and
+    /// [`reject_consecutive_duplicates`](crate::storage::StorageConfig::reject_consecutive_duplicates).
+    /// Useful for "validate, then confirm" flows. Another writer can still append in
+    /// between, so the append itself may fail even after this succeeded.
+    ///
+    /// Since it only borrows the writer, the event is serialized into a fresh buffer rather
+    /// than the writer's reusable arena. The bytes are the same; only the allocation differs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use varvedb::ExpectedVersion;
+    /// # use tempfile::tempdir;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::<u64>::new(storage);
+    /// writer.append(1, 1, 10)?;
+    ///
+    /// assert!(writer.check_append(1, ExpectedVersion::exact(2), &20).is_ok());
+    /// assert!(writer.check_append(1, ExpectedVersion::exact(1), &20).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error [`append_multi`](Self::append_multi) would return for the same
+    /// event, or an error if the read transaction fails.
+    pub fn check_append(
+        &self,
+        stream_id: u128,
+        expected: ExpectedVersion,
+        event: &E,
+    ) -> crate::error::Result<()> {
+        let txn = self.storage.env.read_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let version = match expected {
+            ExpectedVersion::Exact(v) => v.get(),
+            ExpectedVersion::Auto => self.next_version(&txn, stream_id)?,
+        };
+        self.check_unreserved(&txn, stream_id, version)?;
+        self.check_above_head(&txn, stream_id, version)?;
+        self.verify_stream_slot(&txn, stream_id, version)?;
+
+        let event_bytes = rkyv::to_bytes::<RancorError>(event)?;
+        self.check_event_size(event_bytes.len())?;
+        self.verify_not_duplicate(&txn, stream_id, version, &event_bytes)?;
+        Ok(())
+    }
+
+    /// Appends an event to the global log without a stream.
+    ///
+    /// The event gets the next global sequence number, like any other append, but no stream
+    /// index entry: there is no version, no optimistic concurrency check and it is invisible
+    /// to stream lookups such as [`Reader::get_by_stream`]. Read it back by sequence number
+    /// with [`Reader::get`] or by iterating the log; secondary indexes are maintained as usual.
+    ///
+    /// With encryption enabled, raw events are encrypted with the key of stream
+    /// [`RAW_LOG_STREAM_ID`](crate::constants::RAW_LOG_STREAM_ID), which is reserved for
+    /// them, so deleting that stream's key makes them all unreadable. With
+    /// [`encrypted_streams`](crate::storage::StorageConfig::encrypted_streams) set, they are
+    /// only encrypted if the set contains that stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) for stores using
+    /// [`StorageLayout::Clustered`](crate::storage::StorageLayout::Clustered), which keys every
+    /// record by its stream, plus any error [`append`](Self::append) can return.
+    pub fn append_raw(&mut self, event: E) -> crate::error::Result<u64> {
+        if self.storage.events_by_stream.is_some() {
+            return Err(crate::error::Error::InvalidConfig(
+                "raw events are not supported by the clustered layout".to_string(),
+            ));
+        }
+
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+
+        let last_seq = self
+            .storage
+            .events_log
+            .last(&txn)?
+            .map(|(k, _)| k)
+            .unwrap_or(0);
+        let seq = last_seq + 1;
+
+        let event_bytes = self.serialize_event(&event)?;
+        let record = self.encode_record(
+            &mut txn,
+            seq,
+            crate::constants::RAW_LOG_STREAM_ID,
+            &event_bytes,
+        )?;
+        self.storage
+            .events_log
+            .put_with_flags(&mut txn, heed::PutFlags::APPEND, &seq, &record)?;
+        self.record_event_id(&mut txn, seq)?;
+        self.tick_lamport_clock(&mut txn, seq)?;
+        self.index_event(&mut txn, seq, &event)?;
+        self.commit(txn)?;
+
+        self.storage.publish_head(seq);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(record.len() as u64);
+        }
+
+        Ok(seq)
+    }
+
+    /// Deletes all but the most recent `keep_last` versions of a stream, returning how many
+    /// were deleted.
+    ///
+    /// For streams that only need recent history, such as sensor readings. The deleted
+    /// versions' records, stream index entries, event IDs, metadata and Lamport timestamps
+    /// are removed in one transaction, so readers see the stream either untrimmed or
+    /// trimmed, never in between. The deleted sequence numbers are recorded, so sequential
+    /// readers such as `Processor` skip the holes this leaves in the log rather than wait
+    /// for them like for a gap, and [`append_at`](Self::append_at) can't fill them. The head
+    /// version stays, so [`ExpectedVersion::Auto`] keeps counting from it. A version holding
+    /// the newest record of the whole log is kept too, since its sequence number would
+    /// otherwise be handed out again.
+    ///
+    /// Blobs are stored by content hash and may be shared with other events, so each blob
+    /// counts the records referring to it and is removed with the last one. Databases
+    /// created before format version 2 keep their blobs. Secondary index entries are left in
+    /// place; readers skip them once their event is gone.
+    ///
+    /// Use [`trim_stream_keeping_snapshot`](Self::trim_stream_keeping_snapshot) for streams
+    /// whose state cannot be rebuilt from the last `keep_last` versions alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `keep_last` is 0, or
+    /// an error if the transaction fails.
+    pub fn trim_stream(&mut self, stream_id: u128, keep_last: u32) -> crate::error::Result<u32>
+    where
+        E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    {
+        self.trim(stream_id, keep_last, None)
+    }
+
+    /// Like [`trim_stream`](Self::trim_stream), but never trims past the newest snapshot, so
+    /// that replaying the stream still produces its full state.
+    ///
+    /// `is_snapshot` tells snapshot events, which fold everything before them into one
+    /// event, from the others. If none of the `keep_last` most recent versions is a
+    /// snapshot, the newest older snapshot becomes the new start of the stream: it and every
+    /// version after it are kept. Without any snapshot, this trims like `trim_stream`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`trim_stream`](Self::trim_stream), plus any error reading the stream's events.
+    pub fn trim_stream_keeping_snapshot<F>(
+        &mut self,
+        stream_id: u128,
+        keep_last: u32,
+        is_snapshot: F,
+    ) -> crate::error::Result<u32>
+    where
+        E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+        F: Fn(&E::Archived) -> bool,
+    {
+        self.trim(stream_id, keep_last, Some(&is_snapshot))
+    }
+
+    fn trim(
+        &mut self,
+        stream_id: u128,
+        keep_last: u32,
+        is_snapshot: Option<IsSnapshotFn<'_, E>>,
+    ) -> crate::error::Result<u32>
+    where
+        E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    {
+        if keep_last == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "keep_last must be greater than 0".to_string(),
+            ));
+        }
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut entries = Vec::new();
+        for result in self
+            .storage
+            .stream_index
+            .prefix_iter(&txn, &stream_id_bytes)?
+        {
+            let (key, seq) = result?;
+            entries.push((key.to_vec(), seq));
+        }
+
+        // Keys are in version order, so the oldest versions come first.
+        let mut excess = entries.len().saturating_sub(keep_last as usize);
+
+        // Replay must start at a snapshot or at the first version: move the boundary back
+        // to the newest snapshot unless a kept version already is one.
+        if let Some(is_snapshot) = is_snapshot {
+            let mut boundary = None;
+            for (i, (_, seq)) in entries.iter().enumerate().rev() {
+                if let Some(event) = self.reader.get(&txn, *seq)? {
+                    if is_snapshot(&event) {
+                        boundary = Some(i);
+                        break;
+                    }
+                }
+            }
+            if let Some(boundary) = boundary {
+                excess = excess.min(boundary);
+            }
+        }
+
+        let head_seq = self.storage.events_log.last(&txn)?.map(|(seq, _)| seq);
+        let event_metadata = self.storage.event_metadata.open(&txn)?;
+        let mut trimmed = 0;
+        let mut orphans = Vec::new();
+        for (key, seq) in &entries[..excess] {
+            // Writers take the next sequence after the head of the log, so keep the head.
+            if Some(*seq) == head_seq {
+                continue;
+            }
+            if let Some(hash) = self.reader.blob_hash(&txn, *seq)? {
+                if self.storage.release_blob(&mut txn, &hash)? {
+                    orphans.push(hash);
+                }
+            }
+            self.storage.stream_index.delete(&mut txn, key.as_slice())?;
+            self.storage.delete_record(&mut txn, *seq)?;
+            if let Some(event_metadata) = &event_metadata {
+                event_metadata.delete(&mut txn, seq)?;
+            }
+            if let Some(lamport_clocks) = &self.storage.lamport_clocks {
+                lamport_clocks.delete(&mut txn, seq)?;
+            }
+            if let (Some(events_by_id), Some(ids_by_seq)) =
+                (&self.storage.events_by_id, &self.storage.ids_by_seq)
+            {
+                if let Some(id) = ids_by_seq.get(&txn, seq)? {
+                    events_by_id.delete(&mut txn, &id)?;
+                    ids_by_seq.delete(&mut txn, seq)?;
+                }
+            }
+            self.storage.mark_trimmed(&mut txn, *seq)?;
+            trimmed += 1;
+        }
+
+        match &self.storage.blob_env {
+            None => {
+                for hash in &orphans {
+                    self.storage.blobs.delete(&mut txn, hash.as_slice())?;
+                }
+                self.commit(txn)?;
+            }
+            Some(blob_env) => {
+                self.commit(txn)?;
+                if !orphans.is_empty() {
+                    // Holding the write lock keeps appends from referring to the blobs again
+                    // before they are gone; skip those that were since. Failing here only
+                    // leaves garbage.
+                    let txn = env.write_txn()?;
+                    let mut blob_txn = blob_env.write_txn()?;
+                    let blob_refs = self.storage.blob_refs.open(&txn)?;
+                    for hash in &orphans {
+                        let refs = match &blob_refs {
+                            Some(blob_refs) => blob_refs.get(&txn, hash.as_slice())?,
+                            None => None,
+                        };
+                        if refs.is_none() {
+                            self.storage.blobs.delete(&mut blob_txn, hash.as_slice())?;
+                        }
+                    }
+                    blob_txn.commit()?;
+                }
+            }
+        }
+
+        Ok(trimmed)
+    }
+
+    /// Advances the Lamport clock past a timestamp received from elsewhere.
+    ///
+    /// Implements the receive rule of Lamport clocks: the clock becomes the larger of its
+    /// current value and `timestamp`, so every event appended afterwards is stamped later
+    /// than the event `timestamp` came from. Returns the clock after the update.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) unless
+    /// [`lamport_clock`](crate::storage::StorageConfig::lamport_clock) is enabled, or an
+    /// error if the transaction fails.
+    pub fn observe_clock(&mut self, timestamp: u64) -> crate::error::Result<u64> {
+        if self.storage.lamport_clocks.is_none() {
+            return Err(crate::error::Error::InvalidConfig(
+                "lamport_clock is not enabled".to_string(),
+            ));
+        }
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let clock = self.storage.lamport_clock(&txn)?.max(timestamp);
+        self.storage.set_lamport_clock(&mut txn, clock)?;
+        self.commit(txn)?;
+        Ok(clock)
+    }
+
+    /// Commits `txn`, recording the commit duration.
+    fn commit(&self, txn: heed::RwTxn) -> crate::error::Result<()> {
+        if let Some(threshold) = self.storage.config.map_full_warn_threshold {
+            self.check_map_usage(&txn, threshold)?;
+        }
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.commit_duration.start_timer());
+        Ok(txn.commit()?)
+    }
+
+    /// Warns once each time map usage crosses `threshold` (a fraction of `map_size`).
+    fn check_map_usage(&self, txn: &heed::RoTxn, threshold: f64) -> crate::error::Result<()> {
+        let map_size = self.storage.env.info().map_size as u64;
+        let used = map_size.saturating_sub(self.storage.free_space_bytes(txn)?);
+        let near_full = used as f64 >= map_size as f64 * threshold;
+
+        let was_near_full = self
+            .storage
+            .map_near_full
+            .swap(near_full, std::sync::atomic::Ordering::Relaxed);
+        if near_full && !was_near_full {
+            tracing::warn!(
+                "Memory map is {:.1}% full ({} of {} bytes used); increase map_size",
+                used as f64 * 100.0 / map_size as f64,
+                used,
+                map_size
+            );
+            if let Some(metrics) = &self.metrics {
+                metrics.map_full_warnings.inc();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the highest version of `stream_id` visible in `txn`, or 0 for an empty stream.
+    fn stream_head(&self, txn: &heed::RoTxn, stream_id: u128) -> crate::error::Result<u32> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut iter = self
+            .storage
+            .stream_index
+            .rev_prefix_iter(txn, &stream_id_bytes)?;
+
+        match iter.next() {
+            Some(result) => {
+                let (key_bytes, _) = result?;
+                // Key is [StreamID (16)][Version (4)]
+                let version_bytes: [u8; 4] = key_bytes[16..20].try_into().unwrap();
+                Ok(u32::from_be_bytes(version_bytes))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Writes one event inside `txn` without committing.
+    ///
+    /// Returns the assigned global sequence number, the number of bytes written to the log and
+    /// the archived event bytes.
+    fn write_event(
+        &mut self,
+        txn: &mut heed::RwTxn,
+        stream_id: u128,
+        version: u32,
+        event: &E,
+    ) -> crate::error::Result<(u64, u64, AlignedVec)> {
+        self.check_unreserved(txn, stream_id, version)?;
+        self.check_above_head(txn, stream_id, version)?;
+        self.write_slot(txn, stream_id, version, event)
+    }
+
+    /// Like `write_event`, but also writes into versions reserved by
+    /// [`reserve_versions`](Self::reserve_versions).
+    fn write_slot(
+        &mut self,
+        txn: &mut heed::RwTxn,
+        stream_id: u128,
+        version: u32,
+        event: &E,
+    ) -> crate::error::Result<(u64, u64, AlignedVec)> {
+        self.check_stream_slot(txn, stream_id, version)?;
+
+        // Get next Global Sequence
+        let last_seq = self
+            .storage
+            .events_log
+            .last(txn)?
+            .map(|(k, _)| k)
+            .unwrap_or(0);
+        let new_seq = last_seq + 1;
+
+        let event_bytes = self.serialize_event(event)?;
+        self.check_not_duplicate(txn, stream_id, version, &event_bytes)?;
+
+        // Sequences are strictly increasing, so the log write is always an append.
+        let bytes_len = self.write_record(
+            txn,
+            heed::PutFlags::APPEND,
+            new_seq,
+            stream_id,
+            version,
+            &event_bytes,
+        )?;
+
+        self.index_event(txn, new_seq, event)?;
+
+        Ok((new_seq, bytes_len, event_bytes))
+    }
+
+    /// Serializes `event` with the writer's reusable arena.
+    fn serialize_event(&mut self, event: &E) -> crate::error::Result<AlignedVec> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.serialize_duration.start_timer());
+        let bytes = rkyv::api::high::to_bytes_with_alloc::<_, RancorError>(
+            event,
+            self.arena.get_mut().acquire(),
+        )?;
+        if let Err(e) = self.check_event_size(bytes.len()) {
+            self.arena.get_mut().shrink();
+            return Err(e);
+        }
+        Ok(bytes)
+    }
+
+    /// Fails with `EventTooLarge` if `size` exceeds `StorageConfig::max_serialize_bytes`.
+    fn check_event_size(&self, size: usize) -> crate::error::Result<()> {
+        match self.storage.config.max_serialize_bytes {
+            Some(max) if size > max => Err(crate::error::Error::EventTooLarge { size, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds `seq` to every secondary index that has a key for `event`.
+    fn index_event(&self, txn: &mut heed::RwTxn, seq: u64, event: &E) -> crate::error::Result<()> {
+        for index in &self.indexes {
+            if let Some(index_key) = (index.key_fn)(event) {
+                index.db.put(txn, index_key.as_slice(), &seq)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if `version` lies in an unexpired reservation of the stream.
+    fn check_unreserved(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<()> {
+        let now = now_millis();
+        for (range, expires_at) in self.reservations(txn, stream_id)? {
+            if expires_at > now && range.contains(&version) {
+                return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if `version` is not above the stream's head, so a plain append never lands behind
+    /// a later version in the global log.
+    fn check_above_head(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<()> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        if let Some(result) = self
+            .storage
+            .stream_index
+            .rev_prefix_iter(txn, &stream_id_bytes)?
+            .next()
+        {
+            let (key_bytes, _) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let version_bytes: [u8; 4] = key_bytes[16..20].try_into().unwrap();
+            if version <= u32::from_be_bytes(version_bytes) {
+                return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the reserved version ranges of a stream with their expiry times (ms since
+    /// the Unix epoch), including expired ones.
+    fn reservations(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+    ) -> crate::error::Result<Vec<(std::ops::Range<u32>, u64)>> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut reservations = Vec::new();
+        let Some(db) = self.storage.stream_reservations.open(txn)? else {
+            return Ok(reservations);
+        };
+        for result in db.prefix_iter(txn, &stream_id_bytes)? {
+            let (key, value) = result?;
+            // Key is [StreamID (16)][First Version (4)], value [End Version (4)][Expiry (8)]
+            let (Ok(key), Ok(value)) = (<&[u8; 20]>::try_from(key), <&[u8; 12]>::try_from(value))
+            else {
+                return Err(crate::error::Error::InvalidConfig(
+                    "malformed stream reservation record".to_string(),
+                ));
+            };
+            let start = u32::from_be_bytes(key[16..].try_into().unwrap());
+            let end = u32::from_be_bytes(value[..4].try_into().unwrap());
+            let expires_at = u64::from_be_bytes(value[4..].try_into().unwrap());
+            reservations.push((start..end, expires_at));
+        }
+        Ok(reservations)
+    }
+
+    /// The version [`ExpectedVersion::Auto`] resolves to: the one after both the stream head
+    /// and its unexpired reservations.
+    fn next_version(&self, txn: &heed::RoTxn, stream_id: u128) -> crate::error::Result<u32> {
+        let now = now_millis();
+        let reserved = self
+            .reservations(txn, stream_id)?
+            .into_iter()
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(range, _)| range.end - 1)
+            .max()
+            .unwrap_or(0);
+        Ok(self.stream_head(txn, stream_id)?.max(reserved) + 1)
+    }
+
+    /// Fails if `version` already exists in the stream or the stream holds another event type.
+    ///
+    /// Registers the writer's type tag for new streams when stream types are enforced.
+    fn check_stream_slot(
+        &self,
+        txn: &mut heed::RwTxn,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<()> {
+        self.verify_stream_slot(txn, stream_id, version)?;
+
+        // Register the stream type on first write
+        if let Some(stream_types) = &self.storage.stream_types {
+            let stored_id = self.storage.stored_stream_id(stream_id);
+            if stream_types.get(txn, &stored_id)?.is_none() {
+                stream_types.put(txn, &stored_id, &self.stream_type)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The read-only part of `check_stream_slot`: fails if `version` may not be written.
+    fn verify_stream_slot(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<()> {
+        self.check_not_raw(stream_id)?;
+        self.check_stream_cap(stream_id, version)?;
+
+        // Concurrency Check
+        let key_bytes = self.storage.stream_key(stream_id, version);
+
+        if self
+            .storage
+            .stream_index
+            .get(txn, key_bytes.as_slice())?
+            .is_some()
+        {
+            // We don't know the expected version here, but we know the current version exists.
+            // Actually, the error I defined `VersionMismatch` expects `expected` and `actual`.
+            // But here we just know that `version` already exists.
+            // Maybe I should add `StreamVersionExists` error?
+            // Or just use `VersionMismatch` with some assumption?
+            // The original code was: "Concurrency conflict: Stream {} version {} already exists"
+
+            // Let's check what I defined in error.rs:
+            // VersionMismatch { stream_id, expected, actual }
+
+            // If I am trying to write version V, and it exists, it means actual is >= V.
+            // But I don't know the head version without querying it.
+
+            // Let's add `ConcurrencyConflict` error to `error.rs` instead of reusing `VersionMismatch` incorrectly here.
+            return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+        }
+
+        // Stream Type Check
+        if let Some(stream_types) = &self.storage.stream_types {
+            let stored_id = self.storage.stored_stream_id(stream_id);
+            if stream_types
+                .get(txn, &stored_id)?
+                .is_some_and(|tag| tag != self.stream_type)
+            {
+                return Err(crate::error::Error::InvalidConfig(
+                    "stream type mismatch".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails with `DuplicateEvent` if `event_bytes` repeat the event at `version - 1`, and
+    /// records them as the stream's last event otherwise.
+    ///
+    /// Only active with `StorageConfig::reject_consecutive_duplicates`.
+    fn check_not_duplicate(
+        &self,
+        txn: &mut heed::RwTxn,
+        stream_id: u128,
+        version: u32,
+        event_bytes: &[u8],
+    ) -> crate::error::Result<()> {
+        let Some(last_hash) = &self.storage.stream_last_hash else {
+            return Ok(());
+        };
+
+        let hash = self.verify_not_duplicate(txn, stream_id, version, event_bytes)?;
+        let stored_id = self.storage.stored_stream_id(stream_id);
+        let mut value = [0u8; 36];
+        value[..4].copy_from_slice(&version.to_be_bytes());
+        value[4..].copy_from_slice(&hash);
+        last_hash.put(txn, &stored_id, &value)?;
+        Ok(())
+    }
+
+    /// The read-only part of `check_not_duplicate`; returns the hash of `event_bytes`.
+    fn verify_not_duplicate(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+        event_bytes: &[u8],
+    ) -> crate::error::Result<[u8; 32]> {
+        // Value: [Version (4, BE)][SHA-256 of the event bytes (32)]
+        let hash: [u8; 32] = Sha256::digest(event_bytes).into();
+        let Some(last_hash) = &self.storage.stream_last_hash else {
+            return Ok(hash);
+        };
+        let stored_id = self.storage.stored_stream_id(stream_id);
+        if let Some(last) = last_hash.get(txn, &stored_id)? {
+            let previous = version.checked_sub(1).map(u32::to_be_bytes);
+            if last.len() == 36 && previous.is_some_and(|p| last[..4] == p) && last[4..] == hash {
+                return Err(crate::error::Error::DuplicateEvent { stream_id, version });
+            }
+        }
+        Ok(hash)
+    }
+
+    /// Fails with `InvalidConfig` if `stream_id` is the stream reserved for raw events.
+    fn check_not_raw(&self, stream_id: u128) -> crate::error::Result<()> {
+        if stream_id == crate::constants::RAW_LOG_STREAM_ID {
+            return Err(crate::error::Error::InvalidConfig(format!(
+                "stream {} is reserved for raw events",
+                stream_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails with `StreamFull` if `version` exceeds `StorageConfig::max_stream_versions`.
+    fn check_stream_cap(&self, stream_id: u128, version: u32) -> crate::error::Result<()> {
+        match self.storage.config.max_stream_versions {
+            Some(max) if version > max => Err(crate::error::Error::StreamFull { stream_id, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stores already serialized event bytes at `seq` and indexes them under `stream_id`/`version`.
+    ///
+    /// `put_flags` must contain either `APPEND` (only valid when `seq` is past the current last
+    /// sequence) or `NO_OVERWRITE`; an occupied or out-of-order `seq` fails with a concurrency
+    /// conflict. Returns the number of bytes written to the log.
+    fn write_record(
+        &mut self,
+        txn: &mut heed::RwTxn,
+        put_flags: heed::PutFlags,
+        seq: u64,
+        stream_id: u128,
+        version: u32,
+        event_bytes: &[u8],
+    ) -> crate::error::Result<u64> {
+        let final_bytes = self.encode_record(txn, seq, stream_id, event_bytes)?;
+        let bytes_len = final_bytes.len() as u64;
+
+        // Write to Log and Index. With `APPEND`, LMDB skips the key search and writes straight
+        // to the last leaf page; it rejects a key that is not greater than the current last one.
+        let key_bytes = self.storage.stream_key(stream_id, version);
+        self.storage
+            .put_record(txn, put_flags, seq, &key_bytes, &final_bytes)
+            .map_err(|e| match e {
+                heed::Error::Mdb(heed::MdbError::KeyExist) => {
+                    crate::error::Error::ConcurrencyConflict { stream_id, version }
+                }
+                e => e.into(),
+            })?;
+        self.storage
+            .stream_index
+            .put(txn, key_bytes.as_slice(), &seq)?;
+        if put_flags.contains(heed::PutFlags::APPEND) {
+            self.record_event_id(txn, seq)?;
+        } else {
+            self.record_gap_event_id(txn, seq)?;
+        }
+        self.tick_lamport_clock(txn, seq)?;
+
+        Ok(bytes_len)
+    }
+
+    /// Advances the Lamport clock and stamps the event at `seq` with it, if
+    /// [`lamport_clock`](crate::storage::StorageConfig::lamport_clock) is enabled.
+    fn tick_lamport_clock(&self, txn: &mut heed::RwTxn, seq: u64) -> crate::error::Result<()> {
+        let Some(lamport_clocks) = &self.storage.lamport_clocks else {
+            return Ok(());
+        };
+        let clock = self.storage.lamport_clock(txn)? + 1;
+        self.storage.set_lamport_clock(txn, clock)?;
+        lamport_clocks.put(txn, &seq, &clock)?;
+        Ok(())
+    }
+
+    /// Stores the event ID of a record appended at `seq`, under [`LogKey::U128`].
+    ///
+    /// Uses the ID given to `append_with_id`, or else the first time-based ID at the current
+    /// time that is greater than the last one. A time-based ID also leaves one ID free for
+    /// each sequence skipped since the last record, so filling that gap later can keep IDs
+    /// in log order.
+    ///
+    /// [`LogKey::U128`]: crate::storage::LogKey::U128
+    fn record_event_id(&mut self, txn: &mut heed::RwTxn, seq: u64) -> crate::error::Result<()> {
+        let requested = self.next_id.take();
+        let (Some(events_by_id), Some(ids_by_seq)) =
+            (&self.storage.events_by_id, &self.storage.ids_by_seq)
+        else {
+            return Ok(());
+        };
+
+        let exhausted =
+            || crate::error::Error::InvalidConfig("event IDs are exhausted".to_string());
+        let last = ids_by_seq.last(txn)?;
+        let next = match last {
+            Some((_, last_id)) => last_id.checked_add(1).ok_or_else(exhausted)?,
+            None => 0,
+        };
+        let id = match requested {
+            Some(id) if id < next => {
+                return Err(crate::error::Error::InvalidConfig(format!(
+                    "event ID {} is not greater than the last ID {}",
+                    id,
+                    next - 1
+                )));
+            }
+            Some(id) => id,
+            None => {
+                let skipped = seq - last.map_or(0, |(last_seq, _)| last_seq) - 1;
+                let next = next
+                    .checked_add(u128::from(skipped))
+                    .ok_or_else(exhausted)?;
+                crate::storage::LogKey::first_id_at(now_millis()).max(next)
+            }
+        };
+        events_by_id.put_with_flags(txn, heed::PutFlags::APPEND, &id, &seq)?;
+        ids_by_seq.put_with_flags(txn, heed::PutFlags::APPEND, &seq, &id)?;
+        Ok(())
+    }
+
+    /// Stores the event ID of a record that fills a gap at `seq`, under [`LogKey::U128`].
+    ///
+    /// The ID sorts between the IDs of the neighbouring sequences, so ID order keeps matching
+    /// log order, and leaves room for the sequences still missing on either side. It is the
+    /// first time-based ID at the current time, moved into that range if it falls outside.
+    ///
+    /// [`LogKey::U128`]: crate::storage::LogKey::U128
+    fn record_gap_event_id(&self, txn: &mut heed::RwTxn, seq: u64) -> crate::error::Result<()> {
+        let (Some(events_by_id), Some(ids_by_seq)) =
+            (&self.storage.events_by_id, &self.storage.ids_by_seq)
+        else {
+            return Ok(());
+        };
+
+        let low = match ids_by_seq.rev_range(txn, &(..seq))?.next() {
+            Some(result) => {
+                let (prev_seq, prev_id) = result?;
+                prev_id.checked_add(u128::from(seq - prev_seq))
+            }
+            None => Some(u128::from(seq - 1)),
+        };
+        let high = match ids_by_seq.range(txn, &(seq + 1..))?.next() {
+            Some(result) => {
+                let (next_seq, next_id) = result?;
+                next_id.checked_sub(u128::from(next_seq - seq))
+            }
+            None => Some(u128::MAX),
+        };
+        let id = match (low, high) {
+            (Some(low), Some(high)) if low <= high => {
+                crate::storage::LogKey::first_id_at(now_millis()).clamp(low, high)
+            }
+            _ => {
+                return Err(crate::error::Error::InvalidConfig(format!(
+                    "no event ID is left between the neighbours of sequence {}",
+                    seq
+                )));
+            }
+        };
+        events_by_id.put_with_flags(txn, heed::PutFlags::NO_OVERWRITE, &id, &seq)?;
+        ids_by_seq.put_with_flags(txn, heed::PutFlags::NO_OVERWRITE, &seq, &id)?;
+        Ok(())
+    }
+
+    /// Returns the key manager to encrypt records of `stream_id` with, or `None` if they
+    /// are stored in plaintext.
+    fn encrypting_key_manager(&self, stream_id: u128) -> Option<&KeyManager> {
+        self.key_manager
+            .as_ref()
+            .filter(|_| self.storage.encrypts_stream(stream_id))
+    }
+
+    /// Appends the encryption flag to `record` if
+    /// [`encrypted_streams`](crate::storage::StorageConfig::encrypted_streams) is set.
+    ///
+    /// The flag goes last so that plaintext records keep the alignment rkyv needs.
+    fn push_encryption_flag(&self, record: &mut Vec<u8>, encrypted: bool) {
+        if self.storage.config.encrypted_streams.is_some() {
+            record.push(if encrypted {
+                crate::constants::RECORD_ENCRYPTED
+            } else {
+                crate::constants::RECORD_PLAINTEXT
+            });
+        }
+    }
+
+    /// Encrypts archived metadata of event `seq` with the key of `stream_id`, if enabled.
+    ///
+    /// Encrypted records are `[StreamID (16)][Nonce (12)][Ciphertext]`, like events, and both
+    /// end with a [`RECORD_ENCRYPTED`](crate::constants::RECORD_ENCRYPTED) or
+    /// [`RECORD_PLAINTEXT`](crate::constants::RECORD_PLAINTEXT) flag when
+    /// [`encrypted_streams`](crate::storage::StorageConfig::encrypted_streams) is set.
+    fn encode_metadata(
+        &self,
+        txn: &mut heed::RwTxn,
+        seq: u64,
+        stream_id: u128,
+        metadata: &[u8],
+    ) -> crate::error::Result<Vec<u8>> {
+        let Some(km) = self.encrypting_key_manager(stream_id) else {
+            let mut record = metadata.to_vec();
+            self.push_encryption_flag(&mut record, false);
+            return Ok(record);
+        };
+        let key = km.get_or_create_key_with_txn(txn, stream_id)?;
+        let stream_id = self.storage.stored_stream_id(stream_id);
+
+        let mut record = stream_id.to_be_bytes().to_vec();
+        record.extend_from_slice(&crypto::encrypt(
+            &key,
+            metadata,
+            &metadata_aad(stream_id, seq),
+        )?);
+        self.push_encryption_flag(&mut record, true);
+        Ok(record)
+    }
+
+    /// Wraps serialized event bytes in the on-disk record format for `seq`.
+    ///
+    /// Handles blob offloading, checksums and encryption with the key of `stream_id`.
+    fn encode_record(
+        &mut self,
+        txn: &mut heed::RwTxn,
+        seq: u64,
+        stream_id: u128,
+        event_bytes: &[u8],
+    ) -> crate::error::Result<Vec<u8>> {
+        // Check size and determine Payload
+        let payload = if event_bytes.len() > self.storage.max_inline_size() {
+            // Large Payload: Store in Blobs DB
+            let mut hasher = Sha256::new();
+            hasher.update(event_bytes);
+            let hash = hasher.finalize();
+            let hash_array: [u8; 32] = hash.into();
+
+            self.storage
+                .put_blob(txn, hash_array.as_slice(), event_bytes)?;
+            match self.schema_version {
+                0 => StoragePayload::BlobRef(hash_array),
+                schema => StoragePayload::BlobRefVersioned {
+                    hash: hash_array,
+                    schema,
+                },
+            }
+        } else {
+            // Small Payload: Inline
+            let checksum = crc32c::crc32c(event_bytes);
+            let data = event_bytes.to_vec();
+            match self.schema_version {
+                0 => StoragePayload::InlineChecked { checksum, data },
+                schema => StoragePayload::InlineVersioned {
+                    data,
+                    checksum,
+                    schema,
+                },
+            }
+        };
+
+        // Serialize Payload
+        let arena = self.arena.get_mut();
+        let bytes =
+            rkyv::api::high::to_bytes_with_alloc::<_, RancorError>(&payload, arena.acquire())?;
+        // Release blocks beyond the largest one so a single huge event doesn't pin memory.
+        arena.shrink();
+
+        // Encrypt if enabled
+        let key_manager = self.encrypting_key_manager(stream_id);
+        let mut final_bytes = if let Some(km) = key_manager {
+            let _timer = self
+                .metrics
+                .as_ref()
+                .map(|m| m.encrypt_duration.start_timer());
+            let key = km.get_or_create_key_with_txn(txn, stream_id)?;
+            let stream_id = self.storage.stored_stream_id(stream_id);
+
+            // Construct AAD: StreamID (16 bytes) + GlobalSeq (8 bytes)
+            let mut aad = [0u8; crate::constants::AAD_CAPACITY];
+            aad[..crate::constants::STREAM_ID_SIZE].copy_from_slice(&stream_id.to_be_bytes());
+            aad[crate::constants::STREAM_ID_SIZE..].copy_from_slice(&seq.to_be_bytes());
+
+            let mut encrypted = crypto::encrypt(&key, &bytes, &aad)?;
+
+            // Prepend StreamID (16 bytes) to allow Reader to find the key
+            let mut final_vec =
+                Vec::with_capacity(crate::constants::STREAM_ID_SIZE + encrypted.len() + 1);
+            final_vec.extend_from_slice(&stream_id.to_be_bytes());
+            final_vec.append(&mut encrypted);
+            final_vec
+        } else {
+            bytes.to_vec()
+        };
+        self.push_encryption_flag(&mut final_bytes, key_manager.is_some());
+
+        Ok(final_bytes)
+    }
+}
+
+impl<E> Writer<E>
+where
+    E: rkyv::Archive
+        + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, RancorError>>,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+{
+    /// Writes pre-serialized event bytes at an exact global sequence number.
+    ///
+    /// This is the write side of replication and deterministic imports: a follower places
+    /// each record at the sequence the leader assigned. `bytes` are the archived event bytes
+    /// (as returned by [`EventView::to_bytes`]); they are validated as an `E` and then stored
+    /// like any other event, including checksums, blob offloading and encryption as configured
+    /// for this store. The stream index is updated in the same transaction.
+    ///
+    /// By default `seq` must be exactly the next sequence number. With
+    /// [`with_allow_gaps`](Self::with_allow_gaps), any free sequence number is accepted,
+    /// including one that fills an earlier gap, as long as it lies between the sequences of
+    /// the stream's neighbouring versions: `version` must come after every lower version of
+    /// `stream_id` in the global log and before every higher one.
+    ///
+    /// Secondary indexes are not maintained for records written this way, since no `E` value
+    /// is available to compute their keys. Under [`LogKey::U128`](crate::storage::LogKey::U128)
+    /// the record gets an event ID like any append; one that fills a gap gets an ID between
+    /// those of its neighbouring sequences, so [`Reader::iter_by_id`] still follows
+    /// the log order.
+    ///
+    /// Re-applying a record that is already stored (same `seq`, `stream_id`, `version` and
+    /// bytes) succeeds without writing anything, so a follower can safely re-run catch-up
+    /// after a crash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConcurrencyConflict`](crate::error::Error::ConcurrencyConflict) if `seq` or
+    /// the `stream_id`/`version` pair is already occupied by a different record, and
+    /// [`InvalidConfig`](crate::error::Error::InvalidConfig) if `seq` is 0, was deleted by
+    /// [`trim_stream`](Self::trim_stream), would create a gap while gaps are not allowed,
+    /// would put `version` out of order with the stream's other versions, or fills a gap that
+    /// IDs given to [`append_with_id`](Self::append_with_id) left no event ID free in.
+    pub fn append_at(
+        &mut self,
+        seq: u64,
+        stream_id: u128,
+        version: u32,
+        bytes: &[u8],
+    ) -> crate::error::Result<()> {
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|m| m.append_latency.start_timer());
+
+        if seq == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "global sequence numbers start at 1".to_string(),
+            ));
+        }
+
+        self.check_event_size(bytes.len())?;
+
+        // Validate before taking the write lock. Copy into an aligned buffer, since
+        // caller-provided slices carry no alignment guarantee.
+        let mut aligned = AlignedVec::<16>::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+        rkyv::access::<E::Archived, RancorError>(&aligned)?;
+
+        let env = self.storage.env.clone();
+        let mut txn = env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+
+        if self.storage.events_log.get(&txn, &seq)?.is_some() {
+            // Replaying a record we already have is a no-op, so catch-up can be re-run.
+            if self.holds_record(&txn, seq, stream_id, version, &aligned)? {
+                return Ok(());
+            }
+            return Err(crate::error::Error::ConcurrencyConflict { stream_id, version });
+        }
+        // Readers have moved past trimmed sequences for good.
+        if self.storage.skip_trimmed(&txn, seq)? != seq {
+            return Err(crate::error::Error::InvalidConfig(format!(
+                "sequence {} was trimmed",
+                seq
+            )));
+        }
+
+        let last_seq = self
+            .storage
+            .events_log
+            .last(&txn)?
+            .map(|(k, _)| k)
+            .unwrap_or(0);
+        if !self.allow_gaps && seq != last_seq + 1 {
+            return Err(crate::error::Error::InvalidConfig(format!(
+                "append_at would leave a gap: next sequence is {}, got {}",
+                last_seq + 1,
+                seq
+            )));
+        }
+
+        self.check_stream_slot(&mut txn, stream_id, version)?;
+        self.check_stream_order(&txn, stream_id, version, seq)?;
+        // Filling a gap inserts below the last key, which `APPEND` would reject.
+        let put_flags = if seq > last_seq {
+            heed::PutFlags::APPEND
+        } else {
+            heed::PutFlags::NO_OVERWRITE
+        };
+        let bytes_len =
+            self.write_record(&mut txn, put_flags, seq, stream_id, version, &aligned)?;
+        self.commit(txn)?;
+
+        // Subscribers track the head, which filling a gap does not move.
+        self.storage.publish_head(seq.max(last_seq));
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_appended.inc();
+            metrics.bytes_written.inc_by(bytes_len);
+        }
+
+        Ok(())
+    }
+
+    /// Fails unless `seq` lies between the sequences of the versions stored next to `version`,
+    /// so a record placed into a gap keeps the stream in global sequence order.
+    fn check_stream_order(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+        seq: u64,
+    ) -> crate::error::Result<()> {
+        let start = self.storage.stream_key(stream_id, 0);
+        let key = self.storage.stream_key(stream_id, version);
+        let end = self.storage.stream_key(stream_id, u32::MAX);
+
+        let before = (
+            Bound::Included(start.as_slice()),
+            Bound::Excluded(key.as_slice()),
+        );
+        if let Some(result) = self.storage.stream_index.rev_range(txn, &before)?.next() {
+            let (_, prev_seq) = result?;
+            if seq <= prev_seq {
+                return Err(crate::error::Error::InvalidConfig(format!(
+                    "append_at would place stream {} version {} at sequence {}, before an earlier version at sequence {}",
+                    stream_id, version, seq, prev_seq
+                )));
+            }
+        }
+
+        let after = (
+            Bound::Excluded(key.as_slice()),
+            Bound::Included(end.as_slice()),
+        );
+        if let Some(result) = self.storage.stream_index.range(txn, &after)?.next() {
+            let (_, next_seq) = result?;
+            if seq >= next_seq {
+                return Err(crate::error::Error::InvalidConfig(format!(
+                    "append_at would place stream {} version {} at sequence {}, after a later version at sequence {}",
+                    stream_id, version, seq, next_seq
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether `seq` holds exactly `bytes` as version `version` of `stream_id`.
+    fn holds_record(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+        stream_id: u128,
+        version: u32,
+        bytes: &[u8],
+    ) -> crate::error::Result<bool> {
+        let key = self.storage.stream_key(stream_id, version);
+        if self.storage.stream_index.get(txn, key.as_slice())? != Some(seq) {
+            return Ok(false);
+        }
+
+        // Compare decoded event bytes: the stored envelope differs between writes when
+        // encryption (random nonces) is enabled.
+        let reader = Reader::<E>::new(self.storage.clone());
+        Ok(reader
+            .get_event_data(txn, seq)?
+            .is_some_and(|data| data.as_slice() == bytes))
+    }
+}
+
+/// A [`Writer`] shared between threads, appending through one writer at a time.
+///
+/// Clones share the same writer behind a mutex, so appends from different tasks are applied
+/// one after the other, in the order they take the lock. This makes the "many handles, one
+/// writer" pattern explicit instead of relying on LMDB to serialize independent writers'
+/// transactions. Use [`with`](Self::with) to run several operations under one lock, e.g. to
+/// read a stream's head and append the next version without another task interleaving.
+///
+/// The lock is a blocking [`std::sync::Mutex`], held only while an append runs. From async
+/// code, appends are as blocking as they are on a plain `Writer`.
+///
+/// # Examples
+///
+/// ```rust
+/// use varvedb::engine::{SyncWriter, Writer};
+/// use varvedb::storage::{Storage, StorageConfig};
+/// use rkyv::{Archive, Serialize, Deserialize};
+/// use tempfile::tempdir;
+///
+/// #[derive(Archive, Serialize, Deserialize)]
+/// struct Tick {
+///     n: u32,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = tempdir()?;
+/// let storage = Storage::open(StorageConfig { path: dir.path().to_path_buf(), ..Default::default() })?;
+/// let writer = SyncWriter::new(Writer::new(storage));
+///
+/// let handles: Vec<_> = (1..=4u128)
+///     .map(|stream_id| {
+///         let writer = writer.clone();
+///         std::thread::spawn(move || writer.append(stream_id, 1, Tick { n: 0 }))
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyncWriter<E> {
+    inner: Arc<std::sync::Mutex<Writer<E>>>,
+}
+
+impl<E> Clone for SyncWriter<E> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for SyncWriter<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncWriter").finish_non_exhaustive()
+    }
+}
+
+impl<E> SyncWriter<E>
+where
+    E: rkyv::Archive
+        + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, RancorError>>,
+{
+    /// Wraps `writer` so it can be shared.
+    pub fn new(writer: Writer<E>) -> Self {
+        Self {
+            inner: Arc::new(std::sync::Mutex::new(writer)),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the writer.
+    ///
+    /// Other handles wait until `f` returns. A panic inside `f` does not poison the writer
+    /// for other handles, since a failed append leaves no partial state behind.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Writer<E>) -> R) -> R {
+        let mut writer = self
+            .inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(&mut writer)
+    }
+
+    /// Appends an event through the shared writer. See [`Writer::append`].
+    pub fn append(&self, stream_id: u128, version: u32, event: E) -> crate::error::Result<u64> {
+        self.with(|writer| writer.append(stream_id, version, event))
+    }
+
+    /// Starts a new stream under a writer-chosen ID through the shared writer. See
+    /// [`Writer::create_stream`].
+    pub fn create_stream(&self, event: E) -> crate::error::Result<(u128, u64)> {
+        self.with(|writer| writer.create_stream(event))
+    }
+
+    /// Appends an event with metadata through the shared writer. See
+    /// [`Writer::append_payload`].
+    pub fn append_payload<M>(
+        &self,
+        payload: Payload<E, M>,
+        expected: ExpectedVersion,
+    ) -> crate::error::Result<u64>
+    where
+        M: MetadataExt
+            + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, RancorError>>,
+    {
+        self.with(|writer| writer.append_payload(payload, expected))
+    }
+
+    /// Returns a receiver notified with the latest sequence number after each commit. See
+    /// [`Writer::subscribe`].
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<u64> {
+        self.with(|writer| writer.subscribe())
+    }
+}
+
+pub enum EventData<'a> {
+    Borrowed(&'a [u8]),
+    Owned(Vec<u8>),
+}
+
+impl EventData<'_> {
+    /// Returns the underlying bytes, regardless of ownership.
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            EventData::Borrowed(b) => b,
+            EventData::Owned(b) => b.as_slice(),
+        }
+    }
+}
+
+impl std::ops::Deref for EventData<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for EventData<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+/// Tag appended to the AAD of encrypted metadata, so a metadata record can never be
+/// decrypted as the event at the same sequence or vice versa.
+const METADATA_AAD_TAG: u8 = b'M';
+
+/// AAD of the metadata of event `seq`: `[StreamID (16)][Seq (8)][METADATA_AAD_TAG]`.
+fn metadata_aad(stored_stream_id: u128, seq: u64) -> [u8; crate::constants::AAD_CAPACITY + 1] {
+    let mut aad = [0u8; crate::constants::AAD_CAPACITY + 1];
+    aad[..crate::constants::STREAM_ID_SIZE].copy_from_slice(&stored_stream_id.to_be_bytes());
+    aad[crate::constants::STREAM_ID_SIZE..crate::constants::AAD_CAPACITY]
+        .copy_from_slice(&seq.to_be_bytes());
+    aad[crate::constants::AAD_CAPACITY] = METADATA_AAD_TAG;
+    aad
+}
+
+/// The schema version of an event type, see [`Writer::with_schema_version`].
+pub type SchemaVersion = u16;
+
+/// Converts archived event bytes of one schema version to the next, see
+/// [`Reader::with_upcaster`].
+type Upcaster = Arc<dyn Fn(SchemaVersion, &[u8]) -> crate::error::Result<Vec<u8>> + Send + Sync>;
+
+/// Reads one batch of `(seq, item)` pairs for [`Reader::tail_into`].
+type ReadBatchFn<E, V> = fn(&Reader<E>, u64, usize) -> crate::error::Result<Vec<(u64, V)>>;
+
+/// A zero-copy view of a stored event.
+///
+/// Dereferences to the archived event. The bytes are validated once, when the view is
+/// created by [`Reader::get`], so dereferencing never re-validates. Views obtained through
+/// [`Reader::get_unchecked`] skip that validation and rely on the caller's guarantee instead.
+pub struct EventView<'a, E>
+where
+    E: rkyv::Archive,
+{
+    data: EventData<'a>,
+    _marker: std::marker::PhantomData<E>,
+}
+
+impl<'a, E> std::ops::Deref for EventView<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: Portable,
+{
+    type Target = E::Archived;
+
+    fn deref(&self) -> &Self::Target {
+        let bytes = self.data.as_slice();
+        // Safety: Views are only built from bytes that `Reader::get` validated with
+        // `rkyv::access`, or that the caller vouched for via `Reader::get_unchecked`.
+        // Validating once at construction keeps every dereference free.
         unsafe { rkyv::access_unchecked::<E::Archived>(bytes) }
     }
-}
+}
+
+/// Exposes the validated archived bytes, like [`EventView::to_bytes`].
+impl<'a, E> AsRef<[u8]> for EventView<'a, E>
+where
+    E: rkyv::Archive,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+}
+
+impl<'a, E> std::fmt::Debug for EventView<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<'a, E> EventView<'a, E>
+where
+    E: rkyv::Archive,
+{
+    /// Converts this `EventView` into an owned version, detaching it from the transaction.
+    ///
+    /// This involves cloning the data to ensure it owns its memory (independent of the transaction).
+    /// Note: This performs a copy of the underlying data.
+    pub fn into_owned<'b>(self) -> EventView<'b, E> {
+        match self.data {
+            EventData::Borrowed(b) => EventView {
+                data: EventData::Owned(b.to_vec()),
+                _marker: std::marker::PhantomData,
+            },
+            EventData::Owned(v) => EventView {
+                data: EventData::Owned(v),
+                _marker: std::marker::PhantomData,
+            },
+        }
+    }
+
+    /// Returns the raw archived bytes of the event.
+    ///
+    /// These are the validated rkyv bytes the view dereferences into (after decryption and
+    /// blob resolution), not the on-disk record.
+    pub fn to_bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+}
+
+impl<'a, E> EventView<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: Portable + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+{
+    /// Deserializes the archived event into an owned `E`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EventValidation`](crate::error::Error::EventValidation) if
+    /// deserialization fails.
+    pub fn try_deserialize(&self) -> crate::error::Result<E> {
+        rkyv::deserialize::<E, RancorError>(&**self)
+            .map_err(|e| crate::error::Error::EventValidation(e.to_string()))
+    }
+}
+
+/// A stored event that serves both zero-copy and owned access from a single read.
+///
+/// Returned by [`Reader::get_lazy`]. [`archived`](Self::archived) borrows the archived event
+/// like an [`EventView`]; [`owned`](Self::owned) deserializes it into an `E` the first time
+/// it is called and returns the cached value afterwards, so code that only occasionally needs
+/// the owned value pays for deserialization only then.
+pub struct LazyEvent<'a, E>
+where
+    E: rkyv::Archive,
+{
+    view: EventView<'a, E>,
+    owned: std::cell::OnceCell<E>,
+}
+
+impl<'a, E> From<EventView<'a, E>> for LazyEvent<'a, E>
+where
+    E: rkyv::Archive,
+{
+    fn from(view: EventView<'a, E>) -> Self {
+        Self {
+            view,
+            owned: std::cell::OnceCell::new(),
+        }
+    }
+}
+
+impl<'a, E> std::fmt::Debug for LazyEvent<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.view.fmt(f)
+    }
+}
+
+impl<'a, E> LazyEvent<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: Portable + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+{
+    /// Returns the archived event, without deserializing it.
+    pub fn archived(&self) -> &E::Archived {
+        &self.view
+    }
+
+    /// Returns the view the event was read into.
+    pub fn view(&self) -> &EventView<'a, E> {
+        &self.view
+    }
+
+    /// Returns the deserialized event, deserializing it on the first call.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventView::try_deserialize`]; a failed attempt is not cached.
+    pub fn owned(&self) -> crate::error::Result<&E> {
+        if let Some(event) = self.owned.get() {
+            return Ok(event);
+        }
+        let event = self.view.try_deserialize()?;
+        Ok(self.owned.get_or_init(|| event))
+    }
+
+    /// Returns the deserialized event, reusing it if [`owned`](Self::owned) already ran.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`owned`](Self::owned).
+    pub fn into_owned(mut self) -> crate::error::Result<E> {
+        match self.owned.take() {
+            Some(event) => Ok(event),
+            None => self.view.try_deserialize(),
+        }
+    }
+}
+
+/// An item of the event iterators: an [`EventView`], alone or with a key such as its
+/// sequence number. Used by [`EventIterExt`].
+pub trait EventItem<'a, E>
+where
+    E: rkyv::Archive,
+{
+    /// What the event comes with: `()` for a bare view, `u64` for `(seq, view)`.
+    type Key;
+
+    /// Splits the item into its key and view.
+    fn into_parts(self) -> (Self::Key, EventView<'a, E>);
+}
+
+impl<'a, E> EventItem<'a, E> for EventView<'a, E>
+where
+    E: rkyv::Archive,
+{
+    type Key = ();
+
+    fn into_parts(self) -> ((), EventView<'a, E>) {
+        ((), self)
+    }
+}
+
+impl<'a, E> EventItem<'a, E> for (u64, EventView<'a, E>)
+where
+    E: rkyv::Archive,
+{
+    type Key = u64;
+
+    fn into_parts(self) -> (u64, EventView<'a, E>) {
+        self
+    }
+}
+
+/// Adapters for iterators over `Result`s of events, such as [`SnapshotIter`],
+/// [`Varve::iter`](crate::Varve::iter) and [`Reader::iter_filtered`].
+///
+/// Each adapter passes errors through unchanged, so a chain still stops at (or collects)
+/// the first failed read.
+///
+/// # Examples
+///
+/// ```rust
+/// # use varvedb::engine::{EventIterExt, Reader, Writer};
+/// # use varvedb::storage::{Storage, StorageConfig};
+/// # use rkyv::{Archive, Serialize, Deserialize};
+/// # use tempfile::tempdir;
+/// #
+/// #[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+/// struct Deposit {
+///     amount: u64,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// # let storage = Storage::open(StorageConfig { path: dir.path().to_path_buf(), ..Default::default() })?;
+/// let mut writer = Writer::new(storage.clone());
+/// for (version, amount) in [(1, 10), (2, 500), (3, 20)] {
+///     writer.append(1, version, Deposit { amount })?;
+/// }
+///
+/// let reader = Reader::<Deposit>::new(storage.clone());
+///
+/// // Owned events, without matching on each `Result` or calling rkyv.
+/// let all = reader.snapshot()?.iter().try_collect_owned()?;
+/// assert_eq!(all[1], Deposit { amount: 500 });
+///
+/// let txn = storage.env.read_txn()?;
+///
+/// // Sequence numbers of the matches, from an iterator of `(seq, view)`.
+/// let large: Vec<u64> = reader
+///     .iter_filtered(&txn, |d| d.amount > 100)?
+///     .sequences()
+///     .collect::<Result<_, _>>()?;
+/// assert_eq!(large, vec![2]);
+///
+/// // Owned events, lazily.
+/// let total: u64 = reader
+///     .iter_filtered(&txn, |_| true)?
+///     .into_owned_iter()
+///     .map(|deposit| deposit.map(|d| d.amount))
+///     .sum::<Result<u64, _>>()?;
+/// assert_eq!(total, 530);
+/// # Ok(())
+/// # }
+/// ```
+pub trait EventIterExt<'a, E, V>: Iterator<Item = crate::error::Result<V>> + Sized
+where
+    E: rkyv::Archive,
+    V: EventItem<'a, E>,
+{
+    /// Yields the sequence number of each event instead of the event.
+    fn sequences(self) -> impl Iterator<Item = crate::error::Result<u64>>
+    where
+        V: EventItem<'a, E, Key = u64>,
+    {
+        self.map(|item| item.map(|item| item.into_parts().0))
+    }
+
+    /// Deserializes each event into an owned `E`.
+    ///
+    /// Items that fail to deserialize yield
+    /// [`Error::EventValidation`](crate::error::Error::EventValidation), as with
+    /// [`EventView::try_deserialize`].
+    fn into_owned_iter(self) -> impl Iterator<Item = crate::error::Result<E>>
+    where
+        E::Archived: Portable + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+    {
+        self.map(|item| item.and_then(|item| item.into_parts().1.try_deserialize()))
+    }
+
+    /// Deserializes every event into an owned `E`, stopping at the first error.
+    fn try_collect_owned(self) -> crate::error::Result<Vec<E>>
+    where
+        E::Archived: Portable + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+    {
+        self.into_owned_iter().collect()
+    }
+}
+
+impl<'a, E, V, I> EventIterExt<'a, E, V> for I
+where
+    E: rkyv::Archive,
+    V: EventItem<'a, E>,
+    I: Iterator<Item = crate::error::Result<V>>,
+{
+}
+
+/// Summary of one stream, returned by [`Reader::stream_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// The highest version stored in the stream.
+    pub head_version: u32,
+    /// How many events the stream holds. Less than `head_version` if it has gaps.
+    pub count: u64,
+    /// The lowest global sequence number of the stream's events.
+    pub first_global_seq: u64,
+    /// The highest global sequence number of the stream's events.
+    pub last_global_seq: u64,
+}
+
+/// An event paired with its global sequence number, returned by [`Reader::get_envelope`]
+/// and [`Reader::get_envelopes`].
+pub struct EventEnvelope<'a, E>
+where
+    E: rkyv::Archive,
+{
+    /// The global sequence number of the event.
+    pub seq: u64,
+    /// The Lamport timestamp of the event. `None` unless
+    /// [`lamport_clock`](crate::storage::StorageConfig::lamport_clock) was enabled when it
+    /// was appended.
+    pub lamport: Option<u64>,
+    /// The event itself.
+    pub event: EventView<'a, E>,
+}
+
+impl<'a, E> std::fmt::Debug for EventEnvelope<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventEnvelope")
+            .field("seq", &self.seq)
+            .field("lamport", &self.lamport)
+            .field("event", &self.event)
+            .finish()
+    }
+}
+
+/// The result of a tolerant read with [`Reader::get_or_skip`].
+pub enum ReadOutcome<'a, E>
+where
+    E: rkyv::Archive,
+{
+    /// The event was valid for this reader's schema.
+    Known(EventView<'a, E>),
+    /// The event uses an enum variant this reader's schema does not know about.
+    Unknown,
+    /// The event exists but its body, stored in the blob with this content hash, is missing.
+    /// Only returned with [`MissingBlob::ReturnPlaceholder`].
+    MissingBody { hash: [u8; 32] },
+}
+
+impl<'a, E> std::fmt::Debug for ReadOutcome<'a, E>
+where
+    E: rkyv::Archive,
+    E::Archived: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadOutcome::Known(view) => f.debug_tuple("Known").field(view).finish(),
+            ReadOutcome::Unknown => f.write_str("Unknown"),
+            ReadOutcome::MissingBody { hash } => {
+                f.debug_struct("MissingBody").field("hash", hash).finish()
+            }
+        }
+    }
+}
+
+/// Provides zero-copy access to events from the store.
+///
+/// The `Reader` allows efficient retrieval of events by sequence number. It leverages memory-mapped
+/// files to provide zero-copy access to data when encryption is disabled. When encryption is enabled,
+/// it transparently handles decryption.
+///
+/// # Examples
+///
+/// ```rust
+/// use varvedb::engine::{Writer, Reader};
+/// use varvedb::storage::{Storage, StorageConfig};
+/// use rkyv::{Archive, Serialize, Deserialize};
+/// use tempfile::tempdir;
+///
+/// #[derive(Archive, Serialize, Deserialize, Debug)]
+/// #[rkyv(derive(Debug))]
+/// struct MyEvent { pub data: u32 }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = tempdir()?;
+/// let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+/// let storage = Storage::open(config)?;
+/// let mut writer = Writer::new(storage.clone());
+/// writer.append(1, 1, MyEvent { data: 42 })?;
+///
+/// let reader = Reader::<MyEvent>::new(storage.clone());
+/// let txn = storage.env.read_txn()?;
+/// if let Some(event) = reader.get(&txn, 1)? {
+///     println!("Event: {:?}", event);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Reader<E> {
+    storage: Storage,
+    metrics: Option<Arc<VarveMetrics>>,
+    key_manager: Option<KeyManager>,
+    schema_version: SchemaVersion,
+    upcaster: Option<Upcaster>,
+    known_variants: Option<u8>,
+    _marker: std::marker::PhantomData<E>,
+}
+
+impl<E> Clone for Reader<E> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            metrics: self.metrics.clone(),
+            key_manager: self.key_manager.clone(),
+            schema_version: self.schema_version,
+            upcaster: self.upcaster.clone(),
+            known_variants: self.known_variants,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for Reader<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reader")
+            .field("schema_version", &self.schema_version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E> Reader<E> {
+    fn with_key_manager(storage: Storage, key_manager: Option<KeyManager>) -> Self {
+        Self {
+            storage,
+            metrics: None,
+            key_manager,
+            schema_version: 0,
+            upcaster: None,
+            known_variants: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E> Reader<E>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+{
+    /// Creates a new `Reader` instance.
+    pub fn new(storage: Storage) -> Self {
+        let key_manager = if storage.config.encryption_enabled {
+            Some(KeyManager::new(storage.clone()))
+        } else {
+            None
+        };
+
+        Self::with_key_manager(storage, key_manager)
+    }
+
+    /// Attaches metrics to the reader for observability.
+    pub fn with_metrics(mut self, metrics: Arc<VarveMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the schema version of `E`, as written by
+    /// [`Writer::with_schema_version`]. Defaults to 0.
+    ///
+    /// Only matters together with [`with_upcaster`](Self::with_upcaster).
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = version;
+        self
+    }
+
+    /// Upcasts events written with an older schema version at read time.
+    ///
+    /// When a record's schema version is lower than this reader's, its archived bytes are
+    /// passed through `upcaster` before validation, once per version step:
+    /// `upcaster(v, bytes)` must turn the archived bytes of schema version `v` into those of
+    /// version `v + 1`. Old events can thus be read as the current type without rewriting
+    /// the log. Records at or above the reader's version are read unchanged. Events without
+    /// a schema tag count as version 0.
+    ///
+    /// Upcast events are always owned, so reading them copies.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// struct UserV1 { name: String }
+    ///
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// struct UserV2 { name: String, email: Option<String> }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// // Written before the schema changed, so untagged: version 0.
+    /// Writer::new(storage.clone()).append(1, 1, UserV1 { name: "ada".into() })?;
+    ///
+    /// let reader = Reader::<UserV2>::new(storage.clone())
+    ///     .with_schema_version(1)
+    ///     .with_upcaster(|version, bytes| match version {
+    ///         0 => {
+    ///             let old = rkyv::from_bytes::<UserV1, rkyv::rancor::Error>(bytes)?;
+    ///             let new = UserV2 { name: old.name, email: None };
+    ///             Ok(rkyv::to_bytes::<rkyv::rancor::Error>(&new)?.to_vec())
+    ///         }
+    ///         _ => unreachable!(),
+    ///     });
+    /// let txn = storage.env.read_txn()?;
+    /// let user = reader.get(&txn, 1)?.unwrap();
+    /// assert_eq!(user.name, "ada");
+    /// assert!(user.email.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_upcaster<F>(mut self, upcaster: F) -> Self
+    where
+        F: Fn(SchemaVersion, &[u8]) -> crate::error::Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.upcaster = Some(Arc::new(upcaster));
+        self
+    }
+
+    /// Declares how many variants the enum `E` has, for [`get_or_skip`](Self::get_or_skip).
+    ///
+    /// An event whose archived tag is `count` or higher was written by a newer schema and is
+    /// reported as [`ReadOutcome::Unknown`]. Without it, `get_or_skip` cannot tell unknown
+    /// variants from corrupt events and fails on both.
+    pub fn with_known_variants(mut self, count: u8) -> Self {
+        self.known_variants = Some(count);
+        self
+    }
+
+    /// Returns a reference to the underlying storage.
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Opens a pinned point-in-time view of the store.
+    ///
+    /// The returned [`SnapshotReader`] holds a single read transaction, so every read made
+    /// through it observes the same consistent state, regardless of concurrent appends.
+    /// Use it when one logical read (e.g. rendering a view) spans several events.
+    ///
+    /// Like any read transaction, the snapshot must stay on the thread that created it and
+    /// should be dropped promptly: long-lived snapshots prevent LMDB from reusing pages.
+    pub fn snapshot(&self) -> crate::error::Result<SnapshotReader<'_, E>> {
+        Ok(SnapshotReader {
+            txn: self.storage.env.read_txn()?,
+            reader: self,
+        })
+    }
+
+    /// Asks the OS to read ahead the events at sequences `[start, start + count)`.
+    ///
+    /// LMDB reads pages on demand, so scanning a log that isn't in the page cache faults in
+    /// one page at a time. Prefetching a window of upcoming events turns that into readahead;
+    /// [`SnapshotIter::with_prefetch`] does this while iterating. This is only a hint: it
+    /// reads nothing back, and events stored as blobs are not prefetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying storage encounters an I/O error.
+    pub fn prefetch(&self, txn: &heed::RoTxn, start: u64, count: u64) -> crate::error::Result<()> {
+        self.storage.prefetch_records(txn, start, count)
+    }
+
+    /// Retrieves an event by its global sequence number.
+    ///
+    /// Returns an `EventView` which provides access to the deserialized event.
+    ///
+    /// # Zero-Copy vs Encryption
+    ///
+    /// *   **Encryption Disabled**: The `EventView` borrows data directly from the memory-mapped file (Zero-Copy).
+    /// *   **Encryption Enabled**: The data is decrypted into a temporary buffer, involving allocation and copying.
+    ///
+    /// If encryption is enabled, this method handles key retrieval and decryption transparently.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The event data is corrupted or fails validation.
+    /// *   Decryption fails (e.g., invalid key or AAD mismatch).
+    /// *   The underlying storage encounters an I/O error.
+    ///
+    /// Failures of the record itself are wrapped in
+    /// [`AtSequence`](crate::error::Error::AtSequence) carrying `seq`.
+    pub fn get<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
+        let Some(data) = self
+            .get_event_data(txn, seq)
+            .map_err(|e| e.at_sequence(seq))?
+        else {
+            return Ok(None);
+        };
+
+        // Verify rkyv validity (zero-copy check) of the actual event
+        rkyv::access::<E::Archived, rkyv::rancor::Error>(data.as_slice())
+            .map_err(|e| crate::error::Error::from(e).at_sequence(seq))?;
+
+        Ok(Some(self.make_view(data)))
+    }
+
+    /// Like [`get`](Self::get), but returns a [`LazyEvent`] that can also hand out the event
+    /// deserialized, on demand and without reading it again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Deposit {
+    ///     amount: u64,
+    ///     memo: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::new(storage.clone());
+    /// writer.append(1, 1, Deposit { amount: 500, memo: "rent".to_string() })?;
+    ///
+    /// let reader = Reader::<Deposit>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let event = reader.get_lazy(&txn, 1)?.unwrap();
+    /// if event.archived().amount > 100 {
+    ///     assert_eq!(event.owned()?.memo, "rent");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`get`](Self::get).
+    pub fn get_lazy<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<LazyEvent<'txn, E>>> {
+        Ok(self.get(txn, seq)?.map(LazyEvent::from))
+    }
+
+    /// Retrieves the event at `seq`, interpreting its bytes as a `T` instead of an `E`.
+    ///
+    /// After splitting or narrowing an event type, records written as the old type can be
+    /// read with it (or with any type whose archived layout matches theirs) without keeping
+    /// the old type as this reader's `E`. The bytes go through the same decryption, checksum
+    /// and upcasting steps as in [`get`](Self::get), and are then validated as a `T`, so an
+    /// incompatible `T` fails with an error instead of misreading the record.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// enum LegacyOrderEvent {
+    ///     Placed { id: u64 },
+    ///     Paid { id: u64, amount: u64 },
+    /// }
+    ///
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// struct PaymentEvent { id: u64, amount: u64 }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// // `LegacyOrderEvent` used to carry both variants; new code writes `PaymentEvent` separately.
+    /// Writer::new(storage.clone()).append(1, 1, LegacyOrderEvent::Paid { id: 7, amount: 30 })?;
+    ///
+    /// let reader = Reader::<PaymentEvent>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let old = reader.get_as::<LegacyOrderEvent>(&txn, 1)?.unwrap();
+    /// assert!(matches!(*old, ArchivedLegacyOrderEvent::Paid { amount, .. } if amount == 30));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`get`](Self::get), with validation done against `T`.
+    pub fn get_as<'txn, T>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventView<'txn, T>>>
+    where
+        T: rkyv::Archive,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    {
+        let Some(data) = self
+            .get_event_data(txn, seq)
+            .map_err(|e| e.at_sequence(seq))?
+        else {
+            return Ok(None);
+        };
+
+        rkyv::access::<T::Archived, RancorError>(data.as_slice())
+            .map_err(|e| crate::error::Error::from(e).at_sequence(seq))?;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_read.inc();
+        }
+        Ok(Some(EventView {
+            data,
+            _marker: std::marker::PhantomData,
+        }))
+    }
+
+    /// Retrieves an event by its global sequence number without validating the archived event.
+    ///
+    /// This is the fast path for trusted data: it behaves like [`get`](Self::get) but skips the
+    /// `rkyv::access` check of the event bytes, which dominates read cost for large events.
+    /// The storage envelope is still checked, and decryption (which authenticates the data)
+    /// and `verify_checksums` still apply.
+    ///
+    /// # Safety
+    ///
+    /// The stored bytes at `seq` must be a valid archive of `E`: written by a [`Writer<E>`] for
+    /// this exact type and schema, and not corrupted since. Dereferencing an `EventView` over
+    /// invalid bytes is undefined behavior. Prefer [`get`](Self::get) unless profiling shows
+    /// validation to be a bottleneck and the database is not exposed to untrusted writers.
+    pub unsafe fn get_unchecked<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
+        Ok(self
+            .get_event_data(txn, seq)
+            .map_err(|e| e.at_sequence(seq))?
+            .map(|data| self.make_view(data)))
+    }
+
+    /// Iterates over the events with sequence numbers in `start..end`, without validating
+    /// them.
+    ///
+    /// The bulk form of [`get_unchecked`](Self::get_unchecked), for rebuilding a projection
+    /// from trusted data: every event is read as in `get_unchecked`, yielded as
+    /// `(seq, view)` in sequence order. Gaps in the log are skipped, and errors are yielded
+    /// as in [`iter_filtered`](Self::iter_filtered). Skipping validation makes scans of events
+    /// with many nested fields several times faster (see `scan_validation` in
+    /// `benches/read_bench.rs`); for small, flat events the gain is negligible.
+    ///
+    /// # Safety
+    ///
+    /// Every stored event in the range must be a valid archive of `E`, as required by
+    /// [`get_unchecked`](Self::get_unchecked).
+    pub unsafe fn iter_range_unchecked<'a, 'txn: 'a>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+        start: u64,
+        end: u64,
+    ) -> crate::error::Result<
+        impl Iterator<Item = crate::error::Result<(u64, EventView<'txn, E>)>> + 'a,
+    > {
+        self.scan(txn, start..end, move |seq| {
+            // Safety: the caller guarantees every event in the range is a valid archive.
+            unsafe { self.get_unchecked(txn, seq) }
+        })
+    }
+
+    /// Reads a projection of an event without validating the archived event.
+    ///
+    /// `project` receives the archived event and returns the part of it the caller needs,
+    /// e.g. `|payment| payment.amount.to_native()`. Only the fields the closure touches are
+    /// read, so for large events this avoids both the full `bytecheck` pass of
+    /// [`get`](Self::get) and any deserialization. The result must be owned, since encrypted
+    /// events are decrypted into a buffer that does not outlive this call.
+    /// Returns `Ok(None)` if there is no event at `seq`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Writer, Reader};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// struct Payment {
+    ///     amount: u64,
+    ///     memo: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// writer.append(1, 1, Payment { amount: 250, memo: "rent".into() })?;
+    ///
+    /// let reader = Reader::<Payment>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// // Safety: this database is only written by `Writer<Payment>`.
+    /// let amount = unsafe { reader.get_field(&txn, 1, |p| p.amount.to_native())? };
+    /// assert_eq!(amount, Some(250));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as [`get_unchecked`](Self::get_unchecked) apply: the stored bytes
+    /// at `seq` must be a valid archive of `E`. Touching only one field does not make invalid
+    /// bytes safe to read, because relative pointers and enum tags along the path to that
+    /// field are not checked either.
+    pub unsafe fn get_field<T>(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+        project: impl FnOnce(&E::Archived) -> T,
+    ) -> crate::error::Result<Option<T>> {
+        // Safety: forwarded to the caller.
+        let view = unsafe { self.get_unchecked(txn, seq)? };
+        Ok(view.map(|view| project(&view)))
+    }
+
+    /// Retrieves an event like [`get`](Self::get), decoding it into a reusable buffer.
+    ///
+    /// `get` copies each event into a freshly allocated buffer when it has to (for
+    /// decryption and for aligning inline events). This variant uses `scratch` instead, and
+    /// returns the archived event borrowing it, so a hot loop that reuses one `scratch`
+    /// allocates only while the buffer grows to the largest event read. Blob-backed events
+    /// and events rewritten by an upcaster still allocate once before being copied in.
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use rkyv::util::AlignedVec;
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct Deposit { amount: u64 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # for version in 1..=3 {
+    /// #     writer.append(1, version, Deposit { amount: 10 * version as u64 })?;
+    /// # }
+    /// let reader = Reader::<Deposit>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let mut scratch = AlignedVec::new();
+    /// let mut total = 0;
+    /// for seq in 1..=3 {
+    ///     if let Some(event) = reader.get_into(&txn, seq, &mut scratch)? {
+    ///         total += event.amount.to_native();
+    ///     }
+    /// }
+    /// assert_eq!(total, 60);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`get`](Self::get). The contents of `scratch` are unspecified after an error.
+    pub fn get_into<'s>(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+        scratch: &'s mut AlignedVec,
+    ) -> crate::error::Result<Option<&'s E::Archived>> {
+        let Some(bytes) = self.storage.get_record(txn, seq)? else {
+            return Ok(None);
+        };
+        self.decode_into(txn, seq, bytes, scratch)
+            .map_err(|e| e.at_sequence(seq))?;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.events_read.inc();
+        }
+        rkyv::access::<E::Archived, rkyv::rancor::Error>(scratch.as_slice())
+            .map(Some)
+            .map_err(|e| crate::error::Error::from(e).at_sequence(seq))
+    }
+
+    /// Decodes the raw record stored at `seq` into `scratch`, as `decode_record` does into
+    /// an `EventData`.
+    fn decode_into(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+        bytes: &[u8],
+        scratch: &mut AlignedVec,
+    ) -> crate::error::Result<()> {
+        let (key_manager, bytes) = self.record_key_manager(bytes)?;
+        let encrypted = key_manager.is_some();
+        if let Some(km) = key_manager {
+            // Expect: [StreamID (16)][Nonce (12)][Ciphertext]
+            if bytes.len() < crate::constants::ENCRYPTED_EVENT_MIN_SIZE {
+                return Err(crate::error::Error::InvalidEncryptedEventLength {
+                    actual: bytes.len(),
+                    minimum: crate::constants::ENCRYPTED_EVENT_MIN_SIZE,
+                });
+            }
+
+            let (stream_id_bytes, rest) = bytes.split_at(crate::constants::STREAM_ID_SIZE);
+            let stream_id = u128::from_be_bytes(stream_id_bytes.try_into().unwrap());
+            let key = km
+                .get_stored_key_with_txn(txn, stream_id)?
+                .ok_or_else(|| crate::error::Error::KeyNotFound(stream_id))?;
+
+            // AAD: StreamID + Seq
+            let mut aad = [0u8; crate::constants::AAD_CAPACITY];
+            aad[..crate::constants::STREAM_ID_SIZE].copy_from_slice(stream_id_bytes);
+            aad[crate::constants::STREAM_ID_SIZE..].copy_from_slice(&seq.to_be_bytes());
+            crypto::decrypt_into(&key, rest, &aad, scratch)?;
+        }
+
+        let payload_bytes = if encrypted { scratch.as_slice() } else { bytes };
+        let archived_payload = rkyv::access::<
+            crate::model::ArchivedStoragePayload,
+            rkyv::rancor::Error,
+        >(payload_bytes)?;
+
+        // Inline events are located by their range within `payload_bytes`, so `scratch`
+        // can be rewritten once the payload is no longer borrowed.
+        let range = |data: &[u8]| {
+            let start = data.as_ptr() as usize - payload_bytes.as_ptr() as usize;
+            start..start + data.len()
+        };
+        let (inline, schema) = match archived_payload {
+            crate::model::ArchivedStoragePayload::Inline(data) => (Ok(range(data)), 0),
+            crate::model::ArchivedStoragePayload::InlineChecked { data, checksum } => {
+                self.verify_checksum(data, checksum.to_native())?;
+                (Ok(range(data)), 0)
+            }
+            crate::model::ArchivedStoragePayload::InlineVersioned {
+                data,
+                checksum,
+                schema,
+            } => {
+                self.verify_checksum(data, checksum.to_native())?;
+                (Ok(range(data)), schema.to_native())
+            }
+            crate::model::ArchivedStoragePayload::BlobRef(hash) => (Err(*hash), 0),
+            crate::model::ArchivedStoragePayload::BlobRefVersioned { hash, schema } => {
+                (Err(*hash), schema.to_native())
+            }
+        };
+
+        match inline {
+            // Move the event to the start of `scratch`, which is aligned for rkyv.
+            Ok(range) if encrypted => {
+                let len = range.len();
+                scratch.as_mut_slice().copy_within(range, 0);
+                scratch.resize(len, 0);
+            }
+            Ok(range) => {
+                scratch.clear();
+                scratch.extend_from_slice(&bytes[range]);
+            }
+            Err(hash) => {
+                let blob = self.read_blob(txn, seq, &hash)?;
+                scratch.clear();
+                scratch.extend_from_slice(blob.as_slice());
+            }
+        }
+
+        if let EventData::Owned(upcast) = self.upcast(schema, EventData::Borrowed(scratch))? {
+            scratch.clear();
+            scratch.extend_from_slice(&upcast);
+        }
+        Ok(())
+    }
+
+    /// Returns the size in bytes of the record stored at `seq`, without decoding it.
+    ///
+    /// This is the on-disk size: the ciphertext (with stream ID and nonce) when encryption is
+    /// enabled, and only the reference for events offloaded to a blob. Neither decrypts nor
+    /// validates anything, so it is cheap enough for quota accounting over the whole log.
+    /// Returns `Ok(None)` if there is no event at `seq`.
+    pub fn stored_size(&self, txn: &heed::RoTxn, seq: u64) -> crate::error::Result<Option<usize>> {
+        Ok(self.storage.get_record(txn, seq)?.map(<[u8]>::len))
+    }
+
+    /// Returns the size in bytes of the archived event at `seq`, as [`get`](Self::get)
+    /// would return it.
+    ///
+    /// The record is decrypted, its blob (if any) loaded and the bytes upcast, but the event
+    /// itself is not validated or deserialized. Returns `Ok(None)` if there is no event at
+    /// `seq`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`get`](Self::get) other than validation failures of the event.
+    pub fn decoded_size(&self, txn: &heed::RoTxn, seq: u64) -> crate::error::Result<Option<usize>> {
+        Ok(self
+            .get_event_data(txn, seq)
+            .map_err(|e| e.at_sequence(seq))?
+            .map(|data| data.as_slice().len()))
+    }
+
+    /// Retrieves an event by its global sequence number, tolerating unknown enum variants.
+    ///
+    /// Behaves like [`get`](Self::get), except that when the stored event fails validation
+    /// and its archived tag is beyond the variants declared with
+    /// [`with_known_variants`](Self::with_known_variants), it returns
+    /// [`ReadOutcome::Unknown`] instead of an error. With
+    /// [`MissingBlob::ReturnPlaceholder`], an event whose blob is gone is returned as
+    /// [`ReadOutcome::MissingBody`].
+    ///
+    /// # Rolling Upgrades
+    ///
+    /// Event types are usually enums that grow over time. During a rolling upgrade, producers
+    /// may start writing a new variant before every consumer has been redeployed. An old consumer
+    /// reading such an event cannot interpret it, but it usually should not stop consuming either.
+    /// Use `get_or_skip` in those consumers and treat `Unknown` as "skip and move on":
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{ReadOutcome, Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// enum AccountEvent {
+    ///     Opened { owner: String },
+    ///     Closed,
+    /// }
+    ///
+    /// # // The upgraded producer's type, with a variant old consumers don't know.
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # enum UpgradedAccountEvent { Opened { owner: String }, Closed, Frozen }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # writer.append(1, 1, UpgradedAccountEvent::Opened { owner: "ada".into() })?;
+    /// # writer.append(1, 2, UpgradedAccountEvent::Frozen)?;
+    /// # writer.append(1, 3, UpgradedAccountEvent::Closed)?;
+    /// let reader = Reader::<AccountEvent>::new(storage.clone()).with_known_variants(2);
+    /// let txn = storage.env.read_txn()?;
+    /// let mut handled = 0;
+    /// for seq in 1.. {
+    ///     match reader.get_or_skip(&txn, seq)? {
+    ///         Some(ReadOutcome::Known(_event)) => handled += 1,
+    ///         Some(ReadOutcome::Unknown) => println!("Skipping unknown event at {}", seq),
+    ///         Some(ReadOutcome::MissingBody { .. }) => unreachable!(),
+    ///         None => break,
+    ///     }
+    /// }
+    /// assert_eq!(handled, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Only append new variants at the end of the enum: reordering variants changes the
+    /// discriminants of existing ones, which cannot be detected.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`get`](Self::get), except for events with an unknown tag.
+    pub fn get_or_skip<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<ReadOutcome<'txn, E>>> {
+        let data = match self.get_event_data(txn, seq) {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(None),
+            Err(crate::error::Error::BlobMissing { hash, .. }) => {
+                return Ok(Some(ReadOutcome::MissingBody { hash }));
+            }
+            Err(e) => return Err(e.at_sequence(seq)),
+        };
+
+        let bytes = data.as_slice();
+        match rkyv::access::<E::Archived, rkyv::rancor::Error>(bytes) {
+            Ok(_) => Ok(Some(ReadOutcome::Known(self.make_view(data)))),
+            Err(e) => {
+                // The archived root, and so an enum's tag, sits at the end of the buffer.
+                let tag = bytes.get(rkyv::api::root_position::<E::Archived>(bytes.len()));
+                match (tag, self.known_variants) {
+                    (Some(&tag), Some(count)) if tag >= count => {
+                        tracing::debug!("Event {} has an unknown enum tag {}", seq, tag);
+                        Ok(Some(ReadOutcome::Unknown))
+                    }
+                    _ => Err(crate::error::Error::from(e).at_sequence(seq)),
+                }
+            }
+        }
+    }
+
+    /// Retrieves up to `count` consecutive events starting at `seq = start`.
+    ///
+    /// Useful when replaying the log in batches, e.g. to rebuild a projection. Sequence
+    /// numbers deleted by [`trim_stream`](Writer::trim_stream) are skipped, but the result
+    /// stops early at the first other missing sequence number (a gap left by `append_at` or
+    /// the end of the log), so fewer than `count` envelopes mean the next call should start
+    /// at the sequence after the last one returned, if it exists.
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct Deposit { amount: u64 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # for version in 1..=5 {
+    /// #     writer.append(1, version, Deposit { amount: 10 })?;
+    /// # }
+    /// let reader = Reader::<Deposit>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let mut next = 1;
+    /// let mut balance = 0;
+    /// loop {
+    ///     let batch = reader.get_envelopes(&txn, next, 2)?;
+    ///     let Some(last) = batch.last() else { break };
+    ///     next = last.seq + 1;
+    ///     for envelope in &batch {
+    ///         balance += envelope.event.amount.to_native();
+    ///     }
+    /// }
+    /// assert_eq!(balance, 50);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`get`](Self::get) for the first record that fails to read.
+    pub fn get_envelopes<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        start: u64,
+        count: usize,
+    ) -> crate::error::Result<Vec<EventEnvelope<'txn, E>>> {
+        let mut envelopes = Vec::with_capacity(count.min(1024));
+        for seq in self.storage.sequences(txn, start).take(count) {
+            let seq = seq?;
+            let Some(bytes) = self.storage.get_record(txn, seq)? else {
+                break;
+            };
+
+            let data = self
+                .decode_record(txn, seq, bytes)
+                .map_err(|e| e.at_sequence(seq))?;
+            rkyv::access::<E::Archived, rkyv::rancor::Error>(data.as_slice())
+                .map_err(|e| crate::error::Error::from(e).at_sequence(seq))?;
+            envelopes.push(EventEnvelope {
+                seq,
+                lamport: self.lamport_timestamp(txn, seq)?,
+                event: self.make_view(data),
+            });
+        }
+        Ok(envelopes)
+    }
+
+    /// Retrieves the event at `seq` together with its sequence number and Lamport timestamp.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`get`](Self::get).
+    pub fn get_envelope<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventEnvelope<'txn, E>>> {
+        let Some(event) = self.get(txn, seq)? else {
+            return Ok(None);
+        };
+        Ok(Some(EventEnvelope {
+            seq,
+            lamport: self.lamport_timestamp(txn, seq)?,
+            event,
+        }))
+    }
+
+    /// Returns the Lamport timestamp the event at `seq` was stamped with, if any.
+    fn lamport_timestamp(&self, txn: &heed::RoTxn, seq: u64) -> crate::error::Result<Option<u64>> {
+        match &self.storage.lamport_clocks {
+            Some(lamport_clocks) => Ok(lamport_clocks.get(txn, &seq)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterates over every stored record, yielding a separate result per sequence number.
+    ///
+    /// Unlike [`get`](Self::get)-based iteration, a record that fails to decrypt, checksum or
+    /// validate does not end the iteration: it is yielded as `(seq, Err(..))` and the
+    /// iterator moves on to the next stored key. This lets recovery tooling salvage the
+    /// readable events around a corrupt record and collect the bad sequence numbers.
+    ///
+    /// Records are visited in global sequence order; gaps in the log are skipped. If the
+    /// underlying cursor itself fails, the iterator ends.
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use tempfile::tempdir;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// let mut writer = Writer::<u64>::new(storage.clone());
+    /// writer.append(1, 1, 10)?;
+    /// writer.append(1, 2, 20)?;
+    ///
+    /// let reader = Reader::<u64>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let mut recovered = Vec::new();
+    /// let mut corrupt = Vec::new();
+    /// for (seq, result) in reader.iter_lossy(&txn)? {
+    ///     match result {
+    ///         Ok(event) => recovered.push(*event),
+    ///         Err(e) => corrupt.push((seq, e)),
+    ///     }
+    /// }
+    /// assert_eq!(recovered, [10, 20]);
+    /// assert!(corrupt.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_lossy<'a, 'txn: 'a>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+    ) -> crate::error::Result<
+        impl Iterator<Item = (u64, crate::error::Result<EventView<'txn, E>>)> + 'a,
+    > {
+        // Failed reads become items, so only a cursor error comes out of `scan` as `Err`.
+        let entries = self.scan(txn, .., move |seq| {
+            let result = self.get(txn, seq).and_then(|view| {
+                view.ok_or_else(|| {
+                    crate::error::Error::EventValidation("record not found".to_string())
+                })
+            });
+            Ok(Some(result))
+        })?;
+
+        Ok(entries.map_while(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::warn!("Lossy iteration stopped: {}", e);
+                None
+            }
+        }))
+    }
+
+    /// Iterates over every event together with how its record is stored.
+    ///
+    /// Each item reports the [`StoragePayload`] variant of the record (inline or blob, with
+    /// or without checksum and schema version) and whether it is encrypted, for tooling that
+    /// looks into why some events are large on disk or slow to read. Encrypted records are
+    /// decrypted twice, once for the event and once for the report. Errors are yielded as
+    /// in [`iter_filtered`](Self::iter_filtered).
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct Note { text: String }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # writer.append(1, 1, Note { text: "short".into() })?;
+    /// # writer.append(1, 2, Note { text: "long ".repeat(1000) })?;
+    /// let reader = Reader::<Note>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let mut blobs = Vec::new();
+    /// for result in reader.iter_with_storage_kind(&txn)? {
+    ///     let (seq, kind, _event) = result?;
+    ///     if kind.payload.is_blob() {
+    ///         blobs.push(seq);
+    ///     }
+    /// }
+    /// assert_eq!(blobs, [2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_with_storage_kind<'a, 'txn: 'a>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+    ) -> crate::error::Result<
+        impl Iterator<
+                Item = crate::error::Result<(u64, crate::model::StorageKind, EventView<'txn, E>)>,
+            > + 'a,
+    > {
+        Ok(self
+            .scan(txn, .., move |seq| self.get(txn, seq))?
+            .map(move |result| {
+                let (seq, view) = result?;
+                Ok((seq, self.storage_kind(txn, seq)?, view))
+            }))
+    }
+
+    /// Iterates over the events matching `filter`, in global sequence order.
+    ///
+    /// Each event is validated once and `filter` runs on the archived event, so events are
+    /// matched without deserializing them; only matches are yielded, as `(seq, view)`.
+    /// Gaps in the log are skipped. A record that fails to read is yielded as an error and
+    /// the iteration continues with the next one; if the underlying cursor fails, its error
+    /// is the last item.
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct Payment { amount: u64 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # for (version, amount) in [(1, 50), (2, 5_000), (3, 20)] {
+    /// #     writer.append(1, version, Payment { amount })?;
+    /// # }
+    /// let reader = Reader::<Payment>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let mut flagged = Vec::new();
+    /// for result in reader.iter_filtered(&txn, |e| e.amount > 1_000)? {
+    ///     let (seq, event) = result?;
+    ///     flagged.push((seq, event.amount.to_native()));
+    /// }
+    /// assert_eq!(flagged, [(2, 5_000)]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_filtered<'a, 'txn: 'a, F>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+        filter: F,
+    ) -> crate::error::Result<
+        impl Iterator<Item = crate::error::Result<(u64, EventView<'txn, E>)>> + 'a,
+    >
+    where
+        F: Fn(&E::Archived) -> bool + 'a,
+    {
+        self.scan(txn, .., move |seq| {
+            Ok(self.get(txn, seq)?.filter(|view| filter(view)))
+        })
+    }
+
+    /// Iterates over the events of an enum event type whose variant is one of `tags`.
+    ///
+    /// A variant's tag is its index in the enum declaration, starting at 0. Only the tag
+    /// byte of each record is read before deciding, so records of other variants are never
+    /// validated; for a scan like "every `Payment` event" this skips most of the cost of
+    /// [`iter_filtered`](Self::iter_filtered). Matching records are validated as usual.
+    /// Encrypted records still have to be decrypted to read their tag.
+    ///
+    /// For an `E` that is not an enum, the byte read is the start of the archived value and
+    /// has no meaning. Errors are yielded as in `iter_filtered`.
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// #[derive(Archive, Serialize, Deserialize)]
+    /// enum BankEvent {
+    ///     Deposit { amount: u64 },
+    ///     Payment { amount: u64 },
+    ///     Refund { amount: u64 },
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # writer.append(1, 1, BankEvent::Deposit { amount: 100 })?;
+    /// # writer.append(1, 2, BankEvent::Payment { amount: 30 })?;
+    /// # writer.append(1, 3, BankEvent::Refund { amount: 10 })?;
+    /// # writer.append(1, 4, BankEvent::Deposit { amount: 5 })?;
+    /// let reader = Reader::<BankEvent>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// // Payments and refunds only.
+    /// let mut seqs = Vec::new();
+    /// for result in reader.iter_by_discriminant(&txn, &[1, 2])? {
+    ///     let (seq, _event) = result?;
+    ///     seqs.push(seq);
+    /// }
+    /// assert_eq!(seqs, [2, 3]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_by_discriminant<'a, 'txn: 'a>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+        tags: &'a [u8],
+    ) -> crate::error::Result<
+        impl Iterator<Item = crate::error::Result<(u64, EventView<'txn, E>)>> + 'a,
+    > {
+        self.scan(txn, .., move |seq| {
+            let Some(data) = self
+                .get_event_data(txn, seq)
+                .map_err(|e| e.at_sequence(seq))?
+            else {
+                return Ok(None);
+            };
+
+            // The archived root, and so an enum's tag, sits at the end of the buffer.
+            let bytes = data.as_slice();
+            let tag = bytes.get(rkyv::api::root_position::<E::Archived>(bytes.len()));
+            if tag.is_some_and(|tag| !tags.contains(tag)) {
+                return Ok(None);
+            }
+
+            rkyv::access::<E::Archived, rkyv::rancor::Error>(bytes)
+                .map_err(|e| crate::error::Error::from(e).at_sequence(seq))?;
+            Ok(Some(self.make_view(data)))
+        })
+    }
+
+    /// Retrieves the event stored under event ID `id`, with its sequence number.
+    ///
+    /// Requires [`LogKey::U128`](crate::storage::LogKey::U128); see
+    /// [`Writer::append_with_id`]. Returns `Ok(None)` if no event has this ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if the storage was not
+    /// created with `LogKey::U128`, and otherwise the same errors as [`get`](Self::get).
+    pub fn get_by_id<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        id: u128,
+    ) -> crate::error::Result<Option<(u64, EventView<'txn, E>)>> {
+        match self.event_ids()?.get(txn, &id)? {
+            Some(seq) => Ok(self.get(txn, seq)?.map(|view| (seq, view))),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterates over the events whose event IDs fall in `ids`, as `(id, seq, view)`.
+    ///
+    /// Events come in ID order, which is also log order. With writer-assigned IDs, a time
+    /// range is `LogKey::first_id_at(from)..LogKey::first_id_at(to)`. Errors are yielded as
+    /// in [`iter_filtered`](Self::iter_filtered).
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Reader, Writer};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// use varvedb::storage::LogKey;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize)]
+    /// # struct Reading { celsius: i32 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig {
+    /// #     path: dir.path().to_path_buf(),
+    /// #     log_key: LogKey::U128,
+    /// #     ..Default::default()
+    /// # };
+    /// # let storage = Storage::open(config)?;
+    /// # let now_ms = 10_000_000;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # for (version, (at, celsius)) in [(1_000, 18), (now_ms - 60_000, 21)].into_iter().enumerate() {
+    /// #     writer.append_with_id(1, version as u32 + 1, LogKey::first_id_at(at), Reading { celsius })?;
+    /// # }
+    /// let reader = Reader::<Reading>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// let last_hour = LogKey::first_id_at(now_ms - 3_600_000)..;
+    /// let mut recent = Vec::new();
+    /// for result in reader.iter_by_id(&txn, last_hour)? {
+    ///     let (_id, seq, event) = result?;
+    ///     recent.push((seq, event.celsius.to_native()));
+    /// }
+    /// assert_eq!(recent, [(2, 21)]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if the storage was not
+    /// created with [`LogKey::U128`](crate::storage::LogKey::U128).
+    pub fn iter_by_id<'a, 'txn: 'a>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+        ids: impl std::ops::RangeBounds<u128>,
+    ) -> crate::error::Result<
+        impl Iterator<Item = crate::error::Result<(u128, u64, EventView<'txn, E>)>> + 'a,
+    > {
+        let entries = self.event_ids()?.range(txn, &ids)?;
+
+        // As in `scan`, a failing cursor ends the iteration.
+        let mut failed = false;
+        Ok(entries
+            .map_while(move |entry| match entry {
+                _ if failed => None,
+                Ok((id, seq)) => Some(
+                    self.get(txn, seq)
+                        .map(|view| view.map(|view| (id, seq, view)))
+                        .transpose(),
+                ),
+                Err(e) => {
+                    failed = true;
+                    Some(Some(Err(e.into())))
+                }
+            })
+            .flatten())
+    }
+
+    /// The event ID database, which only exists under `LogKey::U128`.
+    fn event_ids(&self) -> crate::error::Result<&crate::storage::EventIdDb> {
+        self.storage.events_by_id.as_ref().ok_or_else(|| {
+            crate::error::Error::InvalidConfig("event IDs require LogKey::U128".to_string())
+        })
+    }
+
+    /// Runs `read` on every stored sequence number in `seqs` and yields the values it returns,
+    /// usually events.
+    fn scan<'a, 'txn: 'a, T, F>(
+        &'a self,
+        txn: &'txn heed::RoTxn,
+        seqs: impl std::ops::RangeBounds<u64>,
+        mut read: F,
+    ) -> crate::error::Result<impl Iterator<Item = crate::error::Result<(u64, T)>> + 'a>
+    where
+        F: FnMut(u64) -> crate::error::Result<Option<T>> + 'a,
+    {
+        let keys = self
+            .storage
+            .events_log
+            .remap_data_type::<heed::types::DecodeIgnore>()
+            .range(txn, &seqs)?;
+
+        // A failing cursor would fail again on every call, so its error ends the scan.
+        let mut failed = false;
+        Ok(keys
+            .map_while(move |entry| match entry {
+                _ if failed => None,
+                Ok((seq, ())) => Some(
+                    read(seq)
+                        .map(|view| view.map(|view| (seq, view)))
+                        .transpose(),
+                ),
+                Err(e) => {
+                    failed = true;
+                    Some(Some(Err(e.into())))
+                }
+            })
+            .flatten())
+    }
+
+    /// Loads the raw event bytes for `seq`: decrypts the record, decodes the storage
+    /// envelope and resolves blob references. The returned bytes are not validated.
+    fn get_event_data<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventData<'txn>>> {
+        match self.storage.get_record(txn, seq)? {
+            Some(bytes) => self.decode_record(txn, seq, bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Strips the encryption flag from a stored record, if it has one, and returns the key
+    /// manager to decrypt the rest with, or `None` if it is plaintext.
+    fn record_key_manager<'b>(
+        &self,
+        bytes: &'b [u8],
+    ) -> crate::error::Result<(Option<&KeyManager>, &'b [u8])> {
+        if self.storage.config.encrypted_streams.is_none() {
+            return Ok((self.key_manager.as_ref(), bytes));
+        }
+        match bytes.split_last() {
+            Some((&crate::constants::RECORD_PLAINTEXT, rest)) => Ok((None, rest)),
+            Some((&crate::constants::RECORD_ENCRYPTED, rest)) => {
+                Ok((self.key_manager.as_ref(), rest))
+            }
+            _ => Err(crate::error::Error::EventValidation(
+                "invalid record encryption flag".to_string(),
+            )),
+        }
+    }
+
+    /// Decodes the raw record stored at `seq` into event bytes (see `get_event_data`).
+    fn decode_record<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+        bytes: &'txn [u8],
+    ) -> crate::error::Result<EventData<'txn>> {
+        let (key_manager, bytes) = self.record_key_manager(bytes)?;
+        let payload_data = match key_manager {
+            Some(km) => EventData::Owned(self.decrypt_record(txn, seq, km, bytes)?),
+            None => EventData::Borrowed(bytes),
+        };
+
+        // Deserialize Payload
+        let payload_bytes = payload_data.as_slice();
+
+        let archived_payload = rkyv::access::<
+            crate::model::ArchivedStoragePayload,
+            rkyv::rancor::Error,
+        >(payload_bytes)?;
+
+        let (data, schema) = match archived_payload {
+            crate::model::ArchivedStoragePayload::Inline(inline_bytes) => {
+                (EventData::Owned(inline_bytes.as_slice().to_vec()), 0)
+            }
+            crate::model::ArchivedStoragePayload::InlineChecked { data, checksum } => {
+                (self.check_inline(data.as_slice(), checksum.to_native())?, 0)
+            }
+            crate::model::ArchivedStoragePayload::InlineVersioned {
+                data,
+                checksum,
+                schema,
+            } => (
+                self.check_inline(data.as_slice(), checksum.to_native())?,
+                schema.to_native(),
+            ),
+            crate::model::ArchivedStoragePayload::BlobRef(hash) => {
+                (self.read_blob(txn, seq, hash)?, 0)
+            }
+            crate::model::ArchivedStoragePayload::BlobRefVersioned { hash, schema } => {
+                (self.read_blob(txn, seq, hash)?, schema.to_native())
+            }
+        };
+
+        self.upcast(schema, data)
+    }
+
+    /// Decrypts the record stored at `seq` into its storage payload bytes.
+    fn decrypt_record(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+        km: &KeyManager,
+        bytes: &[u8],
+    ) -> crate::error::Result<Vec<u8>> {
+        // Expect: [StreamID (16)][Nonce (12)][Ciphertext]
+        if bytes.len() < crate::constants::ENCRYPTED_EVENT_MIN_SIZE {
+            return Err(crate::error::Error::InvalidEncryptedEventLength {
+                actual: bytes.len(),
+                minimum: crate::constants::ENCRYPTED_EVENT_MIN_SIZE,
+            });
+        }
+
+        let (stream_id_bytes, rest) = bytes.split_at(crate::constants::STREAM_ID_SIZE);
+        let stream_id = u128::from_be_bytes(stream_id_bytes.try_into().unwrap());
+
+        let key = km
+            .get_stored_key_with_txn(txn, stream_id)?
+            .ok_or_else(|| crate::error::Error::KeyNotFound(stream_id))?;
+
+        // AAD: StreamID + Seq
+        let mut aad = Vec::with_capacity(crate::constants::AAD_CAPACITY);
+        aad.extend_from_slice(stream_id_bytes);
+        aad.extend_from_slice(&seq.to_be_bytes());
+
+        crypto::decrypt(&key, rest, &aad)
+    }
+
+    /// Reports how the record stored at `seq` is stored, without reading blobs.
+    fn storage_kind(
+        &self,
+        txn: &heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<crate::model::StorageKind> {
+        let bytes = self
+            .storage
+            .get_record(txn, seq)?
+            .ok_or_else(|| crate::error::Error::EventValidation("record not found".to_string()))?;
+        let (key_manager, bytes) = self.record_key_manager(bytes)?;
+        let payload_data = match key_manager {
+            Some(km) => EventData::Owned(self.decrypt_record(txn, seq, km, bytes)?),
+            None => EventData::Borrowed(bytes),
+        };
+        let archived_payload = rkyv::access::<
+            crate::model::ArchivedStoragePayload,
+            rkyv::rancor::Error,
+        >(payload_data.as_slice())?;
+
+        Ok(crate::model::StorageKind {
+            payload: archived_payload.into(),
+            encrypted: key_manager.is_some(),
+        })
+    }
+
+    /// Returns the content hash of the blob holding the event at `seq`, or `None` if the
+    /// event is stored inline or there is no record at `seq`.
+    fn blob_hash(&self, txn: &heed::RoTxn, seq: u64) -> crate::error::Result<Option<[u8; 32]>> {
+        let Some(bytes) = self.storage.get_record(txn, seq)? else {
+            return Ok(None);
+        };
+        let (key_manager, bytes) = self.record_key_manager(bytes)?;
+        let payload_data = match key_manager {
+            Some(km) => EventData::Owned(self.decrypt_record(txn, seq, km, bytes)?),
+            None => EventData::Borrowed(bytes),
+        };
+        let archived_payload = rkyv::access::<
+            crate::model::ArchivedStoragePayload,
+            rkyv::rancor::Error,
+        >(payload_data.as_slice())?;
+
+        Ok(match archived_payload {
+            crate::model::ArchivedStoragePayload::BlobRef(hash)
+            | crate::model::ArchivedStoragePayload::BlobRefVersioned { hash, .. } => Some(*hash),
+            _ => None,
+        })
+    }
+
+    /// Returns inline event bytes, verifying their CRC32C if checksums are enabled.
+    fn check_inline<'txn>(
+        &self,
+        data: &[u8],
+        checksum: u32,
+    ) -> crate::error::Result<EventData<'txn>> {
+        self.verify_checksum(data, checksum)?;
+        Ok(EventData::Owned(data.to_vec()))
+    }
+
+    /// Fails if checksums are enabled and `data` does not match its CRC32C.
+    fn verify_checksum(&self, data: &[u8], checksum: u32) -> crate::error::Result<()> {
+        if self.storage.config.verify_checksums && crc32c::crc32c(data) != checksum {
+            return Err(crate::error::Error::EventValidation(
+                "checksum mismatch".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Loads the blob holding the bytes of event `seq`.
+    fn read_blob<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+        hash: &[u8; 32],
+    ) -> crate::error::Result<EventData<'txn>> {
+        let verify_checksums = self.storage.config.verify_checksums;
+        self.storage.with_blob(txn, hash.as_slice(), |blob| {
+            let blob_bytes = blob.ok_or_else(|| match self.storage.config.on_missing_blob {
+                MissingBlob::Error => {
+                    crate::error::Error::EventValidation("Blob not found".to_string())
+                }
+                MissingBlob::ReturnPlaceholder => {
+                    crate::error::Error::BlobMissing { seq, hash: *hash }
+                }
+            })?;
+
+            // Blobs are content-addressed, so the hash doubles as their checksum.
+            if verify_checksums && Sha256::digest(blob_bytes).as_slice() != hash {
+                return Err(crate::error::Error::EventValidation(
+                    "checksum mismatch".to_string(),
+                ));
+            }
+
+            // MADVISE: Tell OS we don't need this page anymore
+            #[cfg(unix)]
+            unsafe {
+                let ptr = blob_bytes.as_ptr() as *const libc::c_void;
+                let len = blob_bytes.len();
+                // Round down to page boundary (required by madvise)
+                // Actually, heed/lmdb gives us a pointer. We should probably madvise the whole page containing it?
+                // Or just the range. madvise usually requires page alignment.
+                // Let's try to align it.
+                let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+                let addr = ptr as usize;
+                let aligned_addr = addr & !(page_size - 1);
+                let offset = addr - aligned_addr;
+                let aligned_len = len + offset;
+
+                libc::madvise(
+                    aligned_addr as *mut libc::c_void,
+                    aligned_len,
+                    libc::MADV_DONTNEED,
+                );
+            }
+
+            Ok(EventData::Owned(blob_bytes.to_vec()))
+        })
+    }
+
+    /// Runs event bytes written with schema version `schema` through the upcaster, one
+    /// version at a time, until they match this reader's schema version.
+    fn upcast<'txn>(
+        &self,
+        schema: SchemaVersion,
+        data: EventData<'txn>,
+    ) -> crate::error::Result<EventData<'txn>> {
+        let Some(upcaster) = &self.upcaster else {
+            return Ok(data);
+        };
+        if schema >= self.schema_version {
+            return Ok(data);
+        }
+
+        let mut bytes = upcaster(schema, data.as_slice())?;
+        for version in schema + 1..self.schema_version {
+            bytes = upcaster(version, &bytes)?;
+        }
+        Ok(EventData::Owned(bytes))
+    }
+
+    fn make_view<'txn>(&self, data: EventData<'txn>) -> EventView<'txn, E> {
+        if let Some(metrics) = &self.metrics {
+            metrics.events_read.inc();
+        }
+
+        EventView {
+            data,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Retrieves the metadata stored with event `seq` by [`Writer::append_payload`].
+    ///
+    /// Returns `None` if there is no such event or it was appended without metadata. The
+    /// view dereferences to `M::Archived`; `M` must be the type the metadata was written as.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata fails validation as an `M`, or if decrypting it fails
+    /// (including [`KeyNotFound`](crate::error::Error::KeyNotFound) once the stream key has
+    /// been deleted).
+    pub fn get_metadata<'txn, M>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> crate::error::Result<Option<EventView<'txn, M>>>
+    where
+        M: rkyv::Archive,
+        M::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    {
+        let Some(event_metadata) = self.storage.event_metadata.open(txn)? else {
+            return Ok(None);
+        };
+        let Some(bytes) = event_metadata.get(txn, &seq)? else {
+            return Ok(None);
+        };
+
+        let (key_manager, bytes) = self.record_key_manager(bytes)?;
+        let data = match key_manager {
+            Some(km) => {
+                if bytes.len() < crate::constants::ENCRYPTED_EVENT_MIN_SIZE {
+                    return Err(crate::error::Error::InvalidEncryptedEventLength {
+                        actual: bytes.len(),
+                        minimum: crate::constants::ENCRYPTED_EVENT_MIN_SIZE,
+                    });
+                }
+                let (stream_id_bytes, rest) = bytes.split_at(crate::constants::STREAM_ID_SIZE);
+                let stream_id = u128::from_be_bytes(stream_id_bytes.try_into().unwrap());
+                let key = km
+                    .get_stored_key_with_txn(txn, stream_id)?
+                    .ok_or_else(|| crate::error::Error::KeyNotFound(stream_id))?;
+                EventData::Owned(crypto::decrypt(&key, rest, &metadata_aad(stream_id, seq))?)
+            }
+            None => EventData::Borrowed(bytes),
+        };
+
+        rkyv::access::<M::Archived, RancorError>(data.as_slice())?;
+        Ok(Some(EventView {
+            data,
+            _marker: std::marker::PhantomData,
+        }))
+    }
+
+    /// Retrieves an event by its stream ID and version (sequence number in the stream).
+    ///
+    /// This method looks up the global sequence number for the given stream and version,
+    /// and then retrieves the event using `get`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::{Writer, Reader};
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use rkyv::{Archive, Serialize, Deserialize};
+    /// # use tempfile::tempdir;
+    /// #
+    /// # #[derive(Archive, Serialize, Deserialize, Debug)]
+    /// # #[rkyv(derive(Debug))]
+    /// # struct MyEvent { pub data: u32 }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let mut writer = Writer::new(storage.clone());
+    /// # writer.append(1, 1, MyEvent { data: 42 })?;
+    /// #
+    /// let reader = Reader::<MyEvent>::new(storage.clone());
+    /// let txn = storage.env.read_txn()?;
+    /// if let Some(event) = reader.get_by_stream(&txn, 1, 1)? {
+    ///     println!("Event: {:?}", event);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// For more examples, see the [examples directory](https://github.com/Cardosaum/varvedb/tree/main/examples).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn get_by_stream<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
+        let key_bytes = self.storage.stream_key(stream_id, version);
+
+        let Some(seq) = self.storage.stream_index.get(txn, key_bytes.as_slice())? else {
+            return Ok(None);
+        };
+
+        self.get_indexed(txn, &key_bytes, seq)
+    }
+
+    /// Retrieves the versions `[from_version, to_version)` of a stream, in version order.
+    ///
+    /// The window is read with a single bounded scan over the stream index instead of one
+    /// lookup per version. Versions missing from the stream are simply absent from the
+    /// result, and an empty window (`from_version >= to_version`) returns an empty `Vec`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn get_by_stream_range<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        stream_id: u128,
+        from_version: u32,
+        to_version: u32,
+    ) -> crate::error::Result<Vec<(u32, EventView<'txn, E>)>> {
+        if from_version >= to_version {
+            return Ok(Vec::new());
+        }
+
+        let start = self.storage.stream_key(stream_id, from_version);
+        let end = self.storage.stream_key(stream_id, to_version);
+        let range = (
+            Bound::Included(start.as_slice()),
+            Bound::Excluded(end.as_slice()),
+        );
+
+        let mut events = Vec::new();
+        for result in self.storage.stream_index.range(txn, &range)? {
+            let (key_bytes, seq) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let key: [u8; 20] = key_bytes.try_into().unwrap();
+            let version = u32::from_be_bytes(key[16..20].try_into().unwrap());
+            if let Some(view) = self.get_indexed(txn, &key, seq)? {
+                events.push((version, view));
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Retrieves the highest version of a stream at or below `as_of_version`.
+    ///
+    /// This is the event that was the head of the stream when it reached `as_of_version`,
+    /// for reconstructing state as of that version. The lookup is a single reverse scan
+    /// over the stream index, bounded at `as_of_version`. Returns `None` if the stream has
+    /// no version at or below it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn get_by_stream_as_of<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        stream_id: u128,
+        as_of_version: u32,
+    ) -> crate::error::Result<Option<(u32, EventView<'txn, E>)>> {
+        let start = self.storage.stream_key(stream_id, 0);
+        let end = self.storage.stream_key(stream_id, as_of_version);
+        let range = (
+            Bound::Included(start.as_slice()),
+            Bound::Included(end.as_slice()),
+        );
+
+        for result in self.storage.stream_index.rev_range(txn, &range)? {
+            let (key_bytes, seq) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let key: [u8; 20] = key_bytes.try_into().unwrap();
+            let version = u32::from_be_bytes(key[16..20].try_into().unwrap());
+            if let Some(view) = self.get_indexed(txn, &key, seq)? {
+                return Ok(Some((version, view)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Checks that the versions of a stream map to strictly increasing global sequences.
+    ///
+    /// This is the ordering invariant documented on [`Writer`]. The check only walks the
+    /// stream index, without reading any event. Returns `Ok(())` for empty streams.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EventValidation`](crate::error::Error::EventValidation) naming the first
+    /// version whose sequence number is not greater than the previous version's, or an error
+    /// if the underlying storage encounters an I/O error.
+    pub fn verify_stream_monotonic(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+    ) -> crate::error::Result<()> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut previous: Option<(u32, u64)> = None;
+
+        for result in self
+            .storage
+            .stream_index
+            .prefix_iter(txn, &stream_id_bytes)?
+        {
+            let (key_bytes, seq) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let version_bytes: [u8; 4] = key_bytes[16..20].try_into().unwrap();
+            let version = u32::from_be_bytes(version_bytes);
+
+            if let Some((prev_version, prev_seq)) = previous {
+                if seq <= prev_seq {
+                    return Err(crate::error::Error::EventValidation(format!(
+                        "stream {} version {} has sequence {}, not after version {} at sequence {}",
+                        stream_id, version, seq, prev_version, prev_seq
+                    )));
+                }
+            }
+            previous = Some((version, seq));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the head version, event count and global sequence range of a stream.
+    ///
+    /// Everything is gathered in one scan of the stream index, without reading any event,
+    /// so it is cheaper than combining [`get_latest`](Self::get_latest) with a count.
+    /// Returns `Ok(None)` for a stream with no events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying storage encounters an I/O error.
+    pub fn stream_info(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+    ) -> crate::error::Result<Option<StreamInfo>> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut info: Option<StreamInfo> = None;
+
+        for result in self
+            .storage
+            .stream_index
+            .prefix_iter(txn, &stream_id_bytes)?
+        {
+            let (key_bytes, seq) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let version_bytes: [u8; 4] = key_bytes[16..20].try_into().unwrap();
+            let version = u32::from_be_bytes(version_bytes);
+
+            // Keys are in version order, so the last one seen is the head.
+            let info = info.get_or_insert(StreamInfo {
+                head_version: version,
+                count: 0,
+                first_global_seq: seq,
+                last_global_seq: seq,
+            });
+            info.head_version = version;
+            info.count += 1;
+            // Sequences follow versions, except for records placed with `append_at`.
+            info.first_global_seq = info.first_global_seq.min(seq);
+            info.last_global_seq = info.last_global_seq.max(seq);
+        }
+
+        Ok(info)
+    }
+
+    /// Fetches the event behind a stream index entry.
+    fn get_indexed<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        key_bytes: &[u8; 20],
+        seq: u64,
+    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
+        // The clustered layout stores records by stream key, so skip the sequence lookup.
+        match self.storage.get_clustered_record(txn, key_bytes)? {
+            Some(bytes) => {
+                let data = self
+                    .decode_record(txn, seq, bytes)
+                    .map_err(|e| e.at_sequence(seq))?;
+                rkyv::access::<E::Archived, rkyv::rancor::Error>(data.as_slice())
+                    .map_err(|e| crate::error::Error::from(e).at_sequence(seq))?;
+                Ok(Some(self.make_view(data)))
+            }
+            None => self.get(txn, seq),
+        }
+    }
 
-impl<'a, E> std::fmt::Debug for EventView<'a, E>
-where
-    E: rkyv::Archive,
-    E::Archived: std::fmt::Debug,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        (**self).fmt(f)
+    /// Retrieves all events recorded under `key` in the secondary index `index_name`.
+    ///
+    /// Results are returned in global sequence order, paired with their sequence numbers.
+    /// Returns an empty `Vec` if the index does not exist or has no entry for `key`.
+    /// Indexes are registered on the write side with [`Writer::with_index`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn find_by<'txn, K>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        index_name: &str,
+        key: &K,
+    ) -> crate::error::Result<Vec<(u64, EventView<'txn, E>)>>
+    where
+        K: IndexKey + ?Sized,
+    {
+        let Some(db) = self.storage.open_secondary_index(txn, index_name)? else {
+            return Ok(Vec::new());
+        };
+
+        let key_bytes = key.to_index_bytes();
+        let mut events = Vec::new();
+        if let Some(seqs) = db.get_duplicates(txn, key_bytes.as_slice())? {
+            for result in seqs {
+                let (_, seq) = result?;
+                if let Some(view) = self.get(txn, seq)? {
+                    events.push((seq, view));
+                }
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Retrieves the event with the lowest global sequence number, paired with that number.
+    ///
+    /// Returns `Ok(None)` for an empty log. Together with [`last`](Self::last) this gives
+    /// the sequence range that is currently stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn first<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+    ) -> crate::error::Result<Option<(u64, EventView<'txn, E>)>> {
+        let Some((seq, _)) = self.storage.events_log.first(txn)? else {
+            return Ok(None);
+        };
+        Ok(self.get(txn, seq)?.map(|view| (seq, view)))
+    }
+
+    /// Retrieves the event with the highest global sequence number, paired with that number.
+    ///
+    /// Returns `Ok(None)` for an empty log.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn last<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+    ) -> crate::error::Result<Option<(u64, EventView<'txn, E>)>> {
+        let Some((seq, _)) = self.storage.events_log.last(txn)? else {
+            return Ok(None);
+        };
+        Ok(self.get(txn, seq)?.map(|view| (seq, view)))
+    }
+
+    /// Retrieves the newest event in a stream together with its version.
+    ///
+    /// The highest version is found with a reverse prefix scan over the stream index, so
+    /// this costs a single index seek plus the event lookup. Returns `Ok(None)` if the
+    /// stream has no events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The underlying storage encounters an I/O error.
+    /// *   The event retrieval fails (see `get` errors).
+    pub fn get_latest<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        stream_id: u128,
+    ) -> crate::error::Result<Option<(u32, EventView<'txn, E>)>> {
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
+        let mut iter = self
+            .storage
+            .stream_index
+            .rev_prefix_iter(txn, &stream_id_bytes)?;
+
+        let Some(result) = iter.next() else {
+            return Ok(None);
+        };
+        let (key_bytes, seq) = result?;
+        // Key is [StreamID (16)][Version (4)]
+        let version_bytes: [u8; 4] = key_bytes[16..20].try_into().unwrap();
+        let version = u32::from_be_bytes(version_bytes);
+
+        Ok(self.get(txn, seq)?.map(|view| (version, view)))
+    }
+
+    /// Backfills from `start_seq`, then tails new commits, sending `wrap(seq, item)` for every
+    /// item `read_batch(self, from, limit)` returns. Ends with `Ok(())` once `tx` is closed.
+    ///
+    /// This is the loop behind [`forward_to`](Reader::forward_to) and
+    /// [`Varve::subscribe_from`](crate::Varve::subscribe_from). Each batch is read on Tokio's
+    /// blocking pool, since LMDB reads and decryption would otherwise stall a runtime worker.
+    pub(crate) async fn tail_into<V, T>(
+        &self,
+        start_seq: u64,
+        tx: &tokio::sync::mpsc::Sender<T>,
+        read_batch: ReadBatchFn<E, V>,
+        wrap: impl Fn(u64, V) -> T,
+    ) -> crate::error::Result<()>
+    where
+        E: Send + 'static,
+        V: Send + 'static,
+    {
+        let mut rx = self.storage.notifier.subscribe();
+        // Events are stored starting at sequence 1.
+        let mut next_seq = start_seq.max(1);
+
+        loop {
+            // Mark the current head as seen *before* reading, so appends that land during the
+            // backfill still trigger another pass.
+            rx.borrow_and_update();
+
+            loop {
+                let reader = self.clone();
+                let batch = tokio::task::spawn_blocking(move || {
+                    read_batch(&reader, next_seq, crate::constants::DEFAULT_BATCH_SIZE)
+                })
+                .await
+                .map_err(|e| crate::error::Error::Io(std::io::Error::other(e)))??;
+                if batch.is_empty() {
+                    break;
+                }
+                for (seq, item) in batch {
+                    if tx.send(wrap(seq, item)).await.is_err() {
+                        return Ok(());
+                    }
+                    next_seq = seq + 1;
+                }
+            }
+
+            tokio::select! {
+                changed = rx.changed() => {
+                    changed.map_err(|_| {
+                        crate::error::Error::Io(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "Sender dropped",
+                        ))
+                    })?;
+                }
+                _ = tx.closed() => return Ok(()),
+            }
+        }
+    }
+
+    /// Reads up to `limit` events from `start_seq` on as unvalidated event
+    /// bytes (as returned by [`EventView::to_bytes`]), in its own read transaction.
+    #[cfg(feature = "net")]
+    pub(crate) fn read_bytes_batch(
+        &self,
+        start_seq: u64,
+        limit: usize,
+    ) -> crate::error::Result<Vec<(u64, Vec<u8>)>> {
+        let txn = self.storage.env.read_txn()?;
+        let mut batch = Vec::new();
+
+        for seq in self.storage.sequences(&txn, start_seq).take(limit) {
+            let seq = seq?;
+            match self
+                .get_event_data(&txn, seq)
+                .map_err(|e| e.at_sequence(seq))?
+            {
+                Some(data) => batch.push((seq, data.as_slice().to_vec())),
+                None => break,
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Reads up to `limit` events from `start_seq` on as owned views, in its
+    /// own read transaction.
+    pub(crate) fn read_view_batch(
+        &self,
+        start_seq: u64,
+        limit: usize,
+    ) -> crate::error::Result<Vec<(u64, EventView<'static, E>)>> {
+        let txn = self.storage.env.read_txn()?;
+        let mut batch = Vec::new();
+
+        for seq in self.storage.sequences(&txn, start_seq).take(limit) {
+            let seq = seq?;
+            match self.get(&txn, seq)? {
+                Some(view) => batch.push((seq, view.into_owned())),
+                None => break,
+            }
+        }
+
+        Ok(batch)
     }
 }
 
-impl<'a, E> EventView<'a, E>
+#[cfg(feature = "testing")]
+impl<E> Reader<E>
 where
     E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
 {
-    /// Converts this `EventView` into an owned version, detaching it from the transaction.
+    /// Folds a stream with `fold` and checks the state against recorded checkpoints.
     ///
-    /// This involves cloning the data to ensure it owns its memory (independent of the transaction).
-    /// Note: This performs a copy of the underlying data.
-    pub fn into_owned<'b>(self) -> EventView<'b, E> {
-        match self.data {
-            EventData::Borrowed(b) => EventView {
-                data: EventData::Owned(b.to_vec()),
-                _marker: std::marker::PhantomData,
-            },
-            EventData::Owned(v) => EventView {
-                data: EventData::Owned(v),
-                _marker: std::marker::PhantomData,
-            },
+    /// Starting from `S::default()`, every event of the stream up to the last checkpoint is
+    /// applied in version order, and after applying version `v` the state must equal the `S`
+    /// recorded for `v` in `expected_states`. Capture checkpoints from a known-good
+    /// projection once, and this catches any later change that makes it produce a different
+    /// state for the same events.
+    ///
+    /// Only available with the `testing` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EventValidation`](crate::error::Error::EventValidation) describing the
+    /// first checkpoint whose state differs, or whose version the stream does not contain,
+    /// and [`InvalidConfig`](crate::error::Error::InvalidConfig) if the checkpoint versions
+    /// are not strictly increasing. Read errors are returned as from
+    /// [`get_by_stream_range`](Self::get_by_stream_range).
+    pub fn replay_verify<S, F>(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        mut fold: F,
+        expected_states: &[(u32, S)],
+    ) -> crate::error::Result<()>
+    where
+        S: Default + PartialEq + std::fmt::Debug,
+        F: FnMut(&mut S, &E::Archived),
+    {
+        if expected_states
+            .windows(2)
+            .any(|pair| pair[0].0 >= pair[1].0)
+        {
+            return Err(crate::error::Error::InvalidConfig(
+                "checkpoint versions must be strictly increasing".to_string(),
+            ));
+        }
+
+        let mut state = S::default();
+        let mut next_version = 1;
+        for (version, expected) in expected_states {
+            let window =
+                self.get_by_stream_range(txn, stream_id, next_version, version.saturating_add(1))?;
+            if window.last().map(|(v, _)| v) != Some(version) {
+                return Err(crate::error::Error::EventValidation(format!(
+                    "stream {} has no event at checkpoint version {}",
+                    stream_id, version
+                )));
+            }
+            for (_, event) in &window {
+                fold(&mut state, event);
+            }
+            if state != *expected {
+                return Err(crate::error::Error::EventValidation(format!(
+                    "state of stream {} at version {} is {:?}, expected {:?}",
+                    stream_id, version, state, expected
+                )));
+            }
+            next_version = version.saturating_add(1);
         }
+        Ok(())
     }
 }
 
-/// Provides zero-copy access to events from the store.
+/// A [`Reader`] bound to a single read transaction.
 ///
-/// The `Reader` allows efficient retrieval of events by sequence number. It leverages memory-mapped
-/// files to provide zero-copy access to data when encryption is disabled. When encryption is enabled,
-/// it transparently handles decryption.
+/// Created by [`Reader::snapshot`]. All reads through the same `SnapshotReader` see one
+/// consistent snapshot of the store; events appended after the snapshot was opened are not
+/// visible through it.
 ///
 /// # Examples
 ///
 /// ```rust
-/// use varvedb::engine::{Writer, Reader};
-/// use varvedb::storage::{Storage, StorageConfig};
-/// use rkyv::{Archive, Serialize, Deserialize};
-/// use tempfile::tempdir;
-///
-/// #[derive(Archive, Serialize, Deserialize, Debug)]
-/// #[rkyv(derive(Debug))]
-/// struct MyEvent { pub data: u32 }
-///
+/// # use varvedb::engine::{Writer, Reader};
+/// # use varvedb::storage::{Storage, StorageConfig};
+/// # use rkyv::{Archive, Serialize, Deserialize};
+/// # use tempfile::tempdir;
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # #[rkyv(derive(Debug))]
+/// # struct MyEvent { pub data: u32 }
+/// #
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// let dir = tempdir()?;
-/// let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
-/// let storage = Storage::open(config)?;
-/// let mut writer = Writer::new(storage.clone());
-/// writer.append(1, 1, MyEvent { data: 42 })?;
+/// # let dir = tempdir()?;
+/// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+/// # let storage = Storage::open(config)?;
+/// # let mut writer = Writer::new(storage.clone());
+/// writer.append(1, 1, MyEvent { data: 1 })?;
 ///
 /// let reader = Reader::<MyEvent>::new(storage.clone());
-/// let txn = storage.env.read_txn()?;
-/// if let Some(event) = reader.get(&txn, 1)? {
-///     println!("Event: {:?}", event);
-/// }
+/// let snapshot = reader.snapshot()?;
+///
+/// writer.append(1, 2, MyEvent { data: 2 })?;
+///
+/// // The snapshot still sees the store as it was when it was opened.
+/// assert_eq!(snapshot.iter().count(), 1);
+/// assert!(snapshot.get_by_stream(1, 2)?.is_none());
 /// # Ok(())
 /// # }
 /// ```
-pub struct Reader<E> {
-    storage: Storage,
-    metrics: Option<Arc<VarveMetrics>>,
-    key_manager: Option<KeyManager>,
-    _marker: std::marker::PhantomData<E>,
+pub struct SnapshotReader<'r, E> {
+    txn: heed::RoTxn<'r>,
+    reader: &'r Reader<E>,
 }
 
-impl<E> Clone for Reader<E> {
-    fn clone(&self) -> Self {
-        Self {
-            storage: self.storage.clone(),
-            metrics: self.metrics.clone(),
-            key_manager: self.key_manager.clone(),
-            _marker: std::marker::PhantomData,
+impl<'r, E> SnapshotReader<'r, E>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+{
+    /// Returns the read transaction backing this snapshot.
+    pub fn txn(&self) -> &heed::RoTxn<'r> {
+        &self.txn
+    }
+
+    /// Retrieves an event by its global sequence number. See [`Reader::get`].
+    pub fn get(&self, seq: u64) -> crate::error::Result<Option<EventView<'_, E>>> {
+        self.reader.get(&self.txn, seq)
+    }
+
+    /// Retrieves an event by its stream ID and version. See [`Reader::get_by_stream`].
+    pub fn get_by_stream(
+        &self,
+        stream_id: u128,
+        version: u32,
+    ) -> crate::error::Result<Option<EventView<'_, E>>> {
+        self.reader.get_by_stream(&self.txn, stream_id, version)
+    }
+
+    /// Retrieves a version window of a stream. See [`Reader::get_by_stream_range`].
+    pub fn get_by_stream_range(
+        &self,
+        stream_id: u128,
+        from_version: u32,
+        to_version: u32,
+    ) -> crate::error::Result<Vec<(u32, EventView<'_, E>)>> {
+        self.reader
+            .get_by_stream_range(&self.txn, stream_id, from_version, to_version)
+    }
+
+    /// Retrieves a stream as of a version. See [`Reader::get_by_stream_as_of`].
+    pub fn get_by_stream_as_of(
+        &self,
+        stream_id: u128,
+        as_of_version: u32,
+    ) -> crate::error::Result<Option<(u32, EventView<'_, E>)>> {
+        self.reader
+            .get_by_stream_as_of(&self.txn, stream_id, as_of_version)
+    }
+
+    /// Retrieves the first event of the log. See [`Reader::first`].
+    pub fn first(&self) -> crate::error::Result<Option<(u64, EventView<'_, E>)>> {
+        self.reader.first(&self.txn)
+    }
+
+    /// Retrieves the last event of the log. See [`Reader::last`].
+    pub fn last(&self) -> crate::error::Result<Option<(u64, EventView<'_, E>)>> {
+        self.reader.last(&self.txn)
+    }
+
+    /// Retrieves the metadata stored with event `seq`. See [`Reader::get_metadata`].
+    pub fn get_metadata<M>(&self, seq: u64) -> crate::error::Result<Option<EventView<'_, M>>>
+    where
+        M: rkyv::Archive,
+        M::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    {
+        self.reader.get_metadata(&self.txn, seq)
+    }
+
+    /// Retrieves the newest event in a stream. See [`Reader::get_latest`].
+    pub fn get_latest(
+        &self,
+        stream_id: u128,
+    ) -> crate::error::Result<Option<(u32, EventView<'_, E>)>> {
+        self.reader.get_latest(&self.txn, stream_id)
+    }
+
+    /// Returns an iterator over all events visible in this snapshot, in global sequence order.
+    pub fn iter(&self) -> SnapshotIter<'_, 'r, E> {
+        SnapshotIter {
+            snapshot: self,
+            // Events are stored starting at sequence 1.
+            current_seq: 1,
+            prefetch: 0,
+            prefetched_to: 0,
         }
     }
 }
 
-impl<E> Reader<E>
+/// An iterator over the events of a [`SnapshotReader`].
+pub struct SnapshotIter<'s, 'r, E> {
+    snapshot: &'s SnapshotReader<'r, E>,
+    current_seq: u64,
+    /// How many sequences to prefetch at a time; 0 disables prefetching.
+    prefetch: u64,
+    /// The first sequence not covered by the last prefetch.
+    prefetched_to: u64,
+}
+
+impl<E> SnapshotIter<'_, '_, E> {
+    /// Prefetches the next `window` events with [`Reader::prefetch`] whenever the iterator
+    /// runs past the previously prefetched ones.
+    ///
+    /// Speeds up full scans, such as projection rebuilds, of logs that are not in the page
+    /// cache. A `window` of 0 disables prefetching, which is the default.
+    pub fn with_prefetch(mut self, window: u64) -> Self {
+        self.prefetch = window;
+        self
+    }
+}
+
+impl<'s, 'r, E> Iterator for SnapshotIter<'s, 'r, E>
 where
     E: rkyv::Archive,
     E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
 {
-    /// Creates a new `Reader` instance.
-    pub fn new(storage: Storage) -> Self {
-        let key_manager = if storage.config.encryption_enabled {
-            Some(KeyManager::new(storage.clone()))
-        } else {
-            None
-        };
+    type Item = crate::error::Result<EventView<'s, E>>;
 
-        Self {
-            storage,
-            metrics: None,
-            key_manager,
-            _marker: std::marker::PhantomData,
+    fn next(&mut self) -> Option<Self::Item> {
+        // Trimmed streams leave holes in the log, so seek to the next record.
+        let snapshot = self.snapshot;
+        match snapshot
+            .reader
+            .storage()
+            .next_sequence(&snapshot.txn, self.current_seq)
+        {
+            Ok(Some(seq)) => self.current_seq = seq,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
         }
-    }
 
-    /// Attaches metrics to the reader for observability.
-    pub fn with_metrics(mut self, metrics: Arc<VarveMetrics>) -> Self {
-        self.metrics = Some(metrics);
-        self
-    }
+        if self.prefetch > 0 && self.current_seq >= self.prefetched_to {
+            if let Err(e) = snapshot
+                .reader
+                .prefetch(&snapshot.txn, self.current_seq, self.prefetch)
+            {
+                return Some(Err(e));
+            }
+            self.prefetched_to = self.current_seq.saturating_add(self.prefetch);
+        }
 
-    /// Returns a reference to the underlying storage.
-    pub fn storage(&self) -> &Storage {
-        &self.storage
+        match self.snapshot.get(self.current_seq) {
+            Ok(Some(view)) => {
+                self.current_seq += 1;
+                Some(Ok(view))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
     }
+}
 
-    /// Retrieves an event by its global sequence number.
-    ///
-    /// Returns an `EventView` which provides access to the deserialized event.
-    ///
-    /// # Zero-Copy vs Encryption
-    ///
-    /// *   **Encryption Disabled**: The `EventView` borrows data directly from the memory-mapped file (Zero-Copy).
-    /// *   **Encryption Enabled**: The data is decrypted into a temporary buffer, involving allocation and copying.
+impl<E> Reader<E>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>
+        + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+{
+    /// Writes every event of a stream to `w` as JSON Lines, in version order.
     ///
-    /// If encryption is enabled, this method handles key retrieval and decryption transparently.
+    /// Each event is deserialized and written as one line holding a JSON object
+    /// `{"seq": .., "version": .., "event": ..}`, where `event` is `E` serialized with serde.
+    /// Events are written one at a time, so memory use does not grow with the stream length;
+    /// wrap `w` in a `BufWriter` when writing to a file. VarveDB does not record wall-clock
+    /// times, so timestamps appear only if `E` carries them. Returns the number of events
+    /// written.
     ///
-    /// # Errors
+    /// Requires the `serde` feature.
     ///
-    /// Returns an error if:
-    /// *   The event data is corrupted or fails validation.
-    /// *   Decryption fails (e.g., invalid key or AAD mismatch).
-    /// *   The underlying storage encounters an I/O error.
-    pub fn get<'txn>(
-        &self,
-        txn: &'txn heed::RoTxn,
-        seq: u64,
-    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
-        match self.storage.events_log.get(txn, &seq)? {
-            Some(bytes) => {
-                let payload_data = if let Some(km) = &self.key_manager {
-                    // Expect: [StreamID (16)][Nonce (12)][Ciphertext]
-                    if bytes.len() < crate::constants::ENCRYPTED_EVENT_MIN_SIZE {
-                        return Err(crate::error::Error::InvalidEncryptedEventLength {
-                            actual: bytes.len(),
-                            minimum: crate::constants::ENCRYPTED_EVENT_MIN_SIZE,
-                        });
-                    }
-
-                    let (stream_id_bytes, rest) = bytes.split_at(crate::constants::STREAM_ID_SIZE);
-                    let stream_id = u128::from_be_bytes(stream_id_bytes.try_into().unwrap());
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// *   The event retrieval or deserialization fails (see `get` errors).
+    /// *   Serializing an event to JSON fails.
+    /// *   Writing to `w` fails.
+    #[cfg(feature = "serde")]
+    pub fn export_stream_json<W>(
+        &self,
+        txn: &heed::RoTxn,
+        stream_id: u128,
+        mut w: W,
+    ) -> crate::error::Result<u64>
+    where
+        E: serde::Serialize,
+        W: std::io::Write,
+    {
+        #[derive(serde::Serialize)]
+        struct Row<'e, E> {
+            seq: u64,
+            version: u32,
+            event: &'e E,
+        }
 
-                    let key = km
-                        .get_key_with_txn(txn, stream_id)?
-                        .ok_or_else(|| crate::error::Error::KeyNotFound(stream_id))?;
+        let start = self.storage.stream_key(stream_id, 0);
+        let end = self.storage.stream_key(stream_id, u32::MAX);
+        let range = (
+            Bound::Included(start.as_slice()),
+            Bound::Included(end.as_slice()),
+        );
 
-                    // AAD: StreamID + Seq
-                    let mut aad = Vec::with_capacity(crate::constants::AAD_CAPACITY);
-                    aad.extend_from_slice(stream_id_bytes);
-                    aad.extend_from_slice(&seq.to_be_bytes());
+        let mut written = 0;
+        for result in self.storage.stream_index.range(txn, &range)? {
+            let (key_bytes, seq) = result?;
+            // Key is [StreamID (16)][Version (4)]
+            let key: [u8; 20] = key_bytes.try_into().unwrap();
+            let version = u32::from_be_bytes(key[16..20].try_into().unwrap());
+            let Some(view) = self.get_indexed(txn, &key, seq)? else {
+                continue;
+            };
 
-                    let decrypted = crypto::decrypt(&key, rest, &aad)?;
-                    EventData::Owned(decrypted)
+            let event = view.try_deserialize()?;
+            serde_json::to_writer(
+                &mut w,
+                &Row {
+                    seq,
+                    version,
+                    event: &event,
+                },
+            )
+            .map_err(|e| {
+                if e.is_io() {
+                    crate::error::Error::Io(e.into())
                 } else {
-                    EventData::Borrowed(bytes)
-                };
-
-                // Deserialize Payload
-                let payload_bytes = match &payload_data {
-                    EventData::Borrowed(b) => *b,
-                    EventData::Owned(b) => b.as_slice(),
-                };
-
-                let archived_payload = rkyv::access::<
-                    crate::model::ArchivedStoragePayload,
-                    rkyv::rancor::Error,
-                >(payload_bytes)?;
-
-                let final_data = match archived_payload {
-                    crate::model::ArchivedStoragePayload::Inline(inline_bytes) => {
-                        EventData::Owned(inline_bytes.as_slice().to_vec())
-                    }
-                    crate::model::ArchivedStoragePayload::BlobRef(hash) => {
-                        let blob_bytes =
-                            self.storage
-                                .blobs
-                                .get(txn, hash.as_slice())?
-                                .ok_or_else(|| {
-                                    crate::error::Error::EventValidation(
-                                        "Blob not found".to_string(),
-                                    )
-                                })?;
-
-                        // MADVISE: Tell OS we don't need this page anymore
-                        #[cfg(unix)]
-                        unsafe {
-                            let ptr = blob_bytes.as_ptr() as *const libc::c_void;
-                            let len = blob_bytes.len();
-                            // Round down to page boundary (required by madvise)
-                            // Actually, heed/lmdb gives us a pointer. We should probably madvise the whole page containing it?
-                            // Or just the range. madvise usually requires page alignment.
-                            // Let's try to align it.
-                            let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
-                            let addr = ptr as usize;
-                            let aligned_addr = addr & !(page_size - 1);
-                            let offset = addr - aligned_addr;
-                            let aligned_len = len + offset;
-
-                            libc::madvise(
-                                aligned_addr as *mut libc::c_void,
-                                aligned_len,
-                                libc::MADV_DONTNEED,
-                            );
-                        }
-
-                        EventData::Owned(blob_bytes.to_vec())
-                    }
-                };
-
-                // Verify rkyv validity (zero-copy check) of the actual event
-                rkyv::access::<E::Archived, rkyv::rancor::Error>(match &final_data {
-                    EventData::Borrowed(b) => b,
-                    EventData::Owned(b) => b.as_slice(),
-                })?;
-
-                let view = EventView {
-                    data: final_data,
-                    _marker: std::marker::PhantomData,
-                };
-
-                if let Some(metrics) = &self.metrics {
-                    metrics.events_read.inc();
+                    crate::error::Error::EventSerialization(e.to_string())
                 }
-
-                Ok(Some(view))
-            }
-            None => Ok(None),
+            })?;
+            w.write_all(b"\n")?;
+            written += 1;
         }
+
+        w.flush()?;
+        Ok(written)
     }
 
-    /// Retrieves an event by its stream ID and version (sequence number in the stream).
+    /// Streams events into an mpsc channel: backfills from `start_seq`, then tails new appends.
     ///
-    /// This method looks up the global sequence number for the given stream and version,
-    /// and then retrieves the event using `get`.
+    /// Every event from `start_seq` (inclusive) up to the current head is deserialized and
+    /// sent as `(seq, event)`, after which the method waits on the storage notifier and
+    /// forwards new events as they are committed. It returns `Ok(())` once the receiver is
+    /// dropped.
+    ///
+    /// Read transactions are opened and dropped between sends, so the returned future never
+    /// holds one across an `.await` and is safe to `tokio::spawn`.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// # use varvedb::engine::{Writer, Reader};
+    /// # use varvedb::engine::{Reader, Writer};
     /// # use varvedb::storage::{Storage, StorageConfig};
-    /// # use rkyv::{Archive, Serialize, Deserialize};
     /// # use tempfile::tempdir;
-    /// #
-    /// # #[derive(Archive, Serialize, Deserialize, Debug)]
-    /// # #[rkyv(derive(Debug))]
-    /// # struct MyEvent { pub data: u32 }
-    /// #
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// # let dir = tempdir()?;
     /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
     /// # let storage = Storage::open(config)?;
-    /// # let mut writer = Writer::new(storage.clone());
-    /// # writer.append(1, 1, MyEvent { data: 42 })?;
-    /// #
-    /// let reader = Reader::<MyEvent>::new(storage.clone());
-    /// let txn = storage.env.read_txn()?;
-    /// if let Some(event) = reader.get_by_stream(&txn, 1, 1)? {
-    ///     println!("Event: {:?}", event);
-    /// }
+    /// let mut writer = Writer::<u64>::new(storage.clone());
+    /// writer.append(1, 1, 10)?;
+    /// writer.append(1, 2, 20)?;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    /// runtime.block_on(async {
+    ///     let (tx, mut rx) = tokio::sync::mpsc::channel(128);
+    ///     let reader = Reader::<u64>::new(storage.clone());
+    ///     tokio::spawn(async move { reader.forward_to(1, tx).await });
+    ///
+    ///     assert_eq!(rx.recv().await, Some((1, 10)));
+    ///     assert_eq!(rx.recv().await, Some((2, 20)));
+    /// });
     /// # Ok(())
     /// # }
     /// ```
     ///
-    /// For more examples, see the [examples directory](https://github.com/Cardosaum/varvedb/tree/main/examples).
-    ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// *   The underlying storage encounters an I/O error.
-    /// *   The event retrieval fails (see `get` errors).
-    pub fn get_by_stream<'txn>(
+    /// Returns an error if reading or deserializing an event fails (see `get` errors).
+    pub async fn forward_to(
         &self,
-        txn: &'txn heed::RoTxn,
-        stream_id: u128,
-        version: u32,
-    ) -> crate::error::Result<Option<EventView<'txn, E>>> {
-        let key = crate::storage::StreamKey::new(stream_id, version);
-        let key_bytes = key.to_be_bytes();
+        start_seq: u64,
+        tx: tokio::sync::mpsc::Sender<(u64, E)>,
+    ) -> crate::error::Result<()>
+    where
+        E: Send + 'static,
+    {
+        self.tail_into(start_seq, &tx, Self::read_owned_batch, |seq, event| {
+            (seq, event)
+        })
+        .await
+    }
 
-        self.storage
-            .stream_index
-            .get(txn, key_bytes.as_slice())?
-            .map(|seq| self.get(txn, seq))
-            .transpose()
-            .map(Option::flatten)
+    /// Reads up to `limit` events from `start_seq` on into owned values.
+    fn read_owned_batch(
+        &self,
+        start_seq: u64,
+        limit: usize,
+    ) -> crate::error::Result<Vec<(u64, E)>> {
+        let txn = self.storage.env.read_txn()?;
+        let mut batch = Vec::new();
+
+        for seq in self.storage.sequences(&txn, start_seq).take(limit) {
+            let seq = seq?;
+            match self.get(&txn, seq)? {
+                Some(view) => batch.push((seq, view.try_deserialize()?)),
+                None => break,
+            }
+        }
+
+        Ok(batch)
     }
 }
 
+fn millis(duration: std::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(millis)
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     // use super::*;
     use crate::{
-        engine::{Reader, Writer},
-        storage::{Storage, StorageConfig},
+        engine::{Reader, StreamInfo, Writer},
+        storage::{Storage, StorageConfig, StorageLayout},
     };
     use rkyv::{Archive, Deserialize, Serialize};
     use tempfile::tempdir;
@@ -610,4 +5278,373 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_get_latest() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+        writer.append(2, 1, TestEvent { value: 30 })?;
+        writer.append(1, 2, TestEvent { value: 20 })?;
+
+        let txn = storage.env.read_txn()?;
+
+        let (version, event) = reader.get_latest(&txn, 1)?.unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(event.value, 20);
+
+        let (version, event) = reader.get_latest(&txn, 2)?.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(event.value, 30);
+
+        // Empty stream
+        assert!(reader.get_latest(&txn, 3)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_info() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+        writer.append(2, 1, TestEvent { value: 30 })?;
+        writer.append(1, 2, TestEvent { value: 20 })?;
+        writer.append(1, 3, TestEvent { value: 40 })?;
+
+        let txn = storage.env.read_txn()?;
+        assert_eq!(
+            reader.stream_info(&txn, 1)?,
+            Some(StreamInfo {
+                head_version: 3,
+                count: 3,
+                first_global_seq: 1,
+                last_global_seq: 4,
+            })
+        );
+        assert_eq!(
+            reader.stream_info(&txn, 2)?,
+            Some(StreamInfo {
+                head_version: 1,
+                count: 1,
+                first_global_seq: 2,
+                last_global_seq: 2,
+            })
+        );
+        assert!(reader.stream_info(&txn, 3)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_envelopes_stops_at_gap() -> Result<(), Box<dyn std::error::Error>> {
+        for layout in [StorageLayout::Sequential, StorageLayout::Clustered] {
+            let dir = tempdir()?;
+            let config = StorageConfig {
+                path: dir.path().to_path_buf(),
+                layout,
+                ..Default::default()
+            };
+            let storage = Storage::open(config)?;
+            let mut writer = Writer::<TestEvent>::new(storage.clone()).with_allow_gaps(true);
+            let reader = Reader::<TestEvent>::new(storage.clone());
+
+            let bytes =
+                |value| rkyv::to_bytes::<rkyv::rancor::Error>(&TestEvent { value }).unwrap();
+            for seq in 1..=5 {
+                writer.append_at(seq, 1, seq as u32, &bytes(seq as u32 * 10))?;
+            }
+            writer.append_at(7, 1, 6, &bytes(70))?;
+
+            let txn = storage.env.read_txn()?;
+            let values = |envelopes: Vec<super::EventEnvelope<'_, TestEvent>>| {
+                envelopes
+                    .iter()
+                    .map(|e| (e.seq, e.event.value.to_native()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                values(reader.get_envelopes(&txn, 2, 3)?),
+                vec![(2, 20), (3, 30), (4, 40)]
+            );
+            // The batch ends before the gap at 6, and at the end of the log.
+            assert_eq!(
+                values(reader.get_envelopes(&txn, 4, 10)?),
+                vec![(4, 40), (5, 50)]
+            );
+            assert_eq!(values(reader.get_envelopes(&txn, 7, 10)?), vec![(7, 70)]);
+            assert!(reader.get_envelopes(&txn, 6, 10)?.is_empty());
+            assert!(reader.get_envelopes(&txn, 1, 0)?.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_reader_is_consistent() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+        writer.append(1, 2, TestEvent { value: 20 })?;
+
+        let snapshot = reader.snapshot()?;
+
+        // Appends after the snapshot was opened are invisible through it.
+        writer.append(1, 3, TestEvent { value: 30 })?;
+        writer.append(2, 1, TestEvent { value: 40 })?;
+
+        let values: Vec<u32> = snapshot
+            .iter()
+            .map(|e| e.map(|view| view.value.to_native()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(values, vec![10, 20]);
+
+        assert!(snapshot.get(3)?.is_none());
+        assert!(snapshot.get_by_stream(2, 1)?.is_none());
+        assert_eq!(snapshot.get_by_stream(1, 2)?.unwrap().value, 20);
+        let (version, latest) = snapshot.get_latest(1)?.unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(latest.value, 20);
+        drop(snapshot);
+
+        // A fresh snapshot sees everything.
+        let snapshot = reader.snapshot()?;
+        assert_eq!(snapshot.iter().count(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_iter_with_prefetch() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+        for value in 1..=100 {
+            writer.append(1, value, TestEvent { value })?;
+        }
+
+        // Windows that divide the log evenly, don't, and overshoot it all see every event.
+        let snapshot = reader.snapshot()?;
+        for window in [1, 10, 7, 1000] {
+            let values: Vec<u32> = snapshot
+                .iter()
+                .with_prefetch(window)
+                .map(|e| e.map(|view| view.value.to_native()))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(values, (1..=100).collect::<Vec<_>>());
+        }
+
+        // Prefetching past the head or an empty range is harmless.
+        reader.prefetch(snapshot.txn(), 90, 50)?;
+        reader.prefetch(snapshot.txn(), 500, 0)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_and_last() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone()).with_allow_gaps(true);
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        let txn = storage.env.read_txn()?;
+        assert!(reader.first(&txn)?.is_none());
+        assert!(reader.last(&txn)?.is_none());
+        drop(txn);
+
+        // Start past 1 so the edges are not just 1 and the count.
+        let bytes = |value| rkyv::to_bytes::<rkyv::rancor::Error>(&TestEvent { value }).unwrap();
+        writer.append_at(3, 1, 1, &bytes(30))?;
+        writer.append_at(8, 1, 2, &bytes(80))?;
+
+        let txn = storage.env.read_txn()?;
+        let (first_seq, first) = reader.first(&txn)?.unwrap();
+        let (last_seq, last) = reader.last(&txn)?.unwrap();
+        assert_eq!((first_seq, first.value.to_native()), (3, 30));
+        assert_eq!((last_seq, last.value.to_native()), (8, 80));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_unchecked_matches_get() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+
+        let txn = storage.env.read_txn()?;
+        // Safety: the event was just written by a `Writer<TestEvent>`.
+        let view = unsafe { reader.get_unchecked(&txn, 1)? }.unwrap();
+        assert_eq!(view.value, 10);
+        assert_eq!(view.to_bytes(), reader.get(&txn, 1)?.unwrap().to_bytes());
+        assert_eq!(view.as_ref(), view.to_bytes());
+        assert!(unsafe { reader.get_unchecked(&txn, 2)? }.is_none());
+
+        let value = unsafe { reader.get_field(&txn, 1, |event| event.value.to_native())? };
+        assert_eq!(value, Some(10));
+        assert!(unsafe { reader.get_field(&txn, 2, |_| ())? }.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_range_unchecked_yields_range() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        for version in 1..=5 {
+            writer.append(
+                1,
+                version,
+                TestEvent {
+                    value: version * 10,
+                },
+            )?;
+        }
+
+        let txn = storage.env.read_txn()?;
+        // Safety: every event was just written by a `Writer<TestEvent>`.
+        let events = unsafe { reader.iter_range_unchecked(&txn, 2, 5)? }
+            .map(|result| result.map(|(seq, view)| (seq, view.value.to_native())))
+            .collect::<crate::error::Result<Vec<_>>>()?;
+        assert_eq!(events, vec![(2, 20), (3, 30), (4, 40)]);
+        assert_eq!(
+            unsafe { reader.iter_range_unchecked(&txn, 6, 10)? }.count(),
+            0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_view_try_deserialize() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+
+        let txn = storage.env.read_txn()?;
+        let view = reader.get(&txn, 1)?.unwrap();
+        assert_eq!(view.try_deserialize()?, TestEvent { value: 10 });
+
+        let archived = rkyv::access::<ArchivedTestEvent, rkyv::rancor::Error>(view.to_bytes())?;
+        assert_eq!(archived.value, 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_event_caches_owned_value() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TestEvent>::new(storage.clone());
+        let reader = Reader::<TestEvent>::new(storage.clone());
+
+        writer.append(1, 1, TestEvent { value: 10 })?;
+
+        let txn = storage.env.read_txn()?;
+        let event = reader.get_lazy(&txn, 1)?.unwrap();
+        assert_eq!(event.archived().value, 10);
+
+        let first: *const TestEvent = event.owned()?;
+        assert!(std::ptr::eq(first, event.owned()?));
+        assert_eq!(event.into_owned()?, TestEvent { value: 10 });
+        assert!(reader.get_lazy(&txn, 2)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_reuses_arena_across_appends() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Archive, Serialize, Deserialize, Debug)]
+        struct TaggedEvent {
+            tags: Vec<String>,
+        }
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Writer<TaggedEvent>>();
+
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::<TaggedEvent>::new(storage.clone());
+        let initial = writer.arena.get_mut().capacity();
+
+        // A large event grows the arena...
+        let tags = (0..1000).map(|i| i.to_string()).collect();
+        writer.append(1, 1, TaggedEvent { tags })?;
+        let grown = writer.arena.get_mut().capacity();
+        assert!(grown > initial);
+
+        // ...and later appends serialize into the same, already grown arena.
+        for version in 2..=100 {
+            let tags = vec![version.to_string()];
+            writer.append(1, version, TaggedEvent { tags })?;
+            assert_eq!(writer.arena.get_mut().capacity(), grown);
+        }
+
+        let reader = Reader::<TaggedEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().tags.len(), 1000);
+        assert_eq!(reader.get(&txn, 100)?.unwrap().tags[0], "100");
+
+        Ok(())
+    }
 }
@@ -17,16 +17,64 @@ pub enum Error {
 
     /// LMDB storage error (via `heed`).
     #[error("LMDB error: {0}")]
-    Heed(#[from] heed::Error),
+    Heed(heed::Error),
+
+    /// All LMDB reader slots are in use (`MDB_READERS_FULL`).
+    ///
+    /// Increase `StorageConfig::max_readers` or make sure read transactions are not leaked.
+    #[error("LMDB reader table is full: too many concurrent read transactions")]
+    ReadersFull,
+
+    /// Locking the memory map into RAM failed.
+    ///
+    /// Usually caused by an insufficient `RLIMIT_MEMLOCK`; raise it with `ulimit -l`
+    /// or grant the process `CAP_IPC_LOCK`.
+    #[error("Failed to lock memory map (check RLIMIT_MEMLOCK): {0}")]
+    MemoryLock(std::io::Error),
 
     /// Event serialization failed.
     #[error("Event serialization failed: {0}")]
     EventSerialization(String),
 
+    /// Serializing into a fixed-size buffer with
+    /// [`serialize_into`](crate::engine::serialize_into) ran out of space.
+    ///
+    /// Unlike [`EventSerialization`](Self::EventSerialization), this does not mean the event
+    /// is malformed: retry with a larger buffer or the allocating serialization path.
+    /// `needed` is the size of the full archive, when known.
+    #[error("Serialization buffer too small (capacity is {capacity} bytes)")]
+    BufferTooSmall {
+        needed: Option<usize>,
+        capacity: usize,
+    },
+
+    /// A serialized event exceeds `StorageConfig::max_serialize_bytes`.
+    #[error("Event too large: {size} bytes exceeds the limit of {max} bytes")]
+    EventTooLarge { size: usize, max: usize },
+
     /// Event validation failed (e.g. invalid archive).
     #[error("Event validation failed: {0}")]
     EventValidation(String),
 
+    /// The blob holding the body of the event at `seq` is missing.
+    ///
+    /// Only returned with `MissingBlob::ReturnPlaceholder`; by default a missing blob is an
+    /// [`EventValidation`](Self::EventValidation) error.
+    #[error("Blob for event {seq} is missing")]
+    BlobMissing { seq: u64, hash: [u8; 32] },
+
+    /// Reading the event at `seq` failed with `source`.
+    ///
+    /// [`Reader::get`](crate::engine::Reader::get) and the reads built on it wrap failures
+    /// tied to one record (validation, checksums, decryption) in this variant, so a scan can
+    /// report which record is bad. Use [`Error::root`] to match on the underlying error.
+    #[error("Failed to read event {seq}: {source}")]
+    AtSequence {
+        seq: u64,
+        #[source]
+        source: Box<Error>,
+    },
+
     /// Invalid encrypted event length.
     #[error("Invalid encrypted event length: expected at least {minimum}, got {actual}")]
     InvalidEncryptedEventLength { actual: usize, minimum: usize },
@@ -47,6 +95,14 @@ pub enum Error {
     #[error("Decryption failed: {0}")]
     DecryptionError(String),
 
+    /// The configured master key is not the one the database was created with.
+    ///
+    /// Detected when the database is opened, before any record is read; a
+    /// [`DecryptionError`](Self::DecryptionError) after a successful open means the record
+    /// itself was tampered with or corrupted.
+    #[error("Wrong master key: the database was created with a different master key")]
+    WrongMasterKey,
+
     /// Invalid configuration.
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
@@ -70,10 +126,80 @@ pub enum Error {
     /// Concurrency conflict.
     #[error("Concurrency conflict: Stream {stream_id} version {version} already exists")]
     ConcurrencyConflict { stream_id: u128, version: u32 },
+
+    /// The event is identical to the previous event of its stream, as detected with
+    /// `StorageConfig::reject_consecutive_duplicates`.
+    #[error("Duplicate event: stream {stream_id} version {version} repeats the previous event")]
+    DuplicateEvent { stream_id: u128, version: u32 },
+
+    /// Appending would take a stream past `StorageConfig::max_stream_versions`.
+    #[error("Stream {stream_id} is full: it may hold at most {max} versions")]
+    StreamFull { stream_id: u128, max: u32 },
+
+    /// Another process holds the writer lease.
+    ///
+    /// Retry `Storage::acquire_writer_lease` after `expires_in`, unless the holder renews it.
+    #[error("Writer lease is held by another process (expires in {expires_in:?})")]
+    LeaseHeld { expires_in: std::time::Duration },
+
+    /// This process no longer holds the writer lease: it expired, or another process took it over.
+    ///
+    /// Appends through a storage handle that acquired a lease fail with this error until the
+    /// lease is acquired again.
+    #[error("Writer lease expired or was taken over by another process")]
+    LeaseLost,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Returns the underlying error, looking through [`AtSequence`](Self::AtSequence) context.
+    ///
+    /// ```rust
+    /// # use varvedb::Error;
+    /// let e = Error::AtSequence {
+    ///     seq: 7,
+    ///     source: Box::new(Error::EventValidation("checksum mismatch".to_string())),
+    /// };
+    /// assert!(matches!(e.root(), Error::EventValidation(_)));
+    /// ```
+    pub fn root(&self) -> &Error {
+        let mut error = self;
+        while let Self::AtSequence { source, .. } = error {
+            error = source;
+        }
+        error
+    }
+
+    /// Adds `seq` as context to errors caused by the record at `seq` itself.
+    ///
+    /// Storage, I/O and configuration errors, and errors that already name their record,
+    /// are returned unchanged.
+    pub(crate) fn at_sequence(self, seq: u64) -> Self {
+        match self {
+            Self::EventValidation(_)
+            | Self::EventSerialization(_)
+            | Self::DecryptionError(_)
+            | Self::InvalidEncryptedEventLength { .. }
+            | Self::InvalidCiphertextLength { .. }
+            | Self::KeyNotFound(_) => Self::AtSequence {
+                seq,
+                source: Box::new(self),
+            },
+            other => other,
+        }
+    }
+}
+
+impl From<heed::Error> for Error {
+    fn from(e: heed::Error) -> Self {
+        match e {
+            heed::Error::Mdb(heed::MdbError::ReadersFull) => Self::ReadersFull,
+            e => Self::Heed(e),
+        }
+    }
+}
+
 impl rkyv::rancor::Fallible for Error {
     type Error = Self;
 }
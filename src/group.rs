@@ -0,0 +1,523 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Consumer groups: several workers sharing one logical cursor.
+//!
+//! A [`Processor`](crate::processor::Processor) has a cursor of its own, so two processors
+//! with the same consumer ID handle every event twice. Workers that [`join`](ConsumerGroup::join)
+//! the same group instead compete for events: each one claims a range of sequence numbers in
+//! the `group_claims` database, handles it, and marks it done. The group's shared cursor only
+//! advances over a contiguous prefix of completed ranges, so it always means "every event up
+//! to here has been handled by someone".
+//!
+//! # Claims
+//!
+//! A claim belongs to one worker until it is completed or its
+//! [`claim_ttl`](GroupConfig::claim_ttl) expires. An expired claim (e.g. of a crashed worker)
+//! is taken over by the next worker looking for work, which makes delivery at-least-once:
+//! in normal operation each event is handled by exactly one worker, but a worker that takes
+//! longer than `claim_ttl` over a range may see another worker handle it again.
+
+use crate::engine::Reader;
+use crate::error::Result;
+use crate::processor::{CancellationToken, ErrorPolicy, EventHandler};
+use crate::storage::Storage;
+use crate::traits::MetadataExt;
+use crate::varve::Varve;
+use rkyv::api::high::HighValidator;
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::Error as RancorError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tag of the shared cursor record, keyed `[group ID (8, BE)][CURSOR_TAG]`.
+const CURSOR_TAG: u8 = 0;
+
+/// Tag of claim records, keyed `[group ID (8, BE)][CLAIM_TAG][first seq (8, BE)]`.
+///
+/// The value is `[last seq (8, BE)][worker ID (8, BE)][expires at, ms since the Unix epoch
+/// (8, BE)][done (1)]`.
+const CLAIM_TAG: u8 = 1;
+
+/// The default time a worker may hold a claim before others can take it over.
+pub const DEFAULT_CLAIM_TTL: Duration = Duration::from_secs(30);
+
+/// Configuration for the workers of a [`ConsumerGroup`].
+#[derive(Clone, Copy, Debug)]
+pub struct GroupConfig {
+    /// Maximum number of events a worker claims at once.
+    pub batch_size: usize,
+    /// How long a claim stays with its worker before another worker may take it over.
+    pub claim_ttl: Duration,
+}
+
+impl Default for GroupConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: crate::constants::DEFAULT_BATCH_SIZE,
+            claim_ttl: DEFAULT_CLAIM_TTL,
+        }
+    }
+}
+
+/// A range of sequence numbers claimed by one worker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Claim {
+    start: u64,
+    end: u64,
+    worker_id: u64,
+    expires_at: u64,
+    done: bool,
+}
+
+impl Claim {
+    fn encode(&self) -> [u8; 25] {
+        let mut value = [0u8; 25];
+        value[..8].copy_from_slice(&self.end.to_be_bytes());
+        value[8..16].copy_from_slice(&self.worker_id.to_be_bytes());
+        value[16..24].copy_from_slice(&self.expires_at.to_be_bytes());
+        value[24] = self.done as u8;
+        value
+    }
+
+    fn decode(key: &[u8], value: &[u8]) -> Result<Self> {
+        let malformed =
+            || crate::error::Error::InvalidConfig("malformed consumer group claim".to_string());
+        let start: [u8; 8] = key.get(9..17).ok_or_else(malformed)?.try_into().unwrap();
+        let value: &[u8; 25] = value.try_into().map_err(|_| malformed())?;
+        Ok(Self {
+            start: u64::from_be_bytes(start),
+            end: u64::from_be_bytes(value[..8].try_into().unwrap()),
+            worker_id: u64::from_be_bytes(value[8..16].try_into().unwrap()),
+            expires_at: u64::from_be_bytes(value[16..24].try_into().unwrap()),
+            done: value[24] != 0,
+        })
+    }
+}
+
+/// Entry point for consumer groups over one database.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rkyv::{Archive, Deserialize, Serialize};
+/// # use std::sync::{Arc, Mutex};
+/// # use std::time::Duration;
+/// # use tempfile::tempdir;
+/// # use varvedb::group::ConsumerGroup;
+/// # use varvedb::processor::{CancellationToken, EventHandler};
+/// # use varvedb::traits::MetadataExt;
+/// # use varvedb::{ExpectedVersion, Payload, Varve};
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Job { id: u32 }
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Meta { stream_id: u128, version: u32 }
+/// #
+/// # impl MetadataExt for Meta {
+/// #     fn stream_id(&self) -> u128 { self.stream_id }
+/// #     fn version(&self) -> u32 { self.version }
+/// # }
+/// #
+/// #[derive(Clone, Default)]
+/// struct Done(Arc<Mutex<Vec<u32>>>);
+///
+/// impl EventHandler<Job> for Done {
+///     fn handle(&mut self, job: &ArchivedJob) -> varvedb::error::Result<()> {
+///         self.0.lock().unwrap().push(job.id.to_native());
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// let mut db = Varve::open(dir.path().join("jobs.mdb"))?;
+/// for id in 1..=3 {
+///     let meta = Meta { stream_id: id.into(), version: 1 };
+///     db.append(Payload::new(Job { id }, meta), ExpectedVersion::Auto)?;
+/// }
+///
+/// let group = ConsumerGroup::new(&db);
+/// let done = Done::default();
+/// let token = CancellationToken::new();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+/// runtime.block_on(async {
+///     // Run the same code in every worker, each with its own worker ID.
+///     let worker_id = 1;
+///     let mut worker = group
+///         .join(7, worker_id, done.clone())?
+///         .with_cancellation_token(token.clone());
+///     let running = tokio::spawn(async move { worker.run().await });
+///
+///     while group.committed(7)? < 3 {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///     }
+///     token.cancel();
+///     running.await??;
+///     Ok::<_, Box<dyn std::error::Error>>(())
+/// })?;
+/// assert_eq!(*done.0.lock().unwrap(), [1, 2, 3]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ConsumerGroup<E> {
+    reader: Reader<E>,
+    rx: tokio::sync::watch::Receiver<u64>,
+    config: GroupConfig,
+}
+
+impl<E> ConsumerGroup<E>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+{
+    /// Creates a `ConsumerGroup` for the events of `varve`.
+    pub fn new<M>(varve: &Varve<E, M>) -> Self
+    where
+        E: for<'a> rkyv::Serialize<
+                rkyv::api::high::HighSerializer<
+                    rkyv::util::AlignedVec,
+                    rkyv::ser::allocator::ArenaHandle<'a>,
+                    RancorError,
+                >,
+            > + std::fmt::Debug,
+        M: MetadataExt,
+    {
+        Self {
+            reader: varve.reader().clone(),
+            rx: varve.subscribe(),
+            config: GroupConfig::default(),
+        }
+    }
+
+    /// Sets the configuration used by workers joining through this handle.
+    pub fn with_config(mut self, config: GroupConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Joins group `group_id` as worker `worker_id`, handling events with `handler`.
+    ///
+    /// Worker IDs must be unique within a group; a worker rejoining with the same ID after a
+    /// restart resumes its unexpired claims.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `batch_size` or
+    /// `claim_ttl` is 0.
+    pub fn join<H>(&self, group_id: u64, worker_id: u64, handler: H) -> Result<GroupWorker<E, H>>
+    where
+        H: EventHandler<E>,
+    {
+        if self.config.batch_size == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.claim_ttl.is_zero() {
+            return Err(crate::error::Error::InvalidConfig(
+                "claim_ttl must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(GroupWorker {
+            reader: self.reader.clone(),
+            rx: self.rx.clone(),
+            handler,
+            group_id,
+            worker_id,
+            config: self.config,
+            on_error: ErrorPolicy::default(),
+            cancellation: None,
+        })
+    }
+
+    /// Returns the group's shared cursor: every event up to this sequence number has been
+    /// handled. Returns 0 for a group that hasn't completed anything yet.
+    pub fn committed(&self, group_id: u64) -> Result<u64> {
+        let storage = self.reader.storage();
+        let txn = storage.env.read_txn()?;
+        read_cursor(storage, &txn, group_id)
+    }
+}
+
+/// One worker of a [`ConsumerGroup`], created by [`ConsumerGroup::join`].
+pub struct GroupWorker<E, H> {
+    reader: Reader<E>,
+    rx: tokio::sync::watch::Receiver<u64>,
+    handler: H,
+    group_id: u64,
+    worker_id: u64,
+    config: GroupConfig,
+    on_error: ErrorPolicy,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<E, H> GroupWorker<E, H>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    H: EventHandler<E>,
+{
+    /// Sets what happens when the handler fails. Defaults to [`ErrorPolicy::Stop`], which
+    /// gives up the failed claim so that another worker retries it.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    /// Makes [`run`](Self::run) return `Ok(())` once `token` is cancelled.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Claims and handles ranges of events until cancelled.
+    ///
+    /// When nothing is left to claim, the worker waits for new events, and wakes up every
+    /// `claim_ttl` to take over claims that other workers let expire.
+    pub async fn run(&mut self) -> Result<()> {
+        loop {
+            if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) {
+                return Ok(());
+            }
+
+            // Mark the current head as seen before claiming, so events appended while the
+            // claim is processed wake the worker up again.
+            self.rx.borrow_and_update();
+            if let Some(claim) = self.claim()? {
+                self.process(claim)?;
+                continue;
+            }
+
+            let cancelled = async {
+                match &self.cancellation {
+                    Some(token) => token.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                changed = self.rx.changed() => {
+                    changed.map_err(|_| {
+                        crate::error::Error::Io(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "Sender dropped",
+                        ))
+                    })?;
+                }
+                _ = tokio::time::sleep(self.config.claim_ttl) => {}
+                _ = cancelled => return Ok(()),
+            }
+        }
+    }
+
+    fn process(&mut self, claim: Claim) -> Result<()> {
+        let storage = self.reader.storage().clone();
+        {
+            let txn = storage.env.read_txn()?;
+            for seq in claim.start..=claim.end {
+                // Sequences can be missing, e.g. after a failed `append_at`.
+                let Some(event) = self.reader.get(&txn, seq)? else {
+                    continue;
+                };
+                if let Err(e) = self.handler.handle(&event) {
+                    match self.on_error {
+                        ErrorPolicy::Stop => {
+                            drop(txn);
+                            self.release(&claim)?;
+                            return Err(e);
+                        }
+                        ErrorPolicy::Skip => {
+                            tracing::warn!("Skipping event {} after handler error: {}", seq, e);
+                        }
+                    }
+                }
+            }
+        }
+        self.complete(&claim)
+    }
+
+    /// Takes over an expired claim, or claims the next unclaimed range up to the head.
+    fn claim(&self) -> Result<Option<Claim>> {
+        let storage = self.reader.storage();
+        let env = storage.env.clone();
+        let mut txn = env.write_txn()?;
+        let now = now_millis();
+
+        let mut next_start = read_cursor(storage, &txn, self.group_id)? + 1;
+        let mut expired = None;
+        for claim in read_claims(storage, &txn, self.group_id)? {
+            next_start = next_start.max(claim.end + 1);
+            let ours = claim.worker_id == self.worker_id;
+            if expired.is_none() && !claim.done && (ours || claim.expires_at <= now) {
+                expired = Some(claim);
+            }
+        }
+
+        let (start, end) = match expired {
+            Some(claim) => (claim.start, claim.end),
+            None => {
+                let head = storage.events_log.last(&txn)?.map_or(0, |(seq, _)| seq);
+                if next_start > head {
+                    return Ok(None);
+                }
+                let batch = self.config.batch_size as u64;
+                (next_start, head.min(next_start.saturating_add(batch - 1)))
+            }
+        };
+
+        let claim = Claim {
+            start,
+            end,
+            worker_id: self.worker_id,
+            expires_at: now.saturating_add(millis(self.config.claim_ttl)),
+            done: false,
+        };
+        put_claim(storage, &mut txn, self.group_id, &claim)?;
+        txn.commit()?;
+        Ok(Some(claim))
+    }
+
+    /// Marks `claim` done and advances the shared cursor over completed ranges.
+    ///
+    /// Does nothing if the claim was taken over by another worker in the meantime.
+    fn complete(&self, claim: &Claim) -> Result<()> {
+        let storage = self.reader.storage();
+        let env = storage.env.clone();
+        let mut txn = env.write_txn()?;
+        if !self.still_ours(storage, &txn, claim)? {
+            return Ok(());
+        }
+        put_claim(
+            storage,
+            &mut txn,
+            self.group_id,
+            &Claim {
+                done: true,
+                ..*claim
+            },
+        )?;
+
+        let mut cursor = read_cursor(storage, &txn, self.group_id)?;
+        let advanced = cursor;
+        for claim in read_claims(storage, &txn, self.group_id)? {
+            if claim.start != cursor + 1 || !claim.done {
+                break;
+            }
+            storage
+                .group_claims
+                .create(&mut txn)?
+                .delete(&mut txn, &claim_key(self.group_id, claim.start))?;
+            cursor = claim.end;
+        }
+        if cursor != advanced {
+            storage.group_claims.create(&mut txn)?.put(
+                &mut txn,
+                &group_key(self.group_id, CURSOR_TAG),
+                &cursor.to_be_bytes(),
+            )?;
+        }
+
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Expires `claim` right away so that any worker can retry it.
+    fn release(&self, claim: &Claim) -> Result<()> {
+        let storage = self.reader.storage();
+        let env = storage.env.clone();
+        let mut txn = env.write_txn()?;
+        if self.still_ours(storage, &txn, claim)? {
+            put_claim(
+                storage,
+                &mut txn,
+                self.group_id,
+                &Claim {
+                    worker_id: 0,
+                    expires_at: 0,
+                    ..*claim
+                },
+            )?;
+            txn.commit()?;
+        }
+        Ok(())
+    }
+
+    fn still_ours(&self, storage: &Storage, txn: &heed::RoTxn, claim: &Claim) -> Result<bool> {
+        let Some(group_claims) = storage.group_claims.open(txn)? else {
+            return Ok(false);
+        };
+        let key = claim_key(self.group_id, claim.start);
+        Ok(match group_claims.get(txn, &key)? {
+            Some(value) => Claim::decode(&key, value)?.worker_id == self.worker_id,
+            None => false,
+        })
+    }
+}
+
+fn group_key(group_id: u64, tag: u8) -> [u8; 9] {
+    let mut key = [0u8; 9];
+    key[..8].copy_from_slice(&group_id.to_be_bytes());
+    key[8] = tag;
+    key
+}
+
+fn claim_key(group_id: u64, start: u64) -> [u8; 17] {
+    let mut key = [0u8; 17];
+    key[..9].copy_from_slice(&group_key(group_id, CLAIM_TAG));
+    key[9..].copy_from_slice(&start.to_be_bytes());
+    key
+}
+
+fn read_cursor(storage: &Storage, txn: &heed::RoTxn, group_id: u64) -> Result<u64> {
+    let Some(group_claims) = storage.group_claims.open(txn)? else {
+        return Ok(0);
+    };
+    match group_claims.get(txn, &group_key(group_id, CURSOR_TAG))? {
+        Some(bytes) => {
+            let bytes: [u8; 8] = bytes.try_into().map_err(|_| {
+                crate::error::Error::InvalidConfig("malformed consumer group cursor".to_string())
+            })?;
+            Ok(u64::from_be_bytes(bytes))
+        }
+        None => Ok(0),
+    }
+}
+
+/// Returns the claims of `group_id`, ordered by their first sequence number.
+fn read_claims(storage: &Storage, txn: &heed::RoTxn, group_id: u64) -> Result<Vec<Claim>> {
+    let mut claims = Vec::new();
+    let Some(group_claims) = storage.group_claims.open(txn)? else {
+        return Ok(claims);
+    };
+    for item in group_claims.prefix_iter(txn, &group_key(group_id, CLAIM_TAG))? {
+        let (key, value) = item?;
+        claims.push(Claim::decode(key, value)?);
+    }
+    Ok(claims)
+}
+
+fn put_claim(storage: &Storage, txn: &mut heed::RwTxn, group_id: u64, claim: &Claim) -> Result<()> {
+    Ok(storage.group_claims.create(txn)?.put(
+        txn,
+        &claim_key(group_id, claim.start),
+        &claim.encode(),
+    )?)
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(millis)
+        .unwrap_or(0)
+}
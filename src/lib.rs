@@ -65,15 +65,23 @@ pub mod constants;
 pub mod crypto;
 pub mod engine;
 pub mod error;
+pub mod group;
 pub mod metrics;
 pub mod model;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod outbox;
 pub mod processor;
+#[cfg(feature = "net")]
+pub mod serve;
 pub mod storage;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod traits;
 pub mod varve;
 
-pub use varve::{ExpectedVersion, InvalidVersionError, StreamVersion, Varve};
+pub use varve::{ExpectedVersion, HealthReport, InvalidVersionError, StreamVersion, Varve};
 
 pub use error::Error;
 pub use model::Payload;
-pub use traits::MetadataExt;
+pub use traits::{IndexKey, MetadataExt, StreamState};
@@ -6,7 +6,13 @@
 // v. 2.0. If a copy of the MPL was not distributed with this file, You can
 // obtain one at http://mozilla.org/MPL/2.0/.
 
-use prometheus::{Histogram, IntCounter, Registry};
+use crate::storage::background::BackgroundWorker;
+use crate::storage::Storage;
+use prometheus::{
+    Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Registry,
+};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Prometheus metrics for VarveDB.
 ///
@@ -16,12 +22,39 @@ use prometheus::{Histogram, IntCounter, Registry};
 /// - `varvedb_write_duration_seconds`: Histogram of write latency.
 /// - `varvedb_read_duration_seconds`: Histogram of read latency.
 /// - `varvedb_events_written_total`: Counter of total events written.
+///
+/// The gauges describing on-disk state (`varvedb_events_stored`, `varvedb_map_used_bytes`,
+/// `varvedb_map_free_bytes`, `varvedb_blobs_stored` and `varvedb_consumer_lag`) are only
+/// updated by [`collect`](Self::collect), e.g. periodically through
+/// [`spawn_collector`](Self::spawn_collector).
 #[derive(Debug, Clone)]
 pub struct VarveMetrics {
     pub events_appended: IntCounter,
     pub bytes_written: IntCounter,
     pub append_latency: Histogram,
+    /// Time spent serializing events during appends.
+    pub serialize_duration: Histogram,
+    /// Time spent encrypting records during appends (only observed with encryption enabled).
+    pub encrypt_duration: Histogram,
+    /// Time spent committing append transactions, including the disk flush.
+    pub commit_duration: Histogram,
     pub events_read: IntCounter,
+    /// Times a commit found the map above `StorageConfig::map_full_warn_threshold`.
+    pub map_full_warnings: IntCounter,
+    /// Number of events in the log.
+    pub events_stored: IntGauge,
+    /// Bytes of the memory map occupied by allocated pages.
+    pub map_used_bytes: IntGauge,
+    /// Bytes of the memory map still free, see `Storage::free_space_bytes`.
+    pub map_free_bytes: IntGauge,
+    /// Number of payloads in the blob store.
+    pub blobs_stored: IntGauge,
+    /// Events behind the head of the log, per consumer (label `consumer`).
+    pub consumer_lag: IntGaugeVec,
+    /// Time a `Processor` spent on each committed batch, per consumer (label `consumer`).
+    pub processor_batch_duration: HistogramVec,
+    /// Events handled by each `Processor`, per consumer (label `consumer`).
+    pub processor_events_processed: IntCounterVec,
 }
 
 impl VarveMetrics {
@@ -38,19 +71,161 @@ impl VarveMetrics {
             "varvedb_append_duration_seconds",
             "Duration of append operations",
         ))?;
+        let serialize_duration = Histogram::with_opts(prometheus::HistogramOpts::new(
+            "varvedb_append_serialize_duration_seconds",
+            "Duration of event serialization during appends",
+        ))?;
+        let encrypt_duration = Histogram::with_opts(prometheus::HistogramOpts::new(
+            "varvedb_append_encrypt_duration_seconds",
+            "Duration of record encryption during appends",
+        ))?;
+        let commit_duration = Histogram::with_opts(prometheus::HistogramOpts::new(
+            "varvedb_append_commit_duration_seconds",
+            "Duration of append transaction commits",
+        ))?;
         let events_read =
             IntCounter::new("varvedb_events_read_total", "Total number of events read")?;
+        let map_full_warnings = IntCounter::new(
+            "varvedb_map_full_warnings_total",
+            "Number of times map usage crossed the warning threshold",
+        )?;
+
+        let events_stored = IntGauge::new("varvedb_events_stored", "Number of events in the log")?;
+        let map_used_bytes = IntGauge::new(
+            "varvedb_map_used_bytes",
+            "Bytes of the memory map occupied by allocated pages",
+        )?;
+        let map_free_bytes = IntGauge::new(
+            "varvedb_map_free_bytes",
+            "Bytes of the memory map still free",
+        )?;
+        let blobs_stored = IntGauge::new(
+            "varvedb_blobs_stored",
+            "Number of payloads in the blob store",
+        )?;
+        let consumer_lag = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "varvedb_consumer_lag",
+                "Events behind the head of the log, per consumer",
+            ),
+            &["consumer"],
+        )?;
+
+        let processor_batch_duration = HistogramVec::new(
+            prometheus::HistogramOpts::new(
+                "varvedb_processor_batch_duration_seconds",
+                "Duration of processor batches, per consumer",
+            ),
+            &["consumer"],
+        )?;
+        let processor_events_processed = IntCounterVec::new(
+            prometheus::Opts::new(
+                "varvedb_processor_events_processed_total",
+                "Total number of events handled by processors, per consumer",
+            ),
+            &["consumer"],
+        )?;
 
         registry.register(Box::new(events_appended.clone()))?;
         registry.register(Box::new(bytes_written.clone()))?;
         registry.register(Box::new(append_latency.clone()))?;
+        registry.register(Box::new(serialize_duration.clone()))?;
+        registry.register(Box::new(encrypt_duration.clone()))?;
+        registry.register(Box::new(commit_duration.clone()))?;
         registry.register(Box::new(events_read.clone()))?;
+        registry.register(Box::new(map_full_warnings.clone()))?;
+        registry.register(Box::new(events_stored.clone()))?;
+        registry.register(Box::new(map_used_bytes.clone()))?;
+        registry.register(Box::new(map_free_bytes.clone()))?;
+        registry.register(Box::new(blobs_stored.clone()))?;
+        registry.register(Box::new(consumer_lag.clone()))?;
+        registry.register(Box::new(processor_batch_duration.clone()))?;
+        registry.register(Box::new(processor_events_processed.clone()))?;
 
         Ok(Self {
             events_appended,
             bytes_written,
             append_latency,
+            serialize_duration,
+            encrypt_duration,
+            commit_duration,
             events_read,
+            map_full_warnings,
+            events_stored,
+            map_used_bytes,
+            map_free_bytes,
+            blobs_stored,
+            consumer_lag,
+            processor_batch_duration,
+            processor_events_processed,
         })
     }
+
+    /// Reads the store once and updates the on-disk state gauges.
+    ///
+    /// Runs in one read transaction (plus one on the blob environment, if separate). Lag
+    /// series of consumers whose cursor was deleted are removed.
+    pub fn collect(&self, storage: &Storage) -> crate::error::Result<()> {
+        let txn = storage.env.read_txn()?;
+        let head = storage.events_log.last(&txn)?.map_or(0, |(seq, _)| seq);
+        let free = storage.free_space_bytes(&txn)?;
+        let map_size = storage.env.info().map_size as u64;
+        let blobs = match &storage.blob_env {
+            None => storage.blobs.len(&txn)?,
+            Some(blob_env) => storage.blobs.len(&blob_env.read_txn()?)?,
+        };
+
+        self.events_stored.set(gauge(storage.events_log.len(&txn)?));
+        self.map_used_bytes
+            .set(gauge(map_size.saturating_sub(free)));
+        self.map_free_bytes.set(gauge(free));
+        self.blobs_stored.set(gauge(blobs));
+        self.consumer_lag.reset();
+        for (consumer_id, committed) in storage.list_consumers(&txn)? {
+            self.consumer_lag
+                .with_label_values(&[&consumer_id.to_string()])
+                .set(gauge(head.saturating_sub(committed)));
+        }
+        Ok(())
+    }
+
+    /// Starts a background thread that calls [`collect`](Self::collect) every `interval`.
+    ///
+    /// The thread stops when the returned [`MetricsCollector`] is dropped, and keeps its
+    /// clone of `storage` open until then. Collection errors are logged and retried on the
+    /// next tick.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `interval` is zero,
+    /// or an I/O error if the thread cannot be spawned.
+    pub fn spawn_collector(
+        self: &Arc<Self>,
+        storage: Storage,
+        interval: Duration,
+    ) -> crate::error::Result<MetricsCollector> {
+        if interval.is_zero() {
+            return Err(crate::error::Error::InvalidConfig(
+                "metrics collection interval must be greater than 0".to_string(),
+            ));
+        }
+
+        let metrics = self.clone();
+        let worker = BackgroundWorker::spawn("varvedb-metrics", interval, move || {
+            if let Err(e) = metrics.collect(&storage) {
+                tracing::warn!("Metrics collection failed: {}", e);
+            }
+        })?;
+        Ok(MetricsCollector { _worker: worker })
+    }
+}
+
+/// A running [`VarveMetrics::spawn_collector`] thread; dropping it stops and joins the thread.
+#[derive(Debug)]
+pub struct MetricsCollector {
+    _worker: BackgroundWorker,
+}
+
+fn gauge(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
 }
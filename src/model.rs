@@ -18,6 +18,68 @@ pub enum StoragePayload {
     /// Large data stored in the blob store, referenced by its hash.
     /// The hash is a SHA-256 hash (32 bytes).
     BlobRef([u8; 32]),
+    /// Small data stored inline together with a CRC32C checksum of `data`.
+    ///
+    /// Written for all new inline events. The checksum is verified on read when
+    /// `StorageConfig::verify_checksums` is enabled.
+    InlineChecked { data: Vec<u8>, checksum: u32 },
+    /// Like `InlineChecked`, tagged with the schema version the event was written with.
+    ///
+    /// Written instead of `InlineChecked` by writers with a non-zero schema version.
+    InlineVersioned {
+        data: Vec<u8>,
+        checksum: u32,
+        schema: u16,
+    },
+    /// Like `BlobRef`, tagged with the schema version the event was written with.
+    BlobRefVersioned { hash: [u8; 32], schema: u16 },
+}
+
+/// The variant of a [`StoragePayload`], without its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// [`StoragePayload::Inline`].
+    Inline,
+    /// [`StoragePayload::InlineChecked`].
+    InlineChecked,
+    /// [`StoragePayload::InlineVersioned`], with its schema version.
+    InlineVersioned { schema: u16 },
+    /// [`StoragePayload::BlobRef`].
+    BlobRef,
+    /// [`StoragePayload::BlobRefVersioned`], with its schema version.
+    BlobRefVersioned { schema: u16 },
+}
+
+impl PayloadKind {
+    /// Returns `true` if the event bytes live in the blob store.
+    pub fn is_blob(self) -> bool {
+        matches!(self, Self::BlobRef | Self::BlobRefVersioned { .. })
+    }
+}
+
+impl From<&ArchivedStoragePayload> for PayloadKind {
+    fn from(payload: &ArchivedStoragePayload) -> Self {
+        match payload {
+            ArchivedStoragePayload::Inline(_) => Self::Inline,
+            ArchivedStoragePayload::InlineChecked { .. } => Self::InlineChecked,
+            ArchivedStoragePayload::InlineVersioned { schema, .. } => Self::InlineVersioned {
+                schema: schema.to_native(),
+            },
+            ArchivedStoragePayload::BlobRef(_) => Self::BlobRef,
+            ArchivedStoragePayload::BlobRefVersioned { schema, .. } => Self::BlobRefVersioned {
+                schema: schema.to_native(),
+            },
+        }
+    }
+}
+
+/// How a record is stored, see `Reader::iter_with_storage_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageKind {
+    /// Where the event bytes are and how they are framed.
+    pub payload: PayloadKind,
+    /// Whether the record is encrypted.
+    pub encrypted: bool,
 }
 
 /// A container for an event and its associated metadata.
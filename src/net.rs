@@ -0,0 +1,14 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The client side of [`serve`](crate::serve): decoding served frames into typed events.
+//!
+//! A consumer in another process only needs [`RecordStream`] and a byte source, such as
+//! the socket from [`connect_tcp`]. Enabled by the `net` feature.
+
+pub use crate::serve::{connect_tcp, RecordStream, Stream, TcpRecords, DEFAULT_MAX_FRAME_LEN};
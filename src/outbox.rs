@@ -0,0 +1,493 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Transactional outbox: messages committed together with the events that cause them.
+//!
+//! Publishing to a broker after appending an event loses the message if the process dies in
+//! between, and publishing first sends a message for an event that may never commit.
+//! [`Writer::append_with_outbox`](crate::engine::Writer::append_with_outbox) instead writes
+//! the event and a pending [`OutboxMessage`] to the `outbox` database in one transaction. An
+//! [`OutboxRelay`] then hands pending messages to a dispatch callback, in order, and marks
+//! each one sent once the callback succeeds.
+//!
+//! # Delivery
+//!
+//! A message is marked sent in its own transaction after it was dispatched, so a crash in
+//! between dispatches it again on restart: delivery is at-least-once, and receivers should
+//! deduplicate, e.g. by [`OutboxMessage::id`]. Sent messages stay in the outbox until
+//! [`OutboxRelay::purge_sent`] removes them.
+//!
+//! # Encryption
+//!
+//! With [`StorageConfig::encryption_enabled`](crate::storage::StorageConfig::encryption_enabled),
+//! the destination and payload of a message are encrypted with the key of its event's stream,
+//! like the event itself; its status and sequence number stay readable. Deleting the stream
+//! key makes the message unreadable too, and the relay fails with
+//! [`KeyNotFound`](crate::error::Error::KeyNotFound) once it reaches it.
+
+use crate::crypto::{self, KeyManager};
+use crate::error::Result;
+use crate::processor::CancellationToken;
+use crate::storage::Storage;
+use std::time::Duration;
+
+/// Default number of messages an [`OutboxRelay`] reads per transaction.
+pub const DEFAULT_RELAY_BATCH_SIZE: usize = 100;
+
+/// Key of the last outbox ID handed out in the `meta` database, so IDs are not reused once
+/// the messages holding them are purged.
+const LAST_ID_KEY: &str = "outbox_last_id";
+
+/// Key of the highest outbox ID marked sent in the `meta` database. The relay sends messages
+/// in ID order, so pending messages all come after it.
+const SENT_THROUGH_KEY: &str = "outbox_sent_through";
+
+/// A message to write to the outbox together with an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    /// Where the relay should send the message, e.g. a topic or queue name.
+    pub destination: String,
+    /// The message body.
+    pub payload: Vec<u8>,
+}
+
+impl OutboxEntry {
+    /// Creates an entry for `destination` carrying `payload`.
+    pub fn new(destination: impl Into<String>, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            destination: destination.into(),
+            payload: payload.into(),
+        }
+    }
+}
+
+/// Delivery state of an [`OutboxMessage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxStatus {
+    /// Not yet dispatched successfully.
+    Pending,
+    /// Dispatched and marked sent by an [`OutboxRelay`].
+    Sent,
+}
+
+/// A message stored in the outbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxMessage {
+    /// Outbox ID of the message, increasing in commit order and never reused.
+    pub id: u64,
+    /// Sequence number of the event appended with the message.
+    pub seq: u64,
+    /// See [`OutboxEntry::destination`].
+    pub destination: String,
+    /// See [`OutboxEntry::payload`].
+    pub payload: Vec<u8>,
+    /// Whether the message has been sent.
+    pub status: OutboxStatus,
+}
+
+/// Tag appended to the AAD of encrypted outbox messages, so they can never be decrypted as
+/// an event or its metadata.
+const OUTBOX_AAD_TAG: u8 = b'O';
+
+/// Stores `entry` as a pending message for event `seq` of `stream_id`, returning its
+/// outbox ID.
+///
+/// Values are `[status (1)][seq (8, BE)][body]`, where the body is
+/// `[destination length (4, BE)][destination][payload]`. With `key_manager`, the body is
+/// encrypted with the stream key into `[StreamID (16)][Nonce (12)][Ciphertext]`. Like event
+/// records, values end with an encryption flag when
+/// [`encrypted_streams`](crate::storage::StorageConfig::encrypted_streams) is set.
+pub(crate) fn put_pending(
+    storage: &Storage,
+    txn: &mut heed::RwTxn,
+    key_manager: Option<&KeyManager>,
+    stream_id: u128,
+    seq: u64,
+    entry: &OutboxEntry,
+) -> Result<u64> {
+    let outbox = storage.outbox.create(txn)?;
+    let last = outbox.last(txn)?.map_or(0, |(id, _)| id);
+    let id = read_id(storage, txn, LAST_ID_KEY)?.max(last) + 1;
+    let destination_len = u32::try_from(entry.destination.len()).map_err(|_| {
+        crate::error::Error::InvalidConfig("outbox destination is too long".to_string())
+    })?;
+
+    let mut body = Vec::with_capacity(4 + entry.destination.len() + entry.payload.len());
+    body.extend_from_slice(&destination_len.to_be_bytes());
+    body.extend_from_slice(entry.destination.as_bytes());
+    body.extend_from_slice(&entry.payload);
+
+    let mut value = Vec::with_capacity(9 + body.len());
+    value.push(0);
+    value.extend_from_slice(&seq.to_be_bytes());
+    match key_manager {
+        Some(km) => {
+            let key = km.get_or_create_key_with_txn(txn, stream_id)?;
+            let stream_id = storage.stored_stream_id(stream_id);
+            value.extend_from_slice(&stream_id.to_be_bytes());
+            value.extend_from_slice(&crypto::encrypt(
+                &key,
+                &body,
+                &outbox_aad(stream_id, id, seq),
+            )?);
+        }
+        None => value.extend_from_slice(&body),
+    }
+    if storage.config.encrypted_streams.is_some() {
+        value.push(if key_manager.is_some() {
+            crate::constants::RECORD_ENCRYPTED
+        } else {
+            crate::constants::RECORD_PLAINTEXT
+        });
+    }
+    outbox.put_with_flags(txn, heed::PutFlags::APPEND, &id, &value)?;
+    storage.meta.put(txn, LAST_ID_KEY, &id.to_be_bytes())?;
+    Ok(id)
+}
+
+/// Reads an outbox ID stored under `key` in the `meta` database, or 0 if there is none.
+fn read_id(storage: &Storage, txn: &heed::RoTxn, key: &str) -> Result<u64> {
+    match storage.meta.get(txn, key)? {
+        Some(bytes) => Ok(u64::from_be_bytes(bytes.try_into().map_err(|_| {
+            crate::error::Error::InvalidConfig(format!("malformed {}", key))
+        })?)),
+        None => Ok(0),
+    }
+}
+
+/// AAD of outbox message `id` for event `seq`: `[StreamID (16)][ID (8)][Seq (8)][OUTBOX_AAD_TAG]`.
+fn outbox_aad(stored_stream_id: u128, id: u64, seq: u64) -> [u8; 33] {
+    let mut aad = [0u8; 33];
+    aad[..16].copy_from_slice(&stored_stream_id.to_be_bytes());
+    aad[16..24].copy_from_slice(&id.to_be_bytes());
+    aad[24..32].copy_from_slice(&seq.to_be_bytes());
+    aad[32] = OUTBOX_AAD_TAG;
+    aad
+}
+
+/// Decodes the outbox message stored under `id`, decrypting it with `key_manager` if it is
+/// encrypted.
+fn decode(
+    storage: &Storage,
+    key_manager: Option<&KeyManager>,
+    txn: &heed::RoTxn,
+    id: u64,
+    value: &[u8],
+) -> Result<OutboxMessage> {
+    let malformed = || crate::error::Error::InvalidConfig("malformed outbox message".to_string());
+    if value.len() < 9 {
+        return Err(malformed());
+    }
+
+    let status = match value[0] {
+        0 => OutboxStatus::Pending,
+        1 => OutboxStatus::Sent,
+        _ => return Err(malformed()),
+    };
+    let seq = u64::from_be_bytes(value[1..9].try_into().unwrap());
+    let mut body = &value[9..];
+    let mut key_manager = key_manager;
+    if storage.config.encrypted_streams.is_some() {
+        match body.split_last() {
+            Some((&crate::constants::RECORD_PLAINTEXT, rest)) => {
+                key_manager = None;
+                body = rest;
+            }
+            Some((&crate::constants::RECORD_ENCRYPTED, rest)) => body = rest,
+            _ => return Err(malformed()),
+        }
+    }
+    let decrypted;
+    if let Some(km) = key_manager {
+        if body.len() < crate::constants::ENCRYPTED_EVENT_MIN_SIZE {
+            return Err(crate::error::Error::InvalidEncryptedEventLength {
+                actual: body.len(),
+                minimum: crate::constants::ENCRYPTED_EVENT_MIN_SIZE,
+            });
+        }
+        let (stream_id_bytes, rest) = body.split_at(crate::constants::STREAM_ID_SIZE);
+        let stream_id = u128::from_be_bytes(stream_id_bytes.try_into().unwrap());
+        let key = km
+            .get_stored_key_with_txn(txn, stream_id)?
+            .ok_or(crate::error::Error::KeyNotFound(stream_id))?;
+        decrypted = crypto::decrypt(&key, rest, &outbox_aad(stream_id, id, seq))?;
+        body = &decrypted;
+    }
+
+    if body.len() < 4 {
+        return Err(malformed());
+    }
+    let destination_len = u32::from_be_bytes(body[..4].try_into().unwrap()) as usize;
+    let rest = &body[4..];
+    if rest.len() < destination_len {
+        return Err(malformed());
+    }
+    let (destination, payload) = rest.split_at(destination_len);
+    Ok(OutboxMessage {
+        id,
+        seq,
+        destination: String::from_utf8(destination.to_vec()).map_err(|_| malformed())?,
+        payload: payload.to_vec(),
+        status,
+    })
+}
+
+/// Dispatches pending outbox messages and marks them sent.
+///
+/// Run one relay per database: two relays would dispatch the same pending messages.
+///
+/// # Examples
+///
+/// ```rust
+/// # use varvedb::engine::Writer;
+/// # use varvedb::outbox::{OutboxEntry, OutboxRelay};
+/// # use varvedb::processor::CancellationToken;
+/// # use varvedb::storage::{Storage, StorageConfig};
+/// # use varvedb::ExpectedVersion;
+/// # use tempfile::tempdir;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+/// # let storage = Storage::open(config)?;
+/// let mut writer = Writer::<u64>::new(storage.clone());
+/// let entry = OutboxEntry::new("orders", b"placed 7".to_vec());
+/// writer.append_with_outbox(1, ExpectedVersion::Auto, 7, entry)?;
+///
+/// let token = CancellationToken::new();
+/// let mut relay = OutboxRelay::new(storage.clone()).with_cancellation_token(token.clone());
+/// let mut published = Vec::new();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+/// runtime.block_on(relay.run(|message| {
+///     published.push((message.destination.clone(), message.payload.clone()));
+///     // Stop once the message is out; a real relay keeps running.
+///     token.cancel();
+///     Ok(())
+/// }))?;
+/// assert_eq!(published, [("orders".to_string(), b"placed 7".to_vec())]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct OutboxRelay {
+    storage: Storage,
+    key_manager: Option<KeyManager>,
+    rx: tokio::sync::watch::Receiver<u64>,
+    batch_size: usize,
+    retry_delay: Duration,
+    cancellation: Option<CancellationToken>,
+}
+
+impl OutboxRelay {
+    /// Creates a relay for the outbox of `storage`.
+    pub fn new(storage: Storage) -> Self {
+        let rx = storage.notifier.subscribe();
+        let key_manager = if storage.config.encryption_enabled {
+            Some(KeyManager::new(storage.clone()))
+        } else {
+            None
+        };
+        Self {
+            storage,
+            key_manager,
+            rx,
+            batch_size: DEFAULT_RELAY_BATCH_SIZE,
+            retry_delay: Duration::from_secs(1),
+            cancellation: None,
+        }
+    }
+
+    /// Sets how many messages are read per transaction. Defaults to
+    /// [`DEFAULT_RELAY_BATCH_SIZE`]; values below 1 are treated as 1.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how long [`run`](Self::run) waits before retrying after a failed dispatch.
+    /// Defaults to one second.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Makes [`run`](Self::run) return `Ok(())` once `token` is cancelled.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Returns the message with outbox ID `id`, if it has not been purged.
+    pub fn message(&self, id: u64) -> Result<Option<OutboxMessage>> {
+        let txn = self.storage.env.read_txn()?;
+        let Some(outbox) = self.storage.outbox.open(&txn)? else {
+            return Ok(None);
+        };
+        outbox
+            .get(&txn, &id)?
+            .map(|value| self.decode(&txn, id, value))
+            .transpose()
+    }
+
+    /// Returns up to `limit` pending messages, oldest first.
+    ///
+    /// The scan starts after the last message the relay marked sent, so sent messages that
+    /// were not purged yet are not read again.
+    pub fn pending(&self, limit: usize) -> Result<Vec<OutboxMessage>> {
+        let txn = self.storage.env.read_txn()?;
+        let sent_through = read_id(&self.storage, &txn, SENT_THROUGH_KEY)?;
+        let mut pending = Vec::new();
+        let Some(outbox) = self.storage.outbox.open(&txn)? else {
+            return Ok(pending);
+        };
+        for result in outbox.range(&txn, &(sent_through + 1..))? {
+            if pending.len() == limit {
+                break;
+            }
+            let (id, value) = result?;
+            // Status is the first byte; skip sent messages without decoding them.
+            if value.first() == Some(&0) {
+                pending.push(self.decode(&txn, id, value)?);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Dispatches every pending message with `dispatch`, in outbox order, and returns how
+    /// many were sent.
+    ///
+    /// Each message is marked sent as soon as `dispatch` returns `Ok`. The first error stops
+    /// the relay and is returned, leaving that message and the ones after it pending, so
+    /// messages are never sent out of order.
+    pub fn relay_pending<F>(&self, mut dispatch: F) -> Result<usize>
+    where
+        F: FnMut(&OutboxMessage) -> Result<()>,
+    {
+        let mut sent = 0;
+        loop {
+            let batch = self.pending(self.batch_size)?;
+            if batch.is_empty() {
+                return Ok(sent);
+            }
+            for message in &batch {
+                dispatch(message)?;
+                self.mark_sent(message.id)?;
+                sent += 1;
+            }
+        }
+    }
+
+    /// Relays pending messages until cancelled, waiting for new commits in between.
+    ///
+    /// A failed dispatch is logged and retried after the
+    /// [`retry_delay`](Self::with_retry_delay); storage errors end the loop.
+    pub async fn run<F>(&mut self, mut dispatch: F) -> Result<()>
+    where
+        F: FnMut(&OutboxMessage) -> Result<()>,
+    {
+        loop {
+            if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) {
+                return Ok(());
+            }
+
+            // Mark the head as seen first, so commits made while relaying wake us up again.
+            self.rx.borrow_and_update();
+            let mut failed = false;
+            let relayed = self.relay_pending(|message| {
+                dispatch(message).inspect_err(|e| {
+                    tracing::warn!("Dispatching outbox message {} failed: {}", message.id, e);
+                    failed = true;
+                })
+            });
+            match relayed {
+                Ok(_) => {}
+                Err(_) if failed => {}
+                Err(e) => return Err(e),
+            }
+
+            let cancelled = async {
+                match &self.cancellation {
+                    Some(token) => token.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            if failed {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.retry_delay) => {}
+                    _ = cancelled => return Ok(()),
+                }
+                continue;
+            }
+            tokio::select! {
+                changed = self.rx.changed() => {
+                    changed.map_err(|_| {
+                        crate::error::Error::Io(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "Sender dropped",
+                        ))
+                    })?;
+                }
+                _ = cancelled => return Ok(()),
+            }
+        }
+    }
+
+    /// Deletes every sent message from the outbox and returns how many were deleted.
+    pub fn purge_sent(&self) -> Result<usize> {
+        let mut txn = self.storage.env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let mut sent = Vec::new();
+        let Some(outbox) = self.storage.outbox.open(&txn)? else {
+            return Ok(0);
+        };
+        for result in outbox.iter(&txn)? {
+            let (id, value) = result?;
+            if value.first() == Some(&1) {
+                sent.push(id);
+            }
+        }
+        for id in &sent {
+            outbox.delete(&mut txn, id)?;
+        }
+        txn.commit()?;
+        Ok(sent.len())
+    }
+
+    fn decode(&self, txn: &heed::RoTxn, id: u64, value: &[u8]) -> Result<OutboxMessage> {
+        decode(&self.storage, self.key_manager.as_ref(), txn, id, value)
+    }
+
+    fn mark_sent(&self, id: u64) -> Result<()> {
+        let mut txn = self.storage.env.write_txn()?;
+        self.storage.check_writer_lease(&txn)?;
+        let Some(outbox) = self.storage.outbox.open(&txn)? else {
+            return Ok(());
+        };
+        let Some(value) = outbox.get(&txn, &id)? else {
+            return Ok(());
+        };
+        let mut value = value.to_vec();
+        value[0] = 1;
+        outbox.put(&mut txn, &id, &value)?;
+        if id > read_id(&self.storage, &txn, SENT_THROUGH_KEY)? {
+            self.storage
+                .meta
+                .put(&mut txn, SENT_THROUGH_KEY, &id.to_be_bytes())?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for OutboxRelay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutboxRelay")
+            .field("batch_size", &self.batch_size)
+            .field("retry_delay", &self.retry_delay)
+            .finish_non_exhaustive()
+    }
+}
@@ -7,11 +7,16 @@
 // obtain one at http://mozilla.org/MPL/2.0/.
 
 use crate::engine::Reader;
+use crate::metrics::VarveMetrics;
 use crate::traits::MetadataExt;
 use crate::varve::Varve;
 use rkyv::api::high::HighValidator;
 use rkyv::bytecheck::CheckBytes;
 use rkyv::rancor::Error as RancorError;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use tokio_util::sync::CancellationToken;
 
 pub trait EventHandler<E>
 where
@@ -40,12 +45,173 @@ impl Default for ProcessorConfig {
     }
 }
 
+/// What a [`Processor`] does when its handler returns an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop processing and return the error from [`Processor::run`]. With
+    /// [`Delivery::AtLeastOnce`] the failed event is retried on the next run; with
+    /// [`Delivery::AtMostOnce`] its cursor was already committed, so it is not. This is the
+    /// default.
+    #[default]
+    Stop,
+    /// Log the error and move on to the next event, as if the failed one had been handled.
+    Skip,
+}
+
+/// When a [`Processor`] records progress relative to calling its handler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Delivery {
+    /// Commit the cursor after events are handled, in batches. After a crash, events since
+    /// the last commit are handled again. This is the default.
+    #[default]
+    AtLeastOnce,
+    /// Commit the cursor before each event is handled. After a crash, the event in flight is
+    /// never handled, and neither is an event whose handler failed under
+    /// [`ErrorPolicy::Stop`]. Costs one write transaction per event.
+    AtMostOnce,
+}
+
+/// Called after each committed batch with the number of events in it and the time spent
+/// processing them; see [`ProcessorBuilder::on_batch`].
+pub type BatchCallback = Arc<dyn Fn(usize, Duration) + Send + Sync>;
+
 pub struct Processor<E, H> {
     reader: Reader<E>,
     handler: H,
     consumer_id: u64,
     rx: tokio::sync::watch::Receiver<u64>,
     config: ProcessorConfig,
+    on_error: ErrorPolicy,
+    delivery: Delivery,
+    cancellation: Option<CancellationToken>,
+    metrics: Option<Arc<VarveMetrics>>,
+    on_batch: Option<BatchCallback>,
+}
+
+/// Builds a [`Processor`], validating its options.
+///
+/// Created by [`Processor::builder`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use rkyv::{Archive, Deserialize, Serialize};
+/// # use std::sync::{Arc, Mutex};
+/// # use std::time::Duration;
+/// # use tempfile::tempdir;
+/// # use varvedb::processor::{CancellationToken, ErrorPolicy, EventHandler, Processor};
+/// # use varvedb::traits::MetadataExt;
+/// # use varvedb::{ExpectedVersion, Payload, Varve};
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Note { text: String }
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Meta { stream_id: u128, version: u32 }
+/// #
+/// # impl MetadataExt for Meta {
+/// #     fn stream_id(&self) -> u128 { self.stream_id }
+/// #     fn version(&self) -> u32 { self.version }
+/// # }
+/// #
+/// #[derive(Clone, Default)]
+/// struct Collect(Arc<Mutex<Vec<String>>>);
+///
+/// impl EventHandler<Note> for Collect {
+///     fn handle(&mut self, event: &ArchivedNote) -> varvedb::error::Result<()> {
+///         self.0.lock().unwrap().push(event.text.to_string());
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// let mut db = Varve::open(dir.path().join("notes.mdb"))?;
+/// let note = Note { text: "hello".into() };
+/// db.append(Payload::new(note, Meta { stream_id: 1, version: 1 }), ExpectedVersion::Auto)?;
+///
+/// let handler = Collect::default();
+/// let token = CancellationToken::new();
+/// let mut processor = Processor::builder(&db, handler.clone(), 42u64)
+///     .batch_size(100)
+///     .batch_timeout(Duration::from_millis(50))
+///     .on_error(ErrorPolicy::Skip)
+///     .cancellation_token(token.clone())
+///     .build()?;
+///
+/// // A cancelled processor still handles the backlog before it returns.
+/// token.cancel();
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+/// runtime.block_on(processor.run())?;
+/// assert_eq!(*handler.0.lock().unwrap(), ["hello"]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProcessorBuilder<E, H> {
+    processor: Processor<E, H>,
+}
+
+impl<E, H> ProcessorBuilder<E, H> {
+    /// Sets the maximum number of events to process before committing the cursor.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.processor.config.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the maximum time to wait before committing the cursor.
+    pub fn batch_timeout(mut self, batch_timeout: std::time::Duration) -> Self {
+        self.processor.config.batch_timeout = batch_timeout;
+        self
+    }
+
+    /// Sets what happens when the handler fails. Defaults to [`ErrorPolicy::Stop`].
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.processor.on_error = policy;
+        self
+    }
+
+    /// Sets the delivery guarantee. Defaults to [`Delivery::AtLeastOnce`].
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.processor.delivery = delivery;
+        self
+    }
+
+    /// Makes [`Processor::run`] return `Ok(())` once `token` is cancelled.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.processor.cancellation = Some(token);
+        self
+    }
+
+    /// Records per-batch durations and processed events into `metrics`, labeled by consumer
+    /// ID.
+    pub fn metrics(mut self, metrics: Arc<VarveMetrics>) -> Self {
+        self.processor.metrics = Some(metrics);
+        self
+    }
+
+    /// Calls `callback` after each committed batch with the number of events in it and the
+    /// time spent processing them, e.g. to log or alert on a slow handler.
+    pub fn on_batch<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(usize, Duration) + Send + Sync + 'static,
+    {
+        self.processor.on_batch = Some(Arc::new(callback));
+        self
+    }
+
+    /// Validates the options and returns the processor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if `batch_size` is 0.
+    pub fn build(self) -> crate::error::Result<Processor<E, H>> {
+        if self.processor.config.batch_size == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        Ok(self.processor)
+    }
 }
 
 impl<E, H> Processor<E, H>
@@ -82,29 +248,111 @@ where
             consumer_id: consumer_id.into(),
             rx,
             config: ProcessorConfig::default(),
+            on_error: ErrorPolicy::default(),
+            delivery: Delivery::default(),
+            cancellation: None,
+            metrics: None,
+            on_batch: None,
         }
     }
 
-    /// Sets the configuration for the processor.
+    /// Returns a [`ProcessorBuilder`] for configuring and validating a `Processor`.
+    pub fn builder<M>(
+        varve: &Varve<E, M>,
+        handler: H,
+        consumer_id: impl Into<u64>,
+    ) -> ProcessorBuilder<E, H>
+    where
+        M: MetadataExt,
+    {
+        ProcessorBuilder {
+            processor: Self::new(varve, handler, consumer_id),
+        }
+    }
+
+    /// Sets the configuration for the processor. A `batch_size` of 0 is treated as 1; use
+    /// [`builder`](Self::builder) to have it rejected instead.
     pub fn with_config(mut self, config: ProcessorConfig) -> Self {
-        self.config = config;
+        self.config = ProcessorConfig {
+            batch_size: config.batch_size.max(1),
+            ..config
+        };
+        self
+    }
+
+    /// Makes [`run`](Self::run) return `Ok(())` once `token` is cancelled.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
         self
     }
 
-    // Placeholder for cancellation token if needed
-    pub fn with_cancellation_token(self, _token: ()) -> Self {
+    /// Records per-batch metrics; see [`ProcessorBuilder::metrics`].
+    pub fn with_metrics(mut self, metrics: Arc<VarveMetrics>) -> Self {
+        self.metrics = Some(metrics);
         self
     }
 
+    /// Deletes this consumer's committed cursor so the next [`run`](Self::run) replays
+    /// every event from the beginning.
+    ///
+    /// Useful while developing projections: wipe the read model, reset the cursor and let
+    /// the processor rebuild it. Must not be called while `run` is in progress.
+    pub fn reset_cursor(&mut self) -> crate::error::Result<()> {
+        let mut wtxn = self.reader.storage().env.write_txn()?;
+        self.reader
+            .storage()
+            .consumer_cursors
+            .delete(&mut wtxn, &self.consumer_id)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Moves this consumer's committed cursor to `seq`.
+    ///
+    /// The cursor records the last processed global sequence number, so the next
+    /// [`run`](Self::run) resumes with event `seq + 1`. `seek(0)` is equivalent to
+    /// [`reset_cursor`](Self::reset_cursor). Seeking past the current head is allowed; the
+    /// processor then waits until events beyond `seq` are written.
+    /// Must not be called while `run` is in progress.
+    pub fn seek(&mut self, seq: u64) -> crate::error::Result<()> {
+        let mut wtxn = self.reader.storage().env.write_txn()?;
+        self.reader
+            .storage()
+            .consumer_cursors
+            .put(&mut wtxn, &self.consumer_id, &seq)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Handles every event written so far, commits the cursor and returns.
+    ///
+    /// Unlike [`run`](Self::run), this doesn't wait for new events, nor for a gap left by
+    /// `append_at` to be filled; it stops before the gap. Returns the global sequence number
+    /// of the last handled event (the committed cursor).
+    pub fn catch_up(&mut self) -> crate::error::Result<u64> {
+        let (current_seq, head_seq) = {
+            let txn = self.reader.storage().env.read_txn()?;
+            let head = self
+                .reader
+                .storage()
+                .events_log
+                .last(&txn)?
+                .map_or(0, |(seq, _)| seq);
+            (self.committed_cursor(&txn)?, head)
+        };
+
+        if current_seq < head_seq {
+            self.process_backlog(current_seq, head_seq)
+        } else {
+            Ok(current_seq)
+        }
+    }
+
     /// Starts the event processing loop.
     pub async fn run(&mut self) -> crate::error::Result<()> {
         let mut current_seq = {
             let txn = self.reader.storage().env.read_txn()?;
-            self.reader
-                .storage()
-                .consumer_cursors
-                .get(&txn, &self.consumer_id)?
-                .unwrap_or(0)
+            self.committed_cursor(&txn)?
         };
 
         loop {
@@ -114,8 +362,20 @@ where
                 current_seq = self.process_backlog(current_seq, head_seq)?;
             }
 
-            if current_seq >= *self.rx.borrow() {
-                self.rx.changed().await.map_err(|_| {
+            if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) {
+                return Ok(());
+            }
+
+            // Stopping short of the head means a gap, so wait until it is filled.
+            if current_seq < head_seq || current_seq >= *self.rx.borrow() {
+                let changed = match &self.cancellation {
+                    Some(token) => tokio::select! {
+                        changed = self.rx.changed() => changed,
+                        _ = token.cancelled() => return Ok(()),
+                    },
+                    None => self.rx.changed().await,
+                };
+                changed.map_err(|_| {
                     crate::error::Error::Io(std::io::Error::new(
                         std::io::ErrorKind::BrokenPipe,
                         "Sender dropped",
@@ -125,6 +385,15 @@ where
         }
     }
 
+    fn committed_cursor(&self, txn: &heed::RoTxn) -> crate::error::Result<u64> {
+        Ok(self
+            .reader
+            .storage()
+            .consumer_cursors
+            .get(txn, &self.consumer_id)?
+            .unwrap_or(0))
+    }
+
     fn process_backlog(
         &mut self,
         mut current_seq: u64,
@@ -132,26 +401,47 @@ where
     ) -> crate::error::Result<u64> {
         let mut pending_updates = 0;
         let mut last_commit = std::time::Instant::now();
-        let mut read_txn: Option<heed::RoTxn> = None;
+        let read_txn = self.reader.storage().env.read_txn()?;
+        let txn = &read_txn;
 
         while current_seq < target_seq {
-            if read_txn.is_none() {
-                read_txn = Some(self.reader.storage().env.read_txn()?);
-            }
-            let txn = read_txn.as_ref().unwrap();
-
             let mut processed_any = false;
-            let mut reached_snapshot_end = false;
+            let mut reached_gap = false;
 
             while current_seq < target_seq {
-                let next_seq = current_seq + 1;
+                let next_seq = self.reader.storage().skip_trimmed(txn, current_seq + 1)?;
+                if next_seq > target_seq {
+                    // Everything up to the target was trimmed.
+                    current_seq = target_seq;
+                    break;
+                }
                 if let Some(event) = self.reader.get(txn, next_seq)? {
-                    self.handler.handle(&event)?;
+                    if self.delivery == Delivery::AtMostOnce {
+                        let mut wtxn = self.reader.storage().env.write_txn()?;
+                        self.reader.storage().consumer_cursors.put(
+                            &mut wtxn,
+                            &self.consumer_id,
+                            &next_seq,
+                        )?;
+                        wtxn.commit()?;
+                    }
+                    if let Err(e) = self.handler.handle(&event) {
+                        match self.on_error {
+                            ErrorPolicy::Stop => return Err(e),
+                            ErrorPolicy::Skip => {
+                                tracing::warn!(
+                                    "Skipping event {} after handler error: {}",
+                                    next_seq,
+                                    e
+                                );
+                            }
+                        }
+                    }
                     current_seq = next_seq;
                     pending_updates += 1;
                     processed_any = true;
                 } else {
-                    reached_snapshot_end = true;
+                    reached_gap = true;
                     break;
                 }
 
@@ -163,32 +453,423 @@ where
             if pending_updates >= self.config.batch_size
                 || (processed_any && last_commit.elapsed() >= self.config.batch_timeout)
             {
-                let mut wtxn = self.reader.storage().env.write_txn()?;
-                self.reader.storage().consumer_cursors.put(
-                    &mut wtxn,
-                    &self.consumer_id,
-                    &current_seq,
-                )?;
-                wtxn.commit()?;
+                self.commit_batch(current_seq, pending_updates, last_commit)?;
                 pending_updates = 0;
                 last_commit = std::time::Instant::now();
             }
 
-            if reached_snapshot_end {
-                read_txn = None;
+            if reached_gap {
+                // Wait for the gap to be filled rather than spin on it.
+                break;
             }
         }
 
         if pending_updates > 0 {
-            let mut wtxn = self.reader.storage().env.write_txn()?;
-            self.reader.storage().consumer_cursors.put(
-                &mut wtxn,
-                &self.consumer_id,
-                &current_seq,
-            )?;
-            wtxn.commit()?;
+            self.commit_batch(current_seq, pending_updates, last_commit)?;
+        }
+
+        Ok(current_seq)
+    }
+
+    /// Commits the cursor at `seq` and reports the batch of `events` begun at `started`.
+    fn commit_batch(
+        &self,
+        seq: u64,
+        events: usize,
+        started: std::time::Instant,
+    ) -> crate::error::Result<()> {
+        let mut wtxn = self.reader.storage().env.write_txn()?;
+        self.reader
+            .storage()
+            .consumer_cursors
+            .put(&mut wtxn, &self.consumer_id, &seq)?;
+        wtxn.commit()?;
+
+        let elapsed = started.elapsed();
+        if let Some(metrics) = &self.metrics {
+            let consumer = self.consumer_id.to_string();
+            metrics
+                .processor_batch_duration
+                .with_label_values(&[&consumer])
+                .observe(elapsed.as_secs_f64());
+            metrics
+                .processor_events_processed
+                .with_label_values(&[&consumer])
+                .inc_by(events as u64);
+        }
+        if let Some(on_batch) = &self.on_batch {
+            on_batch(events, elapsed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<E> Processor<E, crate::testing::CollectingHandler<E>>
+where
+    E: rkyv::Archive
+        + for<'a> rkyv::Serialize<
+            rkyv::api::high::HighSerializer<
+                rkyv::util::AlignedVec,
+                rkyv::ser::allocator::ArenaHandle<'a>,
+                RancorError,
+            >,
+        > + std::fmt::Debug,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>
+        + rkyv::Deserialize<E, rkyv::api::high::HighDeserializer<RancorError>>,
+{
+    /// Runs [`catch_up`](Self::catch_up) and returns the events handled since the last
+    /// drain, in order.
+    ///
+    /// Only available with the `testing` feature.
+    pub fn drain_into_vec(&mut self) -> crate::error::Result<Vec<E>> {
+        self.catch_up()?;
+        Ok(self.handler.take())
+    }
+}
+
+/// A handler that a [`ParallelProcessor`] calls from several threads at once.
+///
+/// Implemented for closures taking the archived event.
+pub trait ParallelEventHandler<E>: Sync
+where
+    E: rkyv::Archive,
+{
+    fn handle(&self, event: &E::Archived) -> crate::error::Result<()>;
+}
+
+impl<E, F> ParallelEventHandler<E> for F
+where
+    E: rkyv::Archive,
+    F: Fn(&E::Archived) -> crate::error::Result<()> + Sync,
+{
+    fn handle(&self, event: &E::Archived) -> crate::error::Result<()> {
+        self(event)
+    }
+}
+
+/// Returns the stream an archived event belongs to, see [`ParallelProcessor::new`].
+pub type StreamOf<E> = Box<dyn Fn(&<E as rkyv::Archive>::Archived) -> u128 + Send + Sync>;
+
+/// Processes the events of a batch on several threads, keeping each stream in order.
+///
+/// Reads a batch of up to `batch_size` events, groups it by stream and hands the groups to
+/// a pool of worker threads: events of different streams are handled concurrently, events
+/// of one stream one after another, in sequence order. Records do not say which stream they
+/// belong to, so the stream of each event is taken from the event itself with the
+/// `stream_of` function passed to [`new`](Self::new).
+///
+/// # Delivery
+///
+/// Delivery is at-least-once. After each batch the cursor is committed up to the last
+/// sequence number below which every event was handled. When a handler fails (with
+/// [`ErrorPolicy::Stop`]) its stream stops at the failed event while the other streams
+/// finish the batch, so the cursor stops right before the failed event and events of other
+/// streams after it are handled again on the next run.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rkyv::{Archive, Deserialize, Serialize};
+/// # use std::sync::Mutex;
+/// # use tempfile::tempdir;
+/// # use varvedb::processor::ParallelProcessor;
+/// # use varvedb::traits::MetadataExt;
+/// # use varvedb::{ExpectedVersion, Payload, Varve};
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct OrderEvent { order_id: u128, amount: u64 }
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Meta { stream_id: u128, version: u32 }
+/// #
+/// # impl MetadataExt for Meta {
+/// #     fn stream_id(&self) -> u128 { self.stream_id }
+/// #     fn version(&self) -> u32 { self.version }
+/// # }
+/// #
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// let mut db = Varve::open(dir.path().join("orders.mdb"))?;
+/// for order_id in 1..=3 {
+///     let event = OrderEvent { order_id, amount: 10 };
+///     let meta = Meta { stream_id: order_id, version: 1 };
+///     db.append(Payload::new(event, meta), ExpectedVersion::Auto)?;
+/// }
+///
+/// let invoiced = Mutex::new(Vec::new());
+/// let mut processor = ParallelProcessor::new(
+///     &db,
+///     |event: &ArchivedOrderEvent| {
+///         invoiced.lock().unwrap().push(event.order_id.to_native());
+///         Ok(())
+///     },
+///     7u64,
+///     |event: &ArchivedOrderEvent| event.order_id.to_native(),
+/// )
+/// .with_workers(8);
+/// assert_eq!(processor.catch_up()?, 3);
+/// drop(processor);
+///
+/// let mut invoiced = invoiced.into_inner().unwrap();
+/// invoiced.sort();
+/// assert_eq!(invoiced, [1, 2, 3]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ParallelProcessor<E, H>
+where
+    E: rkyv::Archive,
+{
+    reader: Reader<E>,
+    handler: H,
+    stream_of: StreamOf<E>,
+    consumer_id: u64,
+    rx: tokio::sync::watch::Receiver<u64>,
+    batch_size: usize,
+    workers: usize,
+    on_error: ErrorPolicy,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<E, H> ParallelProcessor<E, H>
+where
+    E: rkyv::Archive
+        + for<'a> rkyv::Serialize<
+            rkyv::api::high::HighSerializer<
+                rkyv::util::AlignedVec,
+                rkyv::ser::allocator::ArenaHandle<'a>,
+                RancorError,
+            >,
+        > + std::fmt::Debug
+        + Sync,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>,
+    H: ParallelEventHandler<E>,
+{
+    /// Creates a `ParallelProcessor` that groups events by `stream_of`.
+    ///
+    /// `consumer_id` identifies the persisted cursor, as for [`Processor::new`]. Uses one
+    /// worker per available CPU and batches of
+    /// [`DEFAULT_BATCH_SIZE`](crate::constants::DEFAULT_BATCH_SIZE) events.
+    pub fn new<M, S>(
+        varve: &Varve<E, M>,
+        handler: H,
+        consumer_id: impl Into<u64>,
+        stream_of: S,
+    ) -> Self
+    where
+        M: MetadataExt,
+        S: Fn(&E::Archived) -> u128 + Send + Sync + 'static,
+    {
+        Self {
+            reader: varve.reader().clone(),
+            handler,
+            stream_of: Box::new(stream_of),
+            consumer_id: consumer_id.into(),
+            rx: varve.subscribe(),
+            batch_size: crate::constants::DEFAULT_BATCH_SIZE,
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            on_error: ErrorPolicy::default(),
+            cancellation: None,
+        }
+    }
+
+    /// Sets how many events are read, handled and committed together. Values below 1 are
+    /// treated as 1.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the number of worker threads. Values below 1 are treated as 1.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Sets what happens when the handler fails. Defaults to [`ErrorPolicy::Stop`].
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    /// Makes [`run`](Self::run) return `Ok(())` once `token` is cancelled.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Handles every event written so far, commits the cursor and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the handler error of the lowest failed sequence number, after committing
+    /// the cursor right before it, or any storage error.
+    pub fn catch_up(&mut self) -> crate::error::Result<u64> {
+        let txn = self.reader.storage().env.read_txn()?;
+        let head_seq = self
+            .reader
+            .storage()
+            .events_log
+            .last(&txn)?
+            .map_or(0, |(seq, _)| seq);
+        let current_seq = self.committed_cursor(&txn)?;
+        drop(txn);
+        self.process_backlog(current_seq, head_seq)
+    }
+
+    /// Processes events as they are written until cancelled or a handler fails.
+    pub async fn run(&mut self) -> crate::error::Result<()> {
+        loop {
+            if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) {
+                return Ok(());
+            }
+
+            // Mark the head as seen first, so commits made while processing wake us up.
+            self.rx.borrow_and_update();
+            self.catch_up()?;
+
+            let changed = match &self.cancellation {
+                Some(token) => tokio::select! {
+                    changed = self.rx.changed() => changed,
+                    _ = token.cancelled() => return Ok(()),
+                },
+                None => self.rx.changed().await,
+            };
+            changed.map_err(|_| {
+                crate::error::Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "Sender dropped",
+                ))
+            })?;
         }
+    }
+
+    fn committed_cursor(&self, txn: &heed::RoTxn) -> crate::error::Result<u64> {
+        Ok(self
+            .reader
+            .storage()
+            .consumer_cursors
+            .get(txn, &self.consumer_id)?
+            .unwrap_or(0))
+    }
 
+    fn process_backlog(
+        &mut self,
+        mut current_seq: u64,
+        target_seq: u64,
+    ) -> crate::error::Result<u64> {
+        while current_seq < target_seq {
+            let txn = self.reader.storage().env.read_txn()?;
+            let mut batch = Vec::new();
+            let mut seq = current_seq;
+            while batch.len() < self.batch_size {
+                seq = self.reader.storage().skip_trimmed(&txn, seq + 1)?;
+                if seq > target_seq {
+                    break;
+                }
+                match self.reader.get(&txn, seq)? {
+                    Some(event) => batch.push((seq, event)),
+                    None => break,
+                }
+            }
+            let Some(&(last_seq, _)) = batch.last() else {
+                if seq > target_seq {
+                    // Everything up to the target was trimmed.
+                    current_seq = target_seq;
+                }
+                // A gap: wait for it to be filled, like `Processor` does.
+                break;
+            };
+
+            let failure = self.handle_batch(&batch);
+            drop(batch);
+            drop(txn);
+
+            // Every event below the first failure was handled, in its stream and in others.
+            let (cursor, result) = match failure {
+                Some((seq, e)) => (seq - 1, Err(e)),
+                None => (last_seq, Ok(())),
+            };
+            if cursor > current_seq {
+                self.commit(cursor)?;
+                current_seq = cursor;
+            }
+            result?;
+        }
         Ok(current_seq)
     }
+
+    /// Handles `batch` on the worker pool and returns the lowest failed sequence number
+    /// with its error, if any.
+    fn handle_batch(
+        &self,
+        batch: &[(u64, crate::engine::EventView<'_, E>)],
+    ) -> Option<(u64, crate::error::Error)> {
+        let mut streams: std::collections::HashMap<u128, Vec<usize>> = Default::default();
+        for (i, (_, event)) in batch.iter().enumerate() {
+            streams.entry((self.stream_of)(event)).or_default().push(i);
+        }
+        let streams: Vec<Vec<usize>> = streams.into_values().collect();
+
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let failures = std::sync::Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..self.workers.min(streams.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let Some(stream) = streams.get(i) else {
+                        return;
+                    };
+                    for &(seq, ref event) in stream.iter().map(|&i| &batch[i]) {
+                        let Err(e) = self.handler.handle(event) else {
+                            continue;
+                        };
+                        match self.on_error {
+                            ErrorPolicy::Stop => {
+                                let mut failures =
+                                    failures.lock().unwrap_or_else(|e| e.into_inner());
+                                failures.push((seq, e));
+                                // Later events of this stream must wait for this one.
+                                break;
+                            }
+                            ErrorPolicy::Skip => {
+                                tracing::warn!("Skipping event {} after handler error: {}", seq, e);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        failures
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .min_by_key(|(seq, _)| *seq)
+    }
+
+    fn commit(&self, seq: u64) -> crate::error::Result<()> {
+        let mut wtxn = self.reader.storage().env.write_txn()?;
+        self.reader
+            .storage()
+            .consumer_cursors
+            .put(&mut wtxn, &self.consumer_id, &seq)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+}
+
+impl<E, H> std::fmt::Debug for ParallelProcessor<E, H>
+where
+    E: rkyv::Archive,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParallelProcessor")
+            .field("consumer_id", &self.consumer_id)
+            .field("batch_size", &self.batch_size)
+            .field("workers", &self.workers)
+            .field("on_error", &self.on_error)
+            .finish_non_exhaustive()
+    }
 }
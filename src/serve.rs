@@ -0,0 +1,466 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Serving the log to other processes over TCP.
+//!
+//! [`Storage::serve_tcp`] streams every event from a requested sequence number onward to
+//! each client that connects, then keeps the connection open and tails new commits.
+//! [`connect_tcp`] is the matching client, and [`RecordStream`] decodes the same frames
+//! from any `AsyncRead` into typed events; both are also exported from [`net`](crate::net)
+//! for consumers that only need the client side. Enabled by the `net` feature.
+//!
+//! # Protocol
+//!
+//! After connecting, the client sends the sequence number to start from, as a `u64` in
+//! big-endian. The server then sends one frame per event, in sequence order:
+//!
+//! ```text
+//! [len (4, BE)][seq (8, BE)][event bytes (len - 8)]
+//! ```
+//!
+//! The event bytes are the archived event as returned by
+//! [`EventView::to_bytes`](crate::engine::EventView::to_bytes): decrypted, with blobs
+//! resolved, ready for `rkyv::access`. Frames carry no stream ID or version, so they suit
+//! consumers of the log, not replicas that rebuild it with
+//! [`Writer::append_at`](crate::engine::Writer::append_at). The connection carries no
+//! authentication or encryption of its own, so only serve trusted networks, or tunnel it.
+//! An encrypted database is only served with
+//! [`StorageConfig::serve_decrypted`](crate::storage::StorageConfig::serve_decrypted) set.
+
+use crate::engine::Reader;
+use crate::error::Result;
+use crate::storage::Storage;
+use rkyv::api::high::{HighDeserializer, HighValidator};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::Error as RancorError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+pub use futures_core::Stream;
+
+/// How many read events a connection buffers ahead of the socket.
+const SEND_BUFFER: usize = 1024;
+
+/// How many bytes a [`RecordStream`] reads from its source at a time.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// The largest frame [`TcpRecords`] and [`RecordStream`] accept by default, in bytes.
+///
+/// A frame's length comes straight from the wire, so it is capped before any memory is
+/// set aside for it. Raise it with `with_max_frame_len` to receive larger events.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+impl Storage {
+    /// Binds `addr` and serves the log to every client that connects.
+    ///
+    /// Each client receives the events from the sequence number it asks for, but never
+    /// before `start_seq`. Runs until accepting a connection fails; drop or abort the
+    /// future to stop the server. Connections are served on their own tasks, so this must
+    /// run inside a Tokio runtime. See the [module documentation](crate::serve) for the
+    /// wire format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::engine::Writer;
+    /// # use varvedb::serve::connect_tcp;
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use tempfile::tempdir;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+    /// # let storage = Storage::open(config)?;
+    /// # let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut writer = Writer::<u64>::new(storage.clone());
+    /// writer.append(1, 1, 10)?;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    /// runtime.block_on(async {
+    ///     let server = tokio::spawn({
+    ///         let storage = storage.clone();
+    ///         async move { storage.serve_tcp(addr, 1).await }
+    ///     });
+    ///     // Let the server bind before connecting.
+    ///     tokio::task::yield_now().await;
+    ///
+    ///     let mut records = connect_tcp(addr, 1).await?;
+    ///     let (seq, _bytes) = records.next().await?.unwrap();
+    ///     assert_eq!(seq, 1);
+    ///     server.abort();
+    ///     Ok::<_, varvedb::error::Error>(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if the database is
+    /// encrypted and [`serve_decrypted`](crate::storage::StorageConfig::serve_decrypted) is not
+    /// set, or an error if `addr` cannot be bound or accepting a connection fails.
+    pub async fn serve_tcp(&self, addr: impl ToSocketAddrs, start_seq: u64) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        self.serve_listener(listener, start_seq).await
+    }
+
+    /// Like [`serve_tcp`](Self::serve_tcp), but serves clients of an already bound listener.
+    ///
+    /// Useful to bind port 0 and read the chosen port from the listener first.
+    pub async fn serve_listener(&self, listener: TcpListener, start_seq: u64) -> Result<()> {
+        if self.config.encryption_enabled && !self.config.serve_decrypted {
+            return Err(crate::error::Error::InvalidConfig(
+                "refusing to serve decrypted events of an encrypted database; set serve_decrypted to allow it"
+                    .to_string(),
+            ));
+        }
+
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let storage = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve_connection(storage, socket, start_seq).await {
+                    tracing::debug!("Connection from {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+/// Streams events to one client until it disconnects.
+async fn serve_connection(storage: Storage, socket: TcpStream, start_seq: u64) -> Result<()> {
+    socket.set_nodelay(true)?;
+    let (read_half, write_half) = socket.into_split();
+    let requested = BufReader::new(read_half).read_u64().await?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(SEND_BUFFER);
+    // The payload is never validated, so any archivable type will do.
+    let reader = Reader::<Vec<u8>>::new(storage);
+    let tail = tokio::spawn(async move {
+        reader
+            .tail_into(
+                requested.max(start_seq),
+                &tx,
+                Reader::read_bytes_batch,
+                |seq, bytes| (seq, bytes),
+            )
+            .await
+    });
+
+    let mut out = BufWriter::new(write_half);
+    let result = async {
+        while let Some((seq, bytes)) = rx.recv().await {
+            write_frame(&mut out, seq, &bytes).await?;
+            // Flush once the backlog is drained, not after every frame.
+            if rx.is_empty() {
+                out.flush().await?;
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    // Closing the channel ends the tail task.
+    drop(rx);
+    match tail.await {
+        Ok(Err(e)) => Err(e),
+        _ => result,
+    }
+}
+
+async fn write_frame(
+    out: &mut BufWriter<tokio::net::tcp::OwnedWriteHalf>,
+    seq: u64,
+    bytes: &[u8],
+) -> Result<()> {
+    let len = u32::try_from(bytes.len() + 8).map_err(|_| {
+        crate::error::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "event too large for a frame",
+        ))
+    })?;
+    out.write_u32(len).await?;
+    out.write_u64(seq).await?;
+    out.write_all(bytes).await?;
+    Ok(())
+}
+
+/// Connects to a [`Storage::serve_tcp`] server and requests events from `start_seq` on.
+///
+/// # Examples
+///
+/// ```rust
+/// # use varvedb::engine::Writer;
+/// # use varvedb::serve::connect_tcp;
+/// # use varvedb::storage::{Storage, StorageConfig};
+/// # use tempfile::tempdir;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+/// # let storage = Storage::open(config)?;
+/// # let mut writer = Writer::<u64>::new(storage.clone());
+/// # writer.append(1, 1, 10)?;
+/// # writer.append(1, 2, 20)?;
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+/// runtime.block_on(async {
+///     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+///     let addr = listener.local_addr()?;
+///     let server = tokio::spawn(async move { storage.serve_listener(listener, 1).await });
+///
+///     let mut records = connect_tcp(addr, 2).await?;
+///     let (seq, bytes) = records.next().await?.unwrap();
+///     assert_eq!(seq, 2);
+///     assert!(!bytes.is_empty());
+///     server.abort();
+///     Ok::<_, varvedb::error::Error>(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if connecting or sending the request fails.
+pub async fn connect_tcp(addr: impl ToSocketAddrs, start_seq: u64) -> Result<TcpRecords> {
+    let mut socket = TcpStream::connect(addr).await?;
+    socket.set_nodelay(true)?;
+    socket.write_u64(start_seq).await?;
+    Ok(TcpRecords {
+        socket: BufReader::new(socket),
+        max_frame_len: DEFAULT_MAX_FRAME_LEN,
+    })
+}
+
+/// Records received from a server, returned by [`connect_tcp`].
+#[derive(Debug)]
+pub struct TcpRecords {
+    socket: BufReader<TcpStream>,
+    max_frame_len: usize,
+}
+
+impl TcpRecords {
+    /// Sets the largest frame accepted, in bytes (length prefix excluded). Defaults to
+    /// [`DEFAULT_MAX_FRAME_LEN`].
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Waits for the next record and returns it as `(seq, event bytes)`.
+    ///
+    /// Returns `Ok(None)` once the server closes the connection between records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or a frame is malformed, larger than the
+    /// [maximum frame length](Self::with_max_frame_len) or cut short, including inside its
+    /// length prefix (as [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof)).
+    pub async fn next(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
+        // End-of-file is only a clean close before the first byte of a frame.
+        let mut header = [0u8; 4];
+        if self.socket.read(&mut header[..1]).await? == 0 {
+            return Ok(None);
+        }
+        self.socket.read_exact(&mut header[1..]).await?;
+        let len = u32::from_be_bytes(header) as usize;
+        check_frame_len(len, self.max_frame_len)?;
+
+        let seq = self.socket.read_u64().await?;
+        let mut bytes = vec![0u8; len - 8];
+        self.socket.read_exact(&mut bytes).await?;
+        Ok(Some((seq, bytes)))
+    }
+
+    /// Turns the connection into a [`RecordStream`] of typed events.
+    ///
+    /// Bytes already buffered from the socket are kept, so no record is lost.
+    pub fn into_record_stream<E>(self) -> RecordStream<E, BufReader<TcpStream>> {
+        RecordStream::new(self.socket).with_max_frame_len(self.max_frame_len)
+    }
+}
+
+fn invalid_frame(message: String) -> crate::error::Error {
+    crate::error::Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+/// Fails unless a frame of `len` bytes holds a sequence number and fits in `max`.
+fn check_frame_len(len: usize, max: usize) -> Result<()> {
+    if len < 8 {
+        return Err(invalid_frame(format!(
+            "frame of {} bytes is too short",
+            len
+        )));
+    }
+    if len > max {
+        return Err(invalid_frame(format!(
+            "frame of {} bytes exceeds the limit of {} bytes",
+            len, max
+        )));
+    }
+    Ok(())
+}
+
+/// Decodes served frames from an `AsyncRead` into `(seq, event)` items.
+///
+/// Reads the [frames](crate::serve) a server sends, validates each event and deserializes
+/// it into an `E`, yielding the items as a [`Stream`]. It only needs
+/// the byte stream, so a consumer can read events from a socket, a pipe or a file of
+/// captured frames without opening a database. The stream ends when the source reaches
+/// end-of-file between frames; end-of-file inside a frame, a malformed frame, one larger
+/// than the [maximum frame length](Self::with_max_frame_len) or an event that fails
+/// validation is yielded as an error, after which the stream should be dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// # use varvedb::engine::Writer;
+/// # use varvedb::net::{connect_tcp, RecordStream, Stream};
+/// # use varvedb::storage::{Storage, StorageConfig};
+/// # use tempfile::tempdir;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// # let config = StorageConfig { path: dir.path().to_path_buf(), ..Default::default() };
+/// # let storage = Storage::open(config)?;
+/// let mut writer = Writer::<u64>::new(storage.clone());
+/// writer.append(1, 1, 10)?;
+/// writer.append(1, 2, 20)?;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+/// runtime.block_on(async {
+///     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+///     let addr = listener.local_addr()?;
+///     let server = tokio::spawn(async move { storage.serve_listener(listener, 1).await });
+///
+///     let records = connect_tcp(addr, 1).await?;
+///     let mut events: RecordStream<u64> = records.into_record_stream();
+///     let mut received = Vec::new();
+///     while received.len() < 2 {
+///         let next = std::future::poll_fn(|cx| std::pin::Pin::new(&mut events).poll_next(cx));
+///         received.push(next.await.unwrap()?);
+///     }
+///     assert_eq!(received, [(1, 10), (2, 20)]);
+///     server.abort();
+///     Ok::<_, varvedb::error::Error>(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The source defaults to the buffered socket of [`TcpRecords::into_record_stream`]; any
+/// other `AsyncRead` works through [`new`](Self::new).
+#[derive(Debug)]
+pub struct RecordStream<E, R = BufReader<TcpStream>> {
+    source: R,
+    buffer: bytes::BytesMut,
+    max_frame_len: usize,
+    _marker: std::marker::PhantomData<fn() -> E>,
+}
+
+impl<E, R> RecordStream<E, R> {
+    /// Creates a stream decoding the frames read from `source`.
+    ///
+    /// A [`TcpStream`] must have already sent its start sequence, as
+    /// [`connect_tcp`] does; see [`TcpRecords::into_record_stream`].
+    pub fn new(source: R) -> Self {
+        Self {
+            source,
+            buffer: bytes::BytesMut::new(),
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Sets the largest frame accepted, in bytes (length prefix excluded). Defaults to
+    /// [`DEFAULT_MAX_FRAME_LEN`].
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Returns the underlying source. Bytes read from it but not yet decoded are lost.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    /// Splits the next complete frame off the buffer, as `(seq, event bytes)`.
+    fn next_frame(&mut self) -> Result<Option<(u64, bytes::BytesMut)>> {
+        let Some(header) = self.buffer.get(..4) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+        check_frame_len(len, self.max_frame_len)?;
+        if self.buffer.len() < 4 + len {
+            self.buffer.reserve(4 + len - self.buffer.len());
+            return Ok(None);
+        }
+
+        let mut frame = self.buffer.split_to(4 + len).split_off(4);
+        let bytes = frame.split_off(8);
+        let seq = u64::from_be_bytes(frame[..].try_into().unwrap());
+        Ok(Some((seq, bytes)))
+    }
+}
+
+impl<E, R> RecordStream<E, R>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>
+        + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+{
+    /// Validates and deserializes the event bytes of one frame.
+    fn decode(seq: u64, bytes: &[u8]) -> Result<(u64, E)> {
+        // The frame may sit anywhere in the buffer, but rkyv needs aligned bytes.
+        let mut aligned = rkyv::util::AlignedVec::<16>::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+        let event = rkyv::from_bytes::<E, RancorError>(&aligned)?;
+        Ok((seq, event))
+    }
+}
+
+impl<E, R> Stream for RecordStream<E, R>
+where
+    E: rkyv::Archive,
+    E::Archived: for<'a> CheckBytes<HighValidator<'a, RancorError>>
+        + rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+    R: AsyncRead + Unpin,
+{
+    type Item = Result<(u64, E)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match this.next_frame() {
+                Ok(Some((seq, bytes))) => return Poll::Ready(Some(Self::decode(seq, &bytes))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            let mut chunk = [0u8; READ_CHUNK];
+            let mut read = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.source).poll_read(cx, &mut read) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(Ok(())) if read.filled().is_empty() => {
+                    if this.buffer.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    let cut = std::mem::take(&mut this.buffer);
+                    return Poll::Ready(Some(Err(invalid_frame(format!(
+                        "stream ended inside a frame, {} bytes in",
+                        cut.len()
+                    )))));
+                }
+                Poll::Ready(Ok(())) => this.buffer.extend_from_slice(read.filled()),
+            }
+        }
+    }
+}
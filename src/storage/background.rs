@@ -0,0 +1,52 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A periodic maintenance thread owned by a [`Storage`](super::Storage).
+///
+/// The thread runs `tick` every `interval` until the worker is dropped. Dropping the worker
+/// closes the shutdown channel, wakes the thread immediately, and joins it.
+#[derive(Debug)]
+pub(crate) struct BackgroundWorker {
+    shutdown: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BackgroundWorker {
+    pub(crate) fn spawn<F>(name: &str, interval: Duration, mut tick: F) -> std::io::Result<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (shutdown, rx) = mpsc::channel::<()>();
+        let handle = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                // Any other outcome means an explicit stop or that the sender was dropped.
+                while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                    tick();
+                }
+            })?;
+
+        Ok(Self {
+            shutdown: Some(shutdown),
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for BackgroundWorker {
+    fn drop(&mut self) {
+        drop(self.shutdown.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
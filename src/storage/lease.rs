@@ -0,0 +1,125 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use super::MetaDb;
+use crate::error::{Error, Result};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Key of the writer lease record in the `meta` database.
+///
+/// The value is `[holder (16, BE)][expires at, ms since the Unix epoch (8, BE)]`.
+const LEASE_KEY: &str = "writer_lease";
+
+/// This handle's side of the writer lease, shared by all clones of a `Storage`.
+#[derive(Debug)]
+pub(super) struct LeaseState {
+    /// Identifies this handle in the lease record.
+    holder: u128,
+    /// The TTL of the lease acquired through this handle, if any.
+    ttl: Mutex<Option<Duration>>,
+}
+
+impl LeaseState {
+    pub(super) fn new() -> Self {
+        Self {
+            holder: uuid::Uuid::new_v4().as_u128(),
+            ttl: Mutex::new(None),
+        }
+    }
+
+    fn ttl(&self) -> Option<Duration> {
+        *self.ttl.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Takes the lease if it is free, expired or already ours.
+    pub(super) fn acquire(
+        &self,
+        meta: &MetaDb,
+        txn: &mut heed::RwTxn,
+        ttl: Duration,
+    ) -> Result<()> {
+        if ttl.is_zero() {
+            return Err(Error::InvalidConfig(
+                "lease ttl must be greater than 0".to_string(),
+            ));
+        }
+
+        let now = now_millis();
+        if let Some((holder, expires_at)) = read(meta, txn)? {
+            if holder != self.holder && expires_at > now {
+                return Err(Error::LeaseHeld {
+                    expires_in: Duration::from_millis(expires_at - now),
+                });
+            }
+        }
+
+        write(meta, txn, self.holder, now.saturating_add(millis(ttl)))?;
+        *self.ttl.lock().unwrap_or_else(|e| e.into_inner()) = Some(ttl);
+        Ok(())
+    }
+
+    /// Extends the lease by its TTL, as long as nobody else has taken it over.
+    pub(super) fn renew(&self, meta: &MetaDb, txn: &mut heed::RwTxn) -> Result<()> {
+        let Some(ttl) = self.ttl() else {
+            return Err(Error::LeaseLost);
+        };
+        match read(meta, txn)? {
+            Some((holder, _)) if holder == self.holder => write(
+                meta,
+                txn,
+                self.holder,
+                now_millis().saturating_add(millis(ttl)),
+            ),
+            _ => Err(Error::LeaseLost),
+        }
+    }
+
+    /// Fails unless this handle holds an unexpired lease. A no-op if no lease was acquired.
+    pub(super) fn check(&self, meta: &MetaDb, txn: &heed::RoTxn) -> Result<()> {
+        if self.ttl().is_none() {
+            return Ok(());
+        }
+        match read(meta, txn)? {
+            Some((holder, expires_at)) if holder == self.holder && expires_at > now_millis() => {
+                Ok(())
+            }
+            _ => Err(Error::LeaseLost),
+        }
+    }
+}
+
+fn read(meta: &MetaDb, txn: &heed::RoTxn) -> Result<Option<(u128, u64)>> {
+    let Some(bytes) = meta.get(txn, LEASE_KEY)? else {
+        return Ok(None);
+    };
+    let record: &[u8; 24] = bytes
+        .try_into()
+        .map_err(|_| Error::InvalidConfig("malformed writer lease record".to_string()))?;
+    let holder = u128::from_be_bytes(record[..16].try_into().unwrap());
+    let expires_at = u64::from_be_bytes(record[16..].try_into().unwrap());
+    Ok(Some((holder, expires_at)))
+}
+
+fn write(meta: &MetaDb, txn: &mut heed::RwTxn, holder: u128, expires_at: u64) -> Result<()> {
+    let mut record = [0u8; 24];
+    record[..16].copy_from_slice(&holder.to_be_bytes());
+    record[16..].copy_from_slice(&expires_at.to_be_bytes());
+    Ok(meta.put(txn, LEASE_KEY, &record)?)
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(millis)
+        .unwrap_or(0)
+}
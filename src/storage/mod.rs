@@ -6,9 +6,19 @@
 // v. 2.0. If a copy of the MPL was not distributed with this file, You can
 // obtain one at http://mozilla.org/MPL/2.0/.
 
+pub(crate) mod background;
+mod lease;
+mod writer_lock;
+
 use crate::error::Result;
+use background::BackgroundWorker;
 use heed::{types::*, Database, Env, EnvOpenOptions};
+use lease::LeaseState;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use writer_lock::WriterLock;
 
 // Type Aliases for readability
 pub type EventLogDb = Database<U64<heed::byteorder::BE>, Bytes>;
@@ -16,6 +26,160 @@ pub type StreamIndexDb = Database<Bytes, U64<heed::byteorder::BE>>;
 pub type ConsumerCursorDb = Database<U64<heed::byteorder::BE>, U64<heed::byteorder::BE>>;
 pub type KeyStoreDb = Database<U128<heed::byteorder::BE>, Bytes>; // StreamID -> Key (32 bytes)
 pub type BlobDb = Database<Bytes, Bytes>; // Hash (32 bytes) -> Data (Variable)
+pub type SecondaryIndexDb = Database<Bytes, U64<heed::byteorder::BE>>; // Key -> Seq (DUP_SORT)
+pub type MetaDb = Database<Str, Bytes>; // Setting Name -> Value
+pub type ClusteredLogDb = Database<Bytes, Bytes>; // StreamID+Ver -> Event Bytes
+pub type StreamTypeDb = Database<U128<heed::byteorder::BE>, Str>; // StreamID -> Event Type Tag
+pub type GroupClaimDb = Database<Bytes, Bytes>; // GroupID+Kind(+Seq) -> Cursor or Claim
+pub type EventMetadataDb = Database<U64<heed::byteorder::BE>, Bytes>; // Seq -> Metadata Bytes
+pub type ReservationDb = Database<Bytes, Bytes>; // StreamID+First Ver -> End Ver + Expiry
+pub type OutboxDb = Database<U64<heed::byteorder::BE>, Bytes>; // Message ID -> Outbox Message
+pub type LastHashDb = Database<U128<heed::byteorder::BE>, Bytes>; // StreamID -> Version + Body Hash
+pub type EventIdDb = Database<U128<heed::byteorder::BE>, U64<heed::byteorder::BE>>; // Event ID -> Seq
+pub type LamportDb = Database<U64<heed::byteorder::BE>, U64<heed::byteorder::BE>>; // Seq -> Lamport Timestamp
+pub type SeqIdDb = Database<U64<heed::byteorder::BE>, U128<heed::byteorder::BE>>; // Seq -> Event ID
+pub type BlobRefDb = Database<Bytes, U64<heed::byteorder::BE>>; // Hash (32 bytes) -> Reference Count
+pub type TrimmedDb = Database<U64<heed::byteorder::BE>, U64<heed::byteorder::BE>>; // First Seq -> Last Seq
+
+/// Key of the on-disk format version in the `meta` database.
+const FORMAT_VERSION_KEY: &str = "format_version";
+
+/// Named databases created by every VarveDB environment.
+const INTERNAL_DATABASES: [&str; 6] = [
+    "events_log",
+    "stream_index",
+    "consumer_cursors",
+    "keystore",
+    "blobs",
+    "meta",
+];
+
+/// Number of named databases every VarveDB environment needs, before optional ones.
+///
+/// Databases backing a single feature (see [`LazyDb`]) are only created once the feature is
+/// used, and are not counted. See [`StorageConfig::required_dbs`] for the total a
+/// configuration needs.
+pub const REQUIRED_DBS: u32 = INTERNAL_DATABASES.len() as u32;
+
+/// Key of the storage layout marker in the `meta` database.
+const LAYOUT_KEY: &str = "layout";
+
+/// Key of the `obscure_stream_ids` marker in the `meta` database.
+const OBSCURE_STREAM_IDS_KEY: &str = "obscure_stream_ids";
+
+/// Name of the database holding records in the clustered layout.
+const CLUSTERED_LOG: &str = "events_by_stream";
+
+/// Key of the `separate_blob_env` marker in the `meta` database.
+const SEPARATE_BLOB_ENV_KEY: &str = "separate_blob_env";
+
+/// Key of the master key check value in the `meta` database.
+///
+/// This is the check value of master key version 0; later versions store theirs under this
+/// key followed by `_` and the version.
+const MASTER_KEY_CHECK_KEY: &str = "master_key_check";
+
+/// Plaintext of the master key check value, encrypted under the master key.
+const MASTER_KEY_CHECK_PLAINTEXT: &[u8] = b"varvedb master key check";
+
+/// Subdirectory of the database directory holding the separate blob environment.
+const BLOB_ENV_DIR: &str = "blobs";
+
+/// Key of the log key marker in the `meta` database.
+const LOG_KEY_KEY: &str = "log_key";
+
+/// Key of the `encrypted_streams` marker in the `meta` database.
+const ENCRYPTED_STREAMS_KEY: &str = "encrypted_streams";
+
+/// Key of the current Lamport clock in the `meta` database.
+const LAMPORT_CLOCK_KEY: &str = "lamport_clock";
+
+/// Key of the durable sequence watermark in the `meta` database.
+const DURABLE_SEQUENCE_KEY: &str = "durable_sequence";
+
+/// Name of the database holding the Lamport timestamp of each event.
+const LAMPORT_LOG: &str = "lamport_clocks";
+
+/// Name of the database mapping event IDs to sequence numbers under [`LogKey::U128`].
+const EVENT_ID_LOG: &str = "events_by_id";
+
+/// Name of the database mapping sequence numbers back to event IDs under [`LogKey::U128`].
+const EVENT_ID_BY_SEQ: &str = "ids_by_seq";
+
+/// Name of the optional database holding the body hash of each stream's last event.
+const STREAM_LAST_HASH: &str = "stream_last_hash";
+
+/// Name of the optional database recording the event type of each stream.
+const STREAM_TYPE_REGISTRY: &str = "stream_type_registry";
+
+/// Prefix applied to secondary index names to keep them apart from internal databases.
+const SECONDARY_INDEX_PREFIX: &str = "index:";
+
+/// A named database that is only created once a feature first writes to it.
+///
+/// `Storage::open` doesn't create the databases of features such as the outbox or consumer
+/// groups, so environments sized with a `max_dbs` for the databases they use keep opening.
+/// Like secondary indexes, the database is opened per transaction: [`open`](Self::open)
+/// returns `None` until some write has [`create`](Self::create)d it.
+pub struct LazyDb<KC, DC> {
+    env: Env,
+    name: &'static str,
+    _types: std::marker::PhantomData<fn() -> (KC, DC)>,
+}
+
+impl<KC: 'static, DC: 'static> LazyDb<KC, DC> {
+    fn new(env: Env, name: &'static str) -> Self {
+        Self {
+            env,
+            name,
+            _types: std::marker::PhantomData,
+        }
+    }
+
+    /// Opens the database in `txn`, or returns `None` if it doesn't exist yet.
+    pub fn open(&self, txn: &heed::RoTxn) -> Result<Option<Database<KC, DC>>> {
+        self.env
+            .open_database(txn, Some(self.name))
+            .map_err(|e| self.map_err(e))
+    }
+
+    /// Opens the database in `txn`, creating it if it doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidConfig`](crate::error::Error::InvalidConfig) if creating it would
+    /// exceed [`StorageConfig::max_dbs`].
+    pub fn create(&self, txn: &mut heed::RwTxn) -> Result<Database<KC, DC>> {
+        self.env
+            .create_database(txn, Some(self.name))
+            .map_err(|e| self.map_err(e))
+    }
+
+    fn map_err(&self, e: heed::Error) -> crate::error::Error {
+        match e {
+            heed::Error::Mdb(heed::MdbError::DbsFull) => crate::error::Error::InvalidConfig(
+                format!("max_dbs is too low to open the {} database", self.name),
+            ),
+            e => e.into(),
+        }
+    }
+}
+
+impl<KC, DC> Clone for LazyDb<KC, DC> {
+    fn clone(&self) -> Self {
+        Self {
+            env: self.env.clone(),
+            name: self.name,
+            _types: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<KC, DC> std::fmt::Debug for LazyDb<KC, DC> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyDb").field("name", &self.name).finish()
+    }
+}
 
 pub struct StreamKey {
     pub stream_id: u128,
@@ -35,6 +199,128 @@ impl StreamKey {
     }
 }
 
+/// Durability level for committed transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SyncMode {
+    /// Flush data and metadata on every commit. A committed transaction survives a crash.
+    #[default]
+    Full,
+    /// Skip the metadata flush on commit (`MDB_NOMETASYNC`). A system crash may undo the
+    /// last committed transaction, but the database stays consistent.
+    NoMetaSync,
+    /// Do not flush on commit (`MDB_NOSYNC`). Much faster writes, but a system crash may
+    /// lose every transaction since the last sync. Pair with
+    /// [`StorageConfig::flush_interval`] to bound the loss.
+    NoSync,
+}
+
+impl SyncMode {
+    fn env_flags(self) -> heed::EnvFlags {
+        match self {
+            SyncMode::Full => heed::EnvFlags::empty(),
+            SyncMode::NoMetaSync => heed::EnvFlags::NO_META_SYNC,
+            SyncMode::NoSync => heed::EnvFlags::NO_SYNC,
+        }
+    }
+}
+
+/// Physical arrangement of event records, fixed when a database is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageLayout {
+    /// Records are keyed by global sequence number in `events_log`. Appends and global
+    /// replays are sequential, but a single stream's events are scattered across the log.
+    #[default]
+    Sequential,
+    /// Records are keyed by `(stream_id, version)` in a separate database, so each stream's
+    /// events are physically contiguous; `events_log` then maps each global sequence number to
+    /// its stream key. Speeds up replaying individual streams at the cost of one extra lookup
+    /// for reads by global sequence.
+    Clustered,
+}
+
+impl StorageLayout {
+    fn marker(self) -> u8 {
+        match self {
+            StorageLayout::Sequential => 0,
+            StorageLayout::Clustered => 1,
+        }
+    }
+}
+
+/// The key events are ordered by, fixed when a database is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogKey {
+    /// Events are only keyed by their `u64` global sequence number.
+    #[default]
+    Sequence,
+    /// Every event also gets a 128-bit ID, strictly increasing in log order.
+    ///
+    /// IDs are kept in the `events_by_id` database next to the log, so sequence numbers
+    /// keep working everywhere and the IDs give the same order. By default a writer assigns
+    /// time-sortable IDs like a ULID: the commit time in milliseconds in the top 48 bits and
+    /// a counter below (see [`LogKey::first_id_at`]), so time-range queries can seek by ID
+    /// without reading event timestamps. [`Writer::append_with_id`] supplies your own, e.g.
+    /// ULIDs generated upstream.
+    ///
+    /// [`Writer::append_with_id`]: crate::engine::Writer::append_with_id
+    U128,
+}
+
+impl LogKey {
+    fn marker(self) -> u8 {
+        match self {
+            LogKey::Sequence => 0,
+            LogKey::U128 => 1,
+        }
+    }
+
+    /// Returns the smallest writer-assigned event ID for an event committed at `millis`
+    /// (milliseconds since the Unix epoch).
+    ///
+    /// Writer-assigned IDs of events committed at or after `millis` are at least this, so
+    /// `first_id_at(from)..first_id_at(to)` selects the events committed in `[from, to)`.
+    pub fn first_id_at(millis: u64) -> u128 {
+        // ULIDs hold a 48-bit timestamp.
+        u128::from(millis & ((1 << 48) - 1)) << 80
+    }
+}
+
+/// How reads treat an event whose large-payload blob is missing from the `blobs` database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MissingBlob {
+    /// Fail the read with [`Error::EventValidation`](crate::error::Error::EventValidation).
+    #[default]
+    Error,
+    /// Report the event as present without a body, so scans over a damaged store can go on.
+    ///
+    /// `Reader::get_or_skip` returns `ReadOutcome::MissingBody` for such events, and other
+    /// reads fail with the dedicated [`Error::BlobMissing`](crate::error::Error::BlobMissing),
+    /// which `Reader::iter_lossy` yields per sequence without stopping.
+    ReturnPlaceholder,
+}
+
+/// Decides which events are stored inline in the log and which go to the `blobs` database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InlineThreshold {
+    /// Events whose archived size exceeds this many bytes are stored as blobs.
+    Fixed(usize),
+    /// Derive the threshold from the LMDB page size: the largest event whose record still
+    /// fits in a leaf page instead of spilling into overflow pages. See
+    /// [`Storage::max_inline_size`].
+    PageSize,
+}
+
+impl Default for InlineThreshold {
+    fn default() -> Self {
+        Self::Fixed(crate::constants::MAX_INLINE_SIZE)
+    }
+}
+
 /// Configuration for opening a VarveDB storage environment.
 ///
 /// This struct controls the physical layout and behavior of the underlying LMDB environment.
@@ -56,24 +342,362 @@ pub struct StorageConfig {
 
     /// The maximum number of named databases.
     ///
-    /// VarveDB uses a fixed number of internal databases (currently 4), but this can be
-    /// increased if custom buckets are needed in the future.
+    /// VarveDB uses a fixed number of internal databases ([`REQUIRED_DBS`], currently 6),
+    /// plus one each for `enforce_stream_types`, `reject_consecutive_duplicates`,
+    /// `lamport_clock` and the clustered [`StorageLayout`], and two for [`LogKey::U128`],
+    /// when enabled; [`required_dbs`](Self::required_dbs) adds these up, and opening fails
+    /// with [`Error::InvalidConfig`](crate::error::Error::InvalidConfig) below it. Leave room
+    /// for one more per secondary index registered with `Writer::with_index`, and for up to
+    /// six more that features create when first used: event metadata, consumer groups,
+    /// version reservations, the outbox, blob reference counts and trimmed ranges.
+    /// Defaults to 24.
     pub max_dbs: u32,
 
+    /// The maximum number of concurrent read transactions (reader slots).
+    ///
+    /// Every live `RoTxn` occupies one slot in LMDB's reader lock table. When all slots are
+    /// taken, opening a new read transaction fails with [`Error::ReadersFull`](crate::error::Error::ReadersFull).
+    /// Increase this value for workloads with many concurrent reader threads.
+    /// The default is 126, matching LMDB's built-in default.
+    pub max_readers: u32,
+
+    /// Interval at which stale reader slots are reclaimed automatically.
+    ///
+    /// Processes that crash while holding a read transaction leave dead entries in LMDB's
+    /// reader lock table, pinning old snapshots and preventing page reuse. When set,
+    /// `Storage::open` spawns a background thread that calls
+    /// [`Storage::clear_stale_readers`] on this interval. The thread stops when the last
+    /// `Storage` clone is dropped. Defaults to `None` (no automatic cleanup).
+    pub auto_reader_cleanup: Option<Duration>,
+
+    /// Controls when LMDB flushes committed transactions to disk.
+    ///
+    /// See [`SyncMode`] for the durability trade-offs of each mode.
+    pub sync_mode: SyncMode,
+
+    /// Interval at which the environment is flushed to disk in the background.
+    ///
+    /// With a relaxed [`SyncMode`], committed transactions are not durable until the OS
+    /// writes the pages back. When set, `Storage::open` spawns a thread that calls
+    /// `force_sync` on this interval, bounding data loss on a crash to roughly one interval.
+    /// The thread stops when the last `Storage` clone is dropped. Defaults to `None`.
+    pub flush_interval: Option<Duration>,
+
+    /// Coalesces new-event notifications to at most one per interval.
+    ///
+    /// By default every commit updates [`Storage::notifier`], waking every subscriber once
+    /// per append. When set, commits only record the new head and a background thread
+    /// publishes it on this interval if it moved, so subscribers wake at most once per
+    /// interval and read everything committed since in one go. The published head still
+    /// catches up with the true head within one interval, so no event is missed, at the
+    /// cost of up to one interval of added latency. The thread stops when the last
+    /// `Storage` clone is dropped. Defaults to `None` (notify on every commit).
+    pub notify_coalesce: Option<Duration>,
+
+    /// Locks the memory map into RAM (`mlock`) so database pages stay resident.
+    ///
+    /// This removes page-fault latency spikes on reads, at the cost of pinning the data file
+    /// in physical memory. Only the pages that exist when the storage is opened are locked;
+    /// call [`Storage::lock_memory`] again after significant growth to lock new pages.
+    ///
+    /// The process needs a sufficient `RLIMIT_MEMLOCK` (see `ulimit -l`) or the
+    /// `CAP_IPC_LOCK` capability. If locking fails, `Storage::open` returns
+    /// [`Error::MemoryLock`](crate::error::Error::MemoryLock) instead of continuing unlocked.
+    /// Only supported on Linux. Defaults to `false`.
+    pub lock_memory: bool,
+
+    /// Faults the events log into memory when the storage is opened.
+    ///
+    /// The first read of a page after opening takes a page fault, and a disk read if the
+    /// page is not cached. When set, `Storage::open` calls [`Storage::warm`] over
+    /// [`warm_window`](Self::warm_window), so reads right after startup run at steady-state
+    /// latency, at the cost of a slower open. Unlike [`lock_memory`](Self::lock_memory),
+    /// the pages may be evicted again later. Defaults to `false`.
+    pub warm_on_open: bool,
+
+    /// How many of the most recent records `warm_on_open` touches; `None` warms the whole
+    /// log. Defaults to `None`.
+    pub warm_window: Option<u64>,
+
+    /// Verifies record checksums on every read.
+    ///
+    /// rkyv validation only rejects structurally invalid archives; a bit flip inside a field
+    /// can still produce a valid-looking event with wrong data. When enabled, the reader checks
+    /// the CRC32C stored with inline events and the SHA-256 content hash of blobs, returning
+    /// [`Error::EventValidation`](crate::error::Error::EventValidation) on mismatch.
+    /// Events written by versions without inline checksums are not verified.
+    /// Defaults to `false`.
+    pub verify_checksums: bool,
+
+    /// Size above which events are stored in the `blobs` database instead of inline.
+    ///
+    /// LMDB moves values that don't fit in a leaf page to dedicated overflow pages, so
+    /// inlining events slightly below that limit is cheapest. Defaults to
+    /// `InlineThreshold::Fixed(MAX_INLINE_SIZE)`; [`InlineThreshold::PageSize`] adapts the
+    /// threshold to the page size of the platform.
+    pub inline_threshold: InlineThreshold,
+
+    /// Stores every event inline in the log, whatever its size, and ignores
+    /// `inline_threshold`.
+    ///
+    /// Keeps reads zero-copy and avoids the extra `blobs` lookup, at a cost in space: LMDB
+    /// stores each large value in its own run of overflow pages, rounded up to whole pages,
+    /// and identical payloads are no longer deduplicated by hash. Size `map_size` for the
+    /// full payloads. Events written as blobs before this was enabled are still read from
+    /// the `blobs` database. Defaults to `false`.
+    pub disable_blob_offload: bool,
+
+    /// How reads handle events whose blob is missing, e.g. after partial corruption.
+    /// Defaults to [`MissingBlob::Error`].
+    pub on_missing_blob: MissingBlob,
+
+    /// Caps the serialized size of a single event, in bytes.
+    ///
+    /// Events whose archive exceeds the cap are rejected with
+    /// [`Error::EventTooLarge`](crate::error::Error::EventTooLarge) before the record is
+    /// hashed, encrypted or written, and the writer's serialization arena is trimmed again.
+    /// `Writer::append_at` applies the cap to the bytes it is given. Set this in stores that
+    /// accept externally sourced events, so one oversized event cannot fill the map.
+    ///
+    /// The check runs once rkyv has finished serializing the event: it bounds what gets
+    /// copied and stored, not the serializer's own output buffer. Defaults to `None`
+    /// (no limit).
+    pub max_serialize_bytes: Option<usize>,
+
+    /// Caps how many versions a single stream may hold.
+    ///
+    /// Appends (and [`Writer::reserve_versions`](crate::engine::Writer::reserve_versions))
+    /// that would put a stream past version `max` fail with
+    /// [`Error::StreamFull`](crate::error::Error::StreamFull), so an aggregate that never
+    /// ends, such as a chat room, is noticed before its replay becomes slow; start a new
+    /// stream from a snapshot instead. Versions start at 1, so without gaps this is the
+    /// number of events. Defaults to `None` (no limit).
+    pub max_stream_versions: Option<u32>,
+
+    /// Rejects an append whose serialized event is identical to the previous event of its
+    /// stream.
+    ///
+    /// Catches the "submitted twice" bug, where a retried request appends the same event
+    /// again under the next version. Appends keep a SHA-256 of each stream's last event in
+    /// the `stream_last_hash` database, and an event whose hash matches the one at the
+    /// version right before it fails with
+    /// [`Error::DuplicateEvent`](crate::error::Error::DuplicateEvent). Only consecutive
+    /// duplicates are caught: an event equal to an older one, or to the previous one when
+    /// that was appended while this was off, is accepted. `Writer::append_at` and raw
+    /// appends are not checked. Uses one additional named database. Defaults to `false`.
+    pub reject_consecutive_duplicates: bool,
+
+    /// Stamps every appended event with a Lamport timestamp.
+    ///
+    /// The store keeps one logical clock in the `meta` database, advanced by one within
+    /// each append transaction, and the value each event got is stored in the
+    /// `lamport_clocks` database and returned by `Reader::get_envelope`. Unlike the global
+    /// sequence number, the clock can take part in happens-before reasoning across stores:
+    /// pass timestamps received from elsewhere to `Writer::observe_clock`, and every event
+    /// appended afterwards is stamped later than them. Events appended while this was off
+    /// have no timestamp. Uses one additional named database. Defaults to `false`.
+    pub lamport_clock: bool,
+
+    /// Fraction of the memory map (between 0 and 1) above which writers warn that the map
+    /// is filling up.
+    ///
+    /// When set, every `Writer` commit checks the map usage, and the first commit that finds
+    /// it at or above the threshold logs a warning and increments the
+    /// `varvedb_map_full_warnings_total` metric. The warning fires again after usage has
+    /// dropped below the threshold and crossed it anew. Use it to grow `map_size` before
+    /// appends start failing with `MDB_MAP_FULL`; see [`Storage::free_space_bytes`].
+    /// Defaults to `None` (no check).
+    pub map_full_warn_threshold: Option<f64>,
+
+    /// Free map space, in bytes, below which `Writer::try_append` stops writing.
+    ///
+    /// `try_append` returns `Ok(None)` instead of appending once
+    /// [`Storage::free_space_bytes`] drops below this, so producers that can slow down back
+    /// off before appends start failing with `MDB_MAP_FULL`. Set it above the largest
+    /// append plus LMDB's copy-on-write overhead (a few pages per transaction). Other
+    /// appends ignore it. Defaults to 0 (never back off).
+    pub append_reserve_bytes: u64,
+
+    /// Restricts every stream to a single event type.
+    ///
+    /// When enabled, the type tag of the first event written to a stream is recorded in a
+    /// `stream_type_registry` database, and later appends carrying a different tag fail with
+    /// [`Error::InvalidConfig`](crate::error::Error::InvalidConfig). The tag is set per
+    /// writer, see `Writer::with_stream_type`. This uses one additional named database.
+    /// Defaults to `false`.
+    pub enforce_stream_types: bool,
+
+    /// Ties LMDB reader slots to threads (thread-local storage) instead of transactions.
+    ///
+    /// With the default (`true`), each thread can hold at most one read transaction at a
+    /// time, and a slot stays reserved for a thread until it exits. Set to `false` to open
+    /// the environment with `MDB_NOTLS`: slots belong to transactions, so one thread can keep
+    /// several read transactions alive (e.g. a [`SnapshotReader`](crate::engine::SnapshotReader)
+    /// next to a `Varve::read_txn`).
+    ///
+    /// Moving a `RoTxn` to another thread additionally requires it to be `Send`, which is a
+    /// compile-time property: enable this crate's `read-txn-no-tls` feature. That feature
+    /// always opens environments without TLS, so this option is ignored when it is enabled.
+    pub reader_tls: bool,
+
+    /// Hides stream IDs from the on-disk keys.
+    ///
+    /// With encryption enabled, event payloads are unreadable, but stream IDs and versions
+    /// still appear in plaintext in the stream index, keystore and record headers, letting
+    /// anyone with file access enumerate streams and their activity. When set, every on-disk
+    /// occurrence of a stream ID is replaced by a keyed HMAC-SHA256 of it (truncated to 128
+    /// bits) derived from `master_key`; see [`Storage::stored_stream_id`].
+    ///
+    /// The mapping is deterministic, so lookups and prefix scans by stream work unchanged, but
+    /// the original ID cannot be recovered from the file: tools that list streams (e.g.
+    /// `Varve::stream_count`) only see opaque IDs. Versions, global sequence numbers and
+    /// consumer cursors remain visible. Requires `encryption_enabled`. Like the layout, this
+    /// is fixed when the database is created. Defaults to `false`.
+    pub obscure_stream_ids: bool,
+
+    /// Stores blobs (event payloads above the inline limit) in a second LMDB environment.
+    ///
+    /// Large blobs in the main environment inflate its map and scatter the log's B-tree
+    /// pages between them. When set, the `blobs` database lives in its own environment in
+    /// the `blobs` subdirectory, opened with the same map size and sync mode, so blob I/O
+    /// never touches log pages.
+    ///
+    /// The two environments cannot share a transaction. A writer commits each blob before
+    /// the log entry that references it, so a crash can leave an unreferenced blob behind but
+    /// never a reference to a missing one. Blob reads open a short read transaction on the
+    /// blob environment. Like the layout, this is fixed when the database is created.
+    /// Defaults to `false`.
+    pub separate_blob_env: bool,
+
+    /// The record layout used when creating a new database.
+    ///
+    /// The layout is recorded in the database on creation and cannot be changed afterwards;
+    /// opening an existing database with a different layout fails with
+    /// [`Error::InvalidConfig`](crate::error::Error::InvalidConfig). The clustered layout
+    /// uses one additional named database. Defaults to [`StorageLayout::Sequential`].
+    pub layout: StorageLayout,
+
+    /// The key events are ordered by, when creating a new database.
+    ///
+    /// Recorded on creation like the [`layout`](Self::layout); opening an existing database
+    /// with a different setting fails with
+    /// [`Error::InvalidConfig`](crate::error::Error::InvalidConfig). [`LogKey::U128`] uses
+    /// one additional named database. Defaults to [`LogKey::Sequence`].
+    pub log_key: LogKey,
+
+    /// Opens the storage without taking the exclusive writer lock.
+    ///
+    /// VarveDB assumes a single writer per database: writers cache the next sequence number,
+    /// so two processes appending to the same path would corrupt the log. Unless this is set,
+    /// `Storage::open` takes an exclusive lock on a `varvedb.lock` file in the database
+    /// directory and fails with [`Error::InvalidConfig`](crate::error::Error::InvalidConfig)
+    /// if another process already holds it. Opens within one process share the lock.
+    ///
+    /// Set this in processes that only read (e.g. projections or dashboards) so they can run
+    /// next to the writer, or that coordinate writes through the writer lease (see
+    /// [`Storage::acquire_writer_lease`]). It does not make the environment read-only: a
+    /// handle opened this way must not be used with a `Writer` unless it holds the lease.
+    /// The lock is only enforced on Unix. Defaults to `false`.
+    pub skip_writer_lock: bool,
+
+    /// How many times `open` retries taking the writer lock or opening the LMDB environment
+    /// after a transient failure.
+    ///
+    /// On some filesystems (NFS, some container overlays) a process that just closed the
+    /// database may still be releasing its locks, so an immediate reopen fails with
+    /// "database already open for writing" or a busy/would-block I/O error. Each retry waits
+    /// [`open_retry_delay`](Self::open_retry_delay) first. Other errors fail at once.
+    /// Defaults to `0` (no retries).
+    pub open_retries: u32,
+
+    /// How long `open` waits before each of the [`open_retries`](Self::open_retries).
+    /// Defaults to 50ms.
+    pub open_retry_delay: std::time::Duration,
+
     /// Whether to create the directory if it doesn't exist.
     pub create_dir: bool,
 
+    /// Unix permission bits for the directories `open` creates, e.g. `0o700`.
+    ///
+    /// Applies to the database directory (and missing parents) when `create_dir` is set, and
+    /// to the `blobs` directory of [`separate_blob_env`](Self::separate_blob_env).
+    /// Directories that already exist keep their permissions. When set, the LMDB data and
+    /// lock files and `varvedb.lock` are also restricted to their owner (`0o600`), so other
+    /// users can't read event data that is stored unencrypted, such as the stream index.
+    /// Files that already existed and belong to another user are left as they are.
+    /// Ignored on other platforms. Defaults to `None` (the process umask decides).
+    pub dir_mode: Option<u32>,
+
     /// Enables encryption at rest for all events.
     ///
     /// When enabled, all event payloads are encrypted using AES-256-GCM before being written to disk.
     /// This requires a `master_key` to be provided.
     pub encryption_enabled: bool,
 
+    /// Restricts encryption to the listed streams.
+    ///
+    /// With `encryption_enabled`, events and metadata of these streams are encrypted and all
+    /// other streams are stored in plaintext, saving the encryption overhead where it is not
+    /// needed. Each record then ends with a one-byte flag saying whether it is encrypted,
+    /// so streams can be added to the set later: their new events are encrypted, and the
+    /// ones written before stay readable. Whether the set is configured at all (`Some`) is
+    /// fixed when the database is created. Defaults to `None` (every stream is encrypted).
+    pub encrypted_streams: Option<std::collections::BTreeSet<u128>>,
+
     /// The master key used to encrypt per-stream keys.
     ///
     /// Required if `encryption_enabled` is true. This key should be 32 bytes (256 bits) and
     /// must be kept secure. Losing this key will render the database unreadable.
+    ///
+    /// This is master key version 0; see [`master_keys`](Self::master_keys) for rotation.
     pub master_key: Option<zeroize::Zeroizing<[u8; 32]>>,
+
+    /// Newer master keys, by version (1 and up), for rotating the master key.
+    ///
+    /// Each wrapped stream key records the version of the master key it was wrapped with, and
+    /// is unwrapped with that version. New stream keys are wrapped with the highest version
+    /// configured here, or with `master_key` if this is empty. To rotate, add the new key
+    /// under the next version: old wraps stay readable with their old master key, and
+    /// [`KeyManager::rewrap_all_keys`](crate::crypto::KeyManager::rewrap_all_keys) moves them
+    /// all to the latest version whenever convenient, after which older versions can be
+    /// removed, including `master_key` itself unless
+    /// [`obscure_stream_ids`](Self::obscure_stream_ids) is set, since that derives the on-disk
+    /// stream IDs from it.
+    pub master_keys: std::collections::BTreeMap<u8, zeroize::Zeroizing<[u8; 32]>>,
+
+    /// Allows [`Storage::serve_tcp`] to serve an encrypted database.
+    ///
+    /// The server sends decrypted events over a connection with no encryption of its own,
+    /// so with `encryption_enabled` it refuses to start unless this is set. Defaults to false.
+    pub serve_decrypted: bool,
+}
+
+impl StorageConfig {
+    /// Returns the minimum `max_dbs` this configuration needs, not counting secondary
+    /// indexes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::storage::{StorageConfig, StorageLayout, REQUIRED_DBS};
+    /// let config = StorageConfig {
+    ///     layout: StorageLayout::Clustered,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(config.required_dbs(), REQUIRED_DBS + 1);
+    /// ```
+    pub fn required_dbs(&self) -> u32 {
+        let optional = [
+            self.enforce_stream_types,
+            self.reject_consecutive_duplicates,
+            self.lamport_clock,
+            self.layout == StorageLayout::Clustered,
+            // `events_by_id` and `ids_by_seq`.
+            self.log_key == LogKey::U128,
+            self.log_key == LogKey::U128,
+        ];
+        REQUIRED_DBS + optional.iter().filter(|&&enabled| enabled).count() as u32
+    }
 }
 
 impl Default for StorageConfig {
@@ -81,14 +705,62 @@ impl Default for StorageConfig {
         Self {
             path: PathBuf::from("varvedb.mdb"),
             map_size: 10 * 1024 * 1024 * 1024, // 10TB
-            max_dbs: 10,
+            max_dbs: 24,
+            max_readers: 126,
+            auto_reader_cleanup: None,
+            sync_mode: SyncMode::Full,
+            flush_interval: None,
+            notify_coalesce: None,
+            lock_memory: false,
+            warm_on_open: false,
+            warm_window: None,
+            verify_checksums: false,
+            inline_threshold: InlineThreshold::default(),
+            disable_blob_offload: false,
+            on_missing_blob: MissingBlob::Error,
+            max_serialize_bytes: None,
+            max_stream_versions: None,
+            reject_consecutive_duplicates: false,
+            lamport_clock: false,
+            map_full_warn_threshold: None,
+            append_reserve_bytes: 0,
+            enforce_stream_types: false,
+            reader_tls: true,
+            layout: StorageLayout::Sequential,
+            log_key: LogKey::Sequence,
+            obscure_stream_ids: false,
+            separate_blob_env: false,
+            skip_writer_lock: false,
+            open_retries: 0,
+            open_retry_delay: std::time::Duration::from_millis(50),
             create_dir: true,
+            dir_mode: None,
             encryption_enabled: false,
+            encrypted_streams: None,
             master_key: None,
+            master_keys: Default::default(),
+            serve_decrypted: false,
         }
     }
 }
 
+/// A snapshot of the LMDB reader lock table.
+///
+/// Returned by [`Storage::reader_table_info`]. LMDB does not report how many slots are in
+/// use right now, only how far into the table slots were ever handed out, so `active` says
+/// how close the environment came to [`ReadersFull`](crate::error::Error::ReadersFull), not
+/// whether read transactions are leaking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderTableInfo {
+    /// High-water mark of the reader table: the number of slots ever taken, live or freed.
+    ///
+    /// Freed slots are reused before the mark moves, but it never goes down while the lock
+    /// file exists, so it cannot tell live readers from ones that already finished.
+    pub active: u32,
+    /// Maximum number of reader slots available in the environment.
+    pub max: u32,
+}
+
 /// A handle to the underlying storage engine.
 ///
 /// `Storage` wraps the LMDB environment and provides access to the internal databases (buckets).
@@ -101,26 +773,80 @@ pub struct Storage {
     // Buckets
     /// Maps Global Sequence Number (u64) -> Event Bytes.
     pub events_log: EventLogDb,
+    /// Maps Stream ID + Version -> Event Bytes. Only present in the clustered layout, where
+    /// `events_log` maps Global Sequence Number -> Stream ID + Version instead.
+    pub events_by_stream: Option<ClusteredLogDb>,
+    /// Maps Global Sequence Number -> Event Metadata Bytes, for events appended with
+    /// `Writer::append_payload`. Encrypted like the events if encryption is enabled.
+    pub event_metadata: LazyDb<U64<heed::byteorder::BE>, Bytes>,
     /// Maps Stream ID + Version -> Global Sequence Number.
     pub stream_index: StreamIndexDb, // Key: StreamID+Ver (16+4 bytes)
     /// Maps Consumer ID -> Last Processed Global Sequence Number.
     pub consumer_cursors: ConsumerCursorDb,
+    /// Maps Group ID -> shared cursor and claimed sequence ranges of each
+    /// [`ConsumerGroup`](crate::group::ConsumerGroup).
+    pub group_claims: LazyDb<Bytes, Bytes>,
+    /// Maps Stream ID + first version -> end version and expiry of the version ranges
+    /// reserved with [`Writer::reserve_versions`](crate::engine::Writer::reserve_versions).
+    pub stream_reservations: LazyDb<Bytes, Bytes>,
+    /// Maps Message ID -> status, sequence, destination and payload of the messages written
+    /// with [`Writer::append_with_outbox`](crate::engine::Writer::append_with_outbox).
+    pub outbox: LazyDb<U64<heed::byteorder::BE>, Bytes>,
     /// Maps Stream ID -> Encrypted Key (variable length).
     pub keystore: KeyStoreDb,
-    /// Maps Blob Hash -> Blob Data.
+    /// Maps Blob Hash -> Blob Data. Belongs to `blob_env` if that is set.
     pub blobs: BlobDb,
+    /// Maps Blob Hash -> number of records referring to the blob.
+    pub blob_refs: LazyDb<Bytes, U64<heed::byteorder::BE>>,
+    /// Maps First Sequence -> Last Sequence of each range of the log deleted by
+    /// [`Writer::trim_stream`](crate::engine::Writer::trim_stream).
+    pub trimmed: LazyDb<U64<heed::byteorder::BE>, U64<heed::byteorder::BE>>,
+    /// The environment holding `blobs`, if [`StorageConfig::separate_blob_env`] is set.
+    pub blob_env: Option<Env>,
+    /// Maps Setting Name -> Value (e.g. the on-disk format version).
+    pub meta: MetaDb,
+    /// Maps Stream ID -> Event Type Tag. Only present if `enforce_stream_types` is enabled.
+    pub stream_types: Option<StreamTypeDb>,
+    /// Maps Stream ID -> version and body hash of its last event. Only present if
+    /// `reject_consecutive_duplicates` is enabled.
+    pub stream_last_hash: Option<LastHashDb>,
+    /// Maps Event ID -> Sequence. Only present if `log_key` is [`LogKey::U128`].
+    pub events_by_id: Option<EventIdDb>,
+    /// Maps Sequence -> Event ID. Only present if `log_key` is [`LogKey::U128`].
+    pub ids_by_seq: Option<SeqIdDb>,
+    /// Maps Sequence -> Lamport timestamp. Only present if `lamport_clock` is enabled.
+    pub lamport_clocks: Option<LamportDb>,
     /// The configuration used to open this storage.
     pub config: StorageConfig,
     /// Shared notification channel for new events.
     pub notifier: std::sync::Arc<tokio::sync::watch::Sender<u64>>,
     /// Receiver for the shared notification channel (kept alive to prevent channel closure).
     pub notifier_rx: tokio::sync::watch::Receiver<u64>,
+    /// Latest committed head not yet published; only present if `notify_coalesce` is set.
+    coalesced_head: Option<Arc<AtomicU64>>,
+    /// Flushes pending state when the last clone is dropped, see [`Storage::close`].
+    _shutdown: Arc<ShutdownFlush>,
+    /// Maintenance threads; stopped and joined when the last clone is dropped.
+    _workers: Arc<Vec<BackgroundWorker>>,
+    /// Exclusive writer lock on the database directory; `None` if opened with `skip_writer_lock`.
+    _writer_lock: Option<Arc<WriterLock>>,
+    /// This handle's writer lease, see [`Storage::acquire_writer_lease`].
+    lease: Arc<LeaseState>,
+    /// The LMDB page size of `env`, in bytes.
+    page_size: u32,
+    /// Whether `blob_refs` counts every reference to a blob. Databases created before
+    /// format version 2 hold references it doesn't count.
+    counts_blob_refs: bool,
+    /// Whether the last check against `map_full_warn_threshold` found the map above it.
+    pub(crate) map_near_full: Arc<AtomicBool>,
+    /// Backing directory of an in-memory store; removed when the last clone is dropped.
+    _scratch_dir: Option<Arc<ScratchDir>>,
 }
 
 impl Storage {
     pub fn open(config: StorageConfig) -> Result<Self> {
         if config.create_dir {
-            std::fs::create_dir_all(&config.path)?;
+            create_dir(&config.path, config.dir_mode)?;
         }
 
         if config.map_size == 0 {
@@ -129,10 +855,11 @@ impl Storage {
             ));
         }
 
-        if config.max_dbs < 4 {
-            return Err(crate::error::Error::InvalidConfig(
-                "max_dbs must be at least 4".to_string(),
-            ));
+        if config.max_dbs < config.required_dbs() {
+            return Err(crate::error::Error::InvalidConfig(format!(
+                "max_dbs must be at least {}",
+                config.required_dbs()
+            )));
         }
 
         #[cfg(target_pointer_width = "32")]
@@ -144,34 +871,1457 @@ impl Storage {
             }
         }
 
-        let env = unsafe {
-            EnvOpenOptions::new()
-                .map_size(config.map_size)
-                .max_dbs(config.max_dbs)
-                .open(&config.path)?
+        if config.max_readers == 0 {
+            return Err(crate::error::Error::InvalidConfig(
+                "max_readers must be greater than 0".to_string(),
+            ));
+        }
+
+        if config.auto_reader_cleanup.is_some_and(|i| i.is_zero()) {
+            return Err(crate::error::Error::InvalidConfig(
+                "auto_reader_cleanup interval must be greater than 0".to_string(),
+            ));
+        }
+
+        if config.obscure_stream_ids && (!config.encryption_enabled || config.master_key.is_none())
+        {
+            return Err(crate::error::Error::InvalidConfig(
+                "obscure_stream_ids requires encryption_enabled and a master_key".to_string(),
+            ));
+        }
+
+        if config.encrypted_streams.is_some() && !config.encryption_enabled {
+            return Err(crate::error::Error::InvalidConfig(
+                "encrypted_streams requires encryption_enabled".to_string(),
+            ));
+        }
+
+        if config.master_keys.contains_key(&0) {
+            return Err(crate::error::Error::InvalidConfig(
+                "master key version 0 is master_key; master_keys start at version 1".to_string(),
+            ));
+        }
+
+        if config.flush_interval.is_some_and(|i| i.is_zero()) {
+            return Err(crate::error::Error::InvalidConfig(
+                "flush_interval must be greater than 0".to_string(),
+            ));
+        }
+
+        if config.notify_coalesce.is_some_and(|i| i.is_zero()) {
+            return Err(crate::error::Error::InvalidConfig(
+                "notify_coalesce must be greater than 0".to_string(),
+            ));
+        }
+
+        if config
+            .map_full_warn_threshold
+            .is_some_and(|t| !(t > 0.0 && t <= 1.0))
+        {
+            return Err(crate::error::Error::InvalidConfig(
+                "map_full_warn_threshold must be in (0, 1]".to_string(),
+            ));
+        }
+
+        // Files from an earlier open may belong to another user, see `restrict_files`.
+        #[cfg(unix)]
+        let existing_files = (
+            existing_files(&config.path),
+            existing_files(&config.path.join(BLOB_ENV_DIR)),
+        );
+
+        let writer_lock = if config.skip_writer_lock {
+            None
+        } else {
+            Some(WriterLock::acquire(
+                &config.path,
+                config.open_retries,
+                config.open_retry_delay,
+            )?)
+        };
+
+        let mut flags = config.sync_mode.env_flags();
+        if !config.reader_tls {
+            flags |= heed::EnvFlags::NO_TLS;
+        }
+
+        let mut retries = config.open_retries;
+        let env = loop {
+            // Safety: relaxed sync modes only weaken durability, never consistency
+            // (see the `SyncMode` docs). `NO_TLS` only changes how reader slots are assigned.
+            let result = unsafe {
+                EnvOpenOptions::new()
+                    .map_size(config.map_size)
+                    .max_dbs(config.max_dbs)
+                    .max_readers(config.max_readers)
+                    .flags(flags)
+                    .open(&config.path)
+            };
+            match result {
+                Err(heed::Error::Io(e)) if retries > 0 && is_transient(&e) => {
+                    tracing::debug!("Retrying environment open after: {}", e);
+                    retries -= 1;
+                    std::thread::sleep(config.open_retry_delay);
+                }
+                result => {
+                    break result.map_err(|e| match e {
+                        heed::Error::Mdb(heed::MdbError::Invalid)
+                        | heed::Error::Mdb(heed::MdbError::VersionMismatch) => {
+                            crate::error::Error::InvalidConfig(format!(
+                                "not a VarveDB database: {}",
+                                e
+                            ))
+                        }
+                        e => e.into(),
+                    })?
+                }
+            }
         };
 
         let mut txn = env.write_txn()?;
-        let events_log = env.create_database(&mut txn, Some("events_log"))?;
+        check_existing_env(&env, &txn)?;
+        let events_log: EventLogDb = env.create_database(&mut txn, Some("events_log"))?;
+        let page_size = events_log.stat(&txn)?.page_size;
         let stream_index = env.create_database(&mut txn, Some("stream_index"))?;
         let consumer_cursors = env.create_database(&mut txn, Some("consumer_cursors"))?;
         let keystore = env.create_database(&mut txn, Some("keystore"))?;
         let blobs = env.create_database(&mut txn, Some("blobs"))?;
+        let meta: MetaDb = env.create_database(&mut txn, Some("meta"))?;
+        let is_new = events_log.is_empty(&txn)?;
+        let format_version = match meta.get(&txn, FORMAT_VERSION_KEY)? {
+            Some(bytes) => {
+                let version = <[u8; 4]>::try_from(bytes)
+                    .map(u32::from_be_bytes)
+                    .map_err(|_| {
+                        crate::error::Error::InvalidConfig(
+                            "not a VarveDB database: malformed format version".to_string(),
+                        )
+                    })?;
+                if version > crate::constants::FORMAT_VERSION {
+                    return Err(crate::error::Error::InvalidConfig(format!(
+                        "unsupported VarveDB format version {} (this build supports up to {})",
+                        version,
+                        crate::constants::FORMAT_VERSION
+                    )));
+                }
+                version
+            }
+            None => {
+                // Databases written before the key existed are at version 1.
+                let version = if is_new {
+                    crate::constants::FORMAT_VERSION
+                } else {
+                    1
+                };
+                meta.put(&mut txn, FORMAT_VERSION_KEY, &version.to_be_bytes())?;
+                version
+            }
+        };
+        check_creation_marker(
+            &meta,
+            &mut txn,
+            LAYOUT_KEY,
+            config.layout.marker(),
+            is_new,
+            "storage layout",
+        )?;
+        check_creation_marker(
+            &meta,
+            &mut txn,
+            OBSCURE_STREAM_IDS_KEY,
+            config.obscure_stream_ids as u8,
+            is_new,
+            "obscure_stream_ids",
+        )?;
+        check_creation_marker(
+            &meta,
+            &mut txn,
+            LOG_KEY_KEY,
+            config.log_key.marker(),
+            is_new,
+            "log_key",
+        )?;
+        check_creation_marker(
+            &meta,
+            &mut txn,
+            SEPARATE_BLOB_ENV_KEY,
+            config.separate_blob_env as u8,
+            is_new,
+            "separate_blob_env",
+        )?;
+        check_creation_marker(
+            &meta,
+            &mut txn,
+            ENCRYPTED_STREAMS_KEY,
+            config.encrypted_streams.is_some() as u8,
+            is_new,
+            "encrypted_streams",
+        )?;
+        if config.encryption_enabled {
+            check_master_key(&meta, &keystore, &mut txn, &config)?;
+        }
+        let events_by_stream = match config.layout {
+            StorageLayout::Sequential => None,
+            StorageLayout::Clustered => Some(env.create_database(&mut txn, Some(CLUSTERED_LOG))?),
+        };
+        let (events_by_id, ids_by_seq) = match config.log_key {
+            LogKey::Sequence => (None, None),
+            LogKey::U128 => {
+                let events_by_id: EventIdDb = env.create_database(&mut txn, Some(EVENT_ID_LOG))?;
+                let ids_by_seq: SeqIdDb = env.create_database(&mut txn, Some(EVENT_ID_BY_SEQ))?;
+                // Databases created before `ids_by_seq` existed only have the forward map.
+                if ids_by_seq.is_empty(&txn)? {
+                    let mut ids = Vec::new();
+                    for result in events_by_id.iter(&txn)? {
+                        ids.push(result?);
+                    }
+                    for (id, seq) in ids {
+                        ids_by_seq.put(&mut txn, &seq, &id)?;
+                    }
+                }
+                (Some(events_by_id), Some(ids_by_seq))
+            }
+        };
+        let stream_types = if config.enforce_stream_types {
+            Some(env.create_database(&mut txn, Some(STREAM_TYPE_REGISTRY))?)
+        } else {
+            None
+        };
+        let stream_last_hash = if config.reject_consecutive_duplicates {
+            Some(env.create_database(&mut txn, Some(STREAM_LAST_HASH))?)
+        } else {
+            None
+        };
+        let lamport_clocks = if config.lamport_clock {
+            Some(env.create_database(&mut txn, Some(LAMPORT_LOG))?)
+        } else {
+            None
+        };
+        let last_seq = events_log.last(&txn)?.map_or(0, |(seq, _)| seq);
         txn.commit()?;
 
-        let (tx, rx) = tokio::sync::watch::channel(0);
+        let (blob_env, blobs) = if config.separate_blob_env {
+            let (blob_env, blobs) = open_blob_env(&config, flags)?;
+            (Some(blob_env), blobs)
+        } else {
+            (None, blobs)
+        };
+
+        #[cfg(unix)]
+        if config.dir_mode.is_some() {
+            restrict_files(&config.path, &existing_files.0)?;
+            if config.separate_blob_env {
+                restrict_files(&config.path.join(BLOB_ENV_DIR), &existing_files.1)?;
+            }
+        }
+
+        if config.lock_memory {
+            lock_map(&env)?;
+        }
+
+        // Publish the committed head, see `recover_tail`.
+        let (tx, rx) = tokio::sync::watch::channel(last_seq);
         let notifier = std::sync::Arc::new(tx);
 
-        Ok(Self {
+        let mut workers = Vec::new();
+        if let Some(interval) = config.auto_reader_cleanup {
+            let env = env.clone();
+            workers.push(BackgroundWorker::spawn(
+                "varvedb-reader-cleanup",
+                interval,
+                move || match env.clear_stale_readers() {
+                    Ok(0) => {}
+                    Ok(n) => tracing::debug!("Reclaimed {} stale reader slots", n),
+                    Err(e) => tracing::warn!("Stale reader cleanup failed: {}", e),
+                },
+            )?);
+        }
+        let coalesced_head = match config.notify_coalesce {
+            Some(interval) => {
+                let head = Arc::new(AtomicU64::new(last_seq));
+                let pending = head.clone();
+                let notifier = notifier.clone();
+                workers.push(BackgroundWorker::spawn(
+                    "varvedb-notify",
+                    interval,
+                    move || publish_coalesced(&notifier, &pending),
+                )?);
+                Some(head)
+            }
+            None => None,
+        };
+        if let Some(interval) = config.flush_interval {
+            let env = env.clone();
+            workers.push(BackgroundWorker::spawn(
+                "varvedb-flush",
+                interval,
+                move || {
+                    if let Err(e) = env.force_sync() {
+                        tracing::warn!("Background flush failed: {}", e);
+                    }
+                },
+            )?);
+        }
+
+        let shutdown = Arc::new(ShutdownFlush {
+            env: env.clone(),
+            blob_env: blob_env.clone(),
+            notifier: notifier.clone(),
+            coalesced_head: coalesced_head.clone(),
+            sync: config.sync_mode != SyncMode::Full,
+        });
+
+        let event_metadata = LazyDb::new(env.clone(), "event_metadata");
+        let group_claims = LazyDb::new(env.clone(), "group_claims");
+        let stream_reservations = LazyDb::new(env.clone(), "stream_reservations");
+        let outbox = LazyDb::new(env.clone(), "outbox");
+        let blob_refs = LazyDb::new(env.clone(), "blob_refs");
+        let trimmed = LazyDb::new(env.clone(), "trimmed");
+        let storage = Self {
             env,
             events_log,
+            events_by_stream,
+            event_metadata,
             stream_index,
             consumer_cursors,
+            group_claims,
+            stream_reservations,
+            outbox,
             keystore,
             blobs,
+            blob_refs,
+            trimmed,
+            blob_env,
+            meta,
+            stream_types,
+            stream_last_hash,
+            events_by_id,
+            ids_by_seq,
+            lamport_clocks,
             config,
             notifier,
             notifier_rx: rx,
-        })
+            coalesced_head,
+            _shutdown: shutdown,
+            _workers: Arc::new(workers),
+            _writer_lock: writer_lock,
+            lease: Arc::new(LeaseState::new()),
+            map_near_full: Arc::new(AtomicBool::new(false)),
+            page_size,
+            counts_blob_refs: format_version >= 2,
+            _scratch_dir: None,
+        };
+        if storage.config.warm_on_open {
+            storage.warm(storage.config.warm_window)?;
+        }
+        Ok(storage)
+    }
+
+    /// Re-derives the next sequence number from the head of the events log and returns it.
+    ///
+    /// The log itself is the only authority on which sequence numbers are taken: writers
+    /// read its head inside every write transaction, so a crash can never make them reuse
+    /// or skip one. What this resets is the head published on [`notifier`](Self::notifier),
+    /// so that subscribers see the committed head rather than whatever was last sent.
+    /// [`open`](Self::open) starts from the same head; call this after the log changed
+    /// behind this handle's back, e.g. after restoring a backup into the environment.
+    pub fn recover_tail(&self) -> Result<u64> {
+        let txn = self.env.read_txn()?;
+        let last_seq = self.events_log.last(&txn)?.map_or(0, |(seq, _)| seq);
+        self.reset_head(last_seq);
+        Ok(last_seq + 1)
+    }
+
+    /// Flushes the environment to disk and records the head sequence as durable.
+    ///
+    /// Reads the current head of the events log, forces a sync (of the blob environment
+    /// first, if there is one), and only then records that head as the durable watermark
+    /// and returns it. Once this returns, every event up to it survives a crash whatever the
+    /// [`SyncMode`]; with a relaxed mode, consumers that must not act on events that could
+    /// still be lost can stop at [`durable_sequence`](Self::durable_sequence).
+    ///
+    /// The watermark itself is written after the sync, so with a relaxed mode a crash may
+    /// roll it back to the previous checkpoint's value: after a restart it can lag by one
+    /// checkpoint, but never claims more than is on disk.
+    pub fn checkpoint(&self) -> Result<u64> {
+        let head = {
+            let txn = self.env.read_txn()?;
+            self.events_log.last(&txn)?.map_or(0, |(seq, _)| seq)
+        };
+
+        if let Some(blob_env) = &self.blob_env {
+            blob_env.force_sync()?;
+        }
+        self.env.force_sync()?;
+
+        let mut txn = self.env.write_txn()?;
+        // A concurrent checkpoint may already have recorded a later head.
+        let recorded = match self.meta.get(&txn, DURABLE_SEQUENCE_KEY)? {
+            Some(bytes) => bytes.try_into().map_or(0, u64::from_be_bytes),
+            None => 0,
+        };
+        if head > recorded {
+            self.meta
+                .put(&mut txn, DURABLE_SEQUENCE_KEY, &head.to_be_bytes())?;
+            txn.commit()?;
+        }
+        Ok(head)
+    }
+
+    /// Returns the sequence recorded by the last [`checkpoint`](Self::checkpoint), or 0 if
+    /// there was none.
+    ///
+    /// The watermark only moves on checkpoints: events committed since may well be on disk
+    /// too, but only those up to it are guaranteed to be.
+    pub fn durable_sequence(&self) -> Result<u64> {
+        let txn = self.env.read_txn()?;
+        match self.meta.get(&txn, DURABLE_SEQUENCE_KEY)? {
+            Some(bytes) => Ok(u64::from_be_bytes(bytes.try_into().map_err(|_| {
+                crate::error::Error::InvalidConfig("malformed durable sequence".to_string())
+            })?)),
+            None => Ok(0),
+        }
+    }
+
+    /// Flushes pending state and drops this handle, reporting flush failures.
+    ///
+    /// Publishes the head held back by [`StorageConfig::notify_coalesce`] and, with a
+    /// relaxed [`SyncMode`], syncs the environments to disk, so everything committed so far
+    /// is durable once this returns `Ok`. Dropping the last clone of a `Storage` does the
+    /// same, but can only log failures; call this where they must be observed. Other clones
+    /// stay usable.
+    pub fn close(self) -> Result<()> {
+        self._shutdown.flush()
+    }
+
+    /// Publishes `seq` as the committed head, or records it for the next coalesced
+    /// notification if [`StorageConfig::notify_coalesce`] is set.
+    pub(crate) fn publish_head(&self, seq: u64) {
+        match &self.coalesced_head {
+            Some(head) => {
+                head.fetch_max(seq, Ordering::AcqRel);
+            }
+            None => {
+                let _ = self.notifier.send(seq);
+            }
+        }
     }
+
+    /// Publishes `seq` as the head right away, even if it moved backwards.
+    fn reset_head(&self, seq: u64) {
+        self.notifier.send_modify(|current| {
+            if let Some(head) = &self.coalesced_head {
+                head.store(seq, Ordering::Release);
+            }
+            *current = seq;
+        });
+    }
+
+    /// Acquires the cooperative writer lease, making this handle the active writer.
+    ///
+    /// The lease is a timestamped record in the `meta` database, so it works across processes
+    /// sharing the database (which must be on the same host, as LMDB requires). It succeeds if
+    /// no lease exists, the current one has expired, or this handle already holds it, and
+    /// fails with [`Error::LeaseHeld`](crate::error::Error::LeaseHeld) otherwise. A standby
+    /// process polls this until the active writer stops renewing.
+    ///
+    /// Once acquired, every append through this handle (and its clones) first checks that the
+    /// lease is still held and unexpired, failing with
+    /// [`Error::LeaseLost`](crate::error::Error::LeaseLost) otherwise; call
+    /// [`renew_lease`](Self::renew_lease) well within `ttl`. The lease relies on the clocks of
+    /// the participating processes, which on one host agree.
+    ///
+    /// Processes taking part in lease-based failover should open the storage with
+    /// [`StorageConfig::skip_writer_lock`], since the exclusive writer lock would otherwise keep the
+    /// standby from opening the database at all.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::storage::{Storage, StorageConfig};
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempfile::tempdir()?;
+    /// let config = StorageConfig {
+    ///     path: dir.path().to_path_buf(),
+    ///     skip_writer_lock: true,
+    ///     ..Default::default()
+    /// };
+    /// let storage = Storage::open(config)?;
+    /// storage.acquire_writer_lease(Duration::from_secs(10))?;
+    /// // ... append, calling `renew_lease` every few seconds.
+    /// storage.renew_lease()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`](crate::error::Error::InvalidConfig) if `ttl` is zero.
+    pub fn acquire_writer_lease(&self, ttl: Duration) -> Result<()> {
+        let mut txn = self.env.write_txn()?;
+        self.lease.acquire(&self.meta, &mut txn, ttl)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Extends the writer lease acquired with [`acquire_writer_lease`](Self::acquire_writer_lease)
+    /// by its TTL.
+    ///
+    /// An expired lease can still be renewed as long as no other process has acquired it in
+    /// the meantime. Fails with [`Error::LeaseLost`](crate::error::Error::LeaseLost) if this
+    /// handle never acquired the lease or another process has taken it over.
+    pub fn renew_lease(&self) -> Result<()> {
+        let mut txn = self.env.write_txn()?;
+        self.lease.renew(&self.meta, &mut txn)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Fails with `LeaseLost` if this handle acquired a writer lease that it no longer holds.
+    pub(crate) fn check_writer_lease(&self, txn: &heed::RoTxn) -> Result<()> {
+        self.lease.check(&self.meta, txn)
+    }
+
+    /// Opens an ephemeral store for tests and quick experiments.
+    ///
+    /// LMDB always needs a backing file, so the environment is created in a fresh directory
+    /// on a RAM-backed filesystem (`/dev/shm` on Linux, falling back to the system temp dir
+    /// elsewhere) with [`SyncMode::NoSync`]. **All data is lost** when the last clone of the
+    /// returned `Storage` is dropped, at which point the directory is removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use varvedb::storage::Storage;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let storage = Storage::open_in_memory()?;
+    /// let mut txn = storage.env.write_txn()?;
+    /// storage.consumer_cursors.put(&mut txn, &1, &42)?;
+    /// txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_in_memory() -> Result<Self> {
+        let scratch_dir = ScratchDir::create()?;
+        let config = StorageConfig {
+            path: scratch_dir.path.clone(),
+            sync_mode: SyncMode::NoSync,
+            ..Default::default()
+        };
+        let mut storage = Self::open(config)?;
+        storage._scratch_dir = Some(Arc::new(scratch_dir));
+        Ok(storage)
+    }
+
+    /// Returns `true` if events of `stream_id` are encrypted at rest.
+    ///
+    /// See [`StorageConfig::encrypted_streams`].
+    pub fn encrypts_stream(&self, stream_id: u128) -> bool {
+        self.config.encryption_enabled
+            && self
+                .config
+                .encrypted_streams
+                .as_ref()
+                .map_or(true, |streams| streams.contains(&stream_id))
+    }
+
+    /// Returns the ID under which `stream_id` is stored on disk.
+    ///
+    /// This is `stream_id` itself unless [`StorageConfig::obscure_stream_ids`] is set, in which
+    /// case it is the first 128 bits of `HMAC-SHA256(master_key, "varvedb:stream-id:" || stream_id)`.
+    pub fn stored_stream_id(&self, stream_id: u128) -> u128 {
+        use hmac::Mac;
+
+        let Some(master_key) = self
+            .config
+            .master_key
+            .as_ref()
+            .filter(|_| self.config.obscure_stream_ids)
+        else {
+            return stream_id;
+        };
+
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(master_key.as_slice())
+            .expect("HMAC accepts keys of any length");
+        mac.update(b"varvedb:stream-id:");
+        mac.update(&stream_id.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        u128::from_be_bytes(digest[..16].try_into().unwrap())
+    }
+
+    /// Returns the current value of the Lamport clock, or 0 if no event was stamped yet.
+    ///
+    /// See [`StorageConfig::lamport_clock`].
+    pub fn lamport_clock(&self, txn: &heed::RoTxn) -> Result<u64> {
+        match self.meta.get(txn, LAMPORT_CLOCK_KEY)? {
+            Some(bytes) => Ok(u64::from_be_bytes(bytes.try_into().map_err(|_| {
+                crate::error::Error::InvalidConfig("malformed lamport clock".to_string())
+            })?)),
+            None => Ok(0),
+        }
+    }
+
+    /// Sets the Lamport clock to `value`.
+    pub(crate) fn set_lamport_clock(&self, txn: &mut heed::RwTxn, value: u64) -> Result<()> {
+        Ok(self
+            .meta
+            .put(txn, LAMPORT_CLOCK_KEY, &value.to_be_bytes())?)
+    }
+
+    /// Returns the on-disk stream index key for `stream_id` and `version`.
+    pub fn stream_key(&self, stream_id: u128, version: u32) -> [u8; 20] {
+        StreamKey::new(self.stored_stream_id(stream_id), version).to_be_bytes()
+    }
+
+    /// Returns the raw record stored at global sequence `seq`, whatever the layout.
+    pub(crate) fn get_record<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        seq: u64,
+    ) -> Result<Option<&'txn [u8]>> {
+        let Some(value) = self.events_log.get(txn, &seq)? else {
+            return Ok(None);
+        };
+        match &self.events_by_stream {
+            None => Ok(Some(value)),
+            Some(clustered) => Ok(clustered.get(txn, value)?),
+        }
+    }
+
+    /// Iterates over the sequence numbers from `start` on that hold a record, in order,
+    /// like repeated calls to [`next_sequence`](Self::next_sequence).
+    pub(crate) fn sequences<'a>(
+        &'a self,
+        txn: &'a heed::RoTxn,
+        start: u64,
+    ) -> impl Iterator<Item = Result<u64>> + 'a {
+        let mut next = Some(start);
+        std::iter::from_fn(move || match self.next_sequence(txn, next.take()?) {
+            Ok(Some(seq)) => {
+                next = seq.checked_add(1);
+                Some(Ok(seq))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Returns `seq`, or the first sequence number after it if `seq` was deleted by
+    /// [`Writer::trim_stream`](crate::engine::Writer::trim_stream).
+    ///
+    /// Sequential readers skip trimmed sequences this way, while a gap left by `append_at`
+    /// still stops them until it is filled.
+    pub(crate) fn skip_trimmed(&self, txn: &heed::RoTxn, seq: u64) -> Result<u64> {
+        let Some(trimmed) = self.trimmed.open(txn)? else {
+            return Ok(seq);
+        };
+        // Adjacent ranges are merged, so one lookup finds the end of the hole.
+        match trimmed.rev_range(txn, &(..=seq))?.next().transpose()? {
+            Some((_, last)) if last >= seq => Ok(last + 1),
+            _ => Ok(seq),
+        }
+    }
+
+    /// Returns the first sequence number at or after `seq` that holds a record, skipping
+    /// trimmed ones, or `None` at the end of the log or a gap.
+    pub(crate) fn next_sequence(&self, txn: &heed::RoTxn, seq: u64) -> Result<Option<u64>> {
+        let seq = self.skip_trimmed(txn, seq)?;
+        Ok(self
+            .events_log
+            .remap_data_type::<DecodeIgnore>()
+            .get(txn, &seq)?
+            .map(|_| seq))
+    }
+
+    /// Records that the record at `seq` was trimmed, merging it with adjacent trimmed ranges.
+    pub(crate) fn mark_trimmed(&self, txn: &mut heed::RwTxn, seq: u64) -> Result<()> {
+        let trimmed = self.trimmed.create(txn)?;
+        let mut first = seq;
+        let mut last = seq;
+        if let Some((start, end)) = trimmed.rev_range(txn, &(..seq))?.next().transpose()? {
+            if end + 1 == seq {
+                first = start;
+            }
+        }
+        if let Some(end) = trimmed.get(txn, &(seq + 1))? {
+            trimmed.delete(txn, &(seq + 1))?;
+            last = end;
+        }
+        trimmed.put(txn, &first, &last)?;
+        Ok(())
+    }
+
+    /// Asks the kernel to read ahead the records at sequences `[start, start + count)`.
+    ///
+    /// Walks the log over that range, which faults in its leaf pages, and advises
+    /// `MADV_WILLNEED` for the mapped memory holding the records, merging adjacent records
+    /// into one call. Blobs are not prefetched. A no-op outside Unix.
+    pub(crate) fn prefetch_records(&self, txn: &heed::RoTxn, start: u64, count: u64) -> Result<()> {
+        let end = start.saturating_add(count);
+        let page = self.page_size as usize;
+        let mut span: Option<(usize, usize)> = None;
+        for result in self.events_log.range(txn, &(start..end))? {
+            let (_, value) = result?;
+            let record = match &self.events_by_stream {
+                None => value,
+                Some(clustered) => match clustered.get(txn, value)? {
+                    Some(record) => record,
+                    None => continue,
+                },
+            };
+
+            let from = record.as_ptr() as usize & !(page - 1);
+            let to = record.as_ptr() as usize + record.len();
+            span = match span {
+                Some((s, e)) if from <= e.saturating_add(page) && to >= s => Some((s, e.max(to))),
+                Some((s, e)) => {
+                    advise_willneed(s, e);
+                    Some((from, to))
+                }
+                None => Some((from, to)),
+            };
+        }
+        if let Some((s, e)) = span {
+            advise_willneed(s, e);
+        }
+        Ok(())
+    }
+
+    /// Faults the most recent `window` records (or all of them) into memory and returns how
+    /// many were touched.
+    ///
+    /// Advises `MADV_WILLNEED` for the records first, then reads one byte of every page they
+    /// span, in log order, which also brings in the index pages above them. Blobs are not
+    /// warmed. Called by `Storage::open` when [`StorageConfig::warm_on_open`] is set.
+    pub fn warm(&self, window: Option<u64>) -> Result<u64> {
+        let txn = self.env.read_txn()?;
+        let Some((last, _)) = self.events_log.last(&txn)? else {
+            return Ok(0);
+        };
+        let start = match window {
+            Some(0) => return Ok(0),
+            Some(window) => last.saturating_sub(window - 1).max(1),
+            None => 0,
+        };
+        self.prefetch_records(&txn, start, last - start + 1)?;
+
+        let page = self.page_size as usize;
+        let mut touched = 0;
+        for result in self.events_log.range(&txn, &(start..=last))? {
+            let (seq, _) = result?;
+            let Some(record) = self.get_record(&txn, seq)? else {
+                continue;
+            };
+            // The last byte too, for a record ending on a page its start does not reach.
+            let last_byte = record.len().checked_sub(1);
+            for offset in (0..record.len()).step_by(page).chain(last_byte) {
+                std::hint::black_box(record[offset]);
+            }
+            touched += 1;
+        }
+        Ok(touched)
+    }
+
+    /// Returns the raw record stored under a stream key, if the layout allows a direct lookup.
+    ///
+    /// Returns `Ok(None)` when the layout is sequential; callers then go through the sequence.
+    pub(crate) fn get_clustered_record<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        key: &[u8; 20],
+    ) -> Result<Option<&'txn [u8]>> {
+        match &self.events_by_stream {
+            None => Ok(None),
+            Some(clustered) => Ok(clustered.get(txn, key.as_slice())?),
+        }
+    }
+
+    /// Deletes the record at global sequence `seq`, returning whether there was one.
+    pub(crate) fn delete_record(&self, txn: &mut heed::RwTxn, seq: u64) -> Result<bool> {
+        if let Some(clustered) = &self.events_by_stream {
+            if let Some(key) = self.events_log.get(txn, &seq)? {
+                let key = key.to_vec();
+                clustered.delete(txn, key.as_slice())?;
+            }
+        }
+        Ok(self.events_log.delete(txn, &seq)?)
+    }
+
+    /// Stores a raw record at global sequence `seq` under stream key `key`.
+    ///
+    /// `flags` applies to the `events_log` write. Returns heed's error untouched so callers can
+    /// map `MDB_KEYEXIST`.
+    pub(crate) fn put_record(
+        &self,
+        txn: &mut heed::RwTxn,
+        flags: heed::PutFlags,
+        seq: u64,
+        key: &[u8; 20],
+        record: &[u8],
+    ) -> heed::Result<()> {
+        match &self.events_by_stream {
+            None => self.events_log.put_with_flags(txn, flags, &seq, record),
+            Some(clustered) => {
+                self.events_log
+                    .put_with_flags(txn, flags, &seq, key.as_slice())?;
+                clustered.put_with_flags(txn, heed::PutFlags::NO_OVERWRITE, key.as_slice(), record)
+            }
+        }
+    }
+
+    /// Stores a blob under its content hash and counts the record about to refer to it.
+    ///
+    /// With a separate blob environment the blob is committed right away in its own
+    /// transaction, before `txn` (and the log entry referencing the blob) commits.
+    pub(crate) fn put_blob(&self, txn: &mut heed::RwTxn, hash: &[u8], blob: &[u8]) -> Result<()> {
+        let blob_refs = self.blob_refs.create(txn)?;
+        let refs = blob_refs.get(txn, hash)?.unwrap_or(0);
+        blob_refs.put(txn, hash, &(refs + 1))?;
+        match &self.blob_env {
+            None => self.blobs.put(txn, hash, blob)?,
+            Some(blob_env) => {
+                let mut blob_txn = blob_env.write_txn()?;
+                self.blobs.put(&mut blob_txn, hash, blob)?;
+                blob_txn.commit()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops a reference to the blob under `hash`, returning whether no record refers to it
+    /// any more, so it can be deleted.
+    ///
+    /// Always false for databases created before format version 2, since records written
+    /// before then aren't counted.
+    pub(crate) fn release_blob(&self, txn: &mut heed::RwTxn, hash: &[u8]) -> Result<bool> {
+        let Some(blob_refs) = self.blob_refs.open(txn)? else {
+            return Ok(false);
+        };
+        match blob_refs.get(txn, hash)? {
+            Some(refs) if refs > 1 => {
+                blob_refs.put(txn, hash, &(refs - 1))?;
+                Ok(false)
+            }
+            Some(_) => {
+                blob_refs.delete(txn, hash)?;
+                Ok(self.counts_blob_refs)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Calls `f` with the blob stored under `hash`, or `None` if there is none.
+    ///
+    /// The blob is only borrowed for the duration of `f`, since with a separate blob
+    /// environment it lives in a transaction opened just for this lookup.
+    pub(crate) fn with_blob<T>(
+        &self,
+        txn: &heed::RoTxn,
+        hash: &[u8],
+        f: impl FnOnce(Option<&[u8]>) -> Result<T>,
+    ) -> Result<T> {
+        match &self.blob_env {
+            None => f(self.blobs.get(txn, hash)?),
+            Some(blob_env) => {
+                let blob_txn = blob_env.read_txn()?;
+                f(self.blobs.get(&blob_txn, hash)?)
+            }
+        }
+    }
+
+    /// Returns the size of the LMDB reader lock table and its high-water mark.
+    ///
+    /// Useful for sizing [`StorageConfig::max_readers`]; see [`ReaderTableInfo::active`]
+    /// for why it does not count live readers.
+    pub fn reader_table_info(&self) -> ReaderTableInfo {
+        let info = self.env.info();
+        ReaderTableInfo {
+            active: info.number_of_readers,
+            max: info.maximum_number_of_readers,
+        }
+    }
+
+    /// Returns the LMDB page size of the environment, in bytes.
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Returns the largest archived event size stored inline, per
+    /// [`StorageConfig::inline_threshold`], or `usize::MAX` with
+    /// [`StorageConfig::disable_blob_offload`].
+    ///
+    /// For [`InlineThreshold::PageSize`], this is LMDB's largest leaf node for the page size
+    /// (half a page minus the page header) minus the node header, the largest key and
+    /// [`INLINE_RECORD_OVERHEAD`](crate::constants::INLINE_RECORD_OVERHEAD).
+    pub fn max_inline_size(&self) -> usize {
+        if self.config.disable_blob_offload {
+            return usize::MAX;
+        }
+        match self.config.inline_threshold {
+            InlineThreshold::Fixed(size) => size,
+            InlineThreshold::PageSize => {
+                let max_node =
+                    ((self.page_size as usize - crate::constants::LMDB_PAGE_HEADER_SIZE) / 2) & !1;
+                // The largest key is a 20-byte `StreamKey`.
+                max_node.saturating_sub(
+                    crate::constants::LMDB_NODE_HEADER_SIZE
+                        + 20
+                        + crate::constants::INLINE_RECORD_OVERHEAD,
+                )
+            }
+        }
+    }
+
+    /// Returns how many bytes of the memory map are still free for new pages.
+    ///
+    /// Computed from the environment info as `map_size` minus the pages allocated so far
+    /// (up to the last used page, as of the last commit) times the page size. Pages on
+    /// LMDB's free list are counted as used, so the actual headroom can be larger; when this
+    /// reaches zero, appends fail with `MDB_MAP_FULL`.
+    pub fn free_space_bytes(&self, txn: &heed::RoTxn) -> Result<u64> {
+        let page_size = u64::from(self.events_log.stat(txn)?.page_size);
+        let info = self.env.info();
+        let used = (info.last_page_number as u64 + 1).saturating_mul(page_size);
+        Ok((info.map_size as u64).saturating_sub(used))
+    }
+
+    /// Reclaims reader lock table slots held by processes that no longer exist.
+    ///
+    /// Returns the number of dead slots that were cleared. See
+    /// [`StorageConfig::auto_reader_cleanup`] to run this periodically.
+    pub fn clear_stale_readers(&self) -> Result<usize> {
+        Ok(self.env.clear_stale_readers()?)
+    }
+
+    /// Returns every `(consumer_id, committed_seq)` pair, ordered by consumer ID.
+    ///
+    /// `committed_seq` is the last global sequence number the consumer has committed, so its
+    /// lag is the current head (the last key of `events_log`) minus this value.
+    pub fn list_consumers(&self, txn: &heed::RoTxn) -> Result<Vec<(u64, u64)>> {
+        self.consumer_cursors
+            .iter(txn)?
+            .map(|result| result.map_err(Into::into))
+            .collect()
+    }
+
+    /// Deletes every event, cursor, claim, secondary index entry and blob, keeping the
+    /// environment open.
+    ///
+    /// Meant for test fixtures and resetting an environment in place, instead of deleting
+    /// the directory and reopening it. Everything in the main environment is cleared in one
+    /// transaction; with [`StorageConfig::separate_blob_env`], the blobs are cleared in a
+    /// second one afterwards. The `meta` database (format version, layout, writer lease) is
+    /// kept, as are the stream keys if `keep_keys` is set, so re-created streams encrypt
+    /// with the same keys. Subscribers are notified that the head is back at 0, and the next
+    /// append gets sequence 1.
+    ///
+    /// Readers holding a transaction opened before the call keep seeing the old data.
+    /// [`Writer`](crate::engine::Writer)s and [`Reader`](crate::engine::Reader)s on this
+    /// storage stay usable.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LeaseLost`](crate::error::Error::LeaseLost) if this handle acquired the
+    /// writer lease and no longer holds it, or an error if the underlying storage fails.
+    pub fn clear_all(&self, keep_keys: bool) -> Result<()> {
+        let mut txn = self.env.write_txn()?;
+        self.check_writer_lease(&txn)?;
+
+        self.events_log.clear(&mut txn)?;
+        if let Some(clustered) = &self.events_by_stream {
+            clustered.clear(&mut txn)?;
+        }
+        if let Some(event_metadata) = self.event_metadata.open(&txn)? {
+            event_metadata.clear(&mut txn)?;
+        }
+        self.stream_index.clear(&mut txn)?;
+        self.consumer_cursors.clear(&mut txn)?;
+        if let Some(group_claims) = self.group_claims.open(&txn)? {
+            group_claims.clear(&mut txn)?;
+        }
+        if let Some(stream_reservations) = self.stream_reservations.open(&txn)? {
+            stream_reservations.clear(&mut txn)?;
+        }
+        if let Some(outbox) = self.outbox.open(&txn)? {
+            outbox.clear(&mut txn)?;
+        }
+        if let Some(stream_types) = &self.stream_types {
+            stream_types.clear(&mut txn)?;
+        }
+        if let Some(stream_last_hash) = &self.stream_last_hash {
+            stream_last_hash.clear(&mut txn)?;
+        }
+        if let Some(blob_refs) = self.blob_refs.open(&txn)? {
+            blob_refs.clear(&mut txn)?;
+        }
+        if let Some(trimmed) = self.trimmed.open(&txn)? {
+            trimmed.clear(&mut txn)?;
+        }
+        if let Some(events_by_id) = &self.events_by_id {
+            events_by_id.clear(&mut txn)?;
+        }
+        if let Some(ids_by_seq) = &self.ids_by_seq {
+            ids_by_seq.clear(&mut txn)?;
+        }
+        if let Some(lamport_clocks) = &self.lamport_clocks {
+            lamport_clocks.clear(&mut txn)?;
+        }
+        // The log restarts at 1, so an old watermark would cover events not yet written.
+        self.meta.delete(&mut txn, DURABLE_SEQUENCE_KEY)?;
+        if !keep_keys {
+            self.keystore.clear(&mut txn)?;
+        }
+
+        let main: Database<Str, Bytes> = self.env.open_database(&txn, None)?.ok_or_else(|| {
+            crate::error::Error::InvalidConfig("main database not found".to_string())
+        })?;
+        let mut index_names = Vec::new();
+        for result in main.prefix_iter(&txn, SECONDARY_INDEX_PREFIX)? {
+            let (name, _) = result?;
+            index_names.push(name[SECONDARY_INDEX_PREFIX.len()..].to_string());
+        }
+        for name in index_names {
+            if let Some(index) = self.open_secondary_index(&txn, &name)? {
+                index.clear(&mut txn)?;
+            }
+        }
+
+        match &self.blob_env {
+            None => {
+                self.blobs.clear(&mut txn)?;
+                txn.commit()?;
+            }
+            Some(blob_env) => {
+                txn.commit()?;
+                // No event refers to a blob anymore, so failing here only leaves garbage.
+                let mut blob_txn = blob_env.write_txn()?;
+                self.blobs.clear(&mut blob_txn)?;
+                blob_txn.commit()?;
+            }
+        }
+
+        self.reset_head(0);
+        Ok(())
+    }
+
+    /// Creates (or opens) the secondary index database named `index_name`.
+    ///
+    /// Secondary indexes map a user-defined key to every global sequence number that
+    /// produced it. The database is stored under a reserved prefix so it cannot collide
+    /// with VarveDB's internal buckets.
+    pub fn create_secondary_index(&self, index_name: &str) -> Result<SecondaryIndexDb> {
+        let name = format!("{}{}", SECONDARY_INDEX_PREFIX, index_name);
+        let mut txn = self.env.write_txn()?;
+        let db = self
+            .env
+            .database_options()
+            .types::<Bytes, U64<heed::byteorder::BE>>()
+            .name(&name)
+            .flags(heed::DatabaseFlags::DUP_SORT)
+            .create(&mut txn)?;
+        txn.commit()?;
+        Ok(db)
+    }
+
+    /// Opens the secondary index database named `index_name`, if it exists.
+    pub fn open_secondary_index(
+        &self,
+        txn: &heed::RoTxn,
+        index_name: &str,
+    ) -> Result<Option<SecondaryIndexDb>> {
+        let name = format!("{}{}", SECONDARY_INDEX_PREFIX, index_name);
+        Ok(self
+            .env
+            .database_options()
+            .types::<Bytes, U64<heed::byteorder::BE>>()
+            .name(&name)
+            .flags(heed::DatabaseFlags::DUP_SORT)
+            .open(txn)?)
+    }
+
+    /// Locks the currently allocated pages of the memory map into RAM.
+    ///
+    /// Called by `Storage::open` when [`StorageConfig::lock_memory`] is set. Calling it again
+    /// locks pages added since the last call; already locked pages are unaffected.
+    pub fn lock_memory(&self) -> Result<()> {
+        lock_map(&self.env)
+    }
+}
+
+/// A uniquely named directory that is deleted on drop.
+#[derive(Debug)]
+struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    fn create() -> std::io::Result<Self> {
+        let shm = std::path::Path::new("/dev/shm");
+        let base = if cfg!(target_os = "linux") && shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let path = base.join(format!("varvedb-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&path)?;
+        Ok(Self { path })
+    }
+}
+
+/// Publishes the head recorded for coalesced notifications if it moved.
+fn publish_coalesced(notifier: &tokio::sync::watch::Sender<u64>, pending: &AtomicU64) {
+    // Read under the channel lock, so a reset cannot be overwritten.
+    notifier.send_if_modified(|current| {
+        let head = pending.load(Ordering::Acquire);
+        let modified = head != *current;
+        *current = head;
+        modified
+    });
+}
+
+/// State flushed by [`Storage::close`] and when the last `Storage` clone is dropped.
+#[derive(Debug)]
+struct ShutdownFlush {
+    env: Env,
+    blob_env: Option<Env>,
+    notifier: std::sync::Arc<tokio::sync::watch::Sender<u64>>,
+    coalesced_head: Option<Arc<AtomicU64>>,
+    /// Whether commits may still be unsynced, i.e. a relaxed `SyncMode`. Handles opened with
+    /// `skip_writer_lock` may write under the writer lease, so they are flushed too.
+    sync: bool,
+}
+
+impl ShutdownFlush {
+    fn flush(&self) -> Result<()> {
+        if let Some(head) = &self.coalesced_head {
+            publish_coalesced(&self.notifier, head);
+        }
+        if self.sync {
+            if let Some(blob_env) = &self.blob_env {
+                blob_env.force_sync()?;
+            }
+            self.env.force_sync()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ShutdownFlush {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            tracing::warn!("Flush on close failed: {}", e);
+        }
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            tracing::warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Opens (creating if needed) the separate blob environment next to the main one.
+fn open_blob_env(config: &StorageConfig, flags: heed::EnvFlags) -> Result<(Env, BlobDb)> {
+    let path = config.path.join(BLOB_ENV_DIR);
+    create_dir(&path, config.dir_mode)?;
+
+    // Safety: same flags as the main environment, see `Storage::open`.
+    let env = unsafe {
+        EnvOpenOptions::new()
+            .map_size(config.map_size)
+            .max_dbs(1)
+            .max_readers(config.max_readers)
+            .flags(flags)
+            .open(&path)?
+    };
+    let mut txn = env.write_txn()?;
+    let blobs = env.create_database(&mut txn, Some("blobs"))?;
+    txn.commit()?;
+    Ok((env, blobs))
+}
+
+/// Whether an I/O error opening the environment may go away on its own, e.g. while another
+/// process releases its file locks.
+fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::WouldBlock
+            | std::io::ErrorKind::ResourceBusy
+            | std::io::ErrorKind::Interrupted
+    )
+}
+
+/// Creates `path` and any missing parents, with permissions `mode` if given (Unix only).
+fn create_dir(path: &std::path::Path, mode: Option<u32>) -> Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(builder.create(path)?)
+}
+
+/// Files `open` restricts to their owner when `dir_mode` is set.
+#[cfg(unix)]
+const RESTRICTED_FILES: [&str; 3] = ["data.mdb", "lock.mdb", writer_lock::LOCK_FILE];
+
+/// Returns which of the [`RESTRICTED_FILES`] already exist in `dir`.
+#[cfg(unix)]
+fn existing_files(dir: &std::path::Path) -> Vec<&'static str> {
+    RESTRICTED_FILES
+        .into_iter()
+        .filter(|name| dir.join(name).exists())
+        .collect()
+}
+
+/// Restricts the files VarveDB keeps in `dir` to their owner.
+///
+/// Only the owner of a file may change its mode, so files in `existing`, which were there
+/// before this open, are skipped if that fails with `PermissionDenied`: another user, such
+/// as a reader next to the writer, may have created them. Files created by this open must
+/// be restricted.
+#[cfg(unix)]
+fn restrict_files(dir: &std::path::Path, existing: &[&str]) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    for name in RESTRICTED_FILES {
+        let path = dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        match std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+            Err(e)
+                if e.kind() == std::io::ErrorKind::PermissionDenied && existing.contains(&name) =>
+            {
+                tracing::debug!("Not restricting {}: {}", path.display(), e);
+            }
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Checks, or records for a new database, a one-byte setting that is fixed at creation.
+///
+/// Databases that predate a marker are treated as having marker `0` (the default setting).
+fn check_creation_marker(
+    meta: &MetaDb,
+    txn: &mut heed::RwTxn,
+    key: &str,
+    marker: u8,
+    is_new: bool,
+    what: &str,
+) -> Result<()> {
+    match meta.get(txn, key)? {
+        Some(&[stored]) if stored == marker => Ok(()),
+        None if marker == 0 || is_new => Ok(meta.put(txn, key, &[marker])?),
+        _ => Err(crate::error::Error::InvalidConfig(format!(
+            "{} mismatch: the database was created with a different setting",
+            what
+        ))),
+    }
+}
+
+/// Verifies the configured master keys against their check values, so a wrong key is
+/// reported on open rather than as a decryption failure on the first read.
+///
+/// Each master key version gets its own check value, sealed the first time the database is
+/// opened with that version as the latest one. Every configured version with a check value
+/// must match it, and at least one must have one, so after
+/// [`KeyManager::rewrap_all_keys`](crate::crypto::KeyManager::rewrap_all_keys) the older
+/// versions can be removed and the database still opens with only the latest.
+///
+/// Databases created before check values existed are verified against their first stream
+/// key instead, and get a check value once that succeeds.
+fn check_master_key(
+    meta: &MetaDb,
+    keystore: &KeyStoreDb,
+    txn: &mut heed::RwTxn,
+    config: &StorageConfig,
+) -> Result<()> {
+    let configured: Vec<(u8, &[u8; crate::constants::KEY_SIZE])> = config
+        .master_key
+        .iter()
+        .map(|key| (0, &**key))
+        .chain(
+            config
+                .master_keys
+                .iter()
+                .map(|(version, key)| (*version, &**key)),
+        )
+        .collect();
+    let Some(&(latest, latest_key)) = configured.last() else {
+        return Ok(());
+    };
+
+    let mut verified = false;
+    for &(version, master_key) in &configured {
+        let key = master_key_check_key(version);
+        if let Some(check) = meta.get(txn, &key)? {
+            match crate::crypto::decrypt(master_key, check, key.as_bytes()) {
+                Ok(plaintext) if plaintext == MASTER_KEY_CHECK_PLAINTEXT => verified = true,
+                _ => return Err(crate::error::Error::WrongMasterKey),
+            }
+        }
+    }
+
+    if !verified {
+        if meta
+            .prefix_iter(txn, MASTER_KEY_CHECK_KEY)?
+            .next()
+            .is_some()
+        {
+            return Err(crate::error::Error::InvalidConfig(
+                "none of the configured master key versions has been used with this database"
+                    .to_string(),
+            ));
+        }
+
+        // Predates check values: verify the first stream key, if its version is configured.
+        if let Some((stream_id, wrapped)) = keystore.first(txn)? {
+            let (version, encrypted_key) = crate::crypto::split_wrapped(wrapped);
+            if let Some(&(_, master_key)) = configured.iter().find(|(v, _)| *v == version) {
+                if crate::crypto::decrypt(master_key, encrypted_key, &stream_id.to_be_bytes())
+                    .is_err()
+                {
+                    return Err(crate::error::Error::WrongMasterKey);
+                }
+            }
+        }
+    }
+
+    let key = master_key_check_key(latest);
+    if meta.get(txn, &key)?.is_none() {
+        let check = crate::crypto::encrypt(latest_key, MASTER_KEY_CHECK_PLAINTEXT, key.as_bytes())?;
+        meta.put(txn, &key, &check)?;
+    }
+    Ok(())
+}
+
+/// Returns the `meta` key of the check value of master key `version`.
+fn master_key_check_key(version: u8) -> String {
+    if version == 0 {
+        MASTER_KEY_CHECK_KEY.to_string()
+    } else {
+        format!("{}_{}", MASTER_KEY_CHECK_KEY, version)
+    }
+}
+
+/// Rejects environments that already contain data but were not created by VarveDB.
+///
+/// An empty environment (freshly created by LMDB) is accepted so it can be initialized.
+/// Environments written by older releases predate the `meta` database and are recognized by
+/// their internal databases instead.
+fn check_existing_env(env: &Env, txn: &heed::RoTxn) -> Result<()> {
+    let Some(main) = env.open_database::<Bytes, DecodeIgnore>(txn, None)? else {
+        return Ok(());
+    };
+
+    let mut is_empty = true;
+    for result in main.iter(txn)? {
+        let (name, ()) = result?;
+        if INTERNAL_DATABASES.iter().any(|db| db.as_bytes() == name) {
+            return Ok(());
+        }
+        is_empty = false;
+    }
+
+    if is_empty {
+        Ok(())
+    } else {
+        Err(crate::error::Error::InvalidConfig(
+            "not a VarveDB database".to_string(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lock_map(env: &Env) -> Result<()> {
+    let file_len = env.real_disk_size()? as usize;
+    if file_len == 0 {
+        return Ok(());
+    }
+
+    let (base, map_len) = map_range(env)?;
+    let len = file_len.min(map_len);
+
+    // Safety: `[base, base + len)` lies within LMDB's mapping of the data file, as listed by
+    // the kernel. mlock does not modify the mapped memory.
+    let rc = unsafe { libc::mlock(base as *const libc::c_void, len) };
+    if rc != 0 {
+        return Err(crate::error::Error::MemoryLock(
+            std::io::Error::last_os_error(),
+        ));
+    }
+    Ok(())
+}
+
+/// Hints that the mapped memory `[start, end)` will be read soon. `start` is page-aligned.
+#[cfg(unix)]
+fn advise_willneed(start: usize, end: usize) {
+    // Safety: the range lies within LMDB's read-only mapping, which outlives this call, and
+    // MADV_WILLNEED only schedules readahead without changing the memory. The result is
+    // ignored since this is only a hint.
+    unsafe {
+        libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_WILLNEED);
+    }
+}
+
+#[cfg(not(unix))]
+fn advise_willneed(_start: usize, _end: usize) {}
+
+/// Finds the address and length of LMDB's memory map of the data file.
+///
+/// `mdb_env_info` only reports the map address for `MDB_FIXEDMAP` environments, so the
+/// mapping is looked up in `/proc/self/maps` by the device and inode of `data.mdb`.
+#[cfg(target_os = "linux")]
+fn map_range(env: &Env) -> Result<(usize, usize)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(env.path().join("data.mdb"))?;
+    let device = format!(
+        "{:02x}:{:02x}",
+        libc::major(metadata.dev()),
+        libc::minor(metadata.dev())
+    );
+    let inode = metadata.ino().to_string();
+
+    let maps = std::fs::read_to_string("/proc/self/maps")?;
+    for line in maps.lines() {
+        // address perms offset dev inode [pathname]
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [range, _, offset, dev, ino, ..] = fields.as_slice() else {
+            continue;
+        };
+        if *dev != device || *ino != inode || u64::from_str_radix(offset, 16) != Ok(0) {
+            continue;
+        }
+        let Some((start, end)) = range.split_once('-') else {
+            continue;
+        };
+        if let (Ok(start), Ok(end)) = (
+            usize::from_str_radix(start, 16),
+            usize::from_str_radix(end, 16),
+        ) {
+            return Ok((start, end - start));
+        }
+    }
+
+    Err(crate::error::Error::InvalidConfig(
+        "memory map of the data file not found".to_string(),
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_map(_env: &Env) -> Result<()> {
+    Err(crate::error::Error::InvalidConfig(
+        "lock_memory is only supported on Linux".to_string(),
+    ))
 }
@@ -0,0 +1,124 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+
+/// Name of the lock file created next to the LMDB files.
+pub(super) const LOCK_FILE: &str = "varvedb.lock";
+
+/// How many times (1ms apart) to retry while another handle in this process releases the lock.
+const RELEASE_RETRIES: u32 = 100;
+
+/// Writer locks held by this process, keyed by canonical database directory.
+///
+/// `flock` locks belong to open file descriptions, so a second `open` of the same lock file
+/// inside this process would conflict with the first. Opens within one process share the
+/// LMDB environment anyway, so they share the lock too.
+static HELD: OnceLock<Mutex<HashMap<PathBuf, Weak<WriterLock>>>> = OnceLock::new();
+
+/// An exclusive, advisory lock on a database directory, held by the process that writes to it.
+///
+/// The lock is an `flock` on `varvedb.lock` inside the database directory, so it is released
+/// by the OS if the process dies. Only enforced on Unix; elsewhere acquiring always succeeds.
+#[derive(Debug)]
+pub(crate) struct WriterLock {
+    key: PathBuf,
+    file: Option<File>,
+}
+
+impl WriterLock {
+    /// Takes the writer lock on `dir`, or shares it if this process already holds it.
+    ///
+    /// If another process holds the lock, tries again up to `retries` times, `retry_delay`
+    /// apart, in case it is just releasing it.
+    pub(crate) fn acquire(dir: &Path, retries: u32, retry_delay: Duration) -> Result<Arc<Self>> {
+        let key = dir.canonicalize()?;
+        let mut held = registry().lock().unwrap_or_else(|e| e.into_inner());
+        // A dead entry means another handle in this process is releasing the lock right now.
+        let releasing = match held.get(&key) {
+            Some(weak) => match weak.upgrade() {
+                Some(lock) => return Ok(lock),
+                None => true,
+            },
+            None => false,
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(key.join(LOCK_FILE))?;
+        let mut attempts = if releasing { RELEASE_RETRIES } else { 0 };
+        let mut retries = retries;
+        while let Err(e) = try_lock_exclusive(&file) {
+            if attempts > 0 {
+                attempts -= 1;
+                std::thread::sleep(Duration::from_millis(1));
+            } else if retries > 0 {
+                tracing::debug!("Retrying writer lock after: {}", e);
+                retries -= 1;
+                std::thread::sleep(retry_delay);
+            } else {
+                return Err(e);
+            }
+        }
+
+        let lock = Arc::new(Self {
+            key: key.clone(),
+            file: Some(file),
+        });
+        held.insert(key, Arc::downgrade(&lock));
+        Ok(lock)
+    }
+}
+
+impl Drop for WriterLock {
+    fn drop(&mut self) {
+        // Release the lock first; a concurrent `acquire` may be waiting for it while holding
+        // the registry.
+        drop(self.file.take());
+        let mut held = registry().lock().unwrap_or_else(|e| e.into_inner());
+        if held.get(&self.key).is_some_and(|w| w.strong_count() == 0) {
+            held.remove(&self.key);
+        }
+    }
+}
+
+fn registry() -> &'static Mutex<HashMap<PathBuf, Weak<WriterLock>>> {
+    HELD.get_or_init(Default::default)
+}
+
+#[cfg(unix)]
+fn try_lock_exclusive(file: &File) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: the descriptor is owned by `file` and stays open for the duration of the call.
+    let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if rc == 0 {
+        return Ok(());
+    }
+
+    let err = std::io::Error::last_os_error();
+    if err.kind() == std::io::ErrorKind::WouldBlock {
+        Err(crate::error::Error::InvalidConfig(
+            "database already open for writing".to_string(),
+        ))
+    } else {
+        Err(err.into())
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock_exclusive(_file: &File) -> Result<()> {
+    Ok(())
+}
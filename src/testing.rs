@@ -0,0 +1,127 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers for testing code built on VarveDB, such as projections.
+//!
+//! Enabled by the `testing` feature.
+
+use crate::processor::EventHandler;
+use rkyv::api::high::HighDeserializer;
+use rkyv::rancor::Error as RancorError;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An [`EventHandler`] that deserializes and records every event it handles.
+///
+/// Clones share the recorded events, so a test can keep one clone and hand another to a
+/// [`Processor`](crate::processor::Processor). See also
+/// [`Processor::drain_into_vec`](crate::processor::Processor::drain_into_vec).
+///
+/// # Examples
+///
+/// ```rust
+/// # use rkyv::{Archive, Deserialize, Serialize};
+/// # use tempfile::tempdir;
+/// # use varvedb::processor::Processor;
+/// # use varvedb::testing::CollectingHandler;
+/// # use varvedb::traits::MetadataExt;
+/// # use varvedb::{ExpectedVersion, Payload, Varve};
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// # struct MyEvent { value: u32 }
+/// #
+/// # #[derive(Archive, Serialize, Deserialize, Debug)]
+/// # struct Meta { stream_id: u128, version: u32 }
+/// #
+/// # impl MetadataExt for Meta {
+/// #     fn stream_id(&self) -> u128 { self.stream_id }
+/// #     fn version(&self) -> u32 { self.version }
+/// # }
+/// #
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempdir()?;
+/// let mut db = Varve::open(dir.path().join("test.mdb"))?;
+/// for value in 1..=3 {
+///     let meta = Meta { stream_id: 1, version: value };
+///     db.append(Payload::new(MyEvent { value }, meta), ExpectedVersion::Auto)?;
+/// }
+///
+/// let handler = CollectingHandler::<MyEvent>::new();
+/// let mut processor = Processor::new(&db, handler.clone(), 1u64);
+/// processor.catch_up()?;
+/// assert_eq!(handler.len(), 3);
+/// assert_eq!(handler.events()[0], MyEvent { value: 1 });
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CollectingHandler<E> {
+    events: Arc<Mutex<Vec<E>>>,
+}
+
+impl<E> CollectingHandler<E> {
+    /// Creates a handler with no recorded events.
+    pub fn new() -> Self {
+        Self {
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns how many events have been recorded.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no events have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns the recorded events, in the order they were handled, and clears them.
+    pub fn take(&self) -> Vec<E> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Returns a copy of the recorded events, in the order they were handled.
+    pub fn events(&self) -> Vec<E>
+    where
+        E: Clone,
+    {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<E>> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<E> Default for CollectingHandler<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Clone for CollectingHandler<E> {
+    fn clone(&self) -> Self {
+        Self {
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<E> EventHandler<E> for CollectingHandler<E>
+where
+    E: rkyv::Archive,
+    E::Archived: rkyv::Deserialize<E, HighDeserializer<RancorError>>,
+{
+    fn handle(&mut self, event: &E::Archived) -> crate::error::Result<()> {
+        let event = rkyv::deserialize::<E, RancorError>(event)
+            .map_err(|e| crate::error::Error::EventValidation(e.to_string()))?;
+        self.lock().push(event);
+        Ok(())
+    }
+}
@@ -27,3 +27,74 @@ pub trait MetadataExt {
     /// So this returns the version this event SHOULD have.
     fn version(&self) -> u32;
 }
+
+/// State rebuilt by folding the events of one stream.
+///
+/// Used by [`Writer::append_if`](crate::engine::Writer::append_if) to check a business rule
+/// against the stream before appending to it.
+pub trait StreamState<E>: Default
+where
+    E: rkyv::Archive,
+{
+    /// Applies the next event of the stream, in version order.
+    fn apply(&mut self, event: &E::Archived);
+}
+
+/// Trait for types that can be used as keys in a secondary index.
+///
+/// Keys are compared byte-wise by LMDB, so implementations should produce an encoding
+/// whose byte order matches the desired key order (e.g. big-endian for integers).
+pub trait IndexKey {
+    /// Encodes the key into the bytes stored in the index.
+    fn to_index_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_index_key_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl IndexKey for $t {
+                fn to_index_bytes(&self) -> Vec<u8> {
+                    self.to_be_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_index_key_for_int!(u8, u16, u32, u64, u128);
+
+impl IndexKey for str {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl IndexKey for String {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl IndexKey for [u8] {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl IndexKey for Vec<u8> {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl<const N: usize> IndexKey for [u8; N] {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl<T: IndexKey + ?Sized> IndexKey for &T {
+    fn to_index_bytes(&self) -> Vec<u8> {
+        (**self).to_index_bytes()
+    }
+}
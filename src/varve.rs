@@ -356,6 +356,41 @@ where
         self.writer.append(stream_id, version, payload.event)
     }
 
+    /// Appends several events, possibly to different streams, in one atomic transaction.
+    ///
+    /// The stream ID of each event is extracted from its metadata. Either all events are
+    /// committed or none is; see [`Writer::append_multi`] for details.
+    pub fn append_multi(
+        &mut self,
+        items: Vec<(Payload<E, M>, ExpectedVersion)>,
+    ) -> crate::error::Result<Vec<u64>> {
+        self.append_all(items)
+    }
+
+    /// Appends every payload of an iterator in one atomic transaction.
+    ///
+    /// Payloads are drained from `payloads` inside the write transaction and applied in
+    /// order, so [`ExpectedVersion::Auto`] sees the events of earlier payloads to the same
+    /// stream. If any version check or write fails, nothing is committed and the error is
+    /// returned. Returns the global sequence numbers of the events, in order.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let seqs = db.append_all(orders.into_iter().map(|order| {
+    ///     (Payload::new(order.placed_event(), order.metadata()), ExpectedVersion::Auto)
+    /// }))?;
+    /// ```
+    pub fn append_all(
+        &mut self,
+        payloads: impl IntoIterator<Item = (Payload<E, M>, ExpectedVersion)>,
+    ) -> crate::error::Result<Vec<u64>> {
+        let items = payloads
+            .into_iter()
+            .map(|(payload, expected)| (payload.metadata.stream_id(), expected, payload.event));
+        self.writer.append_iter(items)
+    }
+
     /// Creates a new read transaction for querying the database.
     ///
     /// Read transactions provide a consistent snapshot of the database at the time
@@ -462,9 +497,30 @@ where
         self.reader.get_by_stream(txn, stream_id, version)
     }
 
+    /// Retrieves the newest event in a stream together with its version.
+    ///
+    /// This is the "current state" lookup for an aggregate: it finds the highest version
+    /// and fetches that event in one call. Returns `Ok(None)` for an empty stream.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let txn = db.read_txn()?;
+    /// if let Some((version, event)) = db.get_latest(&txn, stream_id)? {
+    ///     println!("Stream is at version {}: {:?}", version, event);
+    /// }
+    /// ```
+    pub fn get_latest<'txn>(
+        &self,
+        txn: &'txn heed::RoTxn,
+        stream_id: u128,
+    ) -> crate::error::Result<Option<(u32, EventView<'txn, E>)>> {
+        self.reader.get_latest(txn, stream_id)
+    }
+
     fn get_last_stream_version(&self, stream_id: u128) -> crate::error::Result<u32> {
         let txn = self.storage.env.read_txn()?;
-        let stream_id_bytes = stream_id.to_be_bytes();
+        let stream_id_bytes = self.storage.stored_stream_id(stream_id).to_be_bytes();
         // Since StreamID is the first 16 bytes of the key, we can use prefix_iter
         let iter = self
             .storage
@@ -634,11 +690,11 @@ where
     pub fn collect_events(&self) -> crate::error::Result<Vec<EventView<'static, E>>> {
         let txn = self.storage.env.read_txn()?;
         let mut events = Vec::new();
-        let mut seq = 1u64;
 
-        while let Some(view) = self.reader.get(&txn, seq)? {
-            events.push(view.into_owned());
-            seq += 1;
+        for seq in self.storage.sequences(&txn, 1) {
+            if let Some(view) = self.reader.get(&txn, seq?)? {
+                events.push(view.into_owned());
+            }
         }
 
         Ok(events)
@@ -652,18 +708,213 @@ where
     /// making it safe to call from async code.
     pub fn count(&self) -> crate::error::Result<u64> {
         let txn = self.storage.env.read_txn()?;
-        let mut count = 0u64;
-        let mut seq = 1u64;
+        Ok(self.storage.events_log.len(&txn)?)
+    }
 
-        while self.reader.get(&txn, seq)?.is_some() {
+    /// Deletes all events, cursors and blobs, keeping the database open.
+    ///
+    /// See [`Storage::clear_all`]; stream keys are deleted too unless `keep_keys` is set.
+    pub fn clear_all(&self, keep_keys: bool) -> crate::error::Result<()> {
+        self.storage.clear_all(keep_keys)
+    }
+
+    /// Returns `true` if events are encrypted at rest.
+    pub fn is_encrypted(&self) -> bool {
+        self.storage.config.encryption_enabled
+    }
+
+    /// Checks that the store is usable, for liveness and readiness probes.
+    ///
+    /// Opens a read transaction, reads the head of the events log, measures how full the
+    /// memory map is and, for encrypted stores, decrypts a stored stream key with the master
+    /// key. Failures are reported in the returned [`HealthReport`] rather than as an error,
+    /// so a probe can always serialize the result.
+    ///
+    /// # Async Safety
+    ///
+    /// This method creates and drops its own transaction internally,
+    /// making it safe to call from async code.
+    pub fn health_check(&self) -> HealthReport {
+        let env = &self.storage.env;
+        let mut problems = Vec::new();
+
+        let map_size = env.info().map_size as u64;
+        // Measured before opening our own read transaction, since it opens one internally.
+        let map_used = env.non_free_pages_size().unwrap_or_else(|e| {
+            problems.push(format!("failed to measure map usage: {}", e));
+            0
+        });
+        let min_free = (map_size as f64 * crate::constants::HEALTH_MIN_FREE_MAP_FRACTION) as u64;
+        let low_space = map_size.saturating_sub(map_used) < min_free;
+        if low_space {
+            problems.push(format!(
+                "memory map almost full: {} of {} bytes used",
+                map_used, map_size
+            ));
+        }
+
+        let mut head_seq = None;
+        let mut master_key_ok = None;
+        match env.read_txn() {
+            Ok(txn) => {
+                match self.storage.events_log.last(&txn) {
+                    Ok(last) => head_seq = Some(last.map_or(0, |(seq, _)| seq)),
+                    Err(e) => problems.push(format!("failed to read events log: {}", e)),
+                }
+                if self.storage.config.encryption_enabled {
+                    master_key_ok = self.probe_master_key(&txn, &mut problems);
+                }
+            }
+            Err(e) => problems.push(format!("failed to open read transaction: {}", e)),
+        }
+
+        HealthReport {
+            healthy: problems.is_empty(),
+            head_seq,
+            map_size,
+            map_used,
+            low_space,
+            master_key_ok,
+            problems,
+        }
+    }
+
+    /// Decrypts the first stored stream key, if any, to confirm the master key is correct.
+    fn probe_master_key(&self, txn: &heed::RoTxn, problems: &mut Vec<String>) -> Option<bool> {
+        let stored_id = match self.storage.keystore.first(txn) {
+            Ok(Some((stored_id, _))) => stored_id,
+            Ok(None) => return None,
+            Err(e) => {
+                problems.push(format!("failed to read keystore: {}", e));
+                return Some(false);
+            }
+        };
+
+        let key_manager = crate::crypto::KeyManager::new(self.storage.clone());
+        match key_manager.get_stored_key_with_txn(txn, stored_id) {
+            Ok(_) => Some(true),
+            Err(e) => {
+                problems.push(format!("master key check failed: {}", e));
+                Some(false)
+            }
+        }
+    }
+
+    /// Returns the number of distinct streams that contain at least one event.
+    ///
+    /// Seeks from stream to stream in the stream index, so the cost grows with the number of
+    /// streams rather than the number of events.
+    ///
+    /// # Async Safety
+    ///
+    /// This method creates and drops its own transaction internally,
+    /// making it safe to call from async code.
+    pub fn stream_count(&self) -> crate::error::Result<usize> {
+        let txn = self.storage.env.read_txn()?;
+        let mut count = 0;
+        let mut next_key = [0u8; 20];
+
+        while let Some((key_bytes, _)) = self
+            .storage
+            .stream_index
+            .get_greater_than_or_equal_to(&txn, &next_key)?
+        {
             count += 1;
-            seq += 1;
+            // Key is [StreamID (16)][Version (4)]; jump to the first key of the next stream.
+            let stream_id = u128::from_be_bytes(key_bytes[..16].try_into().unwrap());
+            let Some(next_stream) = stream_id.checked_add(1) else {
+                break;
+            };
+            next_key = crate::storage::StreamKey::new(next_stream, 0).to_be_bytes();
         }
 
         Ok(count)
     }
 }
 
+/// The result of [`Varve::health_check`].
+///
+/// `healthy` is the single bit a readiness probe needs; the other fields explain it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// `true` if every check passed.
+    pub healthy: bool,
+    /// The global sequence number of the newest event (0 for an empty store), or `None` if
+    /// the events log could not be read.
+    pub head_seq: Option<u64>,
+    /// The size of the memory map in bytes (`StorageConfig::map_size`).
+    pub map_size: u64,
+    /// The bytes of the memory map occupied by data pages.
+    pub map_used: u64,
+    /// `true` if less than [`HEALTH_MIN_FREE_MAP_FRACTION`](crate::constants::HEALTH_MIN_FREE_MAP_FRACTION)
+    /// of the memory map is free, i.e. appends are at risk of failing with `MDB_MAP_FULL`.
+    pub low_space: bool,
+    /// For encrypted stores, whether the master key decrypted a stored stream key. `None` if
+    /// encryption is disabled or no stream key has been created yet.
+    pub master_key_ok: Option<bool>,
+    /// Human-readable descriptions of every failed check.
+    pub problems: Vec<String>,
+}
+
+impl<E, M> Varve<E, M>
+where
+    E: rkyv::Archive
+        + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, RancorError>>
+        + std::fmt::Debug
+        + Send
+        + Sync
+        + 'static,
+    E::Archived: for<'a> rkyv::bytecheck::CheckBytes<
+        rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>,
+    >,
+    M: MetadataExt,
+{
+    /// Subscribes to every event from `start_seq` on: first the stored backlog, then new
+    /// appends as they are committed.
+    ///
+    /// Events arrive as `(seq, view)` pairs in global sequence order, with no gap or duplicate
+    /// at the switch from backfill to live tailing: the subscription marks the current head as
+    /// seen before each read pass, so an append that commits while the backlog is being read
+    /// triggers another pass that starts right after the last event sent. The views are
+    /// detached from their transaction with [`EventView::into_owned`].
+    ///
+    /// The events are produced by a task spawned on the current Tokio runtime, which ends
+    /// when the receiver is dropped. If a read fails, the error is sent as the last item.
+    /// Wrap the receiver in `tokio_stream::wrappers::ReceiverStream` to use it as a `Stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let mut events = db.subscribe_from(1);
+    /// while let Some(event) = events.recv().await {
+    ///     let (seq, view) = event?;
+    ///     println!("{seq}: {:?}", view);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn subscribe_from(
+        &self,
+        start_seq: u64,
+    ) -> tokio::sync::mpsc::Receiver<crate::error::Result<(u64, EventView<'static, E>)>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(crate::constants::DEFAULT_BATCH_SIZE);
+        let reader = self.reader.clone();
+        tokio::spawn(async move {
+            let tailed = reader
+                .tail_into(start_seq, &tx, Reader::read_view_batch, |seq, view| {
+                    Ok((seq, view))
+                })
+                .await;
+            if let Err(e) = tailed {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+        rx
+    }
+}
+
 /// An iterator over events in the database.
 ///
 /// This iterator yields events in global sequence order (insertion order).
@@ -725,10 +976,20 @@ where
     type Item = crate::error::Result<crate::engine::EventView<'a, E>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        tracing::trace!("Iterating over event: {}", self.current_seq);
-        match self.reader.get(&self.txn, self.current_seq) {
+        // Trimmed streams leave holes in the log, so seek to the next record.
+        let seq = match self
+            .reader
+            .storage()
+            .next_sequence(&self.txn, self.current_seq)
+        {
+            Ok(Some(seq)) => seq,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        tracing::trace!("Iterating over event: {}", seq);
+        match self.reader.get(&self.txn, seq) {
             Ok(Some(view)) => {
-                self.current_seq += 1;
+                self.current_seq = seq + 1;
                 // Return an owned version of the event data to satisfy standard Iterator.
                 Some(Ok(view.into_owned()))
             }
@@ -992,6 +1253,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_append_all_is_atomic() {
+        let (mut varve, _dir) = create_temp_varve::<TestEvent, TestMetadata>();
+
+        // Auto versions see earlier items of the same call.
+        let seqs = varve
+            .append_all((1..=3).map(|value| {
+                (
+                    Payload::new(TestEvent { value }, TestMetadata::new(1, 0)),
+                    ExpectedVersion::Auto,
+                )
+            }))
+            .expect("append_all should succeed");
+        assert_eq!(seqs, vec![1, 2, 3]);
+
+        // A conflict in the middle rolls back the items before it.
+        let result = varve.append_all([
+            (
+                Payload::new(TestEvent { value: 40 }, TestMetadata::new(2, 1)),
+                ExpectedVersion::exact(1),
+            ),
+            (
+                Payload::new(TestEvent { value: 50 }, TestMetadata::new(1, 3)),
+                ExpectedVersion::exact(3),
+            ),
+        ]);
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::ConcurrencyConflict {
+                stream_id: 1,
+                version: 3
+            })
+        ));
+
+        let txn = varve.read_txn().expect("Failed to create txn");
+        assert!(varve
+            .get_by_stream(&txn, 2, 1)
+            .expect("Get should succeed")
+            .is_none());
+        drop(txn);
+        assert_eq!(varve.count().expect("Count should succeed"), 3);
+        let none = varve
+            .append_all(std::iter::empty())
+            .expect("Empty append_all should succeed");
+        assert!(none.is_empty());
+    }
+
     // =========================================================================
     // Read Tests
     // =========================================================================
@@ -1180,6 +1488,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_iter_with_complex_event() {
         let (mut varve, _dir) = create_temp_varve::<ComplexEvent, TestMetadata>();
 
@@ -1327,6 +1636,23 @@ mod tests {
         assert_eq!(after_skip[2].as_ref().unwrap().value, 10);
     }
 
+    #[test]
+    fn test_iter_try_collect_owned() {
+        use crate::engine::EventIterExt;
+
+        let (mut varve, _dir) = create_temp_varve::<TestEvent, TestMetadata>();
+        for i in 1..=3 {
+            let payload = Payload::new(TestEvent { value: i }, TestMetadata::new(1, i));
+            varve.append(payload, ExpectedVersion::exact(i)).unwrap();
+        }
+
+        let events = varve.iter().unwrap().try_collect_owned().unwrap();
+        assert_eq!(
+            events,
+            (1..=3).map(|value| TestEvent { value }).collect::<Vec<_>>()
+        );
+    }
+
     // =========================================================================
     // ExpectedVersion Tests
     // =========================================================================
@@ -1718,6 +2044,66 @@ mod tests {
         assert_eq!(varve.count().unwrap(), 5);
     }
 
+    #[test]
+    fn test_stream_count_and_encryption_status() {
+        let (mut varve, _dir) = create_temp_varve::<TestEvent, TestMetadata>();
+
+        assert!(!varve.is_encrypted());
+        assert_eq!(varve.stream_count().unwrap(), 0);
+
+        for (stream_id, version) in [(1, 1), (1, 2), (7, 1), (u128::MAX, 1), (7, 2)] {
+            let payload = Payload::new(
+                TestEvent { value: version },
+                TestMetadata::new(stream_id, version),
+            );
+            varve
+                .append(payload, ExpectedVersion::exact(version))
+                .unwrap();
+        }
+
+        assert_eq!(varve.stream_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_health_check() {
+        let (mut varve, _dir) = create_temp_varve::<TestEvent, TestMetadata>();
+
+        let report = varve.health_check();
+        assert!(report.healthy, "{:?}", report.problems);
+        assert_eq!(report.head_seq, Some(0));
+        assert!(!report.low_space);
+        assert_eq!(report.master_key_ok, None);
+
+        let payload = Payload::new(TestEvent { value: 1 }, TestMetadata::new(1, 1));
+        varve.append(payload, ExpectedVersion::exact(1)).unwrap();
+        assert_eq!(varve.health_check().head_seq, Some(1));
+    }
+
+    #[test]
+    fn test_health_check_detects_wrong_master_key() {
+        let dir = tempdir().unwrap();
+        let config = |key: u8| StorageConfig {
+            path: dir.path().to_path_buf(),
+            encryption_enabled: true,
+            master_key: Some(zeroize::Zeroizing::new([key; 32])),
+            ..Default::default()
+        };
+
+        let mut varve = Varve::<TestEvent, TestMetadata>::open_with_config(config(1)).unwrap();
+        // Nothing to probe until a stream key exists.
+        assert_eq!(varve.health_check().master_key_ok, None);
+        let payload = Payload::new(TestEvent { value: 1 }, TestMetadata::new(1, 1));
+        varve.append(payload, ExpectedVersion::exact(1)).unwrap();
+        assert_eq!(varve.health_check().master_key_ok, Some(true));
+        drop(varve);
+
+        // A wrong master key is now rejected on open.
+        assert!(matches!(
+            Varve::<TestEvent, TestMetadata>::open_with_config(config(2)),
+            Err(crate::error::Error::WrongMasterKey)
+        ));
+    }
+
     // =========================================================================
     // Compile-time Safety Tests (Iterator is !Send)
     // =========================================================================
@@ -1726,6 +2112,7 @@ mod tests {
     /// If Iter were Send, this would cause issues in async contexts.
     #[test]
     fn test_iter_is_not_send() {
+        #[allow(dead_code)]
         fn assert_not_send<T>() {}
         // This line would fail to compile if Iter implemented Send
         // We can't directly test !Send, but PhantomData<*const ()> ensures it
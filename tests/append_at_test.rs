@@ -0,0 +1,260 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{LogKey, Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct ReplicatedEvent {
+    value: u64,
+}
+
+fn event_bytes(value: u64) -> Vec<u8> {
+    rkyv::to_bytes::<rkyv::rancor::Error>(&ReplicatedEvent { value })
+        .unwrap()
+        .to_vec()
+}
+
+#[test]
+fn test_append_at_replicates_leader() -> Result<(), Box<dyn std::error::Error>> {
+    let leader_dir = tempdir()?;
+    let leader = Storage::open(StorageConfig {
+        path: leader_dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let follower_dir = tempdir()?;
+    let follower = Storage::open(StorageConfig {
+        path: follower_dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+
+    let mut writer = Writer::<ReplicatedEvent>::new(leader.clone());
+    writer.append(1, 1, ReplicatedEvent { value: 10 })?;
+    writer.append(2, 1, ReplicatedEvent { value: 20 })?;
+    writer.append(1, 2, ReplicatedEvent { value: 30 })?;
+
+    let leader_reader = Reader::<ReplicatedEvent>::new(leader.clone());
+    let mut follower_writer = Writer::<ReplicatedEvent>::new(follower.clone());
+    let records = [(1, 1, 1), (2, 2, 1), (3, 1, 2)];
+    {
+        let txn = leader.env.read_txn()?;
+        for (seq, stream_id, version) in records {
+            let view = leader_reader.get(&txn, seq)?.unwrap();
+            follower_writer.append_at(seq, stream_id, version, view.to_bytes())?;
+        }
+    }
+
+    let reader = Reader::<ReplicatedEvent>::new(follower.clone());
+    let txn = follower.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 2)?.unwrap().value, 20);
+    assert_eq!(reader.get_by_stream(&txn, 1, 2)?.unwrap().value, 30);
+    drop(txn);
+
+    // Regular appends continue after the replicated head.
+    assert_eq!(
+        follower_writer.append(1, 3, ReplicatedEvent { value: 40 })?,
+        4
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_append_at_rejects_occupied_sequence_and_gaps() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ReplicatedEvent>::new(storage.clone());
+    writer.append_at(1, 1, 1, &event_bytes(1))?;
+
+    assert!(matches!(
+        writer.append_at(1, 2, 1, &event_bytes(2)),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+    // The stream index stays consistent: version 1 of stream 1 is taken.
+    assert!(matches!(
+        writer.append_at(2, 1, 1, &event_bytes(2)),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+    assert!(matches!(
+        writer.append_at(3, 2, 1, &event_bytes(3)),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        writer.append_at(0, 2, 1, &event_bytes(3)),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(writer.append_at(2, 2, 1, &[0xFF; 3]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_append_at_with_gaps_allowed() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ReplicatedEvent>::new(storage.clone()).with_allow_gaps(true);
+
+    writer.append_at(5, 1, 2, &event_bytes(50))?;
+    // Filling the hole afterwards is fine too.
+    writer.append_at(2, 1, 1, &event_bytes(20))?;
+
+    let reader = Reader::<ReplicatedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert!(reader.get(&txn, 1)?.is_none());
+    assert_eq!(reader.get(&txn, 2)?.unwrap().value, 20);
+    assert_eq!(reader.get_by_stream(&txn, 1, 2)?.unwrap().value, 50);
+    assert_eq!(*storage.notifier_rx.borrow(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_append_at_keeps_stream_order_when_filling_gaps() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ReplicatedEvent>::new(storage.clone()).with_allow_gaps(true);
+
+    writer.append_at(3, 1, 2, &event_bytes(20))?;
+    writer.append_at(6, 1, 4, &event_bytes(40))?;
+
+    // Version 1 must come before version 2, and version 3 between versions 2 and 4.
+    assert!(matches!(
+        writer.append_at(4, 1, 1, &event_bytes(10)),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        writer.append_at(2, 1, 3, &event_bytes(30)),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        writer.append_at(7, 1, 3, &event_bytes(30)),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    writer.append_at(1, 1, 1, &event_bytes(10))?;
+    writer.append_at(5, 1, 3, &event_bytes(30))?;
+
+    let reader = Reader::<ReplicatedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    reader.verify_stream_monotonic(&txn, 1)?;
+    assert!(reader.get(&txn, 4)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_append_at_gives_gap_fills_ordered_event_ids() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        log_key: LogKey::U128,
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ReplicatedEvent>::new(storage.clone()).with_allow_gaps(true);
+
+    writer.append_at(1, 1, 1, &event_bytes(10))?;
+    writer.append_at(3, 3, 1, &event_bytes(30))?;
+    writer.append_at(2, 2, 1, &event_bytes(20))?;
+
+    let reader = Reader::<ReplicatedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let entries = reader
+        .iter_by_id(&txn, ..)?
+        .map(|entry| entry.map(|(id, seq, _)| (id, seq)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let seqs: Vec<_> = entries.iter().map(|&(_, seq)| seq).collect();
+    assert_eq!(seqs, [1, 2, 3]);
+
+    let (id, _) = entries[1];
+    let (seq, view) = reader.get_by_id(&txn, id)?.unwrap();
+    assert_eq!(seq, 2);
+    assert_eq!(view.value, 20);
+
+    Ok(())
+}
+
+#[test]
+fn test_append_at_replay_is_idempotent() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ReplicatedEvent>::new(storage.clone());
+    writer.append_at(1, 1, 1, &event_bytes(10))?;
+    writer.append_at(2, 1, 2, &event_bytes(20))?;
+
+    // Re-applying identical records succeeds, even below the head.
+    writer.append_at(1, 1, 1, &event_bytes(10))?;
+    writer.append_at(2, 1, 2, &event_bytes(20))?;
+
+    // Same slot, different bytes or a different stream position is still a conflict.
+    assert!(matches!(
+        writer.append_at(2, 1, 2, &event_bytes(21)),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+    assert!(matches!(
+        writer.append_at(2, 2, 1, &event_bytes(20)),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+
+    let reader = Reader::<ReplicatedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 2)?.unwrap().value, 20);
+    assert!(reader.get(&txn, 3)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_append_returning_bytes_feeds_follower() -> Result<(), Box<dyn std::error::Error>> {
+    let leader_dir = tempdir()?;
+    let leader = Storage::open(StorageConfig {
+        path: leader_dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let follower_dir = tempdir()?;
+    let follower = Storage::open(StorageConfig {
+        path: follower_dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+
+    let mut writer = Writer::<ReplicatedEvent>::new(leader.clone());
+    let mut follower_writer = Writer::<ReplicatedEvent>::new(follower.clone());
+    for (stream_id, version, value) in [(1, 1, 10), (2, 1, 20), (1, 2, 30)] {
+        let (seq, bytes) =
+            writer.append_returning_bytes(stream_id, version, ReplicatedEvent { value })?;
+        assert_eq!(bytes, event_bytes(value));
+        follower_writer.append_at(seq, stream_id, version, &bytes)?;
+    }
+
+    // The returned bytes are exactly what a read hands back.
+    let reader = Reader::<ReplicatedEvent>::new(leader.clone());
+    let txn = leader.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 3)?.unwrap().to_bytes(), event_bytes(30));
+    drop(txn);
+
+    let reader = Reader::<ReplicatedEvent>::new(follower.clone());
+    let txn = follower.env.read_txn()?;
+    assert_eq!(reader.get_by_stream(&txn, 1, 2)?.unwrap().value, 30);
+
+    Ok(())
+}
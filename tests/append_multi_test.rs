@@ -0,0 +1,188 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::{ExpectedVersion, StreamState};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct AccountEvent {
+    delta: i64,
+}
+
+#[test]
+fn test_append_multi_commits_all_streams() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<AccountEvent>::new(storage.clone());
+    writer.append(1, 1, AccountEvent { delta: 100 })?;
+
+    let seqs = writer.append_multi(vec![
+        (1, ExpectedVersion::exact(2), AccountEvent { delta: -30 }),
+        (2, ExpectedVersion::Auto, AccountEvent { delta: 30 }),
+        // Auto sees the event written earlier in the same call.
+        (2, ExpectedVersion::Auto, AccountEvent { delta: 5 }),
+    ])?;
+    assert_eq!(seqs, vec![2, 3, 4]);
+
+    let reader = Reader::<AccountEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get_by_stream(&txn, 1, 2)?.unwrap().delta, -30);
+    assert_eq!(reader.get_by_stream(&txn, 2, 1)?.unwrap().delta, 30);
+    assert_eq!(reader.get_by_stream(&txn, 2, 2)?.unwrap().delta, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_append_multi_rolls_back_on_conflict() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<AccountEvent>::new(storage.clone());
+    writer.append(2, 1, AccountEvent { delta: 100 })?;
+
+    let result = writer.append_multi(vec![
+        (1, ExpectedVersion::exact(1), AccountEvent { delta: -30 }),
+        (2, ExpectedVersion::exact(1), AccountEvent { delta: 30 }),
+    ]);
+    assert!(matches!(
+        result,
+        Err(Error::ConcurrencyConflict {
+            stream_id: 2,
+            version: 1
+        })
+    ));
+
+    // Nothing from the failed batch is visible, including the valid first item.
+    let reader = Reader::<AccountEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert!(reader.get_by_stream(&txn, 1, 1)?.is_none());
+    assert!(reader.get(&txn, 2)?.is_none());
+    assert_eq!(reader.get_by_stream(&txn, 2, 1)?.unwrap().delta, 100);
+    drop(txn);
+
+    // The writer remains usable and sequence numbers have no gaps.
+    assert_eq!(writer.append(1, 1, AccountEvent { delta: 1 })?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_append_and_commit_cursor_is_atomic() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<AccountEvent>::new(storage.clone());
+    writer.append(1, 1, AccountEvent { delta: 10 })?;
+    writer.append(1, 2, AccountEvent { delta: 20 })?;
+
+    // A projection consumes seq 1 and emits a derived event into stream 9.
+    let seq = writer.append_and_commit_cursor(
+        9,
+        ExpectedVersion::Auto,
+        AccountEvent { delta: 10 },
+        7,
+        1,
+    )?;
+    assert_eq!(seq, 3);
+
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.consumer_cursors.get(&txn, &7)?, Some(1));
+    drop(txn);
+
+    // A failed append leaves the cursor where it was.
+    assert!(matches!(
+        writer.append_and_commit_cursor(
+            9,
+            ExpectedVersion::exact(1),
+            AccountEvent { delta: 20 },
+            7,
+            2
+        ),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+
+    let reader = Reader::<AccountEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.consumer_cursors.get(&txn, &7)?, Some(1));
+    assert_eq!(reader.get_by_stream(&txn, 9, 1)?.unwrap().delta, 10);
+    assert!(reader.get(&txn, 4)?.is_none());
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Balance(i64);
+
+impl StreamState<AccountEvent> for Balance {
+    fn apply(&mut self, event: &ArchivedAccountEvent) {
+        self.0 += event.delta.to_native();
+    }
+}
+
+#[test]
+fn test_append_if_checks_predicate_atomically() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<AccountEvent>::new(storage.clone());
+    writer.append(1, 1, AccountEvent { delta: 100 })?;
+
+    // Several writers race to withdraw 60; the balance covers only one of them.
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let mut writer = writer.clone();
+            std::thread::spawn(move || {
+                writer.append_if(
+                    1,
+                    ExpectedVersion::Auto,
+                    AccountEvent { delta: -60 },
+                    |balance: &Balance| balance.0 >= 60,
+                )
+            })
+        })
+        .collect();
+    let results = handles
+        .into_iter()
+        .map(|h| h.join().unwrap())
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(results.iter().filter(|r| r.is_some()).count(), 1);
+    assert!(results.contains(&Some(2)));
+
+    // A rejected append writes nothing.
+    let reader = Reader::<AccountEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get_by_stream(&txn, 1, 2)?.unwrap().delta, -60);
+    assert!(reader.get(&txn, 3)?.is_none());
+    drop(txn);
+
+    // An exact version that is already taken still conflicts.
+    let result = writer.append_if(
+        1,
+        ExpectedVersion::exact(2),
+        AccountEvent { delta: 10 },
+        |_: &Balance| true,
+    );
+    assert!(matches!(result, Err(Error::ConcurrencyConflict { .. })));
+
+    Ok(())
+}
@@ -63,3 +63,100 @@ fn test_basic_write_read() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct SharedEvent {
+    pub left: std::sync::Arc<String>,
+    pub right: std::sync::Arc<String>,
+}
+
+#[test]
+fn test_shared_pointers_are_stored_once() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<SharedEvent>::new(storage.clone());
+
+    let body = "x".repeat(512);
+    let shared = std::sync::Arc::new(body.clone());
+    writer.append(
+        1,
+        1,
+        SharedEvent {
+            left: shared.clone(),
+            right: shared,
+        },
+    )?;
+    writer.append(
+        2,
+        1,
+        SharedEvent {
+            left: std::sync::Arc::new(body.clone()),
+            right: std::sync::Arc::new(body),
+        },
+    )?;
+
+    let reader = Reader::<SharedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let aliased = reader.get(&txn, 1)?.unwrap();
+    let distinct = reader.get(&txn, 2)?.unwrap();
+
+    // The aliased string is archived once and both fields point at it.
+    assert!(std::ptr::eq(&*aliased.left, &*aliased.right));
+    assert!(aliased.to_bytes().len() + 512 <= distinct.to_bytes().len());
+
+    // Deserializing restores the sharing.
+    let event = aliased.try_deserialize()?;
+    assert!(std::sync::Arc::ptr_eq(&event.left, &event.right));
+    let event = distinct.try_deserialize()?;
+    assert!(!std::sync::Arc::ptr_eq(&event.left, &event.right));
+
+    Ok(())
+}
+
+#[test]
+fn test_create_stream_assigns_unique_ids() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    let event = |kind| {
+        SystemEvent::V1(EventV1 {
+            stream_id: 0,
+            kind,
+            timestamp: 0,
+            payload: Vec::new(),
+        })
+    };
+
+    let mut created = std::collections::HashMap::new();
+    for kind in 0..100 {
+        let (stream_id, seq) = writer.create_stream(event(kind))?;
+        assert_eq!(seq, kind as u64 + 1);
+        assert!(created.insert(stream_id, kind).is_none());
+    }
+
+    let reader = Reader::<SystemEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    for (&stream_id, &kind) in &created {
+        let view = reader.get_by_stream(&txn, stream_id, 1)?.unwrap();
+        let ArchivedSystemEvent::V1(stored) = &*view;
+        assert_eq!(stored.kind, kind);
+    }
+    drop(txn);
+
+    // The assigned stream is an ordinary stream: version 1 is taken.
+    let (stream_id, _) = writer.create_stream(event(100))?;
+    assert!(matches!(
+        writer.append(stream_id, 1, event(101)),
+        Err(varvedb::error::Error::ConcurrencyConflict { .. })
+    ));
+    writer.append(stream_id, 2, event(101))?;
+
+    Ok(())
+}
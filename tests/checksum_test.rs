@@ -0,0 +1,210 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+struct BalanceEvent {
+    amount: u64,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+struct LargeEvent {
+    payload: Vec<u8>,
+}
+
+fn open(path: &std::path::Path, verify_checksums: bool) -> Storage {
+    let config = StorageConfig {
+        path: path.to_path_buf(),
+        verify_checksums,
+        ..Default::default()
+    };
+    Storage::open(config).unwrap()
+}
+
+#[test]
+fn test_checksum_detects_payload_bit_flip() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open(dir.path(), false);
+    let mut writer = Writer::<BalanceEvent>::new(storage.clone());
+    writer.append(1, 1, BalanceEvent { amount: 100 })?;
+
+    // The inline event bytes come first in the record; flipping a bit in the
+    // `amount` field leaves a structurally valid archive.
+    let mut wtxn = storage.env.write_txn()?;
+    let mut record = storage.events_log.get(&wtxn, &1)?.unwrap().to_vec();
+    record[0] ^= 0x01;
+    storage.events_log.put(&mut wtxn, &1, &record)?;
+    wtxn.commit()?;
+
+    // Without verification the corruption goes unnoticed.
+    let reader = Reader::<BalanceEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().amount, 101);
+    drop(txn);
+
+    let storage = open(dir.path(), true);
+    let reader = Reader::<BalanceEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    match reader.get(&txn, 1) {
+        Err(varvedb::Error::AtSequence { seq: 1, source }) => match *source {
+            varvedb::Error::EventValidation(msg) => assert_eq!(msg, "checksum mismatch"),
+            other => panic!("Expected checksum mismatch, got {:?}", other),
+        },
+        other => panic!("Expected checksum mismatch, got {:?}", other.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_checksum_detects_blob_bit_flip() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open(dir.path(), true);
+    let mut writer = Writer::<LargeEvent>::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        LargeEvent {
+            payload: vec![7u8; 4096],
+        },
+    )?;
+
+    let reader = Reader::<LargeEvent>::new(storage.clone());
+    {
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().payload.len(), 4096);
+    }
+
+    let mut wtxn = storage.env.write_txn()?;
+    let (hash, blob) = storage.blobs.first(&wtxn)?.unwrap();
+    let (hash, mut blob) = (hash.to_vec(), blob.to_vec());
+    blob[0] ^= 0x01;
+    storage.blobs.put(&mut wtxn, &hash, &blob)?;
+    wtxn.commit()?;
+
+    let txn = storage.env.read_txn()?;
+    let err = reader.get(&txn, 1).map(|_| ()).unwrap_err();
+    assert!(matches!(err, varvedb::Error::AtSequence { seq: 1, .. }));
+    assert!(matches!(err.root(), varvedb::Error::EventValidation(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_iter_lossy_skips_corrupt_records() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open(dir.path(), true);
+    let mut writer = Writer::<BalanceEvent>::new(storage.clone());
+    for amount in 1..=3 {
+        writer.append(1, amount as u32, BalanceEvent { amount })?;
+    }
+
+    let mut wtxn = storage.env.write_txn()?;
+    let mut record = storage.events_log.get(&wtxn, &2)?.unwrap().to_vec();
+    record[0] ^= 0x01;
+    storage.events_log.put(&mut wtxn, &2, &record)?;
+    wtxn.commit()?;
+
+    let reader = Reader::<BalanceEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let mut good = Vec::new();
+    let mut bad = Vec::new();
+    for (seq, result) in reader.iter_lossy(&txn)? {
+        match result {
+            Ok(event) => good.push((seq, event.amount.to_native())),
+            Err(e) => {
+                // The error names the record it came from.
+                assert!(matches!(e, varvedb::Error::AtSequence { seq: s, .. } if s == seq));
+                bad.push(seq);
+            }
+        }
+    }
+
+    assert_eq!(good, vec![(1, 1), (3, 3)]);
+    assert_eq!(bad, vec![2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_blob_placeholder() -> Result<(), Box<dyn std::error::Error>> {
+    use sha2::{Digest, Sha256};
+    use varvedb::engine::ReadOutcome;
+    use varvedb::storage::MissingBlob;
+
+    let dir = tempdir()?;
+    let storage = open(dir.path(), false);
+    let mut writer = Writer::<LargeEvent>::new(storage.clone());
+    let events: Vec<LargeEvent> = (1..=3u8)
+        .map(|fill| LargeEvent {
+            payload: vec![fill; 4096],
+        })
+        .collect();
+    for (version, event) in events.iter().enumerate() {
+        writer.append(
+            1,
+            version as u32 + 1,
+            LargeEvent {
+                payload: event.payload.clone(),
+            },
+        )?;
+    }
+
+    // Lose the blob behind the second event.
+    let hash = Sha256::digest(rkyv::to_bytes::<rkyv::rancor::Error>(&events[1])?);
+    let mut wtxn = storage.env.write_txn()?;
+    assert!(storage.blobs.delete(&mut wtxn, hash.as_slice())?);
+    wtxn.commit()?;
+
+    // By default the read fails like any other validation error.
+    let reader = Reader::<LargeEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let err = reader.get(&txn, 2).map(|_| ()).unwrap_err();
+    assert!(matches!(err, varvedb::Error::AtSequence { seq: 2, .. }));
+    assert!(matches!(err.root(), varvedb::Error::EventValidation(_)));
+    drop(txn);
+
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        on_missing_blob: MissingBlob::ReturnPlaceholder,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let reader = Reader::<LargeEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+
+    match reader.get_or_skip(&txn, 2)? {
+        Some(ReadOutcome::MissingBody { hash: missing }) => assert_eq!(missing, hash.as_slice()),
+        other => panic!("Expected MissingBody, got {:?}", other),
+    }
+    assert!(matches!(
+        reader.get_or_skip(&txn, 3)?,
+        Some(ReadOutcome::Known(_))
+    ));
+
+    // A scan reports exactly which sequences lost their bodies.
+    let lost: Vec<u64> = reader
+        .iter_lossy(&txn)?
+        .filter_map(|(seq, result)| match result {
+            Err(varvedb::Error::BlobMissing { seq: missing, .. }) => {
+                assert_eq!(missing, seq);
+                Some(seq)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lost, vec![2]);
+
+    Ok(())
+}
@@ -27,6 +27,7 @@ fn test_crypto_shredding() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: true,
         master_key: Some(zeroize::Zeroizing::new([1u8; 32])), // Use a dummy master key for crypto test
+        ..Default::default()
     };
 
     let storage = Storage::open(config)?;
@@ -73,3 +74,33 @@ fn test_crypto_shredding() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_cached_keys_follow_the_keystore() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let cached = KeyManager::new(storage.clone());
+    let other = KeyManager::new(storage);
+
+    let key = cached.get_or_create_key(7)?;
+    assert_eq!(cached.get_key(7)?, Some(key.clone()));
+    assert_eq!(cached.get_key(7)?, Some(key.clone()));
+    assert!(!format!("{:?}", cached).contains(&format!("{:?}", *key)));
+
+    // A key shredded through another manager is not served from the cache.
+    other.delete_key(7)?;
+    assert_eq!(cached.get_key(7)?, None);
+
+    // Nor is a stale key once the stream is re-keyed.
+    let new_key = other.get_or_create_key(7)?;
+    assert_ne!(key, new_key);
+    assert_eq!(cached.get_key(7)?, Some(new_key));
+
+    Ok(())
+}
@@ -116,3 +116,423 @@ fn test_disk_inspection() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_master_key_rotation() -> Result<(), Box<dyn std::error::Error>> {
+    use varvedb::crypto::KeyManager;
+
+    let dir = tempdir()?;
+    let base = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        ..Default::default()
+    };
+    let mut rotated = base.clone();
+    rotated
+        .master_keys
+        .insert(1, zeroize::Zeroizing::new([2u8; 32]));
+
+    let secret = |s: &str| SecretEvent {
+        secret_data: s.to_string(),
+    };
+    {
+        let storage = Storage::open(base.clone())?;
+        Writer::new(storage).append(1, 1, secret("old"))?;
+    }
+
+    // After the rotation, new keys use version 1 while old ones stay readable.
+    {
+        let storage = Storage::open(rotated.clone())?;
+        Writer::new(storage.clone()).append(2, 1, secret("new"))?;
+        let keys = KeyManager::new(storage.clone());
+        assert_eq!(keys.key_version(1)?, Some(0));
+        assert_eq!(keys.key_version(2)?, Some(1));
+        assert_eq!(keys.key_version(3)?, None);
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().secret_data, "old");
+        assert_eq!(reader.get(&txn, 2)?.unwrap().secret_data, "new");
+    }
+
+    // Without version 1, keys wrapped with it can't be unwrapped.
+    {
+        let storage = Storage::open(base.clone())?;
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().secret_data, "old");
+        let err = reader.get(&txn, 2).unwrap_err();
+        assert!(
+            matches!(err.root(), varvedb::Error::InvalidConfig(_)),
+            "{:?}",
+            err
+        );
+    }
+
+    // Re-wrapping moves every key to the latest version.
+    {
+        let storage = Storage::open(rotated.clone())?;
+        let keys = KeyManager::new(storage.clone());
+        assert_eq!(keys.rewrap_all_keys()?, 1);
+        assert_eq!(keys.rewrap_all_keys()?, 0);
+        assert_eq!(keys.key_version(1)?, Some(1));
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().secret_data, "old");
+        assert_eq!(reader.get(&txn, 2)?.unwrap().secret_data, "new");
+    }
+
+    // Once every key is re-wrapped, version 0 can be dropped, and version 1 is still checked.
+    let mut latest_only = rotated.clone();
+    latest_only.master_key = None;
+    {
+        let storage = Storage::open(latest_only.clone())?;
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get(&txn, 1)?.unwrap().secret_data, "old");
+        assert_eq!(reader.get(&txn, 2)?.unwrap().secret_data, "new");
+    }
+    let mut wrong = latest_only.clone();
+    wrong
+        .master_keys
+        .insert(1, zeroize::Zeroizing::new([9u8; 32]));
+    assert!(matches!(
+        Storage::open(wrong),
+        Err(varvedb::Error::WrongMasterKey)
+    ));
+    // A key set with no version the database has seen can't be verified at all.
+    let mut unknown = latest_only.clone();
+    unknown.master_keys = [(2, zeroize::Zeroizing::new([3u8; 32]))].into();
+    assert!(matches!(
+        Storage::open(unknown),
+        Err(varvedb::Error::InvalidConfig(_))
+    ));
+
+    // Version 0 can't be given twice.
+    let mut invalid = base.clone();
+    invalid
+        .master_keys
+        .insert(0, zeroize::Zeroizing::new([3u8; 32]));
+    assert!(matches!(
+        Storage::open(invalid),
+        Err(varvedb::Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_get_into_reuses_scratch() -> Result<(), Box<dyn std::error::Error>> {
+    for encryption_enabled in [false, true] {
+        let dir = tempdir()?;
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            encryption_enabled,
+            master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+            ..Default::default()
+        };
+        let storage = Storage::open(config)?;
+        let mut writer = Writer::new(storage.clone());
+        for i in 1..=10 {
+            let secret_data = format!("secret {}", i);
+            writer.append(1, i, SecretEvent { secret_data })?;
+        }
+        // Large enough to be offloaded to a blob.
+        let large = "x".repeat(8 * 1024);
+        writer.append(
+            2,
+            1,
+            SecretEvent {
+                secret_data: large.clone(),
+            },
+        )?;
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        let mut scratch = rkyv::util::AlignedVec::new();
+        scratch.reserve(1024);
+        let buffer = scratch.as_ptr();
+        for seq in 1..=10 {
+            let event = reader.get_into(&txn, seq, &mut scratch)?.unwrap();
+            assert_eq!(event.secret_data, format!("secret {}", seq));
+        }
+        // Small events fit in the buffer, so it was never reallocated.
+        assert_eq!(scratch.as_ptr(), buffer);
+
+        let event = reader.get_into(&txn, 11, &mut scratch)?.unwrap();
+        assert_eq!(event.secret_data, large);
+        assert!(reader.get_into(&txn, 12, &mut scratch)?.is_none());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_wrong_master_key_rejected_on_open() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = |key: u8| StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([key; 32])),
+        ..Default::default()
+    };
+
+    // The check value is written at creation, before any stream key exists.
+    drop(Storage::open(config(1))?);
+    assert!(matches!(
+        Storage::open(config(2)),
+        Err(varvedb::error::Error::WrongMasterKey)
+    ));
+
+    let storage = Storage::open(config(1))?;
+    let mut writer = Writer::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        SecretEvent {
+            secret_data: "Top Secret".to_string(),
+        },
+    )?;
+
+    // Databases without a check value are verified against their first stream key.
+    let mut txn = storage.env.write_txn()?;
+    storage.meta.delete(&mut txn, "master_key_check")?;
+    txn.commit()?;
+    drop(writer);
+    drop(storage);
+    assert!(matches!(
+        Storage::open(config(2)),
+        Err(varvedb::error::Error::WrongMasterKey)
+    ));
+
+    let storage = Storage::open(config(1))?;
+    let txn = storage.env.read_txn()?;
+    assert!(storage.meta.get(&txn, "master_key_check")?.is_some());
+    let reader = Reader::<SecretEvent>::new(storage.clone());
+    assert!(reader.get(&txn, 1)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_stored_and_decoded_size() -> Result<(), Box<dyn std::error::Error>> {
+    for encryption_enabled in [false, true] {
+        let dir = tempdir()?;
+        let storage = Storage::open(StorageConfig {
+            path: dir.path().to_path_buf(),
+            encryption_enabled,
+            master_key: encryption_enabled.then(|| zeroize::Zeroizing::new([1u8; 32])),
+            ..Default::default()
+        })?;
+        let mut writer = Writer::new(storage.clone());
+        let (_, event_bytes) = writer.append_returning_bytes(
+            1,
+            1,
+            SecretEvent {
+                secret_data: "Top Secret".repeat(10),
+            },
+        )?;
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        let stored = reader.stored_size(&txn, 1)?.unwrap();
+        let decoded = reader.decoded_size(&txn, 1)?.unwrap();
+        assert_eq!(decoded, event_bytes.len());
+        assert_eq!(decoded, reader.get(&txn, 1)?.unwrap().to_bytes().len());
+        // The envelope (and, when encrypted, stream ID, nonce and tag) come on top.
+        assert!(stored > decoded);
+        if encryption_enabled {
+            assert!(stored >= decoded + 16 + 12 + 16);
+        }
+
+        assert_eq!(reader.stored_size(&txn, 2)?, None);
+        assert_eq!(reader.decoded_size(&txn, 2)?, None);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rewrap_keys_in_batches_stops_when_cancelled() -> Result<(), Box<dyn std::error::Error>> {
+    use varvedb::crypto::KeyManager;
+    use varvedb::processor::CancellationToken;
+
+    let dir = tempdir()?;
+    let base = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        ..Default::default()
+    };
+    let mut rotated = base.clone();
+    rotated
+        .master_keys
+        .insert(1, zeroize::Zeroizing::new([2u8; 32]));
+
+    {
+        let storage = Storage::open(base)?;
+        let mut writer = Writer::new(storage);
+        for stream_id in 1..=5 {
+            writer.append(
+                stream_id,
+                1,
+                SecretEvent {
+                    secret_data: format!("stream {}", stream_id),
+                },
+            )?;
+        }
+    }
+
+    let storage = Storage::open(rotated)?;
+    let keys = KeyManager::new(storage.clone());
+    let cancelled = CancellationToken::new();
+    cancelled.cancel();
+    assert_eq!(keys.rewrap_keys_in_batches(2, &cancelled)?, 0);
+    assert_eq!(keys.key_version(1)?, Some(0));
+
+    let token = CancellationToken::new();
+    assert!(matches!(
+        keys.rewrap_keys_in_batches(0, &token),
+        Err(varvedb::Error::InvalidConfig(_))
+    ));
+    assert_eq!(keys.rewrap_keys_in_batches(2, &token)?, 5);
+    assert_eq!(keys.rewrap_keys_in_batches(2, &token)?, 0);
+    for stream_id in 1..=5 {
+        assert_eq!(keys.key_version(stream_id)?, Some(1));
+    }
+
+    let reader = Reader::<SecretEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 5)?.unwrap().secret_data, "stream 5");
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypted_streams_only_encrypts_listed_streams() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        encrypted_streams: Some([1].into()),
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    let mut writer = Writer::new(storage.clone());
+    for stream_id in [1, 2] {
+        let secret_data = format!("stream {} secret", stream_id);
+        writer.append(stream_id, 1, SecretEvent { secret_data })?;
+    }
+
+    {
+        let txn = storage.env.read_txn()?;
+        let raw = |seq| -> Result<String, heed::Error> {
+            let bytes = storage.events_log.get(&txn, &seq)?.unwrap();
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        };
+        assert!(!raw(1)?.contains("stream 1 secret"));
+        assert!(raw(2)?.contains("stream 2 secret"));
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        let mut scratch = rkyv::util::AlignedVec::new();
+        for seq in [1, 2] {
+            let expected = format!("stream {} secret", seq);
+            assert_eq!(reader.get(&txn, seq)?.unwrap().secret_data, expected);
+            let event = reader.get_into(&txn, seq, &mut scratch)?.unwrap();
+            assert_eq!(event.secret_data, expected);
+        }
+    }
+    drop(writer);
+    drop(storage);
+
+    // Streams can be added later; their earlier events stay readable.
+    let storage = Storage::open(StorageConfig {
+        encrypted_streams: Some([1, 2].into()),
+        ..config.clone()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    writer.append(
+        2,
+        2,
+        SecretEvent {
+            secret_data: "stream 2 second secret".to_string(),
+        },
+    )?;
+    {
+        let txn = storage.env.read_txn()?;
+        let bytes = storage.events_log.get(&txn, &3)?.unwrap();
+        assert!(!String::from_utf8_lossy(bytes).contains("second secret"));
+
+        let reader = Reader::<SecretEvent>::new(storage.clone());
+        assert_eq!(reader.get(&txn, 2)?.unwrap().secret_data, "stream 2 secret");
+        assert_eq!(
+            reader.get(&txn, 3)?.unwrap().secret_data,
+            "stream 2 second secret"
+        );
+    }
+    drop(writer);
+    drop(storage);
+
+    // Whether records carry the flag is fixed at creation.
+    let result = Storage::open(StorageConfig {
+        encrypted_streams: None,
+        ..config
+    });
+    assert!(matches!(
+        result,
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_iter_with_storage_kind() -> Result<(), Box<dyn std::error::Error>> {
+    use varvedb::model::PayloadKind;
+    use varvedb::storage::InlineThreshold;
+
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        encrypted_streams: Some([1].into()),
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        inline_threshold: InlineThreshold::Fixed(64),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        SecretEvent {
+            secret_data: "small".to_string(),
+        },
+    )?;
+    writer.append(
+        2,
+        1,
+        SecretEvent {
+            secret_data: "large".repeat(100),
+        },
+    )?;
+
+    let reader = Reader::<SecretEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let records = reader
+        .iter_with_storage_kind(&txn)?
+        .map(|result| result.map(|(seq, kind, event)| (seq, kind, event.secret_data.len())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].0, records[0].2), (1, 5));
+    assert_eq!(records[0].1.payload, PayloadKind::InlineChecked);
+    assert!(records[0].1.encrypted);
+    assert_eq!((records[1].0, records[1].2), (2, 500));
+    assert_eq!(records[1].1.payload, PayloadKind::BlobRef);
+    assert!(records[1].1.payload.is_blob());
+    assert!(!records[1].1.encrypted);
+
+    Ok(())
+}
@@ -27,6 +27,7 @@ fn test_concurrency_conflict() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: false,
         master_key: None,
+        ..Default::default()
     };
     let storage = Storage::open(config)?;
     let mut writer = Writer::<ErrorEvent>::new(storage.clone());
@@ -52,3 +53,192 @@ fn test_concurrency_conflict() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_buffer_too_small_is_distinguished() {
+    let mut buf = [0u8; 4];
+    let event = ErrorEvent { id: 1 };
+
+    match varvedb::engine::serialize_into(&event, &mut buf) {
+        Err(varvedb::error::Error::BufferTooSmall { needed, capacity }) => {
+            assert_eq!(capacity, 4);
+            assert_eq!(needed, Some(8));
+        }
+        other => panic!("Expected BufferTooSmall error, got {:?}", other),
+    }
+
+    let mut buf = [0u8; 8];
+    let bytes = varvedb::engine::serialize_into(&event, &mut buf).unwrap();
+    assert_eq!(
+        bytes,
+        rkyv::to_bytes::<rkyv::rancor::Error>(&event)
+            .unwrap()
+            .as_slice()
+    );
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct UntrustedEvent {
+    pub body: Vec<u8>,
+}
+
+#[test]
+fn test_event_too_large() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        max_serialize_bytes: Some(256),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<UntrustedEvent>::new(storage.clone());
+
+    writer.append(1, 1, UntrustedEvent { body: vec![1; 64] })?;
+
+    match writer.append(
+        1,
+        2,
+        UntrustedEvent {
+            body: vec![1; 4096],
+        },
+    ) {
+        Err(varvedb::error::Error::EventTooLarge { size, max }) => {
+            assert_eq!(max, 256);
+            assert!(size > 4096);
+        }
+        other => panic!("Expected EventTooLarge error, got {:?}", other),
+    }
+
+    let oversized = rkyv::to_bytes::<rkyv::rancor::Error>(&UntrustedEvent { body: vec![1; 512] })?;
+    assert!(matches!(
+        writer.append_at(2, 1, 2, &oversized),
+        Err(varvedb::error::Error::EventTooLarge { .. })
+    ));
+
+    // Nothing was written for the rejected events.
+    assert_eq!(writer.append(1, 2, UntrustedEvent { body: vec![] })?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_full_at_max_stream_versions() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        max_stream_versions: Some(3),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<ErrorEvent>::new(storage.clone());
+
+    for version in 1..=3 {
+        writer.append(1, version, ErrorEvent { id: 1 })?;
+    }
+    assert!(matches!(
+        writer.append(1, 4, ErrorEvent { id: 1 }),
+        Err(varvedb::error::Error::StreamFull {
+            stream_id: 1,
+            max: 3
+        })
+    ));
+
+    // The cap is per stream, and reservations can't get around it.
+    writer.append(2, 1, ErrorEvent { id: 2 })?;
+    assert!(matches!(
+        writer.reserve_versions(2, 3),
+        Err(varvedb::error::Error::StreamFull { .. })
+    ));
+    assert_eq!(writer.reserve_versions(2, 2)?, 2..4);
+
+    Ok(())
+}
+
+#[test]
+fn test_reject_consecutive_duplicates() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        reject_consecutive_duplicates: true,
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    let mut writer = Writer::<ErrorEvent>::new(storage.clone());
+
+    writer.append(1, 1, ErrorEvent { id: 7 })?;
+    assert!(matches!(
+        writer.append(1, 2, ErrorEvent { id: 7 }),
+        Err(varvedb::error::Error::DuplicateEvent {
+            stream_id: 1,
+            version: 2
+        })
+    ));
+
+    // Other streams, different bodies and non-consecutive repeats are accepted.
+    writer.append(2, 1, ErrorEvent { id: 7 })?;
+    writer.append(1, 2, ErrorEvent { id: 8 })?;
+    writer.append(1, 3, ErrorEvent { id: 7 })?;
+    drop(writer);
+    drop(storage);
+
+    // Events appended while the check is off don't count as the previous event.
+    {
+        let storage = Storage::open(StorageConfig {
+            reject_consecutive_duplicates: false,
+            ..config.clone()
+        })?;
+        let mut writer = Writer::<ErrorEvent>::new(storage);
+        writer.append(1, 4, ErrorEvent { id: 7 })?;
+    }
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<ErrorEvent>::new(storage);
+    writer.append(1, 5, ErrorEvent { id: 7 })?;
+    assert!(matches!(
+        writer.append(1, 6, ErrorEvent { id: 7 }),
+        Err(varvedb::error::Error::DuplicateEvent { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_append_reports_errors_without_writing() -> Result<(), Box<dyn std::error::Error>> {
+    use varvedb::error::Error;
+    use varvedb::ExpectedVersion;
+
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        max_stream_versions: Some(3),
+        reject_consecutive_duplicates: true,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<ErrorEvent>::new(storage.clone());
+    writer.append(1, 1, ErrorEvent { id: 1 })?;
+
+    writer.check_append(1, ExpectedVersion::Auto, &ErrorEvent { id: 2 })?;
+    writer.check_append(2, ExpectedVersion::exact(1), &ErrorEvent { id: 1 })?;
+    assert!(matches!(
+        writer.check_append(1, ExpectedVersion::exact(1), &ErrorEvent { id: 2 }),
+        Err(Error::ConcurrencyConflict {
+            stream_id: 1,
+            version: 1
+        })
+    ));
+    assert!(matches!(
+        writer.check_append(1, ExpectedVersion::exact(4), &ErrorEvent { id: 2 }),
+        Err(Error::StreamFull { .. })
+    ));
+    assert!(matches!(
+        writer.check_append(1, ExpectedVersion::Auto, &ErrorEvent { id: 1 }),
+        Err(Error::DuplicateEvent { .. })
+    ));
+
+    // Nothing was written, so the checked append still goes through.
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.events_log.len(&txn)?, 1);
+    drop(txn);
+    writer.append(1, 2, ErrorEvent { id: 2 })?;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+#![cfg(feature = "serde")]
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, serde::Serialize, Debug, PartialEq)]
+struct ExportEvent {
+    amount: i64,
+    note: String,
+}
+
+#[test]
+fn test_export_stream_json() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<ExportEvent>::new(storage.clone());
+
+    for (version, amount) in [(1, 100), (2, -40)] {
+        writer.append(
+            7,
+            version,
+            ExportEvent {
+                amount,
+                note: format!("entry {version}"),
+            },
+        )?;
+        // Other streams are left out of the export.
+        writer.append(
+            8,
+            version,
+            ExportEvent {
+                amount: 0,
+                note: String::new(),
+            },
+        )?;
+    }
+
+    let reader = Reader::<ExportEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let mut out = Vec::new();
+    assert_eq!(reader.export_stream_json(&txn, 7, &mut out)?, 2);
+
+    let rows: Vec<serde_json::Value> = String::from_utf8(out)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        rows,
+        vec![
+            serde_json::json!({"seq": 1, "version": 1, "event": {"amount": 100, "note": "entry 1"}}),
+            serde_json::json!({"seq": 3, "version": 2, "event": {"amount": -40, "note": "entry 2"}}),
+        ]
+    );
+
+    assert_eq!(reader.export_stream_json(&txn, 9, std::io::sink())?, 0);
+
+    Ok(())
+}
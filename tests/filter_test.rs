@@ -0,0 +1,103 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+enum BankEvent {
+    Deposit { amount: u64 },
+    Payment { amount: u64, to: String },
+    Refund { amount: u64 },
+}
+
+fn open_bank(encrypted: bool) -> Result<(tempfile::TempDir, Storage), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: encrypted,
+        master_key: encrypted.then(|| zeroize::Zeroizing::new([7u8; 32])),
+        ..Default::default()
+    })?;
+
+    let mut writer = Writer::new(storage.clone());
+    for i in 1..=30u64 {
+        let event = match i % 3 {
+            0 => BankEvent::Deposit { amount: i },
+            1 => BankEvent::Payment {
+                amount: i * 100,
+                to: format!("acct-{}", i),
+            },
+            _ => BankEvent::Refund { amount: i },
+        };
+        writer.append(1, i as u32, event)?;
+    }
+    Ok((dir, storage))
+}
+
+#[test]
+fn test_iter_filtered_yields_matches_only() -> Result<(), Box<dyn std::error::Error>> {
+    for encrypted in [false, true] {
+        let (_dir, storage) = open_bank(encrypted)?;
+        let reader = Reader::<BankEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+
+        let large = reader
+            .iter_filtered(&txn, |event| {
+                matches!(event, ArchivedBankEvent::Payment { amount, .. } if *amount > 1_000)
+            })?
+            .map(|result| result.map(|(seq, _)| seq))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(large, vec![13, 16, 19, 22, 25, 28]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_iter_by_discriminant_selects_variants() -> Result<(), Box<dyn std::error::Error>> {
+    for encrypted in [false, true] {
+        let (_dir, storage) = open_bank(encrypted)?;
+        let reader = Reader::<BankEvent>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+
+        let mut payments = 0;
+        for result in reader.iter_by_discriminant(&txn, &[1])? {
+            let (seq, event) = result?;
+            assert_eq!(seq % 3, 1);
+            match &*event {
+                ArchivedBankEvent::Payment { amount, to } => {
+                    assert_eq!(*amount, seq * 100);
+                    assert_eq!(to.as_str(), format!("acct-{}", seq));
+                }
+                other => panic!("unexpected variant at {}: {:?}", seq, other),
+            }
+            payments += 1;
+        }
+        assert_eq!(payments, 10);
+
+        // Several tags, in any order, match the same events as the equivalent predicate.
+        let by_tag = reader
+            .iter_by_discriminant(&txn, &[2, 0])?
+            .map(|result| result.map(|(seq, _)| seq))
+            .collect::<Result<Vec<_>, _>>()?;
+        let by_filter = reader
+            .iter_filtered(&txn, |event| {
+                !matches!(event, ArchivedBankEvent::Payment { .. })
+            })?
+            .map(|result| result.map(|(seq, _)| seq))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(by_tag.len(), 20);
+        assert_eq!(by_tag, by_filter);
+
+        assert_eq!(reader.iter_by_discriminant(&txn, &[])?.count(), 0);
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::traits::MetadataExt;
+use varvedb::{ExpectedVersion, Payload, Varve};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[rkyv(derive(Debug))]
+struct TickEvent {
+    value: u32,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+struct TickMetadata {
+    stream_id: u128,
+    version: u32,
+}
+
+impl MetadataExt for TickMetadata {
+    fn stream_id(&self) -> u128 {
+        self.stream_id
+    }
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+fn tick(value: u32) -> Payload<TickEvent, TickMetadata> {
+    Payload::new(
+        TickEvent { value },
+        TickMetadata {
+            stream_id: 1,
+            version: value,
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_forward_to_backfills_then_tails() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<TickEvent>::new(storage.clone());
+    for v in 1..=3 {
+        writer.append(1, v, TickEvent { value: v })?;
+    }
+
+    let reader = Reader::<TickEvent>::new(storage.clone());
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let task = tokio::spawn(async move { reader.forward_to(2, tx).await });
+
+    // Backfill starts at the requested sequence.
+    assert_eq!(rx.recv().await, Some((2, TickEvent { value: 2 })));
+    assert_eq!(rx.recv().await, Some((3, TickEvent { value: 3 })));
+
+    // Live tail picks up new appends.
+    writer.append(1, 4, TickEvent { value: 4 })?;
+    let next = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await?;
+    assert_eq!(next, Some((4, TickEvent { value: 4 })));
+
+    // Dropping the receiver stops forwarding.
+    drop(rx);
+    tokio::time::timeout(Duration::from_secs(5), task).await???;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_subscribe_from_has_no_gaps_across_handoff() -> Result<(), Box<dyn std::error::Error>>
+{
+    const BACKLOG: u32 = 300;
+    const TOTAL: u32 = 1_000;
+
+    let dir = tempdir()?;
+    let mut db = Varve::<TickEvent, TickMetadata>::open(dir.path())?;
+    for v in 1..=BACKLOG {
+        db.append(tick(v), ExpectedVersion::Auto)?;
+    }
+
+    // Keep appending while the subscription is still reading the backlog.
+    let mut events = db.subscribe_from(1);
+    let mut appender = db.clone();
+    let appends = tokio::task::spawn_blocking(move || {
+        for v in BACKLOG + 1..=TOTAL {
+            appender.append(tick(v), ExpectedVersion::Auto)?;
+        }
+        Ok::<_, varvedb::error::Error>(())
+    });
+
+    for expected in 1..=TOTAL {
+        let (seq, view) = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await?
+            .expect("subscription ended early")?;
+        assert_eq!(seq, u64::from(expected));
+        assert_eq!(view.value, expected);
+    }
+    appends.await??;
+
+    // Nothing is delivered twice.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), events.recv())
+            .await
+            .is_err()
+    );
+
+    // Subscriptions can start mid-log.
+    let mut events = db.subscribe_from(u64::from(TOTAL));
+    let (seq, _) = events.recv().await.expect("subscription ended early")?;
+    assert_eq!(seq, u64::from(TOTAL));
+
+    Ok(())
+}
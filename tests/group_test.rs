@@ -0,0 +1,216 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::group::{ConsumerGroup, GroupConfig};
+use varvedb::processor::{CancellationToken, EventHandler};
+use varvedb::traits::MetadataExt;
+use varvedb::{ExpectedVersion, Payload, Varve};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+struct JobEvent {
+    id: u32,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+struct JobMetadata {
+    stream_id: u128,
+    version: u32,
+}
+
+impl MetadataExt for JobMetadata {
+    fn stream_id(&self) -> u128 {
+        self.stream_id
+    }
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Records `(worker_id, job id)` for every handled event; fails on `fail_on`.
+struct JobHandler {
+    worker_id: u64,
+    handled: Arc<Mutex<Vec<(u64, u32)>>>,
+    fail_on: Option<u32>,
+}
+
+impl EventHandler<JobEvent> for JobHandler {
+    fn handle(&mut self, event: &ArchivedJobEvent) -> varvedb::error::Result<()> {
+        let id = event.id.to_native();
+        if self.fail_on == Some(id) {
+            return Err(varvedb::error::Error::EventValidation(format!(
+                "job {} failed",
+                id
+            )));
+        }
+        self.handled.lock().unwrap().push((self.worker_id, id));
+        Ok(())
+    }
+}
+
+fn append_jobs(
+    db: &mut Varve<JobEvent, JobMetadata>,
+    ids: std::ops::RangeInclusive<u32>,
+) -> varvedb::error::Result<()> {
+    for id in ids {
+        db.append(
+            Payload::new(
+                JobEvent { id },
+                JobMetadata {
+                    stream_id: 1,
+                    version: id,
+                },
+            ),
+            ExpectedVersion::Auto,
+        )?;
+    }
+    Ok(())
+}
+
+async fn wait_for(handled: &Arc<Mutex<Vec<(u64, u32)>>>, count: usize) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while handled.lock().unwrap().len() < count {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("workers did not handle every event");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_workers_share_events() -> Result<(), Box<dyn std::error::Error>> {
+    const JOBS: u32 = 500;
+
+    let dir = tempdir()?;
+    let mut db = Varve::<JobEvent, JobMetadata>::open(dir.path())?;
+    append_jobs(&mut db, 1..=200)?;
+
+    let group = ConsumerGroup::new(&db).with_config(GroupConfig {
+        batch_size: 16,
+        ..Default::default()
+    });
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let token = CancellationToken::new();
+    let mut tasks = Vec::new();
+    for worker_id in 1..=3 {
+        let mut worker = group
+            .join(
+                9,
+                worker_id,
+                JobHandler {
+                    worker_id,
+                    handled: handled.clone(),
+                    fail_on: None,
+                },
+            )?
+            .with_cancellation_token(token.clone());
+        tasks.push(tokio::spawn(async move { worker.run().await }));
+    }
+
+    // Events appended while the workers run are picked up too.
+    append_jobs(&mut db, 201..=JOBS)?;
+    wait_for(&handled, JOBS as usize).await;
+    token.cancel();
+    for task in tasks {
+        task.await??;
+    }
+
+    // Every event was handled by exactly one worker.
+    let handled = handled.lock().unwrap().clone();
+    let mut ids: Vec<u32> = handled.iter().map(|(_, id)| *id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, (1..=JOBS).collect::<Vec<_>>());
+    assert_eq!(group.committed(9)?, u64::from(JOBS));
+
+    // Other groups keep their own cursor.
+    assert_eq!(group.committed(10)?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_claim_is_retried_by_another_worker() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempdir()?;
+    let mut db = Varve::<JobEvent, JobMetadata>::open(dir.path())?;
+    append_jobs(&mut db, 1..=10)?;
+
+    let group = ConsumerGroup::new(&db).with_config(GroupConfig {
+        batch_size: 4,
+        ..Default::default()
+    });
+    let handled = Arc::new(Mutex::new(Vec::new()));
+
+    // Worker 1 fails on job 6, in its second claim, and gives that claim up.
+    let mut failing = group.join(
+        1,
+        1,
+        JobHandler {
+            worker_id: 1,
+            handled: handled.clone(),
+            fail_on: Some(6),
+        },
+    )?;
+    assert!(failing.run().await.is_err());
+    assert_eq!(group.committed(1)?, 4);
+
+    let token = CancellationToken::new();
+    let mut worker = group
+        .join(
+            1,
+            2,
+            JobHandler {
+                worker_id: 2,
+                handled: handled.clone(),
+                fail_on: None,
+            },
+        )?
+        .with_cancellation_token(token.clone());
+    let task = tokio::spawn(async move { worker.run().await });
+
+    // Job 5 was handled before the failure, and again by worker 2 on retry.
+    wait_for(&handled, 11).await;
+    token.cancel();
+    task.await??;
+
+    let handled = handled.lock().unwrap().clone();
+    let retried: Vec<u32> = handled
+        .iter()
+        .filter(|(worker, _)| *worker == 2)
+        .map(|(_, id)| *id)
+        .collect();
+    assert_eq!(retried, (5..=10).collect::<Vec<_>>());
+    assert_eq!(group.committed(1)?, 10);
+
+    Ok(())
+}
+
+#[test]
+fn test_join_rejects_zero_batch_size() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let db = Varve::<JobEvent, JobMetadata>::open(dir.path())?;
+    let group = ConsumerGroup::new(&db).with_config(GroupConfig {
+        batch_size: 0,
+        ..Default::default()
+    });
+
+    let handler = JobHandler {
+        worker_id: 1,
+        handled: Arc::default(),
+        fail_on: None,
+    };
+    assert!(matches!(
+        group.join(1, 1, handler),
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
@@ -50,8 +50,10 @@ fn test_validation_error_on_corrupted_data() -> Result<(), Box<dyn std::error::E
 
     // 4. Assert that we get a Validation error
     match result {
-        Err(varvedb::error::Error::EventSerialization(_)) => {
-            // Success! We caught the corruption.
+        Err(varvedb::error::Error::AtSequence { seq: 1, source })
+            if matches!(*source, varvedb::error::Error::EventSerialization(_)) =>
+        {
+            // Success! We caught the corruption, and know where it is.
         }
         Ok(_) => {
             panic!("Expected validation error, got Ok");
@@ -7,7 +7,7 @@
 // obtain one at http://mozilla.org/MPL/2.0/.
 
 use tempfile::tempdir;
-use varvedb::storage::{Storage, StorageConfig};
+use varvedb::storage::{LogKey, Storage, StorageConfig, StorageLayout, REQUIRED_DBS};
 
 #[test]
 fn test_storage_validation() -> Result<(), Box<dyn std::error::Error>> {
@@ -43,3 +43,35 @@ fn test_storage_validation() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_max_dbs_counts_optional_databases() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        layout: StorageLayout::Clustered,
+        log_key: LogKey::U128,
+        lamport_clock: true,
+        max_dbs: REQUIRED_DBS + 3,
+        ..Default::default()
+    };
+    assert_eq!(config.required_dbs(), REQUIRED_DBS + 4);
+
+    match Storage::open(config.clone()) {
+        Err(varvedb::error::Error::InvalidConfig(msg)) => {
+            assert_eq!(
+                msg,
+                format!("max_dbs must be at least {}", REQUIRED_DBS + 4)
+            );
+        }
+        Ok(_) => panic!("Expected validation error for max_dbs"),
+        Err(e) => panic!("Expected Validation error, got {:?}", e),
+    }
+
+    Storage::open(StorageConfig {
+        max_dbs: config.required_dbs(),
+        ..config
+    })?;
+
+    Ok(())
+}
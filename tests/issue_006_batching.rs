@@ -7,10 +7,10 @@
 // obtain one at http://mozilla.org/MPL/2.0/.
 
 use rkyv::{Archive, Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tempfile::tempdir;
-use varvedb::processor::{EventHandler, Processor, ProcessorConfig};
+use varvedb::processor::{Processor, ProcessorConfig};
+use varvedb::testing::CollectingHandler;
 use varvedb::traits::MetadataExt;
 use varvedb::{ExpectedVersion, Payload, Varve};
 
@@ -36,18 +36,6 @@ impl MetadataExt for MyMetadata {
     }
 }
 
-struct BatchHandler {
-    pub processed_count: Arc<Mutex<usize>>,
-}
-
-impl EventHandler<MyEvent> for BatchHandler {
-    fn handle(&mut self, _event: &ArchivedMyEvent) -> varvedb::error::Result<()> {
-        let mut count = self.processed_count.lock().unwrap();
-        *count += 1;
-        Ok(())
-    }
-}
-
 #[tokio::test]
 async fn test_batch_processing() -> Result<(), Box<dyn std::error::Error>> {
     let dir = tempdir()?;
@@ -65,16 +53,14 @@ async fn test_batch_processing() -> Result<(), Box<dyn std::error::Error>> {
         db.append(Payload::new(event, metadata), ExpectedVersion::Auto)?;
     }
 
-    let processed_count = Arc::new(Mutex::new(0));
-    let handler = BatchHandler {
-        processed_count: processed_count.clone(),
-    };
+    let handler = CollectingHandler::<MyEvent>::new();
 
     let consumer_id = 12345u64;
-    let mut processor = Processor::new(&db, handler, consumer_id).with_config(ProcessorConfig {
-        batch_size: 10,
-        batch_timeout: Duration::from_millis(10),
-    });
+    let mut processor =
+        Processor::new(&db, handler.clone(), consumer_id).with_config(ProcessorConfig {
+            batch_size: 10,
+            batch_timeout: Duration::from_millis(10),
+        });
 
     let handle = tokio::spawn(async move {
         processor.run().await.unwrap();
@@ -84,10 +70,7 @@ async fn test_batch_processing() -> Result<(), Box<dyn std::error::Error>> {
     tokio::time::sleep(Duration::from_millis(500)).await;
 
     // Check if processed
-    {
-        let count = processed_count.lock().unwrap();
-        assert_eq!(*count, event_count as usize);
-    }
+    assert_eq!(handler.len(), event_count as usize);
 
     // Abort processor (since it runs forever)
     handle.abort();
@@ -7,10 +7,10 @@
 // obtain one at http://mozilla.org/MPL/2.0/.
 
 use rkyv::{Archive, Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tempfile::tempdir;
-use varvedb::processor::{EventHandler, Processor, ProcessorConfig};
+use varvedb::processor::{Processor, ProcessorConfig};
+use varvedb::testing::CollectingHandler;
 use varvedb::traits::MetadataExt;
 use varvedb::{ExpectedVersion, Payload, Varve};
 
@@ -36,34 +36,20 @@ impl MetadataExt for MyMetadata {
     }
 }
 
-struct SimpleHandler {
-    count: Arc<Mutex<u32>>,
-}
-
-impl EventHandler<MyEvent> for SimpleHandler {
-    fn handle(&mut self, _event: &ArchivedMyEvent) -> varvedb::error::Result<()> {
-        let mut c = self.count.lock().unwrap();
-        *c += 1;
-        Ok(())
-    }
-}
-
 #[tokio::test]
 async fn test_txn_reuse() -> Result<(), Box<dyn std::error::Error>> {
     let dir = tempdir()?;
     let db_path = dir.path().join("txn_reuse_test.mdb");
     let mut db = Varve::open(&db_path)?;
 
-    let count = Arc::new(Mutex::new(0));
-    let handler = SimpleHandler {
-        count: count.clone(),
-    };
+    let handler = CollectingHandler::<MyEvent>::new();
 
     let consumer_id = 999u64;
-    let mut processor = Processor::new(&db, handler, consumer_id).with_config(ProcessorConfig {
-        batch_size: 5,
-        batch_timeout: Duration::from_millis(10),
-    });
+    let mut processor =
+        Processor::new(&db, handler.clone(), consumer_id).with_config(ProcessorConfig {
+            batch_size: 5,
+            batch_timeout: Duration::from_millis(10),
+        });
 
     let handle = tokio::spawn(async move {
         processor.run().await.unwrap();
@@ -82,10 +68,7 @@ async fn test_txn_reuse() -> Result<(), Box<dyn std::error::Error>> {
 
     tokio::time::sleep(Duration::from_millis(200)).await;
 
-    {
-        let c = count.lock().unwrap();
-        assert_eq!(*c, 15);
-    }
+    assert_eq!(handler.len(), 15);
 
     handle.abort();
 
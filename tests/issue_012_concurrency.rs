@@ -13,7 +13,7 @@ use std::sync::{
 };
 use tempfile::tempdir;
 use tokio::task::JoinSet;
-use varvedb::engine::Writer;
+use varvedb::engine::{Reader, SyncWriter, Writer};
 use varvedb::error::Error;
 use varvedb::storage::{Storage, StorageConfig};
 
@@ -93,3 +93,64 @@ async fn test_concurrent_writes() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_sync_writer_shared_between_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Writer<MyEvent>>();
+    assert_send_sync::<SyncWriter<MyEvent>>();
+
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let writer = SyncWriter::new(Writer::<MyEvent>::new(storage.clone()));
+
+    let num_tasks = 10;
+    let events_per_task = 20;
+    let mut set = JoinSet::new();
+    for i in 0..num_tasks {
+        let writer = writer.clone();
+        let storage = storage.clone();
+        set.spawn(async move {
+            let reader = Reader::<MyEvent>::new(storage);
+            let mut seqs = Vec::new();
+            for j in 0..events_per_task {
+                let event = MyEvent {
+                    data: i * events_per_task + j,
+                };
+                // Reading the head and appending under one lock never conflicts.
+                let seq = writer.with(|writer| {
+                    let txn = reader.storage().env.read_txn()?;
+                    let next = reader
+                        .stream_info(&txn, 1)?
+                        .map_or(1, |info| info.head_version + 1);
+                    drop(txn);
+                    writer.append(1, next, event)
+                })?;
+                seqs.push(seq);
+            }
+            Ok::<_, Error>(seqs)
+        });
+    }
+
+    let mut seqs = Vec::new();
+    while let Some(res) = set.join_next().await {
+        seqs.extend(res??);
+    }
+
+    // Every append got its own sequence number, with no gaps or reuse.
+    let total = (num_tasks * events_per_task) as u64;
+    seqs.sort_unstable();
+    assert_eq!(seqs, (1..=total).collect::<Vec<_>>());
+
+    let reader = Reader::<MyEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let info = reader.stream_info(&txn, 1)?.unwrap();
+    assert_eq!((info.head_version, info.count), (total as u32, total));
+    assert_eq!(reader.last(&txn)?.map(|(seq, _)| seq), Some(total));
+
+    Ok(())
+}
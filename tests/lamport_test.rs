@@ -0,0 +1,97 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct ChatEvent {
+    text: String,
+}
+
+fn chat(text: &str) -> ChatEvent {
+    ChatEvent {
+        text: text.to_string(),
+    }
+}
+
+fn open(dir: &std::path::Path, lamport_clock: bool) -> varvedb::error::Result<Storage> {
+    Storage::open(StorageConfig {
+        path: dir.to_path_buf(),
+        lamport_clock,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_lamport_clock_orders_events_across_stores() -> Result<(), Box<dyn std::error::Error>> {
+    let alice_dir = tempdir()?;
+    let bob_dir = tempdir()?;
+    let alice = open(alice_dir.path(), true)?;
+    let bob = open(bob_dir.path(), true)?;
+    let mut alice_writer = Writer::new(alice.clone());
+    let mut bob_writer = Writer::new(bob.clone());
+
+    for i in 1..=5 {
+        alice_writer.append(1, i, chat("busy"))?;
+    }
+    let question = alice_writer.append(2, 1, chat("lunch?"))?;
+    let stamp = {
+        let txn = alice.env.read_txn()?;
+        let envelope = Reader::<ChatEvent>::new(alice.clone())
+            .get_envelope(&txn, question)?
+            .unwrap();
+        assert_eq!(envelope.seq, question);
+        assert_eq!(envelope.event.text, "lunch?");
+        envelope.lamport.unwrap()
+    };
+    assert_eq!(stamp, 6);
+
+    // Bob's store has seen less, but his reply must still come after the question.
+    bob_writer.append(1, 1, chat("hi"))?;
+    assert_eq!(bob_writer.observe_clock(stamp)?, 6);
+    let reply = bob_writer.append(2, 1, chat("sure"))?;
+
+    let txn = bob.env.read_txn()?;
+    let reader = Reader::<ChatEvent>::new(bob.clone());
+    assert_eq!(reader.get_envelope(&txn, 1)?.unwrap().lamport, Some(1));
+    assert_eq!(reader.get_envelope(&txn, reply)?.unwrap().lamport, Some(7));
+    assert_eq!(bob.lamport_clock(&txn)?, 7);
+
+    let stamps: Vec<_> = reader
+        .get_envelopes(&txn, 1, 10)?
+        .iter()
+        .map(|e| e.lamport)
+        .collect();
+    assert_eq!(stamps, vec![Some(1), Some(7)]);
+    assert!(reader.get_envelope(&txn, 3)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_events_without_lamport_clock_have_no_timestamp() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open(dir.path(), false)?;
+    let mut writer = Writer::new(storage.clone());
+    writer.append(1, 1, chat("hi"))?;
+
+    assert!(matches!(
+        writer.observe_clock(10),
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+    let txn = storage.env.read_txn()?;
+    let envelope = Reader::<ChatEvent>::new(storage.clone())
+        .get_envelope(&txn, 1)?
+        .unwrap();
+    assert_eq!(envelope.lamport, None);
+
+    Ok(())
+}
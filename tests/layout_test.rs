@@ -0,0 +1,204 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{LogKey, Storage, StorageConfig, StorageLayout};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct LayoutEvent {
+    value: u32,
+}
+
+fn clustered_config(path: &std::path::Path, encryption_enabled: bool) -> StorageConfig {
+    StorageConfig {
+        path: path.to_path_buf(),
+        layout: StorageLayout::Clustered,
+        encryption_enabled,
+        master_key: encryption_enabled.then(|| zeroize::Zeroizing::new([3u8; 32])),
+        ..Default::default()
+    }
+}
+
+fn check_clustered_roundtrip(encryption_enabled: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(clustered_config(dir.path(), encryption_enabled))?;
+    let mut writer = Writer::<LayoutEvent>::new(storage.clone());
+
+    // Interleave two streams.
+    writer.append(2, 1, LayoutEvent { value: 21 })?;
+    writer.append(1, 1, LayoutEvent { value: 11 })?;
+    writer.append(2, 2, LayoutEvent { value: 22 })?;
+    writer.append(1, 2, LayoutEvent { value: 12 })?;
+
+    let reader = Reader::<LayoutEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().value, 21);
+    assert_eq!(reader.get(&txn, 4)?.unwrap().value, 12);
+    assert_eq!(reader.get_by_stream(&txn, 2, 2)?.unwrap().value, 22);
+    assert!(reader.get_by_stream(&txn, 1, 3)?.is_none());
+    let (version, latest) = reader.get_latest(&txn, 1)?.unwrap();
+    assert_eq!(version, 2);
+    assert_eq!(latest.value, 12);
+
+    // Records are physically ordered by stream, then version.
+    let clustered = storage.events_by_stream.unwrap();
+    let keys: Vec<(u128, u32)> = clustered
+        .iter(&txn)?
+        .map(|entry| {
+            let (key, _) = entry.unwrap();
+            let stream_id = u128::from_be_bytes(key[..16].try_into().unwrap());
+            let version = u32::from_be_bytes(key[16..20].try_into().unwrap());
+            (stream_id, version)
+        })
+        .collect();
+    assert_eq!(keys, vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_clustered_layout_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    check_clustered_roundtrip(false)
+}
+
+#[test]
+fn test_clustered_layout_with_encryption() -> Result<(), Box<dyn std::error::Error>> {
+    check_clustered_roundtrip(true)
+}
+
+#[test]
+fn test_layouts_cannot_be_mixed() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(clustered_config(dir.path(), false))?;
+    assert!(storage.events_by_stream.is_some());
+    drop(storage);
+
+    let sequential = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    match Storage::open(sequential.clone()) {
+        Err(Error::InvalidConfig(msg)) => assert!(msg.contains("storage layout mismatch")),
+        other => panic!("Expected layout mismatch, got {:?}", other.map(|_| ())),
+    }
+
+    // A populated sequential database cannot be reopened as clustered either.
+    let dir = tempdir()?;
+    let sequential = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..sequential
+    };
+    let storage = Storage::open(sequential)?;
+    assert!(storage.events_by_stream.is_none());
+    Writer::<LayoutEvent>::new(storage.clone()).append(1, 1, LayoutEvent { value: 1 })?;
+    drop(storage);
+    assert!(matches!(
+        Storage::open(clustered_config(dir.path(), false)),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_u128_log_key_orders_events_by_id() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        log_key: LogKey::U128,
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    let mut writer = Writer::<LayoutEvent>::new(storage.clone());
+
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis() as u64;
+    writer.append(1, 1, LayoutEvent { value: 1 })?;
+    writer.append(1, 2, LayoutEvent { value: 2 })?;
+
+    // Supplied IDs must keep increasing.
+    let custom = LogKey::first_id_at(u64::MAX >> 16) + 7;
+    assert_eq!(
+        writer.append_with_id(2, 1, custom, LayoutEvent { value: 3 })?,
+        3
+    );
+    assert!(matches!(
+        writer.append_with_id(2, 2, custom, LayoutEvent { value: 4 }),
+        Err(Error::InvalidConfig(_))
+    ));
+    // The failed append leaves no trace; the next one continues after `custom`.
+    writer.append(2, 2, LayoutEvent { value: 4 })?;
+
+    let reader = Reader::<LayoutEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let events = reader
+        .iter_by_id(&txn, ..)?
+        .map(|result| result.map(|(id, seq, event)| (id, seq, event.value.to_native())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let seqs: Vec<u64> = events.iter().map(|(_, seq, _)| *seq).collect();
+    assert_eq!(seqs, vec![1, 2, 3, 4]);
+    assert_eq!(events[2].0, custom);
+    assert_eq!(events[3].0, custom + 1);
+
+    // Writer-assigned IDs embed the commit time.
+    assert!(events[0].0 >= LogKey::first_id_at(before));
+    assert!(events[0].0 < events[1].0);
+    let recent = reader
+        .iter_by_id(&txn, LogKey::first_id_at(before)..custom)?
+        .count();
+    assert_eq!(recent, 2);
+
+    let (seq, event) = reader.get_by_id(&txn, custom)?.unwrap();
+    assert_eq!((seq, event.value.to_native()), (3, 3));
+    assert!(reader.get_by_id(&txn, custom + 2)?.is_none());
+    drop(txn);
+    drop(writer);
+    drop(storage);
+
+    // The log key is fixed at creation.
+    let sequential = StorageConfig {
+        log_key: LogKey::Sequence,
+        ..config
+    };
+    assert!(matches!(
+        Storage::open(sequential),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_event_ids_require_u128_log_key() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<LayoutEvent>::new(storage.clone());
+    assert!(matches!(
+        writer.append_with_id(1, 1, 1, LayoutEvent { value: 1 }),
+        Err(Error::InvalidConfig(_))
+    ));
+    writer.append(1, 1, LayoutEvent { value: 1 })?;
+
+    let reader = Reader::<LayoutEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert!(matches!(
+        reader.get_by_id(&txn, 1),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(reader.iter_by_id(&txn, ..).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::engine::Writer;
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct LeaseEvent {
+    value: u32,
+}
+
+#[test]
+fn test_standby_takes_over_expired_lease() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        skip_writer_lock: true,
+        ..Default::default()
+    };
+    // Two independently opened handles behave like two processes.
+    let primary = Storage::open(config.clone())?;
+    let standby = Storage::open(config)?;
+
+    let ttl = Duration::from_millis(200);
+    primary.acquire_writer_lease(ttl)?;
+    assert!(matches!(
+        standby.acquire_writer_lease(ttl),
+        Err(Error::LeaseHeld { .. })
+    ));
+
+    let mut writer = Writer::<LeaseEvent>::new(primary.clone());
+    writer.append(1, 1, LeaseEvent { value: 1 })?;
+    primary.renew_lease()?;
+
+    // The primary stops renewing; once the lease expires it may no longer write.
+    std::thread::sleep(ttl + Duration::from_millis(50));
+    assert!(matches!(
+        writer.append(1, 2, LeaseEvent { value: 2 }),
+        Err(Error::LeaseLost)
+    ));
+
+    standby.acquire_writer_lease(ttl)?;
+    let mut standby_writer = Writer::<LeaseEvent>::new(standby.clone());
+    standby_writer.append(1, 2, LeaseEvent { value: 2 })?;
+
+    // The old primary cannot renew a lease that was taken over.
+    assert!(matches!(primary.renew_lease(), Err(Error::LeaseLost)));
+    assert!(matches!(
+        writer.append(1, 3, LeaseEvent { value: 3 }),
+        Err(Error::LeaseLost)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_lease_requires_acquire_and_nonzero_ttl() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    assert!(matches!(storage.renew_lease(), Err(Error::LeaseLost)));
+    assert!(matches!(
+        storage.acquire_writer_lease(Duration::ZERO),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    // Writers that never acquired a lease are unaffected.
+    let mut writer = Writer::<LeaseEvent>::new(storage.clone());
+    writer.append(1, 1, LeaseEvent { value: 1 })?;
+
+    Ok(())
+}
+
+#[test]
+fn test_check_append_fails_once_lease_is_lost() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        skip_writer_lock: true,
+        ..Default::default()
+    };
+    let primary = Storage::open(config.clone())?;
+    let standby = Storage::open(config)?;
+
+    let ttl = Duration::from_millis(200);
+    primary.acquire_writer_lease(ttl)?;
+    let writer = Writer::<LeaseEvent>::new(primary.clone());
+    writer.check_append(1, ExpectedVersion::Auto, &LeaseEvent { value: 1 })?;
+
+    std::thread::sleep(ttl + Duration::from_millis(50));
+    standby.acquire_writer_lease(ttl)?;
+    assert!(matches!(
+        writer.check_append(1, ExpectedVersion::Auto, &LeaseEvent { value: 1 }),
+        Err(Error::LeaseLost)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrap_keys_fails_once_lease_is_lost() -> Result<(), Box<dyn std::error::Error>> {
+    use varvedb::crypto::KeyManager;
+    use varvedb::processor::CancellationToken;
+
+    let dir = tempdir()?;
+    let mut config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        skip_writer_lock: true,
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([1u8; 32])),
+        ..Default::default()
+    };
+    {
+        let mut writer = Writer::<LeaseEvent>::new(Storage::open(config.clone())?);
+        writer.append(1, 1, LeaseEvent { value: 1 })?;
+    }
+
+    config
+        .master_keys
+        .insert(1, zeroize::Zeroizing::new([2u8; 32]));
+    let primary = Storage::open(config.clone())?;
+    let standby = Storage::open(config)?;
+
+    let ttl = Duration::from_millis(200);
+    primary.acquire_writer_lease(ttl)?;
+    std::thread::sleep(ttl + Duration::from_millis(50));
+    standby.acquire_writer_lease(ttl)?;
+
+    let keys = KeyManager::new(primary.clone());
+    assert!(matches!(keys.rewrap_all_keys(), Err(Error::LeaseLost)));
+    assert!(matches!(
+        keys.rewrap_keys_in_batches(10, &CancellationToken::new()),
+        Err(Error::LeaseLost)
+    ));
+    assert_eq!(keys.key_version(1)?, Some(0));
+
+    Ok(())
+}
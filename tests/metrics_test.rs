@@ -12,7 +12,7 @@ use std::sync::Arc;
 use tempfile::tempdir;
 use varvedb::engine::{Reader, Writer};
 use varvedb::metrics::VarveMetrics;
-use varvedb::storage::{Storage, StorageConfig};
+use varvedb::storage::{Storage, StorageConfig, SyncMode};
 
 #[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
 #[repr(C)]
@@ -30,6 +30,7 @@ fn test_metrics_collection() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: false,
         master_key: None,
+        ..Default::default()
     };
 
     let storage = Storage::open(config)?;
@@ -64,5 +65,204 @@ fn test_metrics_collection() -> Result<(), Box<dyn std::error::Error>> {
         .expect("events_read metric not found");
     assert_eq!(events_read.get_metric()[0].get_counter().value(), 1.0);
 
+    let sample_count = |name: &str| {
+        metric_families
+            .iter()
+            .find(|m| m.name() == name)
+            .unwrap_or_else(|| panic!("{name} metric not found"))
+            .get_metric()[0]
+            .get_histogram()
+            .get_sample_count()
+    };
+    assert_eq!(sample_count("varvedb_append_serialize_duration_seconds"), 1);
+    assert_eq!(sample_count("varvedb_append_commit_duration_seconds"), 1);
+    // Nothing is encrypted without a master key.
+    assert_eq!(sample_count("varvedb_append_encrypt_duration_seconds"), 0);
+
+    Ok(())
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct BulkEvent {
+    pub data: Vec<u8>,
+}
+
+#[test]
+fn test_map_full_warning() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let map_size = 2 * 1024 * 1024;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        map_size,
+        map_full_warn_threshold: Some(0.5),
+        sync_mode: SyncMode::NoSync,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let registry = Registry::new();
+    let metrics = Arc::new(VarveMetrics::new(&registry)?);
+    let mut writer = Writer::<BulkEvent>::new(storage.clone()).with_metrics(metrics.clone());
+
+    let free = |storage: &Storage| -> Result<u64, Box<dyn std::error::Error>> {
+        let txn = storage.env.read_txn()?;
+        Ok(storage.free_space_bytes(&txn)?)
+    };
+    let initial_free = free(&storage)?;
+    assert!(initial_free > 0 && initial_free < map_size as u64);
+
+    // Fill the map past the threshold, then keep going a little.
+    let mut version = 0;
+    while free(&storage)? > map_size as u64 * 3 / 10 {
+        version += 1;
+        writer.append(
+            1,
+            version,
+            BulkEvent {
+                data: vec![7; 4000],
+            },
+        )?;
+        if free(&storage)? > map_size as u64 / 2 {
+            assert_eq!(metrics.map_full_warnings.get(), 0);
+        }
+    }
+    assert!(free(&storage)? < initial_free);
+
+    // The warning fired once, on crossing, not on every append past it.
+    assert_eq!(metrics.map_full_warnings.get(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_try_append_backs_off_near_map_full() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let map_size = 2 * 1024 * 1024;
+    let reserve = 512 * 1024;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        map_size,
+        append_reserve_bytes: reserve,
+        sync_mode: SyncMode::NoSync,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<BulkEvent>::new(storage.clone());
+    let free = || -> Result<u64, Box<dyn std::error::Error>> {
+        let txn = storage.env.read_txn()?;
+        Ok(storage.free_space_bytes(&txn)?)
+    };
+
+    let mut version = 0;
+    loop {
+        let event = BulkEvent {
+            data: vec![7; 4000],
+        };
+        match writer.try_append(1, version + 1, event)? {
+            Some(_) => version += 1,
+            None => break,
+        }
+    }
+
+    // It stopped once the reserve was reached, well before the map was full.
+    assert!(version > 0);
+    assert!(free()? < reserve);
+    assert!(free()? > 0);
+
+    // Plain appends still use the reserve.
+    writer.append(1, version + 1, BulkEvent { data: vec![7; 10] })?;
+
+    Ok(())
+}
+
+#[test]
+fn test_map_full_warn_threshold_out_of_range_is_rejected() {
+    let dir = tempdir().unwrap();
+    for threshold in [0.0, 1.5, f64::NAN] {
+        let config = StorageConfig {
+            path: dir.path().to_path_buf(),
+            map_full_warn_threshold: Some(threshold),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Storage::open(config),
+            Err(varvedb::error::Error::InvalidConfig(_))
+        ));
+    }
+}
+
+fn gauge_value(registry: &Registry, name: &str) -> Vec<(Vec<String>, f64)> {
+    registry
+        .gather()
+        .iter()
+        .filter(|m| m.name() == name)
+        .flat_map(|m| m.get_metric().iter())
+        .map(|m| {
+            let labels = m
+                .get_label()
+                .iter()
+                .map(|l| l.value().to_string())
+                .collect();
+            (labels, m.get_gauge().value())
+        })
+        .collect()
+}
+
+#[test]
+fn test_collector_reports_on_disk_state() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        map_size: 10 * 1024 * 1024,
+        sync_mode: SyncMode::NoSync,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let registry = Registry::new();
+    let metrics = Arc::new(VarveMetrics::new(&registry)?);
+
+    let mut writer = Writer::<MetricEvent>::new(storage.clone());
+    for id in 1..=5 {
+        writer.append(1, id as u32, MetricEvent { id })?;
+    }
+    let mut txn = storage.env.write_txn()?;
+    storage.consumer_cursors.put(&mut txn, &7, &2)?;
+    txn.commit()?;
+
+    let collector =
+        metrics.spawn_collector(storage.clone(), std::time::Duration::from_millis(5))?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    assert_eq!(
+        gauge_value(&registry, "varvedb_events_stored"),
+        vec![(vec![], 5.0)]
+    );
+    assert_eq!(
+        gauge_value(&registry, "varvedb_blobs_stored"),
+        vec![(vec![], 0.0)]
+    );
+    assert_eq!(
+        gauge_value(&registry, "varvedb_consumer_lag"),
+        vec![(vec!["7".to_string()], 3.0)]
+    );
+    let used = gauge_value(&registry, "varvedb_map_used_bytes")[0].1;
+    let free = gauge_value(&registry, "varvedb_map_free_bytes")[0].1;
+    assert!(used > 0.0);
+    assert_eq!(used + free, (10 * 1024 * 1024) as f64);
+
+    // Dropping the collector stops it; later changes are no longer reflected.
+    drop(collector);
+    writer.append(1, 6, MetricEvent { id: 6 })?;
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert_eq!(
+        gauge_value(&registry, "varvedb_events_stored"),
+        vec![(vec![], 5.0)]
+    );
+
+    // A zero interval is rejected.
+    assert!(matches!(
+        metrics.spawn_collector(storage.clone(), std::time::Duration::ZERO),
+        Err(varvedb::Error::InvalidConfig(_))
+    ));
+
     Ok(())
 }
@@ -0,0 +1,151 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct OrderedEvent {
+    stream: u32,
+    version: u32,
+}
+
+#[test]
+fn test_interleaved_appends_keep_stream_order() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<OrderedEvent>::new(storage.clone());
+
+    // Interleave three streams in a fixed, uneven pattern.
+    let pattern = [0u32, 1, 1, 2, 0, 2, 2, 1, 0, 0, 2, 1];
+    let mut heads = [0u32; 3];
+    for stream in pattern {
+        heads[stream as usize] += 1;
+        let version = heads[stream as usize];
+        writer.append(stream as u128, version, OrderedEvent { stream, version })?;
+    }
+
+    let reader = Reader::<OrderedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+
+    // The global log holds the events in append order.
+    for (i, &stream) in pattern.iter().enumerate() {
+        assert_eq!(reader.get(&txn, i as u64 + 1)?.unwrap().stream, stream);
+    }
+
+    // Replaying the global log visits every stream in version order.
+    let mut seen = [0u32; 3];
+    for seq in 1..=pattern.len() as u64 {
+        let event = reader.get(&txn, seq)?.unwrap();
+        let stream = event.stream.to_native() as usize;
+        assert_eq!(event.version, seen[stream] + 1);
+        seen[stream] += 1;
+    }
+
+    for stream in 0..3 {
+        reader.verify_stream_monotonic(&txn, stream)?;
+    }
+    reader.verify_stream_monotonic(&txn, 99)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_append_rejects_versions_below_head() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<OrderedEvent>::new(storage.clone());
+
+    // Skipping ahead is allowed, but the skipped versions can't be written afterwards.
+    writer.append(
+        1,
+        3,
+        OrderedEvent {
+            stream: 1,
+            version: 3,
+        },
+    )?;
+    assert!(matches!(
+        writer.append(
+            1,
+            2,
+            OrderedEvent {
+                stream: 1,
+                version: 2,
+            },
+        ),
+        Err(Error::ConcurrencyConflict {
+            stream_id: 1,
+            version: 2
+        })
+    ));
+    writer.append(
+        1,
+        4,
+        OrderedEvent {
+            stream: 1,
+            version: 4,
+        },
+    )?;
+
+    let reader = Reader::<OrderedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    reader.verify_stream_monotonic(&txn, 1)?;
+    assert!(reader.get_by_stream(&txn, 1, 2)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_stream_monotonic_detects_violation() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<OrderedEvent>::new(storage.clone());
+
+    // A reserved version filled after a later append lands behind it in the global log.
+    let reserved = writer.reserve_versions(1, 1)?;
+    writer.append_multi(vec![(
+        1,
+        ExpectedVersion::Auto,
+        OrderedEvent {
+            stream: 1,
+            version: 2,
+        },
+    )])?;
+    writer.append_reserved(
+        1,
+        reserved.start,
+        OrderedEvent {
+            stream: 1,
+            version: 1,
+        },
+    )?;
+
+    let reader = Reader::<OrderedEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    match reader.verify_stream_monotonic(&txn, 1) {
+        Err(Error::EventValidation(msg)) => assert!(msg.contains("version 2")),
+        other => panic!("Expected EventValidation, got {:?}", other),
+    }
+
+    Ok(())
+}
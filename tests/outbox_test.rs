@@ -0,0 +1,254 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::crypto::KeyManager;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::outbox::{OutboxEntry, OutboxRelay, OutboxStatus};
+use varvedb::processor::CancellationToken;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct OrderEvent {
+    id: u64,
+}
+
+fn open_storage(dir: &std::path::Path) -> varvedb::error::Result<Storage> {
+    Storage::open(StorageConfig {
+        path: dir.to_path_buf(),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_append_with_outbox_writes_event_and_message() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open_storage(dir.path())?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+
+    let seq = writer.append_with_outbox(
+        1,
+        ExpectedVersion::Auto,
+        OrderEvent { id: 7 },
+        OutboxEntry::new("orders", b"placed 7".to_vec()),
+    )?;
+
+    let reader = Reader::<OrderEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, seq)?.unwrap().id, 7);
+    drop(txn);
+
+    let relay = OutboxRelay::new(storage);
+    let pending = relay.pending(10)?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, 1);
+    assert_eq!(pending[0].seq, seq);
+    assert_eq!(pending[0].destination, "orders");
+    assert_eq!(pending[0].payload, b"placed 7");
+    assert_eq!(pending[0].status, OutboxStatus::Pending);
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_append_stores_no_message() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open_storage(dir.path())?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+    writer.append(1, 1, OrderEvent { id: 1 })?;
+
+    let result = writer.append_with_outbox(
+        1,
+        ExpectedVersion::exact(1),
+        OrderEvent { id: 2 },
+        OutboxEntry::new("orders", b"conflict".to_vec()),
+    );
+    assert!(matches!(
+        result,
+        Err(varvedb::error::Error::ConcurrencyConflict { .. })
+    ));
+    assert!(OutboxRelay::new(storage).pending(10)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_relay_marks_messages_sent_in_order() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open_storage(dir.path())?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+    for id in 1..=5 {
+        writer.append_with_outbox(
+            id as u128,
+            ExpectedVersion::Auto,
+            OrderEvent { id },
+            OutboxEntry::new("orders", id.to_be_bytes().to_vec()),
+        )?;
+    }
+
+    let relay = OutboxRelay::new(storage).with_batch_size(2);
+
+    // Dispatch fails on the fourth message: it and the ones after it stay pending.
+    let mut dispatched = Vec::new();
+    let result = relay.relay_pending(|message| {
+        if message.id == 4 {
+            return Err(varvedb::error::Error::Io(std::io::Error::other(
+                "broker down",
+            )));
+        }
+        dispatched.push(message.id);
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(dispatched, vec![1, 2, 3]);
+    assert_eq!(relay.message(3)?.unwrap().status, OutboxStatus::Sent);
+    let pending: Vec<u64> = relay.pending(10)?.iter().map(|m| m.id).collect();
+    assert_eq!(pending, vec![4, 5]);
+
+    // A later run resumes from the failed message.
+    let mut dispatched = Vec::new();
+    let sent = relay.relay_pending(|message| {
+        dispatched.push(message.id);
+        Ok(())
+    })?;
+    assert_eq!(sent, 2);
+    assert_eq!(dispatched, vec![4, 5]);
+    assert!(relay.pending(10)?.is_empty());
+
+    // Sent messages are kept until purged.
+    assert_eq!(relay.purge_sent()?, 5);
+    assert_eq!(relay.message(1)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_outbox_ids_are_not_reused_after_purge() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open_storage(dir.path())?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+    let relay = OutboxRelay::new(storage.clone());
+
+    for id in 1..=2 {
+        writer.append_with_outbox(
+            1,
+            ExpectedVersion::Auto,
+            OrderEvent { id },
+            OutboxEntry::new("orders", id.to_be_bytes().to_vec()),
+        )?;
+    }
+    assert_eq!(relay.relay_pending(|_| Ok(()))?, 2);
+    assert_eq!(relay.purge_sent()?, 2);
+
+    // The outbox is empty again, but IDs keep counting so receivers can still deduplicate.
+    writer.append_with_outbox(
+        1,
+        ExpectedVersion::Auto,
+        OrderEvent { id: 3 },
+        OutboxEntry::new("orders", 3u64.to_be_bytes().to_vec()),
+    )?;
+    let pending: Vec<u64> = relay.pending(10)?.iter().map(|m| m.id).collect();
+    assert_eq!(pending, vec![3]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_relays_new_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = open_storage(dir.path())?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+
+    let token = CancellationToken::new();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut relay = OutboxRelay::new(storage).with_cancellation_token(token.clone());
+    let task = tokio::spawn(async move {
+        relay
+            .run(|message| {
+                let _ = tx.send(message.payload.clone());
+                Ok(())
+            })
+            .await
+    });
+
+    for id in 1..=3u64 {
+        writer.append_with_outbox(
+            1,
+            ExpectedVersion::Auto,
+            OrderEvent { id },
+            OutboxEntry::new("orders", id.to_be_bytes().to_vec()),
+        )?;
+    }
+    for id in 1..=3u64 {
+        let payload = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await?
+            .unwrap();
+        assert_eq!(payload, id.to_be_bytes());
+    }
+
+    token.cancel();
+    task.await??;
+
+    Ok(())
+}
+
+#[test]
+fn test_outbox_messages_are_encrypted_with_the_stream_key() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        encrypted_streams: Some([1].into()),
+        master_key: Some(zeroize::Zeroizing::new([9u8; 32])),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::<OrderEvent>::new(storage.clone());
+    for stream_id in [1, 2] {
+        writer.append_with_outbox(
+            stream_id,
+            ExpectedVersion::Auto,
+            OrderEvent { id: 7 },
+            OutboxEntry::new("orders", b"card 4111".to_vec()),
+        )?;
+    }
+
+    {
+        let txn = storage.env.read_txn()?;
+        let outbox = storage.outbox.open(&txn)?.unwrap();
+        let contains = |id: u64| -> Result<bool, Box<dyn std::error::Error>> {
+            let value = outbox.get(&txn, &id)?.unwrap();
+            Ok(value.windows(9).any(|w| w == b"card 4111"))
+        };
+        assert!(!contains(1)?);
+        // Stream 2 is not in `encrypted_streams`.
+        assert!(contains(2)?);
+    }
+
+    let relay = OutboxRelay::new(storage.clone());
+    let mut sent = Vec::new();
+    assert_eq!(
+        relay.relay_pending(|message| {
+            sent.push((message.destination.clone(), message.payload.clone()));
+            Ok(())
+        })?,
+        2
+    );
+    assert_eq!(sent, vec![("orders".to_string(), b"card 4111".to_vec()); 2]);
+    assert_eq!(relay.message(1)?.unwrap().status, OutboxStatus::Sent);
+
+    KeyManager::new(storage).delete_key(1)?;
+    assert!(matches!(relay.message(1), Err(Error::KeyNotFound(_))));
+    assert_eq!(relay.message(2)?.unwrap().payload, b"card 4111");
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::traits::MetadataExt;
+use varvedb::{ExpectedVersion, Payload};
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct OrderPlaced {
+    total: u64,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct OrderMetadata {
+    order_id: u128,
+    version: u32,
+    correlation_id: String,
+}
+
+impl MetadataExt for OrderMetadata {
+    fn stream_id(&self) -> u128 {
+        self.order_id
+    }
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+fn payload(
+    order_id: u128,
+    total: u64,
+    correlation_id: &str,
+) -> Payload<OrderPlaced, OrderMetadata> {
+    Payload::new(
+        OrderPlaced { total },
+        OrderMetadata {
+            order_id,
+            version: 0,
+            correlation_id: correlation_id.to_string(),
+        },
+    )
+}
+
+fn check_round_trip(config: StorageConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<OrderPlaced>::new(storage.clone());
+
+    assert_eq!(
+        writer.append_payload(payload(7, 100, "req-1"), ExpectedVersion::Auto)?,
+        1
+    );
+    assert_eq!(
+        writer.append_payload(payload(7, 250, "req-2"), ExpectedVersion::Auto)?,
+        2
+    );
+    writer.append(8, 1, OrderPlaced { total: 5 })?;
+
+    // The stream ID comes from the metadata.
+    let reader = Reader::<OrderPlaced>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get_by_stream(&txn, 7, 2)?.unwrap().total, 250);
+
+    let metadata = reader.get_metadata::<OrderMetadata>(&txn, 2)?.unwrap();
+    assert_eq!(metadata.order_id, 7);
+    assert_eq!(metadata.correlation_id, "req-2");
+
+    // Plain appends carry no metadata.
+    assert!(reader.get_metadata::<OrderMetadata>(&txn, 3)?.is_none());
+    drop(txn);
+
+    // Expected versions are still enforced.
+    assert!(matches!(
+        writer.append_payload(payload(7, 1, "req-3"), ExpectedVersion::exact(2)),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_append_payload_stores_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    check_round_trip(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_append_payload_encrypts_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([3u8; 32])),
+        ..Default::default()
+    };
+    check_round_trip(config.clone())?;
+
+    let storage = Storage::open(config)?;
+    let txn = storage.env.read_txn()?;
+    let stored = storage
+        .event_metadata
+        .open(&txn)?
+        .unwrap()
+        .get(&txn, &1)?
+        .unwrap();
+    assert!(!stored.windows(5).any(|w| w == b"req-1"));
+
+    Ok(())
+}
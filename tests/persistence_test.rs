@@ -30,6 +30,7 @@ fn test_persistence_after_close() -> Result<(), Box<dyn std::error::Error>> {
         create_dir: true,
         encryption_enabled: false,
         master_key: None,
+        ..Default::default()
     };
 
     // 1. Open, Write, Close
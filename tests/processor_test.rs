@@ -10,7 +10,8 @@ use rkyv::{Archive, Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tempfile::tempdir;
-use varvedb::processor::{EventHandler, Processor};
+use varvedb::processor::{EventHandler, ParallelProcessor, Processor, ProcessorConfig};
+use varvedb::testing::CollectingHandler;
 use varvedb::traits::MetadataExt;
 use varvedb::{ExpectedVersion, Payload, Varve};
 
@@ -36,31 +37,20 @@ impl MetadataExt for TestMetadata {
     }
 }
 
-struct TestHandler {
-    received: Arc<Mutex<Vec<String>>>,
-}
-
-impl EventHandler<TestEvent> for TestHandler {
-    fn handle(&mut self, event: &ArchivedTestEvent) -> varvedb::error::Result<()> {
-        let mut received = self.received.lock().unwrap();
-        received.push(event.content.to_string());
-        Ok(())
-    }
+fn contents(events: Vec<TestEvent>) -> Vec<String> {
+    events.into_iter().map(|e| e.content).collect()
 }
 
 #[tokio::test]
+#[allow(clippy::useless_vec)]
 async fn test_processor_basic_flow() -> Result<(), Box<dyn std::error::Error>> {
     let dir = tempdir()?;
     let db_path = dir.path().join("processor_test.mdb");
     let mut db = Varve::open(&db_path)?;
 
-    let received = Arc::new(Mutex::new(Vec::new()));
-    let handler = TestHandler {
-        received: received.clone(),
-    };
-
+    let handler = CollectingHandler::new();
     let consumer_id = 101u64;
-    let mut processor = Processor::new(&db, handler, consumer_id);
+    let mut processor = Processor::new(&db, handler.clone(), consumer_id);
 
     let handle = tokio::spawn(async move {
         processor.run().await.unwrap();
@@ -81,12 +71,284 @@ async fn test_processor_basic_flow() -> Result<(), Box<dyn std::error::Error>> {
 
     tokio::time::sleep(Duration::from_millis(200)).await;
 
+    let rec = contents(handler.events());
+    assert_eq!(rec.len(), 3);
+    assert_eq!(rec[0], "Event 1");
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_processor_reset_cursor_reprocesses_everything(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("processor_reset_test.mdb");
+    let mut db = Varve::open(&db_path)?;
+
+    for (i, content) in ["Event 1", "Event 2", "Event 3"].iter().enumerate() {
+        let event = TestEvent {
+            content: content.to_string(),
+        };
+        let metadata = TestMetadata {
+            stream_id: 1,
+            version: (i + 1) as u32,
+        };
+        db.append(Payload::new(event, metadata), ExpectedVersion::Auto)?;
+    }
+
+    let mut processor = Processor::new(&db, CollectingHandler::new(), 7u64);
+    assert_eq!(processor.drain_into_vec()?.len(), 3);
+
+    // The committed cursor means a restart processes nothing new.
+    assert!(processor.drain_into_vec()?.is_empty());
+    assert_eq!(processor.catch_up()?, 3);
+
+    // After a reset, every event is handled again.
+    processor.reset_cursor()?;
+    assert_eq!(
+        contents(processor.drain_into_vec()?),
+        ["Event 1", "Event 2", "Event 3"]
+    );
+
+    // Seeking resumes right after the given sequence.
+    processor.seek(2)?;
+    assert_eq!(contents(processor.drain_into_vec()?), ["Event 3"]);
+
+    Ok(())
+}
+
+struct FailingHandler {
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl EventHandler<TestEvent> for FailingHandler {
+    fn handle(&mut self, event: &ArchivedTestEvent) -> varvedb::error::Result<()> {
+        if event.content == "bad" {
+            return Err(varvedb::error::Error::EventValidation(
+                "bad event".to_string(),
+            ));
+        }
+        self.received
+            .lock()
+            .unwrap()
+            .push(event.content.to_string());
+        Ok(())
+    }
+}
+
+fn append_contents(
+    db: &mut Varve<TestEvent, TestMetadata>,
+    contents: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (i, content) in contents.iter().enumerate() {
+        let event = TestEvent {
+            content: content.to_string(),
+        };
+        let metadata = TestMetadata {
+            stream_id: 1,
+            version: (i + 1) as u32,
+        };
+        db.append(Payload::new(event, metadata), ExpectedVersion::Auto)?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_processor_builder_rejects_zero_batch_size() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempdir()?;
+    let mut db = Varve::<TestEvent, TestMetadata>::open(dir.path().join("builder.mdb"))?;
+    let handler = CollectingHandler::new();
+
+    let result = Processor::builder(&db, handler.clone(), 1u64)
+        .batch_size(0)
+        .build();
+    assert!(matches!(
+        result,
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+
+    // `with_config` can't fail, so it treats 0 as 1.
+    append_contents(&mut db, &["Event 1", "Event 2"])?;
+    let mut processor = Processor::new(&db, handler.clone(), 1u64).with_config(ProcessorConfig {
+        batch_size: 0,
+        ..Default::default()
+    });
+    assert_eq!(processor.catch_up()?, 2);
+    assert_eq!(handler.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_processor_builder_error_policy_and_cancellation(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let mut db = Varve::open(dir.path().join("builder.mdb"))?;
+    append_contents(&mut db, &["Event 1", "bad", "Event 3"])?;
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    // The default policy stops at the failing event.
+    let handler = FailingHandler {
+        received: received.clone(),
+    };
+    let mut processor = Processor::builder(&db, handler, 1u64).build()?;
+    assert!(processor.run().await.is_err());
+    assert_eq!(*received.lock().unwrap(), ["Event 1"]);
+
+    // Skipping moves past it, and cancellation ends `run` cleanly.
+    received.lock().unwrap().clear();
+    let token = varvedb::processor::CancellationToken::new();
+    let handler = FailingHandler {
+        received: received.clone(),
+    };
+    let mut processor = Processor::builder(&db, handler, 2u64)
+        .batch_size(1)
+        .batch_timeout(Duration::from_millis(1))
+        .on_error(varvedb::processor::ErrorPolicy::Skip)
+        .delivery(varvedb::processor::Delivery::AtMostOnce)
+        .cancellation_token(token.clone())
+        .build()?;
+    let handle = tokio::spawn(async move { processor.run().await });
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    token.cancel();
+    tokio::time::timeout(Duration::from_secs(5), handle).await???;
+    assert_eq!(*received.lock().unwrap(), ["Event 1", "Event 3"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_processor_reports_batches() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let mut db = Varve::open(dir.path().join("batches.mdb"))?;
+    append_contents(&mut db, &["1", "2", "3", "4", "5"])?;
+
+    let registry = prometheus::Registry::new();
+    let metrics = Arc::new(varvedb::metrics::VarveMetrics::new(&registry)?);
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let seen = batches.clone();
+    let mut processor = Processor::builder(&db, CollectingHandler::<TestEvent>::new(), 3u64)
+        .batch_size(2)
+        .batch_timeout(Duration::from_secs(60))
+        .metrics(metrics.clone())
+        .on_batch(move |events, _duration| seen.lock().unwrap().push(events))
+        .build()?;
+    assert_eq!(processor.catch_up()?, 5);
+
+    assert_eq!(*batches.lock().unwrap(), [2, 2, 1]);
+    let processed = metrics
+        .processor_events_processed
+        .with_label_values(&["3"])
+        .get();
+    assert_eq!(processed, 5);
+    let durations = metrics
+        .processor_batch_duration
+        .with_label_values(&["3"])
+        .get_sample_count();
+    assert_eq!(durations, 3);
+
+    Ok(())
+}
+
+/// Appends `content` to the stream named by its first character.
+fn append_to_streams(
+    db: &mut Varve<TestEvent, TestMetadata>,
+    contents: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for content in contents {
+        let event = TestEvent {
+            content: content.to_string(),
+        };
+        let metadata = TestMetadata {
+            stream_id: stream_of(content),
+            version: 0,
+        };
+        db.append(Payload::new(event, metadata), ExpectedVersion::Auto)?;
+    }
+    Ok(())
+}
+
+fn stream_of(content: &str) -> u128 {
+    content.as_bytes()[0] as u128
+}
+
+#[test]
+fn test_parallel_processor_keeps_stream_order() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let mut db = Varve::open(dir.path().join("parallel.mdb"))?;
+    let contents: Vec<String> = (0..30)
+        .map(|i| format!("{}{:02}", ["a", "b", "c"][i % 3], i))
+        .collect();
+    append_to_streams(
+        &mut db,
+        &contents.iter().map(String::as_str).collect::<Vec<_>>(),
+    )?;
+
+    let received = Mutex::new(Vec::new());
+    let mut processor = ParallelProcessor::new(
+        &db,
+        |event: &ArchivedTestEvent| {
+            received.lock().unwrap().push(event.content.to_string());
+            Ok(())
+        },
+        1u64,
+        |event: &ArchivedTestEvent| stream_of(&event.content),
+    )
+    .with_batch_size(8)
+    .with_workers(3);
+    assert_eq!(processor.catch_up()?, 30);
+    drop(processor);
+
+    let received = received.into_inner().unwrap();
+    assert_eq!(received.len(), 30);
+    for stream in ["a", "b", "c"] {
+        let in_stream: Vec<&String> = received.iter().filter(|c| c.starts_with(stream)).collect();
+        let expected: Vec<&String> = contents.iter().filter(|c| c.starts_with(stream)).collect();
+        assert_eq!(in_stream, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_processor_stops_cursor_before_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let mut db = Varve::open(dir.path().join("parallel.mdb"))?;
+    append_to_streams(&mut db, &["a1", "b1", "a2", "b2", "a3", "b3"])?;
+
+    // "a2" (seq 3) fails once; stream "b" runs to the end of the batch meanwhile.
+    let failed = std::sync::atomic::AtomicBool::new(false);
+    let received = Mutex::new(Vec::new());
+    let handler = |event: &ArchivedTestEvent| {
+        if event.content == "a2" && !failed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return Err(varvedb::error::Error::Io(std::io::Error::other("boom")));
+        }
+        received.lock().unwrap().push(event.content.to_string());
+        Ok(())
+    };
+    let mut processor = ParallelProcessor::new(&db, &handler, 2u64, |event: &ArchivedTestEvent| {
+        stream_of(&event.content)
+    })
+    .with_workers(2);
+
+    assert!(processor.catch_up().is_err());
     {
-        let rec = received.lock().unwrap();
-        assert_eq!(rec.len(), 3);
-        assert_eq!(rec[0], "Event 1");
+        let mut received = received.lock().unwrap();
+        assert!(!received.contains(&"a3".to_string()));
+        assert!(received.contains(&"b3".to_string()));
+        received.clear();
     }
 
-    handle.abort();
+    // The cursor stopped at seq 2: the retry starts at the failed event.
+    assert_eq!(processor.catch_up()?, 6);
+    drop(processor);
+    let mut retried = received.into_inner().unwrap();
+    retried.sort();
+    assert_eq!(retried, ["a2", "a3", "b2", "b3"]);
+
     Ok(())
 }
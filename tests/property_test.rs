@@ -34,6 +34,7 @@ proptest! {
             create_dir: true,
             encryption_enabled: false,
             master_key: None,
+            ..Default::default()
         };
         let storage = Storage::open(config).unwrap();
         let mut writer = Writer::<PropEvent>::new(storage.clone());
@@ -61,6 +62,7 @@ proptest! {
             create_dir: true,
             encryption_enabled: false,
             master_key: None,
+            ..Default::default()
         };
         let storage = Storage::open(config).unwrap();
         let mut writer = Writer::<PropEvent>::new(storage.clone());
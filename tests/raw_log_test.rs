@@ -0,0 +1,120 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::constants::RAW_LOG_STREAM_ID;
+use varvedb::crypto::KeyManager;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig, StorageLayout};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct LogLine {
+    level: u8,
+    message: String,
+}
+
+fn line(level: u8, message: &str) -> LogLine {
+    LogLine {
+        level,
+        message: message.to_string(),
+    }
+}
+
+fn check_raw_roundtrip(encryption_enabled: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled,
+        master_key: encryption_enabled.then(|| zeroize::Zeroizing::new([5u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<LogLine>::new(storage.clone());
+
+    assert_eq!(writer.append_raw(line(1, "boot"))?, 1);
+    // Raw and stream events share the global sequence.
+    assert_eq!(writer.append(1, 1, line(2, "stream event"))?, 2);
+    assert_eq!(writer.append_raw(line(3, "shutdown"))?, 3);
+
+    let reader = Reader::<LogLine>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().message, "boot");
+    assert_eq!(reader.get(&txn, 3)?.unwrap().level, 3);
+    assert_eq!(
+        reader.get_by_stream(&txn, 1, 1)?.unwrap().message,
+        "stream event"
+    );
+    assert!(reader.get_by_stream(&txn, 1, 2)?.is_none());
+
+    // No stream index entries are created for raw events.
+    assert_eq!(storage.stream_index.len(&txn)?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_append_raw() -> Result<(), Box<dyn std::error::Error>> {
+    check_raw_roundtrip(false)
+}
+
+#[test]
+fn test_append_raw_encrypted() -> Result<(), Box<dyn std::error::Error>> {
+    check_raw_roundtrip(true)
+}
+
+#[test]
+fn test_append_raw_rejected_by_clustered_layout() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        layout: StorageLayout::Clustered,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<LogLine>::new(storage);
+
+    assert!(matches!(
+        writer.append_raw(line(1, "boot")),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_log_stream_is_reserved() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([5u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<LogLine>::new(storage.clone());
+
+    assert!(matches!(
+        writer.append(RAW_LOG_STREAM_ID, 1, line(1, "spoofed")),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    writer.append_raw(line(1, "boot"))?;
+    writer.append(0, 1, line(2, "user stream 0"))?;
+    // Shredding an ordinary stream leaves the raw log readable.
+    KeyManager::new(storage.clone()).delete_key(0)?;
+
+    let reader = Reader::<LogLine>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().message, "boot");
+    assert!(reader.get(&txn, 2).is_err());
+
+    Ok(())
+}
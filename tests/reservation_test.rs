@@ -0,0 +1,110 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+#[rkyv(derive(Debug))]
+struct LineEvent {
+    line: u32,
+}
+
+#[test]
+fn test_reserved_versions_are_kept_for_append_reserved() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    let mut other = writer.clone();
+
+    let reserved = writer.reserve_versions(1, 3)?;
+    assert_eq!(reserved, 1..4);
+
+    // Other appends can't take reserved versions, and Auto skips past them.
+    assert!(matches!(
+        other.append(1, 2, LineEvent { line: 0 }),
+        Err(Error::ConcurrencyConflict {
+            stream_id: 1,
+            version: 2
+        })
+    ));
+    other.append_multi(vec![(1, ExpectedVersion::Auto, LineEvent { line: 4 })])?;
+    assert_eq!(writer.reserve_versions(1, 2)?, 5..7);
+
+    // Reserved versions are filled in any order, once each.
+    for version in [3, 1, 2] {
+        writer.append_reserved(1, version, LineEvent { line: version })?;
+    }
+    assert!(matches!(
+        writer.append_reserved(1, 2, LineEvent { line: 2 }),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+    assert!(matches!(
+        writer.append_reserved(1, 9, LineEvent { line: 9 }),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+
+    let reader = Reader::<LineEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let lines: Vec<u32> = reader
+        .get_by_stream_range(&txn, 1, 1, 5)?
+        .iter()
+        .map(|(_, event)| event.line.to_native())
+        .collect();
+    assert_eq!(lines, vec![1, 2, 3, 4]);
+    // The completed reservation is gone; 5..7 is still pending.
+    assert_eq!(
+        storage.stream_reservations.open(&txn)?.unwrap().len(&txn)?,
+        1
+    );
+
+    assert!(matches!(
+        writer.reserve_versions(1, 0),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_expired_reservations_are_reclaimed() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone()).with_reservation_ttl(Duration::from_millis(20));
+
+    assert_eq!(writer.reserve_versions(1, 3)?, 1..4);
+    writer.append_reserved(1, 1, LineEvent { line: 1 })?;
+    std::thread::sleep(Duration::from_millis(40));
+
+    // The unfilled versions are free again.
+    assert!(matches!(
+        writer.append_reserved(1, 2, LineEvent { line: 2 }),
+        Err(Error::ConcurrencyConflict { .. })
+    ));
+    writer.append(1, 2, LineEvent { line: 2 })?;
+    assert_eq!(writer.reserve_versions(1, 2)?, 3..5);
+
+    let txn = storage.env.read_txn()?;
+    assert_eq!(
+        storage.stream_reservations.open(&txn)?.unwrap().len(&txn)?,
+        1
+    );
+
+    Ok(())
+}
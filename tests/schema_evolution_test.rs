@@ -0,0 +1,280 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{ReadOutcome, Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+/// The schema known to an up-to-date producer.
+mod v2 {
+    use super::*;
+
+    #[derive(Archive, Serialize, Deserialize, Debug)]
+    #[rkyv(derive(Debug))]
+    #[non_exhaustive]
+    pub enum AccountEvent {
+        Opened { id: u32 },
+        Renamed { id: u32 },
+    }
+}
+
+/// The schema known to a consumer that has not been upgraded yet.
+mod v1 {
+    use super::*;
+
+    #[derive(Archive, Serialize, Deserialize, Debug)]
+    #[rkyv(derive(Debug))]
+    #[non_exhaustive]
+    pub enum AccountEvent {
+        Opened { id: u32 },
+    }
+}
+
+#[test]
+fn test_get_or_skip_unknown_variant() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    let mut writer = Writer::<v2::AccountEvent>::new(storage.clone());
+    writer.append(1, 1, v2::AccountEvent::Opened { id: 1 })?;
+    writer.append(1, 2, v2::AccountEvent::Renamed { id: 1 })?;
+
+    let reader = Reader::<v1::AccountEvent>::new(storage.clone()).with_known_variants(1);
+    let txn = storage.env.read_txn()?;
+
+    match reader.get_or_skip(&txn, 1)? {
+        Some(ReadOutcome::Known(event)) => {
+            assert!(matches!(*event, v1::ArchivedAccountEvent::Opened { .. }));
+        }
+        other => panic!("Expected known event, got {:?}", other),
+    }
+
+    assert!(matches!(
+        reader.get_or_skip(&txn, 2)?,
+        Some(ReadOutcome::Unknown)
+    ));
+    assert!(reader.get_or_skip(&txn, 3)?.is_none());
+
+    // The strict API still reports the failure.
+    assert!(reader.get(&txn, 2).is_err());
+
+    // Without the variant count, an unknown tag cannot be told from corruption.
+    let strict = Reader::<v1::AccountEvent>::new(storage.clone());
+    assert!(strict.get_or_skip(&txn, 2).is_err());
+
+    Ok(())
+}
+
+/// Successive layouts of a user profile event, each one incompatible with the last.
+mod profile {
+    use super::*;
+
+    #[derive(Archive, Serialize, Deserialize, Debug)]
+    pub struct V0 {
+        pub name: String,
+    }
+
+    #[derive(Archive, Serialize, Deserialize, Debug)]
+    pub struct V1 {
+        pub name: String,
+        pub age: u32,
+    }
+
+    #[derive(Archive, Serialize, Deserialize, Debug)]
+    pub struct V2 {
+        pub age: u32,
+        pub name: String,
+        pub email: Option<String>,
+    }
+}
+
+fn upcast_profile(version: u16, bytes: &[u8]) -> varvedb::error::Result<Vec<u8>> {
+    let upcast = match version {
+        0 => {
+            let old = rkyv::from_bytes::<profile::V0, rkyv::rancor::Error>(bytes)?;
+            rkyv::to_bytes::<rkyv::rancor::Error>(&profile::V1 {
+                name: old.name,
+                age: 0,
+            })?
+        }
+        1 => {
+            let old = rkyv::from_bytes::<profile::V1, rkyv::rancor::Error>(bytes)?;
+            rkyv::to_bytes::<rkyv::rancor::Error>(&profile::V2 {
+                age: old.age,
+                name: old.name,
+                email: None,
+            })?
+        }
+        v => panic!("no upcaster from schema version {}", v),
+    };
+    Ok(upcast.to_vec())
+}
+
+#[test]
+fn test_upcaster_chain_reads_old_events() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    // Untagged records count as version 0; long names go through the blob store.
+    let long_name = "n".repeat(4096);
+    let mut writer = Writer::<profile::V0>::new(storage.clone());
+    writer.append(1, 1, profile::V0 { name: "ada".into() })?;
+    let mut writer = Writer::<profile::V1>::new(storage.clone()).with_schema_version(1);
+    writer.append(
+        2,
+        1,
+        profile::V1 {
+            name: "bob".into(),
+            age: 41,
+        },
+    )?;
+    writer.append(
+        3,
+        1,
+        profile::V1 {
+            name: long_name.clone(),
+            age: 7,
+        },
+    )?;
+    let mut writer = Writer::<profile::V2>::new(storage.clone()).with_schema_version(2);
+    writer.append(
+        4,
+        1,
+        profile::V2 {
+            age: 30,
+            name: "cy".into(),
+            email: Some("cy@example.com".into()),
+        },
+    )?;
+
+    let reader = Reader::<profile::V2>::new(storage.clone())
+        .with_schema_version(2)
+        .with_upcaster(upcast_profile);
+    let txn = storage.env.read_txn()?;
+
+    let ada = reader.get(&txn, 1)?.unwrap();
+    assert_eq!((ada.name.as_str(), ada.age.to_native()), ("ada", 0));
+    assert!(ada.email.is_none());
+
+    let bob = reader.get(&txn, 2)?.unwrap();
+    assert_eq!((bob.name.as_str(), bob.age.to_native()), ("bob", 41));
+
+    let long = reader.get(&txn, 3)?.unwrap();
+    assert_eq!(long.name.as_str(), long_name);
+
+    // Current events are read unchanged.
+    let cy = reader.get(&txn, 4)?.unwrap();
+    assert_eq!(
+        cy.email.as_ref().map(|e| e.as_str()),
+        Some("cy@example.com")
+    );
+
+    // Without an upcaster, old layouts fail validation as the new type.
+    let plain = Reader::<profile::V2>::new(storage.clone()).with_schema_version(2);
+    assert!(plain.get(&txn, 1).is_err());
+    assert_eq!(plain.get(&txn, 4)?.unwrap().age, 30);
+
+    Ok(())
+}
+
+/// The names seen in a profile stream, in order.
+#[derive(Default)]
+struct Names(Vec<String>);
+
+impl varvedb::StreamState<profile::V2> for Names {
+    fn apply(&mut self, event: &profile::ArchivedV2) {
+        self.0.push(event.name.to_string());
+    }
+}
+
+#[test]
+fn test_append_if_upcasts_old_events() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    let mut writer = Writer::<profile::V0>::new(storage.clone());
+    writer.append(1, 1, profile::V0 { name: "ada".into() })?;
+
+    let mut writer = Writer::<profile::V2>::new(storage.clone())
+        .with_schema_version(2)
+        .with_upcaster(upcast_profile);
+    let renamed = writer.append_if(
+        1,
+        varvedb::ExpectedVersion::Auto,
+        profile::V2 {
+            age: 36,
+            name: "ada l.".into(),
+            email: None,
+        },
+        |names: &Names| names.0 == ["ada"],
+    )?;
+    assert_eq!(renamed, Some(2));
+
+    // The folded state covers both layouts.
+    let again = writer.append_if(
+        1,
+        varvedb::ExpectedVersion::Auto,
+        profile::V2 {
+            age: 36,
+            name: "ada".into(),
+            email: None,
+        },
+        |names: &Names| names.0.len() < 2,
+    )?;
+    assert_eq!(again, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_get_as_reads_records_of_an_older_type() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    // Historical records were written with the combined type.
+    let mut writer = Writer::<v2::AccountEvent>::new(storage.clone());
+    writer.append(1, 1, v2::AccountEvent::Opened { id: 1 })?;
+    writer.append(1, 2, v2::AccountEvent::Renamed { id: 1 })?;
+
+    // The reader's own type only has the variant that was kept.
+    let reader = Reader::<v1::AccountEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+
+    let old = reader.get_as::<v2::AccountEvent>(&txn, 2)?.unwrap();
+    assert!(matches!(*old, v2::ArchivedAccountEvent::Renamed { .. }));
+
+    // A compatible subset type reads the records it can describe...
+    let opened = reader.get_as::<v1::AccountEvent>(&txn, 1)?.unwrap();
+    assert!(matches!(*opened, v1::ArchivedAccountEvent::Opened { .. }));
+
+    // ...and fails validation, naming the record, on the ones it can't.
+    assert!(matches!(
+        reader.get_as::<v1::AccountEvent>(&txn, 2),
+        Err(varvedb::Error::AtSequence { seq: 2, .. })
+    ));
+    assert!(reader.get_as::<v2::AccountEvent>(&txn, 3)?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+struct Payment {
+    customer_id: u64,
+    region: String,
+    amount: u64,
+}
+
+#[test]
+fn test_find_by_secondary_index() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    let mut writer = Writer::<Payment>::new(storage.clone());
+    writer.with_index("customer", |p: &Payment| Some(p.customer_id))?;
+    // Only index payments that have a region.
+    writer.with_index("region", |p: &Payment| {
+        (!p.region.is_empty()).then(|| p.region.clone())
+    })?;
+
+    let payments = [
+        (7, "eu", 100),
+        (8, "us", 200),
+        (7, "", 300),
+        (7, "eu-west", 400),
+    ];
+    for (i, (customer_id, region, amount)) in payments.into_iter().enumerate() {
+        writer.append(
+            customer_id as u128,
+            i as u32 + 1,
+            Payment {
+                customer_id,
+                region: region.to_string(),
+                amount,
+            },
+        )?;
+    }
+
+    let reader = Reader::<Payment>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+
+    let for_seven = reader.find_by(&txn, "customer", &7u64)?;
+    let seqs: Vec<u64> = for_seven.iter().map(|(seq, _)| *seq).collect();
+    assert_eq!(seqs, vec![1, 3, 4]);
+    let amounts: Vec<u64> = for_seven.iter().map(|(_, e)| e.amount.into()).collect();
+    assert_eq!(amounts, vec![100, 300, 400]);
+
+    // Exact key match only: "eu" must not match "eu-west".
+    let eu = reader.find_by(&txn, "region", "eu")?;
+    assert_eq!(eu.len(), 1);
+    assert_eq!(eu[0].0, 1);
+
+    assert!(reader.find_by(&txn, "customer", &9u64)?.is_empty());
+    assert!(reader.find_by(&txn, "missing_index", &7u64)?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,271 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::tempdir;
+use varvedb::engine::Writer;
+use varvedb::serve::{connect_tcp, RecordStream, Stream, TcpRecords};
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct WireEvent {
+    value: u32,
+}
+
+fn value_of(bytes: &[u8]) -> u32 {
+    rkyv::access::<ArchivedWireEvent, rkyv::rancor::Error>(bytes)
+        .unwrap()
+        .value
+        .to_native()
+}
+
+async fn next(records: &mut TcpRecords) -> varvedb::error::Result<Option<(u64, Vec<u8>)>> {
+    tokio::time::timeout(Duration::from_secs(10), records.next())
+        .await
+        .expect("no record received")
+}
+
+#[tokio::test]
+async fn test_serve_tcp_backfills_and_tails() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::new(storage.clone());
+    for value in 1..=3 {
+        writer.append(1, value, WireEvent { value })?;
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn({
+        let storage = storage.clone();
+        async move { storage.serve_listener(listener, 1).await }
+    });
+
+    let mut records = connect_tcp(addr, 2).await?;
+    // Backfill starts at the requested sequence number.
+    for seq in 2..=3 {
+        let (got, bytes) = next(&mut records).await?.unwrap();
+        assert_eq!((got, value_of(&bytes)), (seq, seq as u32));
+    }
+
+    // New commits are streamed as they happen.
+    writer.append(1, 4, WireEvent { value: 4 })?;
+    let (seq, bytes) = next(&mut records).await?.unwrap();
+    assert_eq!((seq, value_of(&bytes)), (4, 4));
+
+    // A second client is served independently.
+    let mut other = connect_tcp(addr, 0).await?;
+    let (seq, bytes) = next(&mut other).await?.unwrap();
+    assert_eq!((seq, value_of(&bytes)), (1, 1));
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_serve_requires_opt_in_for_encrypted_storage() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([7u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    let mut writer = Writer::new(storage.clone());
+    writer.append(1, 1, WireEvent { value: 1 })?;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    assert!(matches!(
+        storage.serve_listener(listener, 1).await,
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+
+    drop(writer);
+    drop(storage);
+    let storage = Storage::open(StorageConfig {
+        serve_decrypted: true,
+        ..config
+    })?;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn({
+        let storage = storage.clone();
+        async move { storage.serve_listener(listener, 1).await }
+    });
+
+    let mut records = connect_tcp(addr, 1).await?;
+    let (seq, bytes) = next(&mut records).await?.unwrap();
+    assert_eq!((seq, value_of(&bytes)), (1, 1));
+
+    server.abort();
+    Ok(())
+}
+
+async fn next_event<R>(
+    events: &mut RecordStream<WireEvent, R>,
+) -> Option<varvedb::error::Result<(u64, WireEvent)>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let next = std::future::poll_fn(|cx| std::pin::Pin::new(&mut *events).poll_next(cx));
+    tokio::time::timeout(Duration::from_secs(10), next)
+        .await
+        .expect("no event received")
+}
+
+fn frame(seq: u64, event: &WireEvent) -> Vec<u8> {
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(event).unwrap();
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&(bytes.len() as u32 + 8).to_be_bytes());
+    frame.extend_from_slice(&seq.to_be_bytes());
+    frame.extend_from_slice(&bytes);
+    frame
+}
+
+#[tokio::test]
+async fn test_record_stream_decodes_served_events() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    for value in 1..=3 {
+        writer.append(1, value, WireEvent { value })?;
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn({
+        let storage = storage.clone();
+        async move { storage.serve_listener(listener, 1).await }
+    });
+
+    let mut events = connect_tcp(addr, 1)
+        .await?
+        .into_record_stream::<WireEvent>();
+    for seq in 1..=3 {
+        let event = next_event(&mut events).await.unwrap()?;
+        assert_eq!(event, (seq, WireEvent { value: seq as u32 }));
+    }
+    writer.append(1, 4, WireEvent { value: 4 })?;
+    assert_eq!(
+        next_event(&mut events).await.unwrap()?,
+        (4, WireEvent { value: 4 })
+    );
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_stream_over_raw_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bytes = frame(7, &WireEvent { value: 70 });
+    bytes.extend(frame(9, &WireEvent { value: 90 }));
+
+    // Frames are reassembled across reads of any size; a clean end finishes the stream.
+    let (mut tx, rx) = tokio::io::duplex(3);
+    let feed = tokio::spawn({
+        let bytes = bytes.clone();
+        async move { tokio::io::AsyncWriteExt::write_all(&mut tx, &bytes).await }
+    });
+    let mut events = RecordStream::<WireEvent, _>::new(rx);
+    assert_eq!(
+        next_event(&mut events).await.unwrap()?,
+        (7, WireEvent { value: 70 })
+    );
+    assert_eq!(
+        next_event(&mut events).await.unwrap()?,
+        (9, WireEvent { value: 90 })
+    );
+    feed.await??;
+    assert!(next_event(&mut events).await.is_none());
+
+    // A frame cut short is an error, not a clean end.
+    let mut events = RecordStream::<WireEvent, _>::new(&bytes[..bytes.len() - 1]);
+    assert!(next_event(&mut events).await.unwrap().is_ok());
+    assert!(next_event(&mut events).await.unwrap().is_err());
+
+    // So is an event that fails validation.
+    let mut corrupt = frame(1, &WireEvent { value: 1 });
+    corrupt.truncate(12 + 2);
+    corrupt[..4].copy_from_slice(&10u32.to_be_bytes());
+    let mut events = RecordStream::<WireEvent, _>::new(&corrupt[..]);
+    assert!(next_event(&mut events).await.unwrap().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oversized_frames_are_rejected() -> Result<(), Box<dyn std::error::Error>> {
+    let is_invalid_data = |e: &varvedb::error::Error| matches!(e, varvedb::error::Error::Io(io) if io.kind() == std::io::ErrorKind::InvalidData);
+
+    // A length near u32::MAX fails up front instead of waiting for gigabytes.
+    let huge = u32::MAX.to_be_bytes();
+    let mut events = RecordStream::<WireEvent, _>::new(&huge[..]);
+    assert!(is_invalid_data(
+        &next_event(&mut events).await.unwrap().unwrap_err()
+    ));
+
+    // The limit is configurable.
+    let bytes = frame(1, &WireEvent { value: 1 });
+    let mut events = RecordStream::<WireEvent, _>::new(&bytes[..]).with_max_frame_len(8);
+    assert!(is_invalid_data(
+        &next_event(&mut events).await.unwrap().unwrap_err()
+    ));
+    let mut events =
+        RecordStream::<WireEvent, _>::new(&bytes[..]).with_max_frame_len(bytes.len() - 4);
+    assert_eq!(
+        next_event(&mut events).await.unwrap()?,
+        (1, WireEvent { value: 1 })
+    );
+
+    // TcpRecords applies the same limit.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await?;
+        tokio::io::AsyncWriteExt::write_all(&mut socket, &huge).await?;
+        Ok::<_, std::io::Error>(socket)
+    });
+    let mut records = connect_tcp(addr, 1).await?;
+    assert!(is_invalid_data(&next(&mut records).await.unwrap_err()));
+    server.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tcp_records_reject_truncated_length() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await?;
+        tokio::io::AsyncReadExt::read_u64(&mut socket).await?;
+        tokio::io::AsyncWriteExt::write_all(&mut socket, &[0, 0]).await?;
+        Ok::<_, std::io::Error>(())
+    });
+
+    // The server closes halfway through a length prefix.
+    let mut records = connect_tcp(addr, 1).await?;
+    server.await??;
+    assert!(matches!(
+        next(&mut records).await,
+        Err(varvedb::error::Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+
+    Ok(())
+}
@@ -9,7 +9,7 @@
 use rkyv::{Archive, Deserialize, Serialize};
 use tempfile::tempdir;
 use varvedb::engine::{Reader, Writer};
-use varvedb::storage::{Storage, StorageConfig};
+use varvedb::storage::{InlineThreshold, Storage, StorageConfig};
 
 #[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
 #[repr(C)]
@@ -85,3 +85,149 @@ fn test_sidecar_storage_large_payload() -> Result<(), Box<dyn std::error::Error>
 
     Ok(())
 }
+
+#[test]
+fn test_separate_blob_env() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        separate_blob_env: true,
+        verify_checksums: true,
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    assert!(dir.path().join("blobs").join("data.mdb").exists());
+
+    let mut writer = Writer::<TestEvent>::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        TestEvent {
+            id: 1,
+            data: vec![7u8; 5000],
+        },
+    )?;
+    writer.append(
+        1,
+        2,
+        TestEvent {
+            id: 2,
+            data: vec![1u8; 10],
+        },
+    )?;
+
+    let blob_env = storage.blob_env.clone().unwrap();
+    let blob_txn = blob_env.read_txn()?;
+    assert_eq!(storage.blobs.len(&blob_txn)?, 1);
+    drop(blob_txn);
+
+    let reader = Reader::<TestEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let large = reader.get(&txn, 1)?.unwrap();
+    assert_eq!(large.data.len(), 5000);
+    assert!(large.data.iter().all(|&b| b == 7));
+    assert_eq!(reader.get(&txn, 2)?.unwrap().id, 2);
+    drop(txn);
+    drop((reader, writer, storage));
+
+    // The setting is fixed when the database is created.
+    let mismatched = StorageConfig {
+        separate_blob_env: false,
+        ..config
+    };
+    assert!(matches!(
+        Storage::open(mismatched),
+        Err(varvedb::Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_page_size_inline_threshold() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        inline_threshold: InlineThreshold::PageSize,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    assert!(storage.page_size().is_power_of_two());
+    let limit = storage.max_inline_size();
+    assert!(limit > 0 && limit < storage.page_size() as usize / 2);
+
+    // The largest event that fits under the limit, and the smallest one that doesn't.
+    let archived_len = |len: usize| {
+        rkyv::to_bytes::<rkyv::rancor::Error>(&TestEvent {
+            id: 0,
+            data: vec![0u8; len],
+        })
+        .unwrap()
+        .len()
+    };
+    let mut len = 0;
+    while archived_len(len + 1) <= limit {
+        len += 1;
+    }
+
+    let mut writer = Writer::<TestEvent>::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        TestEvent {
+            id: 1,
+            data: vec![1u8; len],
+        },
+    )?;
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.blobs.len(&txn)?, 0);
+    drop(txn);
+
+    writer.append(
+        1,
+        2,
+        TestEvent {
+            id: 2,
+            data: vec![2u8; len + 1],
+        },
+    )?;
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.blobs.len(&txn)?, 1);
+
+    let reader = Reader::<TestEvent>::new(storage.clone());
+    assert_eq!(reader.get(&txn, 1)?.unwrap().data.len(), len);
+    assert_eq!(reader.get(&txn, 2)?.unwrap().data.len(), len + 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_disable_blob_offload_keeps_large_events_inline() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        disable_blob_offload: true,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    assert_eq!(storage.max_inline_size(), usize::MAX);
+
+    let mut writer = Writer::<TestEvent>::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        TestEvent {
+            id: 1,
+            data: vec![9u8; 100_000],
+        },
+    )?;
+
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.blobs.len(&txn)?, 0);
+    let reader = Reader::<TestEvent>::new(storage.clone());
+    let event = reader.get(&txn, 1)?.unwrap();
+    assert_eq!(event.data.len(), 100_000);
+    assert!(event.data.iter().all(|&b| b == 9));
+
+    Ok(())
+}
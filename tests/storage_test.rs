@@ -0,0 +1,768 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::sync::{Arc, Barrier};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig, SyncMode};
+
+#[test]
+fn test_max_readers_exhaustion() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        map_size: 10 * 1024 * 1024,
+        max_readers: 2,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    // Hold both reader slots from two other threads.
+    let opened = Arc::new(Barrier::new(3));
+    let release = Arc::new(Barrier::new(3));
+    let handles: Vec<_> = (0..2)
+        .map(|_| {
+            let storage = storage.clone();
+            let opened = opened.clone();
+            let release = release.clone();
+            std::thread::spawn(move || {
+                let _txn = storage.env.read_txn().unwrap();
+                opened.wait();
+                release.wait();
+            })
+        })
+        .collect();
+
+    opened.wait();
+    let info = storage.reader_table_info();
+    assert_eq!(info.max, 2);
+    assert_eq!(info.active, 2);
+
+    match storage.env.read_txn().map_err(Error::from) {
+        Err(Error::ReadersFull) => {}
+        other => panic!("Expected ReadersFull error, got {:?}", other.map(|_| ())),
+    }
+
+    release.wait();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_max_readers_zero_is_rejected() {
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        max_readers: 0,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Storage::open(config),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+/// Environment variable naming the database `stale_reader_child` opens.
+const STALE_READER_DIR: &str = "VARVEDB_STALE_READER_DIR";
+
+/// Run in a child process by `leave_stale_reader`: takes a reader slot and exits without
+/// releasing it, as a crashed reader would.
+#[test]
+#[ignore = "helper run in a child process by test_clear_stale_readers"]
+fn stale_reader_child() {
+    let Some(path) = std::env::var_os(STALE_READER_DIR) else {
+        return;
+    };
+    let storage = Storage::open(StorageConfig {
+        path: path.into(),
+        skip_writer_lock: true,
+        ..Default::default()
+    })
+    .unwrap();
+    let txn = storage.env.read_txn().unwrap();
+    std::mem::forget(txn);
+    unsafe { libc::_exit(0) };
+}
+
+fn leave_stale_reader(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args([
+            "stale_reader_child",
+            "--exact",
+            "--ignored",
+            "--test-threads=1",
+        ])
+        .env(STALE_READER_DIR, path)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+fn test_clear_stale_readers() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    assert_eq!(storage.clear_stale_readers()?, 0);
+
+    leave_stale_reader(dir.path())?;
+    assert_eq!(storage.clear_stale_readers()?, 1);
+    assert_eq!(storage.clear_stale_readers()?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_reader_cleanup() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        auto_reader_cleanup: Some(std::time::Duration::from_millis(10)),
+        ..Default::default()
+    })?;
+
+    // The background thread reclaims the slot before we get to it.
+    leave_stale_reader(dir.path())?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(storage.clear_stale_readers()?, 0);
+
+    // Dropping the last handle shuts the thread down without hanging.
+    drop(storage);
+
+    Ok(())
+}
+
+#[test]
+fn test_background_flush_with_no_sync() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        sync_mode: SyncMode::NoSync,
+        flush_interval: Some(std::time::Duration::from_millis(10)),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let clone = storage.clone();
+
+    let mut txn = storage.env.write_txn()?;
+    storage.consumer_cursors.put(&mut txn, &1, &42)?;
+    txn.commit()?;
+
+    std::thread::sleep(std::time::Duration::from_millis(30));
+
+    // Dropping one clone must not stop the flusher; dropping the last one must join it.
+    drop(storage);
+    let txn = clone.env.read_txn()?;
+    assert_eq!(clone.consumer_cursors.get(&txn, &1)?, Some(42));
+    drop(txn);
+    drop(clone);
+
+    Ok(())
+}
+
+#[test]
+fn test_zero_flush_interval_is_rejected() {
+    let dir = tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        flush_interval: Some(std::time::Duration::ZERO),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Storage::open(config),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn test_lock_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        map_size: 10 * 1024 * 1024,
+        lock_memory: true,
+        ..Default::default()
+    };
+
+    // Locking either succeeds or reports the limit explicitly; it never silently continues.
+    match Storage::open(config) {
+        Ok(storage) => storage.lock_memory()?,
+        Err(Error::MemoryLock(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_open_rejects_foreign_lmdb_env() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+
+    // Create an LMDB environment that some other application owns.
+    {
+        let env = unsafe { heed::EnvOpenOptions::new().max_dbs(10).open(dir.path())? };
+        let mut txn = env.write_txn()?;
+        let db: heed::Database<heed::types::Str, heed::types::Str> =
+            env.create_database(&mut txn, Some("settings"))?;
+        db.put(&mut txn, "theme", "dark")?;
+        txn.commit()?;
+        env.prepare_for_closing().wait();
+    }
+
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    match Storage::open(config) {
+        Err(Error::InvalidConfig(msg)) => assert!(msg.contains("not a VarveDB database")),
+        other => panic!("Expected InvalidConfig, got {:?}", other.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_open_rejects_corrupted_file() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    std::fs::write(dir.path().join("data.mdb"), vec![0xAB; 64 * 1024])?;
+
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    match Storage::open(config) {
+        Err(Error::InvalidConfig(msg)) => assert!(msg.contains("not a VarveDB database")),
+        other => panic!("Expected InvalidConfig, got {:?}", other.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_open_existing_directory_creates_database() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    std::fs::write(dir.path().join("README.txt"), "unrelated file")?;
+
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    drop(storage);
+
+    // Reopening our own database is accepted.
+    Storage::open(config)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_list_consumers() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    let txn = storage.env.read_txn()?;
+    assert!(storage.list_consumers(&txn)?.is_empty());
+    drop(txn);
+
+    let mut txn = storage.env.write_txn()?;
+    storage.consumer_cursors.put(&mut txn, &9, &3)?;
+    storage.consumer_cursors.put(&mut txn, &2, &40)?;
+    txn.commit()?;
+
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.list_consumers(&txn)?, vec![(2, 40), (9, 3)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_without_tls_allows_nested_read_txns() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        reader_tls: false,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+
+    // Without TLS, reader slots belong to transactions, so one thread can hold several.
+    let first = storage.env.read_txn()?;
+    let second = storage.env.read_txn()?;
+    assert_eq!(storage.reader_table_info().active, 2);
+    drop(first);
+    drop(second);
+
+    Ok(())
+}
+
+#[test]
+fn test_open_in_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let storage = Storage::open_in_memory()?;
+    let path = storage.config.path.clone();
+    assert!(path.exists());
+
+    let clone = storage.clone();
+    let mut txn = storage.env.write_txn()?;
+    storage.consumer_cursors.put(&mut txn, &1, &42)?;
+    txn.commit()?;
+    drop(storage);
+
+    // The data lives as long as any clone does.
+    let txn = clone.env.read_txn()?;
+    assert_eq!(clone.consumer_cursors.get(&txn, &1)?, Some(42));
+    drop(txn);
+    drop(clone);
+
+    assert!(!path.exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_second_writer_process_is_rejected() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::io::AsRawFd;
+
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+
+    // Opens within this process share the writer lock.
+    let storage = Storage::open(config.clone())?;
+    let second = Storage::open(config.clone())?;
+    drop(second);
+    drop(storage);
+
+    // Hold the lock through a separate file description, as another process would.
+    let lock_file = std::fs::File::open(dir.path().join("varvedb.lock"))?;
+    assert_eq!(
+        unsafe { libc::flock(lock_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) },
+        0
+    );
+
+    match Storage::open(config.clone()) {
+        Err(Error::InvalidConfig(msg)) => assert_eq!(msg, "database already open for writing"),
+        other => panic!("Expected InvalidConfig, got {:?}", other.map(|_| ())),
+    }
+
+    // Readers don't need the lock.
+    let reader = Storage::open(StorageConfig {
+        skip_writer_lock: true,
+        ..config.clone()
+    })?;
+    drop(reader);
+
+    drop(lock_file);
+    Storage::open(config)?;
+
+    Ok(())
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct ClearEvent {
+    customer: u64,
+    payload: Vec<u8>,
+}
+
+#[test]
+fn test_clear_all() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        encryption_enabled: true,
+        master_key: Some(zeroize::Zeroizing::new([5u8; 32])),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    writer.with_index("customer", |e: &ClearEvent| Some(e.customer))?;
+    let reader = Reader::<ClearEvent>::new(storage.clone());
+
+    // One small event, one large enough for the blob store.
+    for (version, len) in [(1, 10), (2, 10_000)] {
+        writer.append(
+            1,
+            version,
+            ClearEvent {
+                customer: 7,
+                payload: vec![1u8; len],
+            },
+        )?;
+    }
+    let mut txn = storage.env.write_txn()?;
+    storage.consumer_cursors.put(&mut txn, &1, &2)?;
+    txn.commit()?;
+
+    let txn = storage.env.read_txn()?;
+    assert_eq!(storage.blobs.len(&txn)?, 1);
+    assert_eq!(storage.keystore.len(&txn)?, 1);
+    drop(txn);
+
+    storage.clear_all(true)?;
+    assert_eq!(*storage.notifier_rx.borrow(), 0);
+
+    let txn = storage.env.read_txn()?;
+    assert!(storage.events_log.is_empty(&txn)?);
+    assert!(storage.stream_index.is_empty(&txn)?);
+    assert!(storage.consumer_cursors.is_empty(&txn)?);
+    assert!(storage.blobs.is_empty(&txn)?);
+    assert!(reader.find_by(&txn, "customer", &7u64)?.is_empty());
+    // The stream key was kept.
+    assert_eq!(storage.keystore.len(&txn)?, 1);
+    drop(txn);
+
+    // The existing writer starts over at sequence 1 and version 1.
+    let seq = writer.append(
+        1,
+        1,
+        ClearEvent {
+            customer: 8,
+            payload: vec![2u8; 10],
+        },
+    )?;
+    assert_eq!(seq, 1);
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().customer, 8);
+    drop(txn);
+
+    storage.clear_all(false)?;
+    let txn = storage.env.read_txn()?;
+    assert!(storage.events_log.is_empty(&txn)?);
+    assert!(storage.keystore.is_empty(&txn)?);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dir_mode_restricts_permissions() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir()?;
+    let path = dir.path().join("private");
+    let config = StorageConfig {
+        path: path.clone(),
+        dir_mode: Some(0o700),
+        separate_blob_env: true,
+        ..Default::default()
+    };
+    let _storage = Storage::open(config)?;
+
+    let mode = |p: &std::path::Path| -> std::io::Result<u32> {
+        Ok(std::fs::metadata(p)?.permissions().mode() & 0o777)
+    };
+    assert_eq!(mode(&path)?, 0o700);
+    assert_eq!(mode(&path.join("blobs"))?, 0o700);
+    for file in ["data.mdb", "lock.mdb", "varvedb.lock"] {
+        assert_eq!(
+            mode(&path.join(file))? & 0o077,
+            0,
+            "{} is accessible to others",
+            file
+        );
+    }
+    assert_eq!(mode(&path.join("blobs").join("data.mdb"))? & 0o077, 0);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dir_mode_restricts_permissions_without_writer_lock(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir()?;
+    let path = dir.path().join("private");
+    // Lease writers skip the writer lock but still create the database.
+    let _storage = Storage::open(StorageConfig {
+        path: path.clone(),
+        dir_mode: Some(0o700),
+        skip_writer_lock: true,
+        ..Default::default()
+    })?;
+
+    for file in ["data.mdb", "lock.mdb"] {
+        let mode = std::fs::metadata(path.join(file))?.permissions().mode();
+        assert_eq!(mode & 0o077, 0, "{} is accessible to others", file);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_retries_while_lock_is_released() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::io::AsRawFd;
+
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        open_retries: 50,
+        open_retry_delay: std::time::Duration::from_millis(10),
+        ..Default::default()
+    };
+    drop(Storage::open(config.clone())?);
+
+    // Another "process" holds the lock for a moment after closing.
+    let lock_file = std::fs::File::open(dir.path().join("varvedb.lock"))?;
+    assert_eq!(
+        unsafe { libc::flock(lock_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) },
+        0
+    );
+    assert!(matches!(
+        Storage::open(StorageConfig {
+            open_retries: 0,
+            ..config.clone()
+        }),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        drop(lock_file);
+    });
+    let storage = Storage::open(config)?;
+    release.join().unwrap();
+    drop(storage);
+
+    Ok(())
+}
+
+#[test]
+fn test_open_recovers_tail_after_crash() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+
+    let storage = Storage::open(config.clone())?;
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    for customer in 1..=3 {
+        writer.append(
+            customer as u128,
+            1,
+            ClearEvent {
+                customer,
+                payload: Vec::new(),
+            },
+        )?;
+    }
+    // Leak the handles instead of dropping them, as a crashed process would.
+    std::mem::forget(writer);
+    std::mem::forget(storage);
+
+    let storage = Storage::open(config)?;
+    assert_eq!(*storage.notifier_rx.borrow(), 3);
+    assert_eq!(storage.recover_tail()?, 4);
+
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    let seq = writer.append(
+        9,
+        1,
+        ClearEvent {
+            customer: 9,
+            payload: Vec::new(),
+        },
+    )?;
+    assert_eq!(seq, 4);
+    assert_eq!(*storage.notifier_rx.borrow(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_checkpoint_records_durable_sequence() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        sync_mode: SyncMode::NoSync,
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    assert_eq!(storage.durable_sequence()?, 0);
+
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    for customer in 1..=3 {
+        writer.append(
+            customer as u128,
+            1,
+            ClearEvent {
+                customer,
+                payload: Vec::new(),
+            },
+        )?;
+    }
+    assert_eq!(storage.checkpoint()?, 3);
+
+    // Events appended after the checkpoint are beyond the watermark.
+    writer.append(
+        4,
+        1,
+        ClearEvent {
+            customer: 4,
+            payload: Vec::new(),
+        },
+    )?;
+    assert_eq!(storage.durable_sequence()?, 3);
+    drop(writer);
+    drop(storage);
+
+    let storage = Storage::open(config)?;
+    assert_eq!(storage.durable_sequence()?, 3);
+    storage.clear_all(false)?;
+    assert_eq!(storage.durable_sequence()?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_notify_coalesce_publishes_true_head() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        sync_mode: SyncMode::NoSync,
+        notify_coalesce: Some(std::time::Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut rx = storage.notifier.subscribe();
+
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    for customer in 1..=10 {
+        writer.append(
+            customer as u128,
+            1,
+            ClearEvent {
+                customer,
+                payload: Vec::new(),
+            },
+        )?;
+    }
+    // The appends only recorded the head; it is published on the next tick.
+    assert!(!rx.has_changed()?);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !rx.has_changed()? {
+        assert!(std::time::Instant::now() < deadline, "head never published");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(*rx.borrow_and_update(), 10);
+
+    // Resets are published right away.
+    storage.clear_all(false)?;
+    assert_eq!(*rx.borrow_and_update(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_zero_notify_coalesce_is_rejected() {
+    let dir = tempdir().unwrap();
+    let result = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        notify_coalesce: Some(std::time::Duration::ZERO),
+        ..Default::default()
+    });
+    assert!(matches!(result, Err(Error::InvalidConfig(_))));
+}
+
+#[test]
+fn test_close_publishes_coalesced_head() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        sync_mode: SyncMode::NoSync,
+        notify_coalesce: Some(std::time::Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    let rx = storage.notifier.subscribe();
+
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    writer.append(
+        1,
+        1,
+        ClearEvent {
+            customer: 1,
+            payload: Vec::new(),
+        },
+    )?;
+    drop(writer);
+    assert_eq!(*rx.borrow(), 0);
+
+    // Closing flushes the head that was held back, and synced data survives reopening.
+    storage.close()?;
+    assert_eq!(*rx.borrow(), 1);
+    drop(rx);
+
+    let storage = Storage::open(config)?;
+    let reader = Reader::<ClearEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 1)?.unwrap().customer, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_warm_on_open() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config.clone())?;
+    assert_eq!(storage.warm(None)?, 0);
+    let mut writer = Writer::<ClearEvent>::new(storage.clone());
+    for customer in 1..=5 {
+        writer.append(
+            customer as u128,
+            1,
+            ClearEvent {
+                customer,
+                payload: vec![customer as u8; 10_000],
+            },
+        )?;
+    }
+    drop(writer);
+
+    assert_eq!(storage.warm(None)?, 5);
+    assert_eq!(storage.warm(Some(2))?, 2);
+    assert_eq!(storage.warm(Some(100))?, 5);
+    assert_eq!(storage.warm(Some(0))?, 0);
+    drop(storage);
+
+    let storage = Storage::open(StorageConfig {
+        warm_on_open: true,
+        warm_window: Some(3),
+        ..config
+    })?;
+    let reader = Reader::<ClearEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get(&txn, 5)?.unwrap().customer, 5);
+
+    Ok(())
+}
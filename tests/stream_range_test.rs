@@ -0,0 +1,161 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(derive(Debug))]
+struct RangeEvent {
+    value: u32,
+}
+
+#[test]
+fn test_get_by_stream_range() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<RangeEvent>::new(storage.clone());
+
+    for version in 1..=5 {
+        writer.append(
+            1,
+            version,
+            RangeEvent {
+                value: version * 10,
+            },
+        )?;
+        // A neighbouring stream must never leak into the window.
+        writer.append(2, version, RangeEvent { value: version })?;
+    }
+
+    let reader = Reader::<RangeEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+
+    let window: Vec<(u32, u32)> = reader
+        .get_by_stream_range(&txn, 1, 2, 4)?
+        .into_iter()
+        .map(|(version, view)| (version, view.value.to_native()))
+        .collect();
+    assert_eq!(window, vec![(2, 20), (3, 30)]);
+
+    // Windows running past the end of the stream are truncated.
+    assert_eq!(reader.get_by_stream_range(&txn, 1, 4, 100)?.len(), 2);
+    assert_eq!(reader.get_by_stream_range(&txn, 1, 0, u32::MAX)?.len(), 5);
+
+    // Empty and inverted windows return nothing.
+    assert!(reader.get_by_stream_range(&txn, 1, 3, 3)?.is_empty());
+    assert!(reader.get_by_stream_range(&txn, 1, 4, 2)?.is_empty());
+    assert!(reader.get_by_stream_range(&txn, 3, 1, 10)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<RangeEvent>::new(storage.clone());
+    for version in 1..=5 {
+        writer.append(1, version, RangeEvent { value: version })?;
+        writer.append(2, version, RangeEvent { value: 100 })?;
+    }
+
+    let reader = Reader::<RangeEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let sum = |total: &mut u32, event: &ArchivedRangeEvent| *total += event.value.to_native();
+
+    reader.replay_verify(&txn, 1, sum, &[(1, 1), (3, 6), (5, 15)])?;
+    reader.replay_verify(&txn, 1, sum, &[])?;
+
+    // A projection that drifted from the checkpoints is reported at the first mismatch.
+    let drifted = |total: &mut u32, event: &ArchivedRangeEvent| {
+        *total += event.value.to_native().min(3);
+    };
+    let err = reader
+        .replay_verify(&txn, 1, drifted, &[(3, 6), (5, 15)])
+        .unwrap_err();
+    assert!(err.to_string().contains("at version 5"), "{}", err);
+
+    // Checkpoints past the head or out of order are rejected.
+    assert!(matches!(
+        reader.replay_verify(&txn, 1, sum, &[(6, 21)]),
+        Err(Error::EventValidation(_))
+    ));
+    assert!(matches!(
+        reader.replay_verify(&txn, 1, sum, &[(3, 6), (2, 3)]),
+        Err(Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_get_by_stream_as_of() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::<RangeEvent>::new(storage.clone());
+    for version in 1..=5 {
+        writer.append(1, version, RangeEvent { value: version })?;
+        writer.append(
+            2,
+            version,
+            RangeEvent {
+                value: 100 + version,
+            },
+        )?;
+    }
+
+    let reader = Reader::<RangeEvent>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let as_of = |stream_id, version| -> Result<Option<(u32, u32)>, Error> {
+        Ok(reader
+            .get_by_stream_as_of(&txn, stream_id, version)?
+            .map(|(version, event)| (version, event.value.to_native())))
+    };
+
+    assert_eq!(as_of(1, 3)?, Some((3, 3)));
+    assert_eq!(as_of(1, u32::MAX)?, Some((5, 5)));
+    assert_eq!(as_of(2, 1)?, Some((1, 101)));
+    // Nothing at or below version 0, and nothing in unknown streams.
+    assert_eq!(as_of(1, 0)?, None);
+    assert_eq!(as_of(3, 5)?, None);
+    drop(txn);
+
+    // After trimming, versions below the oldest kept one have no state to return.
+    writer.trim_stream(1, 2)?;
+    let txn = storage.env.read_txn()?;
+    assert_eq!(
+        reader
+            .get_by_stream_as_of(&txn, 1, 3)?
+            .map(|(version, _)| version),
+        None
+    );
+    assert_eq!(
+        reader
+            .get_by_stream_as_of(&txn, 1, 4)?
+            .map(|(version, _)| version),
+        Some(4)
+    );
+
+    Ok(())
+}
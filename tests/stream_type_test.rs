@@ -0,0 +1,93 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use tempfile::tempdir;
+use varvedb::engine::Writer;
+use varvedb::error::Error;
+use varvedb::storage::{Storage, StorageConfig};
+use varvedb::ExpectedVersion;
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+struct OrderPlaced {
+    amount: u64,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+struct UserRegistered {
+    user_id: u64,
+}
+
+fn open(
+    enforce_stream_types: bool,
+) -> Result<(tempfile::TempDir, Storage), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        enforce_stream_types,
+        ..Default::default()
+    };
+    let storage = Storage::open(config)?;
+    Ok((dir, storage))
+}
+
+#[test]
+fn test_stream_type_mismatch_is_rejected() -> Result<(), Box<dyn std::error::Error>> {
+    let (_dir, storage) = open(true)?;
+    let mut orders = Writer::<OrderPlaced>::new(storage.clone());
+    let mut users = Writer::<UserRegistered>::new(storage.clone());
+
+    orders.append(1, 1, OrderPlaced { amount: 10 })?;
+    orders.append(1, 2, OrderPlaced { amount: 20 })?;
+    users.append(2, 1, UserRegistered { user_id: 7 })?;
+
+    match users.append(1, 3, UserRegistered { user_id: 8 }) {
+        Err(Error::InvalidConfig(msg)) => assert_eq!(msg, "stream type mismatch"),
+        other => panic!("Expected stream type mismatch, got {:?}", other),
+    }
+
+    // The rejected append left no trace; the stream still accepts its own type.
+    assert_eq!(orders.append(1, 3, OrderPlaced { amount: 30 })?, 4);
+
+    // Atomic batches are checked too.
+    let result = users.append_multi(vec![
+        (3, ExpectedVersion::Auto, UserRegistered { user_id: 9 }),
+        (1, ExpectedVersion::Auto, UserRegistered { user_id: 10 }),
+    ]);
+    assert!(matches!(result, Err(Error::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_explicit_stream_type_tag() -> Result<(), Box<dyn std::error::Error>> {
+    let (_dir, storage) = open(true)?;
+    let mut orders = Writer::<OrderPlaced>::new(storage.clone()).with_stream_type("order");
+    let mut other = Writer::<UserRegistered>::new(storage.clone()).with_stream_type("order");
+
+    orders.append(1, 1, OrderPlaced { amount: 10 })?;
+    // Writers sharing a tag may write to the same stream.
+    other.append(1, 2, UserRegistered { user_id: 1 })?;
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_types_not_enforced_by_default() -> Result<(), Box<dyn std::error::Error>> {
+    let (_dir, storage) = open(false)?;
+    assert!(storage.stream_types.is_none());
+
+    let mut orders = Writer::<OrderPlaced>::new(storage.clone());
+    let mut users = Writer::<UserRegistered>::new(storage.clone());
+    orders.append(1, 1, OrderPlaced { amount: 10 })?;
+    users.append(1, 2, UserRegistered { user_id: 7 })?;
+
+    Ok(())
+}
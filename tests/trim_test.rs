@@ -0,0 +1,383 @@
+// This file is part of VarveDB.
+//
+// Copyright (C) 2025 Matheus Cardoso <varvedb@matheus.sbs>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License
+// v. 2.0. If a copy of the MPL was not distributed with this file, You can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+use varvedb::engine::{Reader, Writer};
+use varvedb::processor::{ParallelProcessor, Processor};
+use varvedb::storage::{InlineThreshold, LogKey, Storage, StorageConfig, StorageLayout};
+use varvedb::testing::CollectingHandler;
+use varvedb::traits::MetadataExt;
+use varvedb::{ExpectedVersion, Payload, StreamState, Varve};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+struct Reading {
+    celsius: u32,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct Sensor {
+    id: u128,
+    version: u32,
+}
+
+impl MetadataExt for Sensor {
+    fn stream_id(&self) -> u128 {
+        self.id
+    }
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+#[test]
+fn test_trim_stream_keeps_recent_versions() -> Result<(), Box<dyn std::error::Error>> {
+    for layout in [StorageLayout::Sequential, StorageLayout::Clustered] {
+        let dir = tempdir()?;
+        let storage = Storage::open(StorageConfig {
+            path: dir.path().to_path_buf(),
+            layout,
+            ..Default::default()
+        })?;
+        let mut writer = Writer::new(storage.clone());
+        for celsius in 1..=10 {
+            writer.append(1, celsius, Reading { celsius })?;
+            writer.append(2, celsius, Reading { celsius })?;
+        }
+
+        assert_eq!(writer.trim_stream(1, 3)?, 7);
+        // Trimming again finds nothing left to delete.
+        assert_eq!(writer.trim_stream(1, 3)?, 0);
+
+        {
+            let reader = Reader::<Reading>::new(storage.clone());
+            let txn = storage.env.read_txn()?;
+            assert!(reader.get_by_stream(&txn, 1, 7)?.is_none());
+            assert!(reader.get(&txn, 1)?.is_none());
+            assert_eq!(reader.get_by_stream(&txn, 1, 8)?.unwrap().celsius, 8);
+            let info = reader.stream_info(&txn, 1)?.unwrap();
+            assert_eq!((info.count, info.head_version), (3, 10));
+
+            // Other streams are untouched.
+            assert_eq!(reader.stream_info(&txn, 2)?.unwrap().count, 10);
+            assert_eq!(storage.events_log.len(&txn)?, 13);
+        }
+
+        // New events continue after the head.
+        writer.append_multi(vec![(1, ExpectedVersion::Auto, Reading { celsius: 11 })])?;
+        let reader = Reader::<Reading>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.stream_info(&txn, 1)?.unwrap().head_version, 11);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_trim_stream_rejects_zero_keep_last() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage);
+    writer.append(1, 1, Reading { celsius: 20 })?;
+
+    assert!(matches!(
+        writer.trim_stream(1, 0),
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_sequential_readers_skip_trimmed_versions() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let mut db = Varve::<Reading, Sensor>::open(dir.path())?;
+    // Streams 1 and 2 interleave: stream 1 holds sequences 1, 3 and 5.
+    for version in 1..=3 {
+        for id in 1..=2 {
+            db.append(
+                Payload::new(
+                    Reading {
+                        celsius: id as u32 * 10 + version,
+                    },
+                    Sensor { id, version },
+                ),
+                ExpectedVersion::Auto,
+            )?;
+        }
+    }
+
+    let mut writer = Writer::<Reading>::new(db.reader().storage().clone());
+    assert_eq!(writer.trim_stream(1, 1)?, 2);
+    let expected = vec![21, 22, 13, 23];
+
+    assert_eq!(db.count()?, 4);
+    let iterated = db
+        .iter()?
+        .map(|view| view.map(|view| view.celsius.to_native()))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(iterated, expected);
+    let collected: Vec<u32> = db
+        .collect_events()?
+        .iter()
+        .map(|view| view.celsius.to_native())
+        .collect();
+    assert_eq!(collected, expected);
+    let snapshot = db.reader().snapshot()?;
+    let snapshotted = snapshot
+        .iter()
+        .map(|view| view.map(|view| view.celsius.to_native()))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(snapshotted, expected);
+    drop(snapshot);
+    {
+        let txn = db.reader().storage().env.read_txn()?;
+        let envelopes = db.reader().get_envelopes(&txn, 1, 10)?;
+        let seqs: Vec<u64> = envelopes.iter().map(|envelope| envelope.seq).collect();
+        assert_eq!(seqs, [2, 4, 5, 6]);
+        let read: Vec<u32> = envelopes
+            .iter()
+            .map(|envelope| envelope.event.celsius.to_native())
+            .collect();
+        assert_eq!(read, expected);
+    }
+
+    // Processors starting before the trimmed sequences reach the head.
+    let handler = CollectingHandler::new();
+    let mut processor = Processor::new(&db, handler.clone(), 1u64);
+    assert_eq!(processor.catch_up()?, 6);
+    let handled: Vec<u32> = handler.events().iter().map(|r| r.celsius).collect();
+    assert_eq!(handled, expected);
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let mut parallel = ParallelProcessor::new(
+        &db,
+        move |reading: &ArchivedReading| {
+            sink.lock().unwrap().push(reading.celsius.to_native());
+            Ok(())
+        },
+        2u64,
+        |reading: &ArchivedReading| reading.celsius.to_native() as u128 / 10,
+    )
+    .with_batch_size(1)
+    .with_workers(1);
+    assert_eq!(parallel.catch_up()?, 6);
+    assert_eq!(*received.lock().unwrap(), expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_trim_stream_removes_unshared_blobs_and_ids() -> Result<(), Box<dyn std::error::Error>> {
+    for separate_blob_env in [false, true] {
+        let dir = tempdir()?;
+        let storage = Storage::open(StorageConfig {
+            path: dir.path().to_path_buf(),
+            inline_threshold: InlineThreshold::Fixed(0),
+            separate_blob_env,
+            log_key: LogKey::U128,
+            ..Default::default()
+        })?;
+        let mut writer = Writer::new(storage.clone());
+        for celsius in 1..=3 {
+            writer.append(1, celsius, Reading { celsius })?;
+        }
+        // Stream 2 stores the same payload as stream 1's first version, so it shares its blob.
+        writer.append(2, 1, Reading { celsius: 1 })?;
+
+        let blob_count = |storage: &Storage| -> Result<u64, Box<dyn std::error::Error>> {
+            Ok(match &storage.blob_env {
+                None => storage.blobs.len(&storage.env.read_txn()?)?,
+                Some(env) => storage.blobs.len(&env.read_txn()?)?,
+            })
+        };
+        assert_eq!(blob_count(&storage)?, 3);
+
+        assert_eq!(writer.trim_stream(1, 1)?, 2);
+        assert_eq!(blob_count(&storage)?, 2);
+
+        let reader = Reader::<Reading>::new(storage.clone());
+        let txn = storage.env.read_txn()?;
+        assert_eq!(reader.get_by_stream(&txn, 2, 1)?.unwrap().celsius, 1);
+        assert_eq!(reader.get_by_stream(&txn, 1, 3)?.unwrap().celsius, 3);
+        let ids = storage.events_by_id.as_ref().unwrap();
+        let seqs = ids
+            .iter(&txn)?
+            .map(|entry| entry.map(|(_, seq)| seq))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(seqs, vec![3, 4]);
+        let by_seq = storage.ids_by_seq.as_ref().unwrap();
+        let seqs = by_seq
+            .iter(&txn)?
+            .map(|entry| entry.map(|(seq, _)| seq))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(seqs, vec![3, 4]);
+        assert_eq!(storage.blob_refs.open(&txn)?.unwrap().len(&txn)?, 2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_trim_stream_keeps_blobs_of_unversioned_databases() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempdir()?;
+    let config = StorageConfig {
+        path: dir.path().to_path_buf(),
+        inline_threshold: InlineThreshold::Fixed(0),
+        ..Default::default()
+    };
+    {
+        let storage = Storage::open(config.clone())?;
+        Writer::new(storage.clone()).append(1, 1, Reading { celsius: 1 })?;
+        // Databases written before the format version existed have no key and no counts.
+        let mut txn = storage.env.write_txn()?;
+        storage.meta.delete(&mut txn, "format_version")?;
+        storage.blob_refs.open(&txn)?.unwrap().clear(&mut txn)?;
+        txn.commit()?;
+    }
+
+    let storage = Storage::open(config)?;
+    let mut writer = Writer::new(storage.clone());
+    // Version 1 shares its blob with stream 1, which was never counted.
+    writer.append(2, 1, Reading { celsius: 1 })?;
+    writer.append(2, 2, Reading { celsius: 2 })?;
+    assert_eq!(writer.trim_stream(2, 1)?, 1);
+
+    let reader = Reader::<Reading>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    assert_eq!(reader.get_by_stream(&txn, 1, 1)?.unwrap().celsius, 1);
+    assert_eq!(
+        storage.meta.get(&txn, "format_version")?,
+        Some(&1u32.to_be_bytes()[..])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_processors_wait_on_gaps_but_skip_trimmed_sequences(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let db = Varve::<Reading, Sensor>::open(dir.path())?;
+    let mut writer = Writer::<Reading>::new(db.reader().storage().clone()).with_allow_gaps(true);
+    for celsius in 1..=3 {
+        writer.append(1, celsius, Reading { celsius })?;
+    }
+    assert_eq!(writer.trim_stream(1, 1)?, 2);
+    // Sequence 4 is left empty.
+    let bytes = |celsius| rkyv::to_bytes::<rkyv::rancor::Error>(&Reading { celsius });
+    writer.append_at(5, 2, 1, &bytes(20)?)?;
+
+    let celsius = |db: &Varve<Reading, Sensor>| -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+        Ok(db
+            .iter()?
+            .map(|view| view.map(|view| view.celsius.to_native()))
+            .collect::<Result<Vec<_>, _>>()?)
+    };
+    assert_eq!(celsius(&db)?, vec![3]);
+
+    let handler = CollectingHandler::new();
+    let mut processor = Processor::new(&db, handler.clone(), 1u64);
+    assert_eq!(processor.catch_up()?, 3);
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let mut parallel = ParallelProcessor::new(
+        &db,
+        move |reading: &ArchivedReading| {
+            sink.lock().unwrap().push(reading.celsius.to_native());
+            Ok(())
+        },
+        2u64,
+        // One partition, so events are handled in log order.
+        |_: &ArchivedReading| 0,
+    );
+    assert_eq!(parallel.catch_up()?, 3);
+
+    // Trimmed sequences stay empty, while the gap can still be filled.
+    assert!(matches!(
+        writer.append_at(1, 3, 1, &bytes(30)?),
+        Err(varvedb::error::Error::InvalidConfig(_))
+    ));
+    writer.append_at(4, 3, 1, &bytes(30)?)?;
+
+    let expected = vec![3, 30, 20];
+    assert_eq!(celsius(&db)?, expected);
+    assert_eq!(processor.catch_up()?, 5);
+    let handled: Vec<u32> = handler.events().iter().map(|r| r.celsius).collect();
+    assert_eq!(handled, expected);
+    assert_eq!(parallel.catch_up()?, 5);
+    assert_eq!(*received.lock().unwrap(), expected);
+
+    Ok(())
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+enum Counter {
+    Added(u32),
+    Snapshot(u32),
+}
+
+#[derive(Default)]
+struct Total(u32);
+
+impl StreamState<Counter> for Total {
+    fn apply(&mut self, event: &ArchivedCounter) {
+        match event {
+            ArchivedCounter::Added(n) => self.0 += n.to_native(),
+            ArchivedCounter::Snapshot(total) => self.0 = total.to_native(),
+        }
+    }
+}
+
+#[test]
+fn test_trim_stream_keeping_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let storage = Storage::open(StorageConfig {
+        path: dir.path().to_path_buf(),
+        ..Default::default()
+    })?;
+    let mut writer = Writer::new(storage.clone());
+    let events = [
+        Counter::Added(1),
+        Counter::Added(2),
+        Counter::Snapshot(3),
+        Counter::Added(4),
+        Counter::Added(5),
+    ];
+    for (version, event) in (1..).zip(events) {
+        writer.append(1, version, event)?;
+    }
+    let is_snapshot = |event: &ArchivedCounter| matches!(event, ArchivedCounter::Snapshot(_));
+
+    // A snapshot among the last three versions already covers the older ones.
+    assert_eq!(writer.trim_stream_keeping_snapshot(1, 3, is_snapshot)?, 2);
+    // Otherwise trimming stops at the newest snapshot.
+    writer.append(1, 6, Counter::Added(6))?;
+    assert_eq!(writer.trim_stream_keeping_snapshot(1, 1, is_snapshot)?, 0);
+    writer.append(1, 7, Counter::Snapshot(18))?;
+    writer.append(1, 8, Counter::Added(7))?;
+    assert_eq!(writer.trim_stream_keeping_snapshot(1, 1, is_snapshot)?, 4);
+
+    let reader = Reader::<Counter>::new(storage.clone());
+    let txn = storage.env.read_txn()?;
+    let mut total = Total::default();
+    for (_, event) in reader.get_by_stream_range(&txn, 1, 1, 9)? {
+        total.apply(&event);
+    }
+    assert_eq!(total.0, 25);
+    assert_eq!(reader.stream_info(&txn, 1)?.unwrap().count, 2);
+
+    Ok(())
+}